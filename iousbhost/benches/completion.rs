@@ -0,0 +1,81 @@
+//! per-transfer cost of handing a completion from the framework's queue to the polling task
+//!
+//! drives the `Completion` the transfer futures share with their handler through a detached
+//! future, whose handler is called here instead of by the framework; no device is needed, each
+//! iteration submits, completes and polls a transfer, once with the handler on the polling thread
+//! and once with it on another thread as the framework's queue would call it:
+//!
+//! cargo bench --bench completion
+use iousbhost::DetachedCompletion;
+use std::future::Future;
+use std::hint::black_box;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 1_000_000;
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: std::sync::Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn report(name: &str, elapsed: Duration, iterations: u32) {
+    println!(
+        "{name:>12}: {:>6.1} ns per transfer",
+        elapsed.as_nanos() as f64 / iterations as f64
+    );
+}
+
+fn same_thread() {
+    let waker = Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut transfer = DetachedCompletion::new();
+        assert!(Pin::new(&mut transfer).poll(&mut cx).is_pending());
+        transfer.handle().complete(0, 64);
+        assert!(matches!(
+            Pin::new(&mut transfer).poll(&mut cx),
+            Poll::Ready(Ok(64))
+        ));
+        black_box(&transfer);
+    }
+    report("same thread", start.elapsed(), ITERATIONS);
+}
+
+fn other_thread() {
+    const ITERATIONS: u32 = 100_000;
+    let (handlers, received) = mpsc::channel::<iousbhost::CompletionHandle>();
+    let queue = std::thread::spawn(move || {
+        for handle in received {
+            handle.complete(0, 64);
+        }
+    });
+    let waker = Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut transfer = DetachedCompletion::new();
+        assert!(Pin::new(&mut transfer).poll(&mut cx).is_pending());
+        handlers
+            .send(transfer.handle())
+            .expect("completion thread exited");
+        while Pin::new(&mut transfer).poll(&mut cx).is_pending() {
+            std::thread::park();
+        }
+        black_box(&transfer);
+    }
+    report("other thread", start.elapsed(), ITERATIONS);
+    drop(handlers);
+    queue.join().expect("completion thread panicked");
+}
+
+fn main() {
+    same_thread();
+    other_thread();
+}
@@ -0,0 +1,294 @@
+//! a loopback device on a virtual controller: a vendor specific high speed device whose bulk IN
+//! endpoint hands back what was written to its bulk OUT endpoint
+//!
+//! reads wait for data like a device NAKing them, so every byte read has to be written first
+use iousbhost::{
+    ControllerBuilder, ControllerCapabilities, ControllerInterface, DeviceRequest, DeviceSpeed,
+    DeviceStateMachine, DoorbellEvent, EndpointAddress, EndpointStateMachine, HostTime, LinkState,
+    Message, MessageStatus, MessageType, PortCapabilities, PortType, StandardRequest, UsbError,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+
+//pid.codes test ids
+pub const VENDOR_ID: u16 = 0x1209;
+pub const PRODUCT_ID: u16 = 0x0001;
+pub const BULK_OUT: EndpointAddress = EndpointAddress(0x01);
+pub const BULK_IN: EndpointAddress = EndpointAddress(0x81);
+
+const PORT: u8 = 1;
+const SPEED: DeviceSpeed = DeviceSpeed::High;
+
+const DEVICE_DESCRIPTOR: [u8; 18] = [
+    18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x09, 0x12, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 1,
+];
+
+//one vendor specific interface with a 512 byte bulk endpoint each way
+const CONFIGURATION_DESCRIPTOR: [u8; 32] = [
+    9, 2, 32, 0, 1, 1, 0, 0x80, 50, //
+    9, 4, 0, 0, 2, 0xff, 0, 0, 0, //
+    7, 5, 0x01, 2, 0x00, 0x02, 0, //
+    7, 5, 0x81, 2, 0x00, 0x02, 0,
+];
+
+struct Loopback {
+    started: HostTime,
+    next_address: u8,
+    devices: HashMap<u8, DeviceStateMachine>,
+    endpoints: HashMap<(u8, u8), EndpointStateMachine>,
+    //what the control transfer in flight answers with, `None` once it stalled
+    reply: Option<Vec<u8>>,
+    //written to the OUT endpoint and not read back yet
+    looped: VecDeque<u8>,
+}
+
+///creates a controller with a single port and attaches the loopback device to it once the port
+///is powered; the device goes away with the controller
+pub fn attach() -> Result<ControllerInterface, UsbError> {
+    let loopback = Arc::new(Mutex::new(Loopback {
+        started: HostTime::now(),
+        next_address: 0,
+        devices: HashMap::new(),
+        endpoints: HashMap::new(),
+        reply: None,
+        looped: VecDeque::new(),
+    }));
+    let commands = loopback.clone();
+    let capabilities = ControllerCapabilities::new()
+        .port(PortCapabilities::new(PORT, PortType::Standard).speeds(&[DeviceSpeed::Full, SPEED]));
+    ControllerBuilder::new(capabilities).build(
+        move |controller, command| {
+            let mut loopback = commands.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Err(err) = loopback.command(controller, command) {
+                eprintln!("loopback: failed to answer {command}: {err:?}");
+            }
+        },
+        move |_, doorbells| {
+            let mut loopback = loopback.lock().unwrap_or_else(PoisonError::into_inner);
+            for &doorbell in doorbells {
+                if let Err(err) = loopback.doorbell(doorbell) {
+                    eprintln!("loopback: failed to serve doorbell {doorbell:#010x}: {err:?}");
+                }
+            }
+        },
+    )
+}
+
+impl Loopback {
+    fn command(
+        &mut self,
+        controller: &ControllerInterface,
+        command: &Message<'_>,
+    ) -> Result<(), UsbError> {
+        use MessageType as MT;
+        match command.message_type() {
+            MT::ControllerPowerOn => {
+                controller.controller_state_machine().power_on(command)?;
+            }
+            MT::ControllerPowerOff => {
+                controller.controller_state_machine().power_off(command)?;
+            }
+            MT::ControllerStart => {
+                controller.controller_state_machine().start(command)?;
+            }
+            MT::ControllerPause => {
+                controller.controller_state_machine().pause(command)?;
+            }
+            MT::ControllerFrameNumner => {
+                let machine = controller.controller_state_machine();
+                machine.inspect_command(command)?;
+                let now = HostTime::now();
+                let frame = now.duration_since(self.started).as_millis() as u64;
+                return machine.respond(
+                    command,
+                    MessageStatus::Success,
+                    Some((frame, now.ticks())),
+                );
+            }
+            MT::PortPowerOn
+            | MT::PortPowerOff
+            | MT::PortResume
+            | MT::PortSuspend
+            | MT::PortReset
+            | MT::PortDisable
+            | MT::PortStatus => {
+                let port = controller.port_state_machine_for_command(command)?;
+                if let Err(err) = port.inspect_command(command) {
+                    port.respond(command, MessageStatus::NotPermitted)?;
+                    return Err(err);
+                }
+                port.respond(command, MessageStatus::Success)?;
+                match command.message_type() {
+                    MT::PortPowerOn => {
+                        port.set_connected(true);
+                        port.update_link_state(LinkState::U0, SPEED, false)?;
+                    }
+                    MT::PortReset => port.update_link_state(LinkState::U0, SPEED, false)?,
+                    _ => {}
+                }
+            }
+            MT::DeviceCreate => {
+                let device = DeviceStateMachine::new(controller, command)?;
+                self.next_address += 1;
+                device.respond(
+                    command,
+                    MessageStatus::Success,
+                    Some(self.next_address as u64),
+                )?;
+                self.devices.insert(self.next_address, device);
+            }
+            MT::DeviceDestroy | MT::DeviceStart | MT::DevicePause | MT::DeviceUpdate => {
+                let address = command.device_address();
+                let device = self
+                    .devices
+                    .get(&address)
+                    .ok_or(UsbError::InvalidArgument)?;
+                device.inspect_command(command)?;
+                device.respond(command, MessageStatus::Success, None)?;
+                if command.message_type() == MT::DeviceDestroy {
+                    self.devices.remove(&address);
+                    self.endpoints.retain(|(device, _), _| *device != address);
+                }
+            }
+            MT::EndpointCreate => {
+                let endpoint = EndpointStateMachine::create(controller, command, SPEED)?;
+                endpoint.respond(command, MessageStatus::Success)?;
+                let key = (
+                    endpoint.device_address() as u8,
+                    endpoint.endpoint_address() as u8,
+                );
+                self.endpoints.insert(key, endpoint);
+            }
+            MT::EndpointDesroy
+            | MT::EndpointPause
+            | MT::EndpointUpdate
+            | MT::EndpointRest
+            | MT::EndpointSetNextTransfer => {
+                let key = (command.device_address(), command.endpoint_address().0);
+                let endpoint = self.endpoints.get(&key).ok_or(UsbError::InvalidArgument)?;
+                endpoint.inspect_command(command)?;
+                endpoint.respond(command, MessageStatus::Success)?;
+                if command.message_type() == MT::EndpointDesroy {
+                    self.endpoints.remove(&key);
+                }
+            }
+            _ => return Err(UsbError::InvalidArgument),
+        }
+        Ok(())
+    }
+
+    fn doorbell(&mut self, doorbell: u32) -> Result<(), UsbError> {
+        let event = DoorbellEvent::from(doorbell);
+        let key = (event.device_address, event.endpoint_address);
+        let Some(endpoint) = self.endpoints.get(&key) else {
+            return Err(UsbError::InvalidArgument);
+        };
+        endpoint.process_doorbell(doorbell)?;
+        self.serve(key)?;
+        //a write can complete reads that were waiting for data
+        if key.1 == BULK_OUT.0 {
+            self.serve((key.0, BULK_IN.0))?;
+        }
+        Ok(())
+    }
+
+    //completes the transfers queued on the endpoint until it runs out of them, or a read runs out
+    //of data to return
+    fn serve(&mut self, key: (u8, u8)) -> Result<(), UsbError> {
+        let Some(endpoint) = self.endpoints.get(&key) else {
+            return Ok(());
+        };
+        let address = EndpointAddress(key.1);
+        while let Some(message) = endpoint.current_transfer_message() {
+            if !message.valid() {
+                break;
+            }
+            let completion = if address == EndpointAddress::CONTROL {
+                Some(control(&mut self.reply, &message))
+            } else if address.is_in() {
+                read(&mut self.looped, &message)
+            } else {
+                Some(write(&mut self.looped, &message))
+            };
+            let Some((status, length)) = completion else {
+                break;
+            };
+            endpoint.enqueue_transfer_completion_for_message(&message, status, length)?;
+        }
+        Ok(())
+    }
+}
+
+//a stage of a control transfer on the default endpoint
+fn control(reply: &mut Option<Vec<u8>>, message: &Message<'_>) -> (MessageStatus, u64) {
+    if let Some(request) = message.setup_request() {
+        *reply = answer(&request);
+        return match reply {
+            Some(_) => (MessageStatus::Success, 0),
+            None => (MessageStatus::StallError, 0),
+        };
+    }
+    match (message.transfer_buffer(), message.transfer_length(), reply) {
+        (Some(buffer), Some(length), Some(reply)) => {
+            let length = reply.len().min(length as usize);
+            unsafe { core::ptr::copy_nonoverlapping(reply.as_ptr(), buffer.as_ptr(), length) };
+            (MessageStatus::Success, length as u64)
+        }
+        _ => (MessageStatus::Success, 0),
+    }
+}
+
+//the data stage of the standard requests the host enumerates the device with, empty for those
+//without one, `None` to stall the rest
+fn answer(request: &DeviceRequest) -> Option<Vec<u8>> {
+    const GET_STATUS: u8 = StandardRequest::GetStatus as u8;
+    const CLEAR_FEATURE: u8 = StandardRequest::ClearFeature as u8;
+    const SET_FEATURE: u8 = StandardRequest::SetFeature as u8;
+    const GET_DESCRIPTOR: u8 = StandardRequest::GetDescriptor as u8;
+    const GET_CONFIGURATION: u8 = StandardRequest::GetConfiguration as u8;
+    const SET_CONFIGURATION: u8 = StandardRequest::SetConfiguration as u8;
+    const SET_INTERFACE: u8 = StandardRequest::SetInterface as u8;
+    if request.request_type() & 0x60 != 0 {
+        return None;
+    }
+    match request.request() {
+        GET_STATUS => Some(vec![0, 0]),
+        GET_DESCRIPTOR => match request.value() >> 8 {
+            1 => Some(DEVICE_DESCRIPTOR.to_vec()),
+            2 => Some(CONFIGURATION_DESCRIPTOR.to_vec()),
+            _ => None,
+        },
+        GET_CONFIGURATION => Some(vec![1]),
+        CLEAR_FEATURE | SET_FEATURE | SET_CONFIGURATION | SET_INTERFACE => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+//a transfer on the OUT endpoint, queueing all of its data to be read back
+fn write(looped: &mut VecDeque<u8>, message: &Message<'_>) -> (MessageStatus, u64) {
+    let (Some(buffer), Some(length)) = (message.transfer_buffer(), message.transfer_length())
+    else {
+        return (MessageStatus::Success, 0);
+    };
+    let data = unsafe { core::slice::from_raw_parts(buffer.as_ptr(), length as usize) };
+    looped.extend(data);
+    (MessageStatus::Success, length as u64)
+}
+
+//a transfer on the IN endpoint, ending short once it returned everything written so far; `None`
+//leaves it queued while there is nothing to return
+fn read(looped: &mut VecDeque<u8>, message: &Message<'_>) -> Option<(MessageStatus, u64)> {
+    let (Some(buffer), Some(length)) = (message.transfer_buffer(), message.transfer_length())
+    else {
+        return Some((MessageStatus::Success, 0));
+    };
+    if looped.is_empty() {
+        return None;
+    }
+    let length = looped.len().min(length as usize);
+    let data = unsafe { core::slice::from_raw_parts_mut(buffer.as_ptr(), length) };
+    for (byte, value) in data.iter_mut().zip(looped.drain(..length)) {
+        *byte = value;
+    }
+    Some((MessageStatus::Success, length as u64))
+}
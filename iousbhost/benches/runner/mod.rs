@@ -0,0 +1,191 @@
+//! bulk throughput of a pipe across buffer sizes and queue depths, and the round trip of device
+//! requests
+use iousbhost::{DeviceRequest, HostPipe, UsbDevice};
+use std::future::Future;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ThroughputSample {
+    pub buffer_size: usize,
+    pub queue_depth: usize,
+    pub transfers: u64,
+    pub failed: u64,
+    ///what the transfers reported moving, short ones counting what actually arrived
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl ThroughputSample {
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LatencySample {
+    pub iterations: usize,
+    pub failed: usize,
+    ///round trip of every successful request, in submission order
+    pub samples: Vec<Duration>,
+}
+
+impl LatencySample {
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.iter().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.samples.iter().sum();
+        (!self.samples.is_empty()).then(|| total / self.samples.len() as u32)
+    }
+
+    ///`quantile` in 0.0..=1.0, nearest rank
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((sorted.len() as f64) * quantile.clamp(0.0, 1.0)).ceil() as usize;
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+pub struct BenchmarkRunner<'p, 'a> {
+    pipe: &'p HostPipe<'a>,
+    buffer_sizes: Vec<usize>,
+    queue_depths: Vec<usize>,
+    iterations: usize,
+}
+
+impl<'p, 'a> BenchmarkRunner<'p, 'a> {
+    pub fn new(pipe: &'p HostPipe<'a>) -> Self {
+        Self {
+            pipe,
+            buffer_sizes: vec![512, 4096, 16384, 65536],
+            queue_depths: vec![1, 2, 4, 8],
+            iterations: 64,
+        }
+    }
+
+    pub fn buffer_sizes(mut self, sizes: &[usize]) -> Self {
+        self.buffer_sizes = sizes.to_vec();
+        self
+    }
+
+    pub fn queue_depths(mut self, depths: &[usize]) -> Self {
+        self.queue_depths = depths.iter().map(|depth| (*depth).max(1)).collect();
+        self
+    }
+
+    ///number of transfers issued for every buffer size and queue depth combination
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    ///runs every buffer size against every queue depth, keeping up to `queue_depth` transfers
+    ///enqueued at once
+    pub async fn throughput(&self) -> Vec<ThroughputSample> {
+        let mut samples = Vec::new();
+        for &buffer_size in &self.buffer_sizes {
+            for &queue_depth in &self.queue_depths {
+                samples.push(self.run_throughput(buffer_size, queue_depth).await);
+            }
+        }
+        samples
+    }
+
+    async fn run_throughput(&self, buffer_size: usize, queue_depth: usize) -> ThroughputSample {
+        let buffers = vec![vec![0u8; buffer_size]; queue_depth];
+        let mut sample = ThroughputSample {
+            buffer_size,
+            queue_depth,
+            transfers: 0,
+            failed: 0,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+        };
+        let start = Instant::now();
+        let mut remaining = self.iterations;
+        while remaining > 0 {
+            let batch = remaining.min(queue_depth);
+            let results = join_all(
+                buffers[..batch]
+                    .iter()
+                    .map(|buffer| self.pipe.enqueue_io_request(buffer)),
+            )
+            .await;
+            for res in results {
+                sample.transfers += 1;
+                match res {
+                    Ok(transferred) => sample.bytes += transferred,
+                    Err(_) => sample.failed += 1,
+                }
+            }
+            remaining -= batch;
+        }
+        sample.elapsed = start.elapsed();
+        sample
+    }
+}
+
+///sends `request` to `device` synchronously `iterations` times, timing each round trip
+pub fn control_latency(
+    device: &UsbDevice<'_>,
+    request: DeviceRequest,
+    iterations: usize,
+) -> LatencySample {
+    let buffer = vec![0u8; request.length() as usize];
+    let mut sample = LatencySample {
+        iterations,
+        failed: 0,
+        samples: Vec::with_capacity(iterations),
+    };
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let res = if buffer.is_empty() {
+            device.send_device_request(request).map(|_| 0)
+        } else {
+            device.send_device_request_with_data(request, &buffer)
+        };
+        match res {
+            Ok(_) => sample.samples.push(start.elapsed()),
+            Err(_) => sample.failed += 1,
+        }
+    }
+    sample
+}
+
+//polls every future until all of them finished, so they are in flight together, resolving with
+//their outputs in order
+async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future)))
+        .collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for (slot, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            let Some(future) = slot else {
+                continue;
+            };
+            match future.as_mut().poll(cx) {
+                Poll::Ready(value) => {
+                    *output = Some(value);
+                    *slot = None;
+                }
+                Poll::Pending => pending = true,
+            }
+        }
+        match pending {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
@@ -0,0 +1,107 @@
+//! bulk throughput and control latency against the loopback device of a virtual controller
+//!
+//! every sample writes its transfers to the bulk OUT endpoint and reads them back from the bulk IN
+//! endpoint, both directions counting the bytes the transfers moved; creating the controller
+//! needs the `com.apple.developer.usb.host-controller-interface` entitlement:
+//!
+//! cargo bench --bench transfer
+mod loopback;
+mod runner;
+
+use iousbhost::{DeviceRequest, DeviceRequestType, HostObjectInitOptions, UsbDevice, UsbError};
+use runner::{BenchmarkRunner, ThroughputSample};
+use std::time::{Duration, Instant};
+
+const BUFFER_SIZES: [usize; 4] = [512, 4096, 16384, 65536];
+const QUEUE_DEPTHS: [usize; 4] = [1, 2, 4, 8];
+const ITERATIONS: usize = 256;
+
+//the device shows up once the kernel driver enumerated it on the virtual controller
+fn open_loopback<'a>(timeout: Duration) -> Result<UsbDevice<'a>, UsbError> {
+    let start = Instant::now();
+    loop {
+        let res = UsbDevice::device::<0>(
+            Some(loopback::VENDOR_ID),
+            Some(loopback::PRODUCT_ID),
+            None,
+            None,
+            None,
+            None,
+            None,
+            HostObjectInitOptions::None,
+        );
+        match res {
+            Ok(device) => return Ok(device),
+            Err(err) if start.elapsed() > timeout => return Err(err),
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+fn report(direction: &str, sample: &ThroughputSample) {
+    println!(
+        "bulk {direction:<5} size={:>6} depth={:>2} transfers={:>4} failed={:>3} \
+         bytes={:>9} {:>10.2} MiB/s",
+        sample.buffer_size,
+        sample.queue_depth,
+        sample.transfers,
+        sample.failed,
+        sample.bytes,
+        sample.bytes_per_second() / (1024.0 * 1024.0),
+    );
+}
+
+fn main() {
+    //destroying the controller detaches the device, so it has to outlive everything opened on it
+    let _controller = match loopback::attach() {
+        Ok(controller) => controller,
+        Err(err) => {
+            println!("skipping: failed to create the virtual controller: {err:?}");
+            return;
+        }
+    };
+
+    let device = open_loopback(Duration::from_secs(10)).expect("loopback device never showed up");
+    device
+        .configure(1, Some(false))
+        .expect("failed to configure the loopback device");
+    let interface = device
+        .interfaces(HostObjectInitOptions::None)
+        .and_then(|mut interfaces| interfaces.next())
+        .expect("loopback device has no interface")
+        .expect("failed to open the loopback interface");
+    let bulk_out = interface
+        .copy_pipe(loopback::BULK_OUT)
+        .expect("failed to open the bulk OUT pipe");
+    let bulk_in = interface
+        .copy_pipe(loopback::BULK_IN)
+        .expect("failed to open the bulk IN pipe");
+
+    //one combination at a time, so each read sample gets back exactly what was just written
+    for buffer_size in BUFFER_SIZES {
+        for queue_depth in QUEUE_DEPTHS {
+            for (direction, pipe) in [("write", &bulk_out), ("read", &bulk_in)] {
+                let runner = BenchmarkRunner::new(pipe)
+                    .buffer_sizes(&[buffer_size])
+                    .queue_depths(&[queue_depth])
+                    .iterations(ITERATIONS);
+                for sample in pollster::block_on(runner.throughput()) {
+                    report(direction, &sample);
+                }
+            }
+        }
+    }
+
+    //GET_DESCRIPTOR(DEVICE)
+    let request = DeviceRequest::new(DeviceRequestType::DirectionIn, 6, 0x0100, 0, 18);
+    let latency = runner::control_latency(&device, request, ITERATIONS);
+    println!(
+        "control requests={} min={:?} mean={:?} p99={:?} max={:?} failed={}",
+        latency.iterations,
+        latency.min(),
+        latency.mean(),
+        latency.percentile(0.99),
+        latency.max(),
+        latency.failed,
+    );
+}
@@ -0,0 +1,52 @@
+#![no_main]
+//! walks arbitrary BOS descriptor blobs, including the billboard and SuperSpeedPlus sublink
+//! walkers, none of which may panic, run forever or yield a capability reaching outside the blob
+
+use iousbhost::CapabilityDescriptor;
+use libfuzzer_sys::fuzz_target;
+
+fn within(data: &[u8], bytes: &[u8]) -> bool {
+    let range = data.as_ptr_range();
+    range.start <= bytes.as_ptr() && bytes.as_ptr_range().end <= range.end
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(bos) = CapabilityDescriptor::from_bytes(data) else {
+        return;
+    };
+    //every capability is at least three bytes long
+    let max = data.len() / 3;
+
+    let mut count = 0;
+    for capability in bos.capabilities() {
+        count += 1;
+        assert!(count <= max, "capability walk did not terminate");
+        assert!(within(data, capability.bytes()));
+        assert!(bos
+            .capabilities_with_type(capability.device_capability_type().into())
+            .take(max + 1)
+            .all(|typed| within(data, typed.bytes())));
+    }
+
+    if let Some(ssp) = bos.super_speed_plus_capability_descriptor() {
+        //at most 32 sublink speed attributes fit the attribute count
+        assert!(ssp.sublink_speed_attributes().count() <= 32);
+    }
+    if let Some(billboard) = bos.billboard_descriptor() {
+        let alt_configurations = billboard.alt_configurations().count();
+        assert!(alt_configurations <= billboard.alternate_modes_count() as usize);
+        for alt in billboard.alt_configurations() {
+            let _ = (
+                alt.svid(),
+                alt.altenate_mode(),
+                alt.alternate_mode_setting(),
+            );
+        }
+    }
+
+    let _ = bos.summary();
+    let _ = bos.power_delivery_capability();
+    let _ = bos.battery_info_capabilities();
+    let _ = bos.pd_consumer_port_capability();
+    let _ = bos.pd_provider_port_capability();
+});
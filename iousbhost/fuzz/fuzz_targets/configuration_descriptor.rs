@@ -0,0 +1,48 @@
+#![no_main]
+//! walks arbitrary configuration descriptor blobs with every iterator, none of which may panic,
+//! run forever or yield a descriptor reaching outside the blob
+
+use iousbhost::ConfigurationDescriptor;
+use libfuzzer_sys::fuzz_target;
+
+fn within(data: &[u8], bytes: &[u8]) -> bool {
+    let range = data.as_ptr_range();
+    range.start <= bytes.as_ptr() && bytes.as_ptr_range().end <= range.end
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(config) = ConfigurationDescriptor::from_bytes(data) else {
+        return;
+    };
+    //every descriptor is at least two bytes long
+    let max = data.len() / 2;
+
+    let mut count = 0;
+    for header in config.descriptors() {
+        count += 1;
+        assert!(count <= max, "descriptor walk did not terminate");
+        assert!(within(data, header.bytes()));
+        if let Some(otg) = header.otg_descriptor() {
+            let _ = (otg.attributes(), otg.bcd_otg());
+        }
+        if let Some(debug) = header.debug_descriptor() {
+            let _ = (debug.debug_in_endpoint(), debug.debug_out_endpoint());
+        }
+        for associated in config.associated_descriptors(&header).take(max + 1) {
+            assert!(within(data, associated.bytes()));
+        }
+    }
+
+    for interface in config.interface_descriptors().take(max + 1) {
+        for class_specific in interface.class_specific_descriptors(&config).take(max + 1) {
+            assert!(within(data, class_specific.bytes()));
+        }
+        let endpoints = config.endpoint_descriptors(&interface).take(max + 1);
+        assert!(endpoints.count() <= max);
+    }
+
+    for function in config.interface_association_descriptors().take(max + 1) {
+        let _ = function.contains(function.first_interface());
+        assert!(function.interfaces(&config).take(max + 1).count() <= max);
+    }
+});
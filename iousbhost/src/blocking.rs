@@ -0,0 +1,212 @@
+use crate::*;
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+///how often a blocked transfer looks at its `Cancel` flag
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+///cancels the blocking transfers it was given to from any thread, they abort their request and
+///fail with `UsbError::Aborted`
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+///polls `transfer` on this thread until it completes; once `timeout` passed or `cancel` was set
+///the request is aborted through `abort` and still driven to completion, so the framework is
+///done with its buffer before this returns
+fn drive<T>(
+    transfer: impl Future<Output = Result<T, UsbError>>,
+    timeout: Option<Duration>,
+    cancel: Option<&Cancel>,
+    abort: impl FnOnce() -> Result<(), UsbError>,
+) -> Result<T, UsbError> {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut transfer = core::pin::pin!(transfer);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut abort = Some(abort);
+    let mut interrupted = None;
+    loop {
+        if let Poll::Ready(res) = transfer.as_mut().poll(&mut cx) {
+            return match interrupted {
+                Some(err) => Err(err),
+                None => res,
+            };
+        }
+        if interrupted.is_none() {
+            if cancel.map_or(false, Cancel::is_cancelled) {
+                interrupted = Some(UsbError::Aborted);
+            } else if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                interrupted = Some(UsbError::OperationTimedOut);
+            }
+            if interrupted.is_some() {
+                if let Some(abort) = abort.take() {
+                    //the request still owns its buffer, so keep driving it even when the abort
+                    //could not be delivered
+                    if let Err(err) = abort() {
+                        crate::diag!(warn, "failed to abort an interrupted transfer: {:?}", err);
+                    }
+                }
+                continue;
+            }
+        }
+        let mut wait = CANCEL_POLL_INTERVAL;
+        if let (None, Some(deadline)) = (interrupted, deadline) {
+            wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        std::thread::park_timeout(wait);
+    }
+}
+
+///runs the `enqueue_*` transfers of a pipe to completion on the calling thread, for tools that
+///want timeouts and cancellation without an async runtime
+pub struct Pipe<'p, 'a> {
+    pipe: &'p HostPipe<'a>,
+    timeout: Option<Duration>,
+    cancel: Option<Cancel>,
+}
+
+impl<'p, 'a> Pipe<'p, 'a> {
+    pub fn new(pipe: &'p HostPipe<'a>) -> Self {
+        Self {
+            pipe,
+            timeout: None,
+            cancel: None,
+        }
+    }
+
+    ///aborts transfers still running after `timeout`, failing them with
+    ///`UsbError::OperationTimedOut`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancel_with(mut self, cancel: Cancel) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn drive<T>(&self, transfer: impl Future<Output = Result<T, UsbError>>) -> Result<T, UsbError> {
+        drive(transfer, self.timeout, self.cancel.as_ref(), || {
+            self.pipe.abort(AbortOption::Asynchronous)
+        })
+    }
+
+    pub fn io_request(&self, data: &[u8]) -> Result<u64, UsbError> {
+        self.drive(self.pipe.enqueue_io_request(data))
+    }
+
+    pub fn control_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        self.drive(self.pipe.enqueue_control_request(request))
+    }
+
+    ///receives up to the length of `data` on an IN pipe, returning how many bytes were transferred
+    pub fn read(&self, data: &mut [u8]) -> Result<u64, UsbError> {
+        let result = self.drive(self.pipe.enqueue_read(IoData::from_slice(data)))?;
+        let transferred = result.result()?;
+        let received = result.received();
+        data[..received.len()].copy_from_slice(received);
+        Ok(transferred as u64)
+    }
+
+    ///reads the data stage into `data` for IN requests, returning how many bytes were transferred
+    pub fn control_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: &mut [u8],
+    ) -> Result<u64, UsbError> {
+        let buffer = IoData::from_slice(data);
+        let result = self.drive(self.pipe.enqueue_control_request_with_data(request, buffer))?;
+        let transferred = result.result()?;
+        if request.is_in() {
+            let received = result.received();
+            data[..received.len()].copy_from_slice(received);
+        }
+        Ok(transferred as u64)
+    }
+
+    ///submits `transfers` together, the timeout and cancellation covering the whole batch; only
+    ///OUT transfers, see `HostPipe::enqueue_batch`
+    pub fn batch(&self, transfers: &[TransferSpec<'_>]) -> Vec<Result<u64, UsbError>> {
+        let batch = async { Ok::<_, UsbError>(self.pipe.enqueue_batch(transfers).await) };
+        match self.drive(batch) {
+            Ok(results) => results,
+            Err(err) => transfers.iter().map(|_| Err(err)).collect(),
+        }
+    }
+}
+
+///`Pipe` for the device requests of a device
+pub struct Device<'d, 'a> {
+    device: &'d UsbDevice<'a>,
+    timeout: Option<Duration>,
+    cancel: Option<Cancel>,
+}
+
+impl<'d, 'a> Device<'d, 'a> {
+    pub fn new(device: &'d UsbDevice<'a>) -> Self {
+        Self {
+            device,
+            timeout: None,
+            cancel: None,
+        }
+    }
+
+    ///aborts requests still running after `timeout`, failing them with
+    ///`UsbError::OperationTimedOut`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancel_with(mut self, cancel: Cancel) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn drive<T>(&self, transfer: impl Future<Output = Result<T, UsbError>>) -> Result<T, UsbError> {
+        drive(transfer, self.timeout, self.cancel.as_ref(), || {
+            self.device.abort_device_requests(AbortOption::Asynchronous)
+        })
+    }
+
+    pub fn device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        self.drive(self.device.enqueue_device_request(request))
+    }
+
+    pub fn device_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: &[u8],
+    ) -> Result<u64, UsbError> {
+        self.drive(self.device.enqueue_device_request_with_data(request, data))
+    }
+}
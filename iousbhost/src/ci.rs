@@ -0,0 +1,2992 @@
+use crate::*;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use iousbhost_sys::*;
+use std::time::{Duration, Instant};
+
+///a single state change observed on one of the CI state machines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateTransition<S> {
+    pub from: S,
+    pub to: S,
+}
+
+///fans state transitions out to every receiver handed out by `transitions()`
+struct StateObservers<S> {
+    senders: std::sync::Mutex<Vec<std::sync::mpsc::Sender<StateTransition<S>>>>,
+}
+
+impl<S: From<u32>> StateObservers<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            senders: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    fn subscribe(&self) -> std::sync::mpsc::Receiver<StateTransition<S>> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        if let Ok(senders) = &mut self.senders.lock() {
+            senders.push(tx);
+        }
+        rx
+    }
+
+    ///runs `op`, publishing a transition if the raw state reported by `state` changed across it
+    fn observe<R>(&self, state: impl Fn() -> u32, op: impl FnOnce() -> R) -> R {
+        let from = state();
+        let res = op();
+        let to = state();
+        if from != to {
+            if let Ok(senders) = &mut self.senders.lock() {
+                senders.retain(|tx| {
+                    tx.send(StateTransition {
+                        from: from.into(),
+                        to: to.into(),
+                    })
+                    .is_ok()
+                });
+            }
+        }
+        res
+    }
+}
+
+pub struct EndpointStateMachine {
+    pub(crate) inner: IOUSBHostCIEndpointStateMachine,
+    observers: StateObservers<EndpointState>,
+}
+
+impl EndpointStateMachine {
+    pub fn inspect_command(&self, command: &Message<'_>) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !unsafe {
+            self.inner
+                .inspectCommand_error_(command.inner.as_ref(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_inspected(unsafe { self.inner.controllerInterface() }, command, &res);
+        res
+    }
+
+    pub fn respond(&self, command: &Message<'_>, status: MessageStatus) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !self.observers.observe(
+            || unsafe { self.inner.endpointState() },
+            || unsafe {
+                self.inner.respondToCommand_status_error_(
+                    command.inner.as_ref(),
+                    status.into(),
+                    &mut *err,
+                )
+            },
+        ) {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_responded(
+            unsafe { self.inner.controllerInterface() },
+            command,
+            status,
+            &res,
+        );
+        res
+    }
+
+    pub fn process_doorbell(&self, doorbell: u32) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !self.observers.observe(
+            || unsafe { self.inner.endpointState() },
+            || unsafe { self.inner.processDoorbell_error_(doorbell, &mut *err) },
+        ) {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn enqueue_transfer_completion_for_message(
+        &self,
+        message: &Message<'_>,
+        status: MessageStatus,
+        transfer_length: u64,
+    ) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !self.observers.observe(
+            || unsafe { self.inner.endpointState() },
+            || unsafe {
+                self.inner
+                    .enqueueTransferCompletionForMessage_status_transferLength_error_(
+                        message.inner.as_ref(),
+                        status.into(),
+                        transfer_length,
+                        &mut *err,
+                    )
+            },
+        ) {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn endpoint_state(&self) -> EndpointState {
+        unsafe { self.inner.endpointState() }.into()
+    }
+
+    ///receives every endpoint state change caused by commands handled through this state machine
+    pub fn transitions(&self) -> std::sync::mpsc::Receiver<StateTransition<EndpointState>> {
+        self.observers.subscribe()
+    }
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.deviceAddress() }
+    }
+
+    pub fn endpoint_address(&self) -> u64 {
+        unsafe { self.inner.endpointAddress() }
+    }
+
+    pub fn current_transfer_message(&self) -> Option<Message<'_>> {
+        Message::new(unsafe { self.inner.currentTransferMessage() })
+    }
+
+    ///creates the state machine of the endpoint an EndpointCreate command asks for, the command
+    ///is left for the caller to answer
+    pub fn new(interface: &ControllerInterface, command: &Message<'_>) -> Result<Self, UsbError> {
+        let mut err = NSErr::new();
+        let machine = unsafe {
+            IOUSBHostCIEndpointStateMachine::alloc().initWithInterface_command_error_(
+                interface.inner,
+                command.inner.as_ref(),
+                &mut *err,
+            )
+        };
+        if err.is_err() || machine.is_null() {
+            return Err(err.into());
+        }
+        Ok(Self {
+            inner: IOUSBHostCIEndpointStateMachine(machine),
+            observers: StateObservers::new(),
+        })
+    }
+
+    ///`new` after checking the command's endpoint descriptor against a device running at
+    ///`speed`, invalid ones are answered with `MessageStatus::BadArgument`
+    pub fn create(
+        interface: &ControllerInterface,
+        command: &Message<'_>,
+        speed: DeviceSpeed,
+    ) -> Result<Self, UsbError> {
+        Self::create_with(interface, command, speed, |_, _| MessageStatus::BadArgument)
+    }
+
+    ///`create` with `on_invalid` choosing the status invalid descriptors are answered with,
+    ///`MessageStatus::Success` accepts the endpoint anyway and leaves the command to the caller
+    pub fn create_with(
+        interface: &ControllerInterface,
+        command: &Message<'_>,
+        speed: DeviceSpeed,
+        on_invalid: impl FnOnce(&EndpointDescriptor<'_>, EndpointDescriptorError) -> MessageStatus,
+    ) -> Result<Self, UsbError> {
+        let machine = Self::new(interface, command)?;
+        let Some(descriptor) = command.endpoint_descriptor() else {
+            machine.respond(command, MessageStatus::BadArgument)?;
+            return Err(UsbError::InvalidEndpointDescriptor(
+                EndpointDescriptorError::Missing,
+            ));
+        };
+        if let Err(err) = validate_endpoint_descriptor(&descriptor, speed) {
+            crate::diag!(warn, "invalid endpoint descriptor in {}: {}", command, err);
+            let status = on_invalid(&descriptor, err);
+            if status != MessageStatus::Success {
+                machine.respond(command, status)?;
+                return Err(UsbError::InvalidEndpointDescriptor(err));
+            }
+        }
+        Ok(machine)
+    }
+
+    pub fn controller_interface(&self) -> ControllerInterface {
+        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
+    }
+}
+
+///what is wrong with the endpoint descriptor of an EndpointCreate command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointDescriptorError {
+    ///the command does not point to a descriptor
+    Missing,
+    ///too short, or not an endpoint descriptor
+    Malformed,
+    ///the device speed is not one endpoints can be created for
+    UnknownSpeed(DeviceSpeed),
+    ///the transfer type does not exist at the speed, such as bulk on low speed devices
+    UnsupportedType(EndpointType),
+    ///reserved bits of wMaxPacketSize are set
+    ReservedBits(u16),
+    ///the max packet size is not allowed for the transfer type at the speed
+    MaxPacketSize { size: u16, max: u16 },
+    ///a high speed periodic endpoint asks for more than 3 transactions per microframe, or a non
+    ///periodic one for more than 1
+    Mult(u8),
+    ///bInterval is outside the range of the transfer type at the speed
+    Interval { interval: u8, min: u8, max: u8 },
+}
+
+impl core::fmt::Display for EndpointDescriptorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use EndpointDescriptorError as EDE;
+        match self {
+            EDE::Missing => write!(f, "the command carries no endpoint descriptor"),
+            EDE::Malformed => write!(f, "malformed endpoint descriptor"),
+            EDE::UnknownSpeed(speed) => write!(f, "no endpoints exist at speed {speed:?}"),
+            EDE::UnsupportedType(ty) => write!(f, "{ty:?} endpoints do not exist at this speed"),
+            EDE::ReservedBits(size) => {
+                write!(f, "wMaxPacketSize {size:#06x} sets reserved bits")
+            }
+            EDE::MaxPacketSize { size, max } => {
+                write!(f, "max packet size {size} is not allowed, at most {max}")
+            }
+            EDE::Mult(mult) => write!(f, "{mult} transactions per microframe are not allowed"),
+            EDE::Interval { interval, min, max } => {
+                write!(f, "bInterval {interval} is outside {min}..={max}")
+            }
+        }
+    }
+}
+
+///checks an endpoint descriptor against the limits chapter 9 of the USB 2.0 and 3.x
+///specifications put on endpoints of a device running at `speed`
+pub fn validate_endpoint_descriptor(
+    descriptor: &EndpointDescriptor<'_>,
+    speed: DeviceSpeed,
+) -> Result<(), EndpointDescriptorError> {
+    use EndpointDescriptorError as EDE;
+    use EndpointType as ET;
+    if descriptor.length() < 7 || descriptor.descriptor_type() != DescriptorType::Endpoint {
+        return Err(EDE::Malformed);
+    }
+    let ty = descriptor.transfer_type();
+    let raw = descriptor.max_packet_size();
+    if raw & 0xe000 != 0 {
+        return Err(EDE::ReservedBits(raw));
+    }
+    let size = raw & 0x07ff;
+    //additional transactions per microframe, only high speed periodic endpoints may have them
+    let mult = ((raw >> 11) & 3) as u8 + 1;
+    let periodic = matches!(ty, ET::Interrupt | ET::Isochronous);
+    let interval = descriptor.interval();
+
+    let (sizes, max, intervals): (&[u16], u16, Option<(u8, u8)>) = match (speed, ty) {
+        (DeviceSpeed::None | DeviceSpeed::Other(_), _) => return Err(EDE::UnknownSpeed(speed)),
+        (DeviceSpeed::Low, ET::Bulk | ET::Isochronous) => return Err(EDE::UnsupportedType(ty)),
+        (DeviceSpeed::Low, ET::Control) => (&[8], 8, None),
+        (DeviceSpeed::Low, ET::Interrupt) => (&[], 8, Some((1, 255))),
+        (DeviceSpeed::Full, ET::Control | ET::Bulk) => (&[8, 16, 32, 64], 64, None),
+        (DeviceSpeed::Full, ET::Interrupt) => (&[], 64, Some((1, 255))),
+        (DeviceSpeed::Full, ET::Isochronous) => (&[], 1023, Some((1, 16))),
+        (DeviceSpeed::High, ET::Control) => (&[64], 64, None),
+        (DeviceSpeed::High, ET::Bulk) => (&[512], 512, None),
+        (DeviceSpeed::High, ET::Interrupt | ET::Isochronous) => (&[], 1024, Some((1, 16))),
+        (_, ET::Control) => (&[512], 512, None),
+        (_, ET::Bulk) => (&[1024], 1024, None),
+        (_, ET::Interrupt | ET::Isochronous) => (&[], 1024, Some((1, 16))),
+    };
+
+    if (mult > 1 && !(speed == DeviceSpeed::High && periodic)) || mult > 3 {
+        return Err(EDE::Mult(mult));
+    }
+    let fits = match sizes {
+        [] => size <= max,
+        sizes => sizes.contains(&size),
+    };
+    if !fits {
+        return Err(EDE::MaxPacketSize { size, max });
+    }
+    if let Some((min, max)) = intervals {
+        if !(min..=max).contains(&interval) {
+            return Err(EDE::Interval { interval, min, max });
+        }
+    }
+    Ok(())
+}
+
+pub struct Message<'a> {
+    pub(crate) inner: NonNull<IOUSBHostCIMessage>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl Message<'_> {
+    const CONTROL_TYPE: u32 = 0x3f;
+    const CONTROL_NO_RESPONSE: u32 = 1 << 14;
+    const CONTROL_VALID: u32 = 1 << 15;
+    const DATA0_TRANSFER_LENGTH: u32 = 0x0fff_ffff;
+
+    pub(crate) fn new(ptr: *const IOUSBHostCIMessage) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBHostCIMessage)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+    pub fn control(&self) -> u32 {
+        unsafe { self.inner.as_ref().control }
+    }
+
+    pub fn data_0(&self) -> u32 {
+        unsafe { self.inner.as_ref().data0 }
+    }
+
+    pub fn data_1(&self) -> u64 {
+        unsafe { self.inner.as_ref().data1 }
+    }
+
+    pub fn data(&self) -> (u32, u64) {
+        let msg = unsafe { self.inner.as_ref() };
+        (msg.data0, msg.data1)
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        (self.control() & Self::CONTROL_TYPE).into()
+    }
+
+    ///the status a command was answered with, or a transfer completed with
+    pub fn status(&self) -> MessageStatus {
+        ((self.control() & u32::from(MessageCommand::ControlStatus))
+            >> MessageCommand::StatusPhase as u32)
+            .into()
+    }
+
+    pub fn no_response(&self) -> bool {
+        self.control() & Self::CONTROL_NO_RESPONSE != 0
+    }
+
+    pub fn valid(&self) -> bool {
+        self.control() & Self::CONTROL_VALID != 0
+    }
+
+    ///the root port a port command is addressed to
+    pub fn root_port(&self) -> u8 {
+        (self.data_0() & u32::from(MessageCommand::Data0RootPort)) as u8
+    }
+
+    ///the device a device or endpoint command is addressed to, or a transfer completion is for
+    pub fn device_address(&self) -> u8 {
+        match self.message_type() {
+            MessageType::TransferComplete => self.completion_field(
+                TransferCompletionMessage::DeviceAddress,
+                TransferCompletionMessage::DeviceAddressPhase,
+            ),
+            _ => (self.data_0() & u32::from(MessageCommand::Data0DeviceAddress)) as u8,
+        }
+    }
+
+    ///the endpoint an endpoint command is addressed to, or a transfer completion is for
+    pub fn endpoint_address(&self) -> EndpointAddress {
+        EndpointAddress(match self.message_type() {
+            MessageType::TransferComplete => self.completion_field(
+                TransferCompletionMessage::EndpointAddress,
+                TransferCompletionMessage::EndpointAddressPhase,
+            ),
+            _ => ((self.data_0() & u32::from(MessageCommand::Data0EndpointAddress)) >> 8) as u8,
+        })
+    }
+
+    //a transfer completion carries its addressing in the control word rather than in data 0
+    fn completion_field(
+        &self,
+        mask: TransferCompletionMessage,
+        phase: TransferCompletionMessage,
+    ) -> u8 {
+        ((self.control() as u64 & u64::from(mask)) >> u64::from(phase)) as u8
+    }
+
+    pub fn stream_id(&self) -> u16 {
+        ((self.data_0() & u32::from(MessageCommand::Data0StreamId))
+            >> MessageCommand::Data0StreamIdPhase as u32) as u16
+    }
+
+    ///the endpoint descriptor an EndpointCreate or EndpointUpdate command points to
+    pub fn endpoint_descriptor(&self) -> Option<EndpointDescriptor<'_>> {
+        match self.message_type() {
+            MessageType::EndpointCreate | MessageType::EndpointUpdate => EndpointDescriptor::new(
+                (self.data_1() & u64::from(EndpointCreateCommand::Data1Descriptor))
+                    as *const IOUSBEndpointDescriptor,
+            ),
+            _ => None,
+        }
+    }
+
+    ///the requested length of a normal or isochronous transfer, or the transferred length of a
+    ///transfer completion
+    pub fn transfer_length(&self) -> Option<u32> {
+        match self.message_type() {
+            MessageType::NormalTransfer
+            | MessageType::IsochronousTransfer
+            | MessageType::TransferComplete => Some(self.data_0() & Self::DATA0_TRANSFER_LENGTH),
+            _ => None,
+        }
+    }
+
+    ///the request of a SetupTransfer, whose data 1 is the setup packet
+    pub fn setup_request(&self) -> Option<DeviceRequest> {
+        if self.message_type() != MessageType::SetupTransfer {
+            return None;
+        }
+        let setup = self.data_1().to_le_bytes();
+        Some(DeviceRequest::new(
+            setup[0].into(),
+            setup[1],
+            u16::from_le_bytes([setup[2], setup[3]]),
+            u16::from_le_bytes([setup[4], setup[5]]),
+            u16::from_le_bytes([setup[6], setup[7]]),
+        ))
+    }
+
+    ///the buffer of a NormalTransfer, mapped into this process; `transfer_length` bytes are read
+    ///from it for OUT endpoints and written into it for IN endpoints
+    pub fn transfer_buffer(&self) -> Option<NonNull<u8>> {
+        match self.message_type() {
+            MessageType::NormalTransfer => NonNull::new(self.data_1() as *mut u8),
+            _ => None,
+        }
+    }
+}
+
+///the decoded message type, status and addressing, e.g.
+///`EndpointCreate status=Success device=1 endpoint=0x81`
+impl core::fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use MessageType as MT;
+        let ty = self.message_type();
+        write!(f, "{:?} status={:?}", ty, self.status())?;
+        match ty {
+            MT::PortPowerOn
+            | MT::PortPowerOff
+            | MT::PortResume
+            | MT::PortSuspend
+            | MT::PortReset
+            | MT::PortDisable
+            | MT::PortStatus
+            | MT::PortEvent => write!(f, " port={}", self.root_port())?,
+            MT::DeviceCreate
+            | MT::DeviceDestroy
+            | MT::DeviceStart
+            | MT::DevicePause
+            | MT::DeviceUpdate => write!(f, " device={}", self.device_address())?,
+            MT::EndpointCreate
+            | MT::EndpointDesroy
+            | MT::EndpointPause
+            | MT::EndpointUpdate
+            | MT::EndpointRest
+            | MT::EndpointSetNextTransfer
+            | MT::TransferComplete => write!(
+                f,
+                " device={} endpoint={:#04x}",
+                self.device_address(),
+                self.endpoint_address().0
+            )?,
+            _ => {}
+        }
+        if let Some(length) = self.transfer_length() {
+            write!(f, " length={}", length)?;
+        }
+        if !self.valid() {
+            write!(f, " (invalid)")?;
+        }
+        Ok(())
+    }
+}
+
+///how often a controller interface raises interrupts to its kernel driver, the ones enqueued in
+///between being coalesced
+///
+///rates are bounded by what xHCI's interrupter moderation can express, an interval of up to 0xffff
+///steps of 250ns; 0 turns moderation off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterruptModeration {
+    rate_hz: u64,
+}
+
+impl InterruptModeration {
+    ///the slowest moderated rate, 0xffff steps of 250ns apart
+    pub const MIN_RATE_HZ: u64 = 62;
+    ///one interrupt per 250ns step
+    pub const MAX_RATE_HZ: u64 = 4_000_000;
+    ///every interrupt is raised as soon as it is enqueued
+    pub const UNMODERATED: Self = Self { rate_hz: 0 };
+
+    pub fn new(rate_hz: u64) -> Result<Self, InterruptModerationError> {
+        Self::builder().rate_hz(rate_hz).build()
+    }
+
+    pub fn builder() -> InterruptModerationBuilder {
+        InterruptModerationBuilder::default()
+    }
+
+    pub fn rate_hz(&self) -> u64 {
+        self.rate_hz
+    }
+
+    pub fn is_moderated(&self) -> bool {
+        self.rate_hz != 0
+    }
+
+    ///the time between interrupts, `None` when unmoderated
+    pub fn interval(&self) -> Option<Duration> {
+        self.is_moderated()
+            .then(|| Duration::from_nanos(1_000_000_000 / self.rate_hz))
+    }
+}
+
+impl TryFrom<u64> for InterruptModeration {
+    type Error = InterruptModerationError;
+    fn try_from(rate_hz: u64) -> Result<Self, Self::Error> {
+        Self::new(rate_hz)
+    }
+}
+
+///checks an interrupt rate before it reaches the controller, see
+///`ControllerBuilder::interrupt_moderation`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InterruptModerationBuilder {
+    rate_hz: u64,
+}
+
+impl InterruptModerationBuilder {
+    pub fn rate_hz(mut self, rate_hz: u64) -> Self {
+        self.rate_hz = rate_hz;
+        self
+    }
+
+    ///at most one interrupt per `interval`, rounded to the nearest rate in Hz
+    pub fn interval(mut self, interval: Duration) -> Self {
+        let nanos = interval.as_nanos();
+        self.rate_hz = match nanos {
+            0 => u64::MAX,
+            _ => ((1_000_000_000 + nanos / 2) / nanos) as u64,
+        };
+        self
+    }
+
+    pub fn unmoderated(mut self) -> Self {
+        self.rate_hz = 0;
+        self
+    }
+
+    pub fn build(self) -> Result<InterruptModeration, InterruptModerationError> {
+        let range = InterruptModeration::MIN_RATE_HZ..=InterruptModeration::MAX_RATE_HZ;
+        if self.rate_hz != 0 && !range.contains(&self.rate_hz) {
+            return Err(InterruptModerationError::RateOutOfRange(self.rate_hz));
+        }
+        Ok(InterruptModeration {
+            rate_hz: self.rate_hz,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterruptModerationError {
+    ///neither 0 nor within `MIN_RATE_HZ..=MAX_RATE_HZ`
+    RateOutOfRange(u64),
+}
+
+impl core::fmt::Display for InterruptModerationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InterruptModerationError::RateOutOfRange(rate_hz) => write!(
+                f,
+                "interrupt rate of {rate_hz}Hz is outside {}..={}Hz",
+                InterruptModeration::MIN_RATE_HZ,
+                InterruptModeration::MAX_RATE_HZ
+            ),
+        }
+    }
+}
+
+pub struct ControllerInterface {
+    pub(crate) inner: IOUSBHostControllerInterface,
+}
+
+impl ControllerInterface {
+    pub(crate) fn new(inner: IOUSBHostControllerInterface) -> Self {
+        Self { inner }
+    }
+
+    ///takes ownership of a controller interface created outside of the crate, it is destroyed
+    ///when dropped
+    ///
+    ///# Safety
+    ///`raw` has to be a valid `IOUSBHostControllerInterface` nothing else destroys
+    pub unsafe fn from_raw(raw: IOUSBHostControllerInterface) -> Self {
+        Self::new(raw)
+    }
+
+    ///the underlying object, still owned by `self` so it must not be destroyed through it
+    pub fn as_raw(&self) -> IOUSBHostControllerInterface {
+        self.inner
+    }
+
+    pub fn enqueue_interrupts(
+        &self,
+        msg: &Message<'_>,
+        expedited: Option<bool>,
+        count: Option<u64>,
+    ) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let is_err = unsafe {
+            match (expedited, count) {
+                (Some(expd), Some(count)) => self.inner.enqueueInterrupts_count_expedite_error_(
+                    msg.inner.as_ref(),
+                    count,
+                    expd,
+                    &mut *err,
+                ),
+                (Some(expd), None) => {
+                    self.inner
+                        .enqueueInterrupt_expedite_error_(msg.inner.as_ref(), expd, &mut *err)
+                }
+                (None, Some(count)) => {
+                    self.inner
+                        .enqueueInterrupts_count_error_(msg.inner.as_ref(), count, &mut *err)
+                }
+                (None, None) => self
+                    .inner
+                    .enqueueInterrupt_error_(msg.inner.as_ref(), &mut *err),
+            }
+        };
+        if is_err {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn message_description(&self, msg: &Message<'_>) -> String {
+        string_from_ns(&self.message_description_raw(msg))
+    }
+
+    ///our decoding of `msg` followed by IOUSBHost's own description of it, for logs
+    pub fn describe(&self, msg: &Message<'_>) -> String {
+        format!("{} ({})", msg, self.message_description(msg))
+    }
+
+    ///`message_description` as the `NSString` IOUSBHost returns
+    pub fn message_description_raw(&self, msg: &Message<'_>) -> NSString {
+        unsafe { self.inner.descriptionForMessage_(msg.inner.as_ref()) }
+    }
+
+    pub fn port_state_machine_for_command(
+        &self,
+        cmd: &Message<'_>,
+    ) -> Result<PortStateMachine, UsbError> {
+        let mut err = NSErr::new();
+        let res = unsafe {
+            self.inner
+                .getPortStateMachineForCommand_error_(cmd.inner.as_ref(), &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(PortStateMachine::new(res))
+        }
+    }
+
+    pub fn port_state_machine_for_port(&self, port: u64) -> Result<PortStateMachine, UsbError> {
+        let mut err = NSErr::new();
+        let res = unsafe {
+            self.inner
+                .getPortStateMachineForPort_error_(port, &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(PortStateMachine::new(res))
+        }
+    }
+
+    pub fn port_capabilities(&self, port: u64) -> Option<Message<'_>> {
+        Message::new(unsafe { self.inner.capabilitiesForPort_(port) })
+    }
+
+    pub fn queue(&self) -> Queue {
+        Queue::new(unsafe { self.inner.queue() })
+    }
+
+    pub fn interupt_rate_hz(&self) -> u64 {
+        unsafe { self.inner.interruptRateHz() }
+    }
+
+    ///sets the interrupt rate as it is, see `set_interrupt_moderation` for a checked one
+    pub fn set_interrupt_rate_hz(&self, rate: u64) {
+        unsafe { self.inner.setInterruptRateHz_(rate) }
+    }
+
+    ///the moderation currently in effect, as reported by the framework
+    pub fn interrupt_moderation(&self) -> InterruptModeration {
+        InterruptModeration {
+            rate_hz: self.interupt_rate_hz(),
+        }
+    }
+
+    pub fn set_interrupt_moderation(&self, moderation: InterruptModeration) {
+        self.set_interrupt_rate_hz(moderation.rate_hz)
+    }
+
+    pub fn controller_state_machine(&self) -> ControllerStateMachine {
+        ControllerStateMachine::new(unsafe { self.inner.controllerStateMachine() })
+    }
+
+    pub fn capabilities(&self) -> Option<Message<'_>> {
+        Message::new(unsafe { self.inner.capabilities() })
+    }
+
+    pub fn uuid(&self) -> NSUUID {
+        unsafe { self.inner.uuid() }
+    }
+
+    ///records every command inspected or responded to through this controller's state machines,
+    ///keeping the last `capacity`, and logs each one; the whole record is logged as a warning
+    ///whenever a command is rejected or a response fails
+    pub fn enable_tracing(&self, capacity: usize) {
+        let mut traces = command_traces();
+        traces.insert(
+            self.inner.0 as usize,
+            CommandTrace {
+                capacity: capacity.max(1),
+                commands: std::collections::VecDeque::new(),
+            },
+        );
+        TRACING.store(traces.len(), Ordering::Release);
+    }
+
+    pub fn disable_tracing(&self) {
+        if TRACING.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let mut traces = command_traces();
+        traces.remove(&(self.inner.0 as usize));
+        TRACING.store(traces.len(), Ordering::Release);
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        TRACING.load(Ordering::Acquire) > 0
+            && command_traces().contains_key(&(self.inner.0 as usize))
+    }
+
+    ///the recorded commands, oldest first
+    pub fn traced_commands(&self) -> Vec<TracedCommand> {
+        command_traces()
+            .get(&(self.inner.0 as usize))
+            .map(|trace| trace.commands.iter().map(|(_, traced)| *traced).collect())
+            .unwrap_or_default()
+    }
+
+    ///logs the recorded commands as a warning, e.g. on `Exception::ProtocolError`
+    pub fn dump_trace(&self) {
+        if let Some(trace) = command_traces().get(&(self.inner.0 as usize)) {
+            trace.dump();
+        }
+    }
+}
+
+///creates a user space controller interface, checking its capabilities before they are submitted
+pub struct ControllerBuilder {
+    capabilities: ControllerCapabilities,
+    queue: Option<Queue>,
+    moderation: InterruptModeration,
+}
+
+impl ControllerBuilder {
+    pub fn new(capabilities: ControllerCapabilities) -> Self {
+        Self {
+            capabilities,
+            queue: None,
+            moderation: InterruptModeration::UNMODERATED,
+        }
+    }
+
+    ///the rate the controller raises interrupts at from the start, unmoderated by default
+    pub fn interrupt_moderation(mut self, moderation: InterruptModeration) -> Self {
+        self.moderation = moderation;
+        self
+    }
+
+    ///runs the handlers on `queue` instead of a new serial queue
+    pub fn queue(mut self, queue: Queue) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    ///creates the controller, `command` being called with every command the kernel driver sends
+    ///and `doorbell` with the doorbells it rings; the controller the handlers are given is only
+    ///borrowed for the call
+    pub fn build(
+        self,
+        command: impl Fn(&ControllerInterface, &Message<'_>) + 'static,
+        doorbell: impl Fn(&ControllerInterface, &[IOUSBHostCIDoorbell]) + 'static,
+    ) -> Result<ControllerInterface, UsbError> {
+        let capabilities = self
+            .capabilities
+            .to_bytes()
+            .map_err(UsbError::InvalidCapabilities)?;
+        let queue = self.queue.unwrap_or_else(|| {
+            let label = &0;
+            let attr = NSObject(core::ptr::null_mut());
+            Queue::created(unsafe { dispatch_queue_create(label, attr) })
+        });
+        let command = block::ConcreteBlock::new(
+            move |controller: IOUSBHostControllerInterface, message: IOUSBHostCIMessage| {
+                //the framework owns the controller, the handle must not destroy it
+                let controller = core::mem::ManuallyDrop::new(ControllerInterface::new(controller));
+                if let Some(message) = Message::new(&message) {
+                    command(&controller, &message);
+                }
+            },
+        )
+        .copy();
+        let doorbell = block::ConcreteBlock::new(
+            move |controller: IOUSBHostControllerInterface,
+                  doorbells: *mut IOUSBHostCIDoorbell,
+                  count: u32| {
+                let controller = core::mem::ManuallyDrop::new(ControllerInterface::new(controller));
+                let doorbells = match doorbells.is_null() {
+                    true => &[][..],
+                    false => unsafe { core::slice::from_raw_parts(doorbells, count as usize) },
+                };
+                doorbell(&controller, doorbells);
+            },
+        )
+        .copy();
+        let data = MutData::with_data(&capabilities).raw();
+        let mut err = NSErr::new();
+        //the framework copies both blocks
+        let controller = unsafe {
+            IOUSBHostControllerInterface::alloc()
+                .initWithCapabilities_queue_interruptRateHz_error_commandHandler_doorbellHandler_interestHandler_(
+                    NSData(data.0),
+                    queue.inner.clone(),
+                    self.moderation.rate_hz,
+                    &mut *err,
+                    &*command as *const block::Block<_, ()> as *mut c_void,
+                    &*doorbell as *const block::Block<_, ()> as *mut c_void,
+                    core::ptr::null_mut(),
+                )
+        };
+        if err.is_err() || controller.is_null() {
+            return Err(err.into());
+        }
+        Ok(ControllerInterface::new(IOUSBHostControllerInterface(
+            controller,
+        )))
+    }
+}
+
+///a command seen by one of the CI state machines while tracing, see
+///`ControllerInterface::enable_tracing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TracedCommand {
+    pub message_type: MessageType,
+    pub control: u32,
+    pub data_0: u32,
+    pub data_1: u64,
+    pub received: Instant,
+    ///whether the state machine accepted the command when it was inspected
+    pub accepted: bool,
+    ///the status the command was answered with, `None` until it is responded to
+    pub response: Option<MessageStatus>,
+    ///the time between inspecting the command and responding to it
+    pub latency: Option<Duration>,
+    pub response_failed: bool,
+}
+
+impl TracedCommand {
+    fn new(command: &Message<'_>, received: Instant) -> Self {
+        Self {
+            message_type: command.message_type(),
+            control: command.control(),
+            data_0: command.data_0(),
+            data_1: command.data_1(),
+            received,
+            accepted: true,
+            response: None,
+            latency: None,
+            response_failed: false,
+        }
+    }
+}
+
+struct CommandTrace {
+    capacity: usize,
+    //the commands along with the address of their message, to match responses to them
+    commands: std::collections::VecDeque<(usize, TracedCommand)>,
+}
+
+impl CommandTrace {
+    fn push(&mut self, message: usize, traced: TracedCommand) {
+        if self.commands.len() == self.capacity {
+            self.commands.pop_front();
+        }
+        self.commands.push_back((message, traced));
+    }
+
+    //records the response on the command it answers, or on a new entry if the command was not
+    //inspected while tracing
+    fn responded(
+        &mut self,
+        command: &Message<'_>,
+        status: MessageStatus,
+        res: &Result<(), UsbError>,
+        now: Instant,
+    ) {
+        let message = command.inner.as_ptr() as usize;
+        let pending = self
+            .commands
+            .iter_mut()
+            .rev()
+            .find(|(msg, traced)| *msg == message && traced.response.is_none());
+        match pending {
+            Some((_, traced)) => {
+                traced.response = Some(status);
+                traced.latency = Some(now - traced.received);
+                traced.response_failed = res.is_err();
+            }
+            None => {
+                let mut traced = TracedCommand::new(command, now);
+                traced.response = Some(status);
+                traced.response_failed = res.is_err();
+                self.push(message, traced);
+            }
+        }
+    }
+
+    fn dump(&self) {
+        crate::diag!(warn, "last {} ci commands:", self.commands.len());
+        for (_, traced) in &self.commands {
+            crate::diag!(warn, "  {:?}", traced);
+        }
+    }
+}
+
+//how many controllers are traced, so the state machines of untraced ones skip the lock
+static TRACING: AtomicUsize = AtomicUsize::new(0);
+
+//kept by controller interface object rather than on `ControllerInterface`, as every state machine
+//hands out a wrapper of its own; a controller's entry goes with it when it is dropped
+fn command_traces() -> std::sync::MutexGuard<'static, std::collections::HashMap<usize, CommandTrace>>
+{
+    static TRACES: std::sync::OnceLock<
+        std::sync::Mutex<std::collections::HashMap<usize, CommandTrace>>,
+    > = std::sync::OnceLock::new();
+    TRACES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn trace_inspected(
+    controller: IOUSBHostControllerInterface,
+    command: &Message<'_>,
+    res: &Result<(), UsbError>,
+) {
+    if TRACING.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let mut traces = command_traces();
+    let Some(trace) = traces.get_mut(&(controller.0 as usize)) else {
+        return;
+    };
+    let mut traced = TracedCommand::new(command, Instant::now());
+    traced.accepted = res.is_ok();
+    trace.push(command.inner.as_ptr() as usize, traced);
+    match res {
+        Ok(()) => crate::diag!(trace, "ci command {}", command),
+        Err(err) => {
+            crate::diag!(warn, "ci command {} rejected: {:?}", command, err);
+            trace.dump();
+        }
+    }
+}
+
+fn trace_responded(
+    controller: IOUSBHostControllerInterface,
+    command: &Message<'_>,
+    status: MessageStatus,
+    res: &Result<(), UsbError>,
+) {
+    if TRACING.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let mut traces = command_traces();
+    let Some(trace) = traces.get_mut(&(controller.0 as usize)) else {
+        return;
+    };
+    trace.responded(command, status, res, Instant::now());
+    match res {
+        Ok(()) => crate::diag!(trace, "ci response {:?} to {}", status, command),
+        Err(err) => {
+            crate::diag!(
+                warn,
+                "ci response {:?} to {} failed: {:?}",
+                status,
+                command,
+                err
+            );
+            trace.dump();
+        }
+    }
+}
+
+pub struct ControllerStateMachine {
+    pub(crate) inner: IOUSBHostCIControllerStateMachine,
+    observers: StateObservers<ControllerState>,
+}
+
+impl ControllerStateMachine {
+    pub(crate) fn new(inner: IOUSBHostCIControllerStateMachine) -> Self {
+        Self {
+            inner,
+            observers: StateObservers::new(),
+        }
+    }
+
+    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !unsafe {
+            self.inner
+                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_inspected(unsafe { self.inner.controllerInterface() }, cmd, &res);
+        res
+    }
+
+    pub fn respond(
+        &self,
+        cmd: &Message<'_>,
+        status: MessageStatus,
+        frame_timestamp: Option<(u64, u64)>,
+    ) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let ok = self.observers.observe(
+            || unsafe { self.inner.controllerState() },
+            || unsafe {
+                match frame_timestamp {
+                    Some((frame, timestamp)) => {
+                        self.inner.respondToCommand_status_frame_timestamp_error_(
+                            cmd.inner.as_ref(),
+                            status.into(),
+                            frame,
+                            timestamp,
+                            &mut *err,
+                        )
+                    }
+                    None => self.inner.respondToCommand_status_error_(
+                        cmd.inner.as_ref(),
+                        status.into(),
+                        &mut *err,
+                    ),
+                }
+            },
+        );
+        let res = if !ok { Err(err.into()) } else { Ok(()) };
+        trace_responded(
+            unsafe { self.inner.controllerInterface() },
+            cmd,
+            status,
+            &res,
+        );
+        res
+    }
+
+    ///`enqueue_updated` with the timestamp as a `HostTime`
+    pub fn enqueue_frame_time(&self, frame: u64, time: HostTime) -> Result<(), UsbError> {
+        self.enqueue_updated(frame, time.ticks())
+    }
+
+    pub fn enqueue_updated(&self, frame: u64, timestamp: u64) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .enqueueUpdatedFrame_timestamp_error_(frame, timestamp, &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn controller_state(&self) -> ControllerState {
+        unsafe { self.inner.controllerState() }.into()
+    }
+
+    ///handles a `ControllerPowerOn` command, moving the controller from off to paused
+    pub fn power_on(&self, cmd: &Message<'_>) -> Result<ControllerState, UsbError> {
+        self.transition(cmd, MessageType::ControllerPowerOn)
+    }
+
+    ///handles a `ControllerPause` command, moving the controller from active to paused
+    pub fn pause(&self, cmd: &Message<'_>) -> Result<ControllerState, UsbError> {
+        self.transition(cmd, MessageType::ControllerPause)
+    }
+
+    ///handles a `ControllerStart` command, moving the controller from paused to active
+    pub fn start(&self, cmd: &Message<'_>) -> Result<ControllerState, UsbError> {
+        self.transition(cmd, MessageType::ControllerStart)
+    }
+
+    ///handles a `ControllerPowerOff` command, moving the controller from paused to off
+    pub fn power_off(&self, cmd: &Message<'_>) -> Result<ControllerState, UsbError> {
+        self.transition(cmd, MessageType::ControllerPowerOff)
+    }
+
+    fn transition(
+        &self,
+        cmd: &Message<'_>,
+        command: MessageType,
+    ) -> Result<ControllerState, UsbError> {
+        let inspected = self.inspect_command(cmd);
+        answer_transition(
+            inspected,
+            cmd.message_type(),
+            command,
+            self.controller_state(),
+            |status| self.respond(cmd, status, None),
+        )
+    }
+
+    ///receives every controller state change caused by commands handled through this state machine
+    pub fn transitions(&self) -> std::sync::mpsc::Receiver<StateTransition<ControllerState>> {
+        self.observers.subscribe()
+    }
+
+    pub fn controller_interface(&self) -> ControllerInterface {
+        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
+    }
+}
+
+//every command is inspected and answered through `respond`, so the kernel driver is never left
+//waiting: ones the state machine rejects or for a transition the controller can not make with
+//`NotPermitted`, ones of another type than `command` with `BadArgument`
+fn answer_transition(
+    inspected: Result<(), UsbError>,
+    received: MessageType,
+    command: MessageType,
+    state: ControllerState,
+    mut respond: impl FnMut(MessageStatus) -> Result<(), UsbError>,
+) -> Result<ControllerState, UsbError> {
+    if let Err(err) = inspected {
+        respond(MessageStatus::NotPermitted)?;
+        return Err(err);
+    }
+    if received != command {
+        respond(MessageStatus::BadArgument)?;
+        return Err(UsbError::InvalidArgument);
+    }
+    let Some(next) = state.after(command) else {
+        respond(MessageStatus::NotPermitted)?;
+        return Err(UsbError::IllegalTransition(state, command));
+    };
+    respond(MessageStatus::Success)?;
+    Ok(next)
+}
+
+pub struct PortStateMachine {
+    pub(crate) inner: IOUSBHostCIPortStateMachine,
+    observers: StateObservers<PortState>,
+}
+
+impl PortStateMachine {
+    pub(crate) fn new(inner: IOUSBHostCIPortStateMachine) -> Self {
+        Self {
+            inner,
+            observers: StateObservers::new(),
+        }
+    }
+
+    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !unsafe {
+            self.inner
+                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_inspected(unsafe { self.inner.controllerInterface() }, cmd, &res);
+        res
+    }
+
+    pub fn respond(&self, cmd: &Message<'_>, status: MessageStatus) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !self.observers.observe(
+            || unsafe { self.inner.portState() },
+            || unsafe {
+                self.inner.respondToCommand_status_error_(
+                    cmd.inner.as_ref(),
+                    status.into(),
+                    &mut *err,
+                )
+            },
+        ) {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_responded(
+            unsafe { self.inner.controllerInterface() },
+            cmd,
+            status,
+            &res,
+        );
+        res
+    }
+
+    pub fn update_link_state(
+        &self,
+        link_state: LinkState,
+        speed: DeviceSpeed,
+        inhibit_link_state_change: bool,
+    ) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !self.observers.observe(
+            || unsafe { self.inner.portState() },
+            || unsafe {
+                self.inner
+                    .updateLinkState_speed_inhibitLinkStateChange_error_(
+                        link_state.into(),
+                        speed.into(),
+                        inhibit_link_state_change,
+                        &mut *err,
+                    )
+            },
+        ) {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn port_state(&self) -> PortState {
+        unsafe { self.inner.portState() }.into()
+    }
+
+    ///receives every port state change caused by commands or power changes made through this
+    ///state machine
+    pub fn transitions(&self) -> std::sync::mpsc::Receiver<StateTransition<PortState>> {
+        self.observers.subscribe()
+    }
+
+    pub fn port_status(&self) -> PortStatus {
+        unsafe { self.inner.portStatus() }.into()
+    }
+
+    pub fn controller_interface(&self) -> ControllerInterface {
+        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
+    }
+
+    pub fn powered(&self) -> bool {
+        unsafe { self.inner.powered() }
+    }
+
+    pub fn set_powered(&self, powered: bool) {
+        self.observers.observe(
+            || unsafe { self.inner.portState() },
+            || unsafe { self.inner.setPowered_(powered) },
+        )
+    }
+
+    pub fn connected(&self) -> bool {
+        unsafe { self.inner.connected() }
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        unsafe { self.inner.setConnected_(connected) }
+    }
+
+    pub fn overcurrent(&self) -> bool {
+        unsafe { self.inner.overcurrent() }
+    }
+
+    pub fn set_overcurrent(&self, overcurrent: bool) {
+        unsafe { self.inner.setOvercurrent_(overcurrent) }
+    }
+
+    pub fn link_state(&self) -> LinkState {
+        unsafe { self.inner.linkState() }.into()
+    }
+
+    pub fn speed(&self) -> DeviceSpeed {
+        unsafe { self.inner.speed() }.into()
+    }
+}
+
+///something a `Scenario` does to the virtual controller at a point of its timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScenarioAction {
+    ///connects a device of `speed` to `port`
+    Attach {
+        port: u64,
+        speed: DeviceSpeed,
+    },
+    Detach {
+        port: u64,
+    },
+    Overcurrent {
+        port: u64,
+        overcurrent: bool,
+    },
+    ///completes the endpoint's current transfer with `MessageStatus::StallError`
+    Stall {
+        device_address: u8,
+        endpoint_address: u8,
+    },
+}
+
+///a script of hotplug and error events, e.g. attach at port 1 at t=0, stall endpoint 2 at t=5s
+///and detach at t=10s, played back against a `ControllerInterface` through `ScenarioRun`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Scenario {
+    steps: Vec<(Duration, ScenarioAction)>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///runs `action` `at` after the start of the run, actions at the same time run in the order
+    ///they were added
+    pub fn at(mut self, at: Duration, action: ScenarioAction) -> Self {
+        let index = self.steps.partition_point(|(step_at, _)| *step_at <= at);
+        self.steps.insert(index, (at, action));
+        self
+    }
+
+    pub fn attach(self, at: Duration, port: u64, speed: DeviceSpeed) -> Self {
+        self.at(at, ScenarioAction::Attach { port, speed })
+    }
+
+    pub fn detach(self, at: Duration, port: u64) -> Self {
+        self.at(at, ScenarioAction::Detach { port })
+    }
+
+    pub fn overcurrent(self, at: Duration, port: u64, overcurrent: bool) -> Self {
+        self.at(at, ScenarioAction::Overcurrent { port, overcurrent })
+    }
+
+    pub fn stall(self, at: Duration, device_address: u8, endpoint_address: u8) -> Self {
+        self.at(
+            at,
+            ScenarioAction::Stall {
+                device_address,
+                endpoint_address,
+            },
+        )
+    }
+
+    ///the actions in the order they run
+    pub fn steps(&self) -> &[(Duration, ScenarioAction)] {
+        &self.steps
+    }
+
+    ///starts the timeline now
+    pub fn start(&self) -> ScenarioRun {
+        ScenarioRun {
+            steps: self.steps.clone(),
+            next: 0,
+            started: Instant::now(),
+            stalls: Vec::new(),
+        }
+    }
+}
+
+///a `Scenario` being played back, port actions are applied to the controller as they come due
+///and stalls wait for the endpoint they target to be handed to `apply_stalls`
+pub struct ScenarioRun {
+    steps: Vec<(Duration, ScenarioAction)>,
+    next: usize,
+    started: Instant,
+    stalls: Vec<(u8, u8)>,
+}
+
+impl ScenarioRun {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    ///applies the actions due by now, see `advance_to`
+    pub fn advance(
+        &mut self,
+        controller: &ControllerInterface,
+    ) -> Result<Vec<ScenarioAction>, UsbError> {
+        self.advance_to(controller, self.elapsed())
+    }
+
+    ///applies the actions due by `elapsed` into the timeline regardless of the wall clock, for
+    ///stepping through a scenario deterministically, returning them in the order they ran; on
+    ///error the failed action is not retried
+    pub fn advance_to(
+        &mut self,
+        controller: &ControllerInterface,
+        elapsed: Duration,
+    ) -> Result<Vec<ScenarioAction>, UsbError> {
+        let mut applied = Vec::new();
+        while let Some((at, action)) = self.steps.get(self.next).copied() {
+            if at > elapsed {
+                break;
+            }
+            self.next += 1;
+            self.apply(controller, action)?;
+            applied.push(action);
+        }
+        Ok(applied)
+    }
+
+    fn apply(
+        &mut self,
+        controller: &ControllerInterface,
+        action: ScenarioAction,
+    ) -> Result<(), UsbError> {
+        use ScenarioAction as A;
+        match action {
+            A::Attach { port, speed } => {
+                let port = controller.port_state_machine_for_port(port)?;
+                port.set_connected(true);
+                port.update_link_state(LinkState::U0, speed, false)
+            }
+            A::Detach { port } => {
+                let port = controller.port_state_machine_for_port(port)?;
+                port.set_connected(false);
+                port.update_link_state(LinkState::RxDetect, port.speed(), false)
+            }
+            A::Overcurrent { port, overcurrent } => {
+                controller
+                    .port_state_machine_for_port(port)?
+                    .set_overcurrent(overcurrent);
+                Ok(())
+            }
+            A::Stall {
+                device_address,
+                endpoint_address,
+            } => {
+                self.stalls.push((device_address, endpoint_address));
+                Ok(())
+            }
+        }
+    }
+
+    ///stalls the current transfer of `endpoint` if a due stall targets it, returning whether it
+    ///did; stalls for an endpoint without a transfer in flight stay pending
+    pub fn apply_stalls(&mut self, endpoint: &EndpointStateMachine) -> Result<bool, UsbError> {
+        let target = (
+            endpoint.device_address() as u8,
+            endpoint.endpoint_address() as u8,
+        );
+        let Some(index) = self.stalls.iter().position(|stall| *stall == target) else {
+            return Ok(false);
+        };
+        let Some(transfer) = Message::new(unsafe { endpoint.inner.currentTransferMessage() })
+        else {
+            return Ok(false);
+        };
+        endpoint.enqueue_transfer_completion_for_message(
+            &transfer,
+            MessageStatus::StallError,
+            0,
+        )?;
+        self.stalls.remove(index);
+        Ok(true)
+    }
+
+    ///how far into the timeline the next action runs
+    pub fn next_at(&self) -> Option<Duration> {
+        self.steps.get(self.next).map(|(at, _)| *at)
+    }
+
+    ///whether every action ran and every stall was applied
+    pub fn is_finished(&self) -> bool {
+        self.next == self.steps.len() && self.stalls.is_empty()
+    }
+}
+
+impl Drop for ControllerInterface {
+    fn drop(&mut self) {
+        self.disable_tracing();
+        unsafe { self.inner.destroy() }
+    }
+}
+
+pub struct DeviceStateMachine {
+    pub(crate) inner: IOUSBHostCIDeviceStateMachine,
+    observers: StateObservers<DeviceState>,
+}
+
+impl DeviceStateMachine {
+    ///creates the state machine of the device a DeviceCreate command asks for, the command is
+    ///left for the caller to answer with the address it gives the device
+    pub fn new(interface: &ControllerInterface, command: &Message<'_>) -> Result<Self, UsbError> {
+        let mut err = NSErr::new();
+        let machine = unsafe {
+            IOUSBHostCIDeviceStateMachine::alloc().initWithInterface_command_error_(
+                interface.inner,
+                command.inner.as_ref(),
+                &mut *err,
+            )
+        };
+        if err.is_err() || machine.is_null() {
+            return Err(err.into());
+        }
+        Ok(Self {
+            inner: IOUSBHostCIDeviceStateMachine(machine),
+            observers: StateObservers::new(),
+        })
+    }
+
+    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let res = if !unsafe {
+            self.inner
+                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        trace_inspected(unsafe { self.inner.controllerInterface() }, cmd, &res);
+        res
+    }
+
+    pub fn respond(
+        &self,
+        cmd: &Message,
+        status: MessageStatus,
+        device_address: Option<u64>,
+    ) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let ok = self.observers.observe(
+            || unsafe { self.inner.deviceState() },
+            || unsafe {
+                match device_address {
+                    Some(addr) => self.inner.respondToCommand_status_deviceAddress_error_(
+                        cmd.inner.as_ref(),
+                        status.into(),
+                        addr,
+                        &mut *err,
+                    ),
+                    None => self.inner.respondToCommand_status_error_(
+                        cmd.inner.as_ref(),
+                        status.into(),
+                        &mut *err,
+                    ),
+                }
+            },
+        );
+        let res = if !ok { Err(err.into()) } else { Ok(()) };
+        trace_responded(
+            unsafe { self.inner.controllerInterface() },
+            cmd,
+            status,
+            &res,
+        );
+        res
+    }
+
+    pub fn device_state(&self) -> DeviceState {
+        unsafe { self.inner.deviceState() }.into()
+    }
+
+    ///receives every device state change caused by commands handled through this state machine
+    pub fn transitions(&self) -> std::sync::mpsc::Receiver<StateTransition<DeviceState>> {
+        self.observers.subscribe()
+    }
+
+    pub fn complete_route(&self) -> u64 {
+        unsafe { self.inner.completeRoute() }
+    }
+
+    ///`complete_route` decoded into the root port and the hub port of every tier below it
+    pub fn route(&self) -> Route {
+        Route::from_complete_route(self.complete_route())
+    }
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.deviceAddress() }
+    }
+
+    pub fn controller_interface(&self) -> ControllerInterface {
+        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
+    }
+}
+
+///where a device sits below the controller: its root port and, for devices behind hubs, the hub
+///port taken at every tier, as packed into a complete route
+///
+///displays as the root port followed by the hub ports, `3.1.4` for a device on port 4 of a hub on
+///port 1 of a hub on root port 3; `{:x}` gives the xHCI route string, `00041` for the same device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Route(u64);
+
+impl Route {
+    const ROOT_PORT: u64 = 0xf;
+    const STRING_PHASE: u32 = 4;
+    const STRING: u64 = 0xfffff;
+    ///hub tiers a route string has room for
+    pub const MAX_HOPS: usize = 5;
+
+    ///a complete route as reported by the framework: the root port in bits 0..=3 and the route
+    ///string, one hub port per 4 bits starting at the first tier, in bits 4..=23
+    pub fn from_complete_route(route: u64) -> Self {
+        Self(route & (Self::ROOT_PORT | (Self::STRING << Self::STRING_PHASE)))
+    }
+
+    ///the route to a device reached through `hops` hub ports from `root_port`, for writing test
+    ///expectations; `None` unless every port is in 1..=15 and there are at most `MAX_HOPS` hops
+    pub fn new(root_port: u8, hops: &[u8]) -> Option<Self> {
+        let valid = |port: &u8| (1..=15).contains(port);
+        if !valid(&root_port) || hops.len() > Self::MAX_HOPS || !hops.iter().all(valid) {
+            return None;
+        }
+        let string = hops.iter().enumerate().fold(0, |string, (tier, port)| {
+            string | (u64::from(*port) << (tier * 4))
+        });
+        Some(Self(u64::from(root_port) | (string << Self::STRING_PHASE)))
+    }
+
+    pub fn complete_route(&self) -> u64 {
+        self.0
+    }
+
+    pub fn root_port(&self) -> u8 {
+        (self.0 & Self::ROOT_PORT) as u8
+    }
+
+    ///the 20 bit xHCI route string, without the root port
+    pub fn route_string(&self) -> u32 {
+        ((self.0 >> Self::STRING_PHASE) & Self::STRING) as u32
+    }
+
+    ///the hub port taken at every tier below the root port, empty for devices on a root port
+    pub fn hops(&self) -> Vec<u8> {
+        let string = self.route_string();
+        (0..Self::MAX_HOPS)
+            .map(|tier| ((string >> (tier * 4)) & 0xf) as u8)
+            .take_while(|port| *port != 0)
+            .collect()
+    }
+
+    ///the number of hubs between the root port and the device
+    pub fn depth(&self) -> usize {
+        self.hops().len()
+    }
+}
+
+impl core::fmt::Display for Route {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.root_port())?;
+        for port in self.hops() {
+            write!(f, ".{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::LowerHex for Route {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:05x}", self.route_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Exception {
+    Unknown = 0,
+    InvalidCapabilities = 1,
+    Terminated = 2,
+    CommandReadCollision = 3,
+    WriteFailed = 4,
+    Timeout = 5,
+    Failure = 6,
+    InvalidInterrupt = 7,
+    InterruptOverflow = 8,
+    DoorbellReadCollision = 9,
+    DoorbellOverflow = 10,
+    ProtocolError = 11,
+    FrameUpdateError = 12,
+    Other(u32),
+}
+
+impl From<u32> for Exception {
+    fn from(num: u32) -> Exception {
+        use Exception as E;
+        match num {
+            0 => E::Unknown,
+            1 => E::InvalidCapabilities,
+            2 => E::Terminated,
+            3 => E::CommandReadCollision,
+            4 => E::WriteFailed,
+            5 => E::Timeout,
+            6 => E::Failure,
+            7 => E::InvalidInterrupt,
+            8 => E::InterruptOverflow,
+            9 => E::DoorbellReadCollision,
+            10 => E::DoorbellOverflow,
+            11 => E::ProtocolError,
+            12 => E::FrameUpdateError,
+            other => E::Other(other),
+        }
+    }
+}
+
+impl From<Exception> for u32 {
+    fn from(exception: Exception) -> u32 {
+        use Exception as E;
+        match exception {
+            E::Unknown => 0,
+            E::InvalidCapabilities => 1,
+            E::Terminated => 2,
+            E::CommandReadCollision => 3,
+            E::WriteFailed => 4,
+            E::Timeout => 5,
+            E::Failure => 6,
+            E::InvalidInterrupt => 7,
+            E::InterruptOverflow => 8,
+            E::DoorbellReadCollision => 9,
+            E::DoorbellOverflow => 10,
+            E::ProtocolError => 11,
+            E::FrameUpdateError => 12,
+            E::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum MessageType {
+    ControllerCapabilities = 0,
+    PortCapabilities = 1,
+    PortEvent = 8,
+    FrameNumberUpdate = 9,
+    FrameTimestampUpdate = 10,
+    ControllerPowerOn = 16,
+    ControllerPowerOff = 17,
+    ControllerStart = 18,
+    ControllerPause = 19,
+    ControllerFrameNumner = 20,
+    PortPowerOn = 24,
+    PortPowerOff = 25,
+    PortResume = 26,
+    PortSuspend = 27,
+    PortReset = 28,
+    PortDisable = 29,
+    PortStatus = 30,
+    DeviceCreate = 32,
+    DeviceDestroy = 33,
+    DeviceStart = 34,
+    DevicePause = 35,
+    DeviceUpdate = 36,
+    EndpointCreate = 40,
+    EndpointDesroy = 41,
+    EndpointPause = 43,
+    EndpointUpdate = 44,
+    EndpointRest = 45,
+    EndpointSetNextTransfer = 46,
+    CommandMax = 55,
+    SetupTransfer = 56,
+    NormalTransfer = 57,
+    StatusTransfer = 58,
+    IsochronousTransfer = 59,
+    Link = 60,
+    TransferComplete = 61,
+    Other(u32),
+}
+
+impl From<u32> for MessageType {
+    fn from(num: u32) -> MessageType {
+        use MessageType as MT;
+        match num {
+            0 => MT::ControllerCapabilities,
+            1 => MT::PortCapabilities,
+            8 => MT::PortEvent,
+            9 => MT::FrameNumberUpdate,
+            10 => MT::FrameTimestampUpdate,
+            16 => MT::ControllerPowerOn,
+            17 => MT::ControllerPowerOff,
+            18 => MT::ControllerStart,
+            19 => MT::ControllerPause,
+            20 => MT::ControllerFrameNumner,
+            24 => MT::PortPowerOn,
+            25 => MT::PortPowerOff,
+            26 => MT::PortResume,
+            27 => MT::PortSuspend,
+            28 => MT::PortReset,
+            29 => MT::PortDisable,
+            30 => MT::PortStatus,
+            32 => MT::DeviceCreate,
+            33 => MT::DeviceDestroy,
+            34 => MT::DeviceStart,
+            35 => MT::DevicePause,
+            36 => MT::DeviceUpdate,
+            40 => MT::EndpointCreate,
+            41 => MT::EndpointDesroy,
+            43 => MT::EndpointPause,
+            44 => MT::EndpointUpdate,
+            45 => MT::EndpointRest,
+            46 => MT::EndpointSetNextTransfer,
+            55 => MT::CommandMax,
+            56 => MT::SetupTransfer,
+            57 => MT::NormalTransfer,
+            58 => MT::StatusTransfer,
+            59 => MT::IsochronousTransfer,
+            60 => MT::Link,
+            61 => MT::TransferComplete,
+            other => MT::Other(other),
+        }
+    }
+}
+
+impl From<MessageType> for u32 {
+    fn from(ty: MessageType) -> u32 {
+        use MessageType as MT;
+        match ty {
+            MT::ControllerCapabilities => 0,
+            MT::PortCapabilities => 1,
+            MT::PortEvent => 8,
+            MT::FrameNumberUpdate => 9,
+            MT::FrameTimestampUpdate => 10,
+            MT::ControllerPowerOn => 16,
+            MT::ControllerPowerOff => 17,
+            MT::ControllerStart => 18,
+            MT::ControllerPause => 19,
+            MT::ControllerFrameNumner => 20,
+            MT::PortPowerOn => 24,
+            MT::PortPowerOff => 25,
+            MT::PortResume => 26,
+            MT::PortSuspend => 27,
+            MT::PortReset => 28,
+            MT::PortDisable => 29,
+            MT::PortStatus => 30,
+            MT::DeviceCreate => 32,
+            MT::DeviceDestroy => 33,
+            MT::DeviceStart => 34,
+            MT::DevicePause => 35,
+            MT::DeviceUpdate => 36,
+            MT::EndpointCreate => 40,
+            MT::EndpointDesroy => 41,
+            MT::EndpointPause => 43,
+            MT::EndpointUpdate => 44,
+            MT::EndpointRest => 45,
+            MT::EndpointSetNextTransfer => 46,
+            MT::CommandMax => 55,
+            MT::SetupTransfer => 56,
+            MT::NormalTransfer => 57,
+            MT::StatusTransfer => 58,
+            MT::IsochronousTransfer => 59,
+            MT::Link => 60,
+            MT::TransferComplete => 61,
+            MT::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum MessageStatus {
+    Success = 1,
+    Offline = 2,
+    NotPermitted = 3,
+    BadArgument = 4,
+    Timeout = 5,
+    NoResources = 6,
+    EndpointStopped = 7,
+    ProtocolError = 8,
+    TransactionError = 9,
+    OverrunError = 10,
+    StallError = 11,
+    MissedServiceError = 12,
+    Error = 13,
+    Other(u32),
+}
+
+impl From<MessageStatus> for u32 {
+    fn from(status: MessageStatus) -> u32 {
+        use MessageStatus as MS;
+        match status {
+            MS::Success => 1,
+            MS::Offline => 2,
+            MS::NotPermitted => 3,
+            MS::BadArgument => 4,
+            MS::Timeout => 5,
+            MS::NoResources => 6,
+            MS::EndpointStopped => 7,
+            MS::ProtocolError => 8,
+            MS::TransactionError => 9,
+            MS::OverrunError => 10,
+            MS::StallError => 11,
+            MS::MissedServiceError => 12,
+            MS::Error => 13,
+            MS::Other(other) => other,
+        }
+    }
+}
+
+impl From<u32> for MessageStatus {
+    fn from(num: u32) -> MessageStatus {
+        use MessageStatus as MS;
+        match num {
+            1 => MS::Success,
+            2 => MS::Offline,
+            3 => MS::NotPermitted,
+            4 => MS::BadArgument,
+            5 => MS::Timeout,
+            6 => MS::NoResources,
+            7 => MS::EndpointStopped,
+            8 => MS::ProtocolError,
+            9 => MS::TransactionError,
+            10 => MS::OverrunError,
+            11 => MS::StallError,
+            12 => MS::MissedServiceError,
+            13 => MS::Error,
+            other => MS::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum DeviceSpeed {
+    None = 0,
+    Full = 1,
+    Low = 2,
+    High = 3,
+    Super = 4,
+    SuperPlus = 5,
+    SuperPlusBy2 = 6,
+    Other(u32),
+}
+
+impl From<u32> for DeviceSpeed {
+    fn from(num: u32) -> DeviceSpeed {
+        use DeviceSpeed as DS;
+        match num {
+            0 => DS::None,
+            1 => DS::Full,
+            2 => DS::Low,
+            3 => DS::High,
+            4 => DS::Super,
+            5 => DS::SuperPlus,
+            6 => DS::SuperPlusBy2,
+            other => DS::Other(other),
+        }
+    }
+}
+
+impl From<DeviceSpeed> for u32 {
+    fn from(speed: DeviceSpeed) -> u32 {
+        use DeviceSpeed as DS;
+        match speed {
+            DS::None => 0,
+            DS::Full => 1,
+            DS::Low => 2,
+            DS::High => 3,
+            DS::Super => 4,
+            DS::SuperPlus => 5,
+            DS::SuperPlusBy2 => 6,
+            DS::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum LinkState {
+    U0 = 0,
+    U1 = 1,
+    U2 = 2,
+    U3 = 3,
+    Disabled = 4,
+    RxDetect = 5,
+    Inactive = 6,
+    Polling = 7,
+    Recovery = 8,
+    Reset = 9,
+    Compliance = 10,
+    Test = 11,
+    Resume = 15,
+    Other(u32),
+}
+
+impl From<u32> for LinkState {
+    fn from(num: u32) -> LinkState {
+        use LinkState as LS;
+        match num {
+            0 => LS::U0,
+            1 => LS::U1,
+            2 => LS::U2,
+            3 => LS::U3,
+            4 => LS::Disabled,
+            5 => LS::RxDetect,
+            6 => LS::Inactive,
+            7 => LS::Polling,
+            8 => LS::Recovery,
+            9 => LS::Reset,
+            10 => LS::Compliance,
+            11 => LS::Test,
+            15 => LS::Resume,
+            other => LS::Other(other),
+        }
+    }
+}
+
+impl From<LinkState> for u32 {
+    fn from(state: LinkState) -> u32 {
+        use LinkState as LS;
+        match state {
+            LS::U0 => 0,
+            LS::U1 => 1,
+            LS::U2 => 2,
+            LS::U3 => 3,
+            LS::Disabled => 4,
+            LS::RxDetect => 5,
+            LS::Inactive => 6,
+            LS::Polling => 7,
+            LS::Recovery => 8,
+            LS::Reset => 9,
+            LS::Compliance => 10,
+            LS::Test => 11,
+            LS::Resume => 15,
+            LS::Other(other) => other,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    ///status word reported by a virtual port, `LINK_STATE` and `SPEED` are multi-bit fields which
+    ///are decoded by `link_state()` and `speed()`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PortStatus: u32 {
+        const POWERED = 1;
+        const OVERCURRENT = 2;
+        const CONNECTED = 4;
+        const LINK_STATE = 240;
+        const SPEED = 1792;
+        const OVERCURRENT_CHANGE = 131072;
+        const CONNECT_CHANGE = 262144;
+        const LINK_STATE_CHANGE = 1048576;
+    }
+}
+
+impl PortStatus {
+    const LINK_STATE_PHASE: u32 = 4;
+    const SPEED_PHASE: u32 = 8;
+
+    pub fn link_state(&self) -> LinkState {
+        ((self.bits() & Self::LINK_STATE.bits()) >> Self::LINK_STATE_PHASE).into()
+    }
+
+    pub fn speed(&self) -> DeviceSpeed {
+        ((self.bits() & Self::SPEED.bits()) >> Self::SPEED_PHASE).into()
+    }
+
+    pub fn changes(&self) -> PortStatus {
+        self.intersection(Self::OVERCURRENT_CHANGE | Self::CONNECT_CHANGE | Self::LINK_STATE_CHANGE)
+    }
+}
+
+impl From<u32> for PortStatus {
+    fn from(num: u32) -> PortStatus {
+        PortStatus::from_bits_retain(num)
+    }
+}
+
+impl From<PortStatus> for u32 {
+    fn from(status: PortStatus) -> u32 {
+        status.bits()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Doorbell {
+    DeviceAddress = 255,
+    DeviceAddressPhase = 0,
+    EndpointAddress = 65280,
+    EndpointAddressPhase = 8,
+    StreamId = 4294901760,
+    StreamIDPhase = 16,
+}
+
+impl From<Doorbell> for u32 {
+    fn from(mask: Doorbell) -> u32 {
+        mask as u32
+    }
+}
+
+///a doorbell word split up along the `Doorbell` masks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DoorbellEvent {
+    pub device_address: u8,
+    pub endpoint_address: u8,
+    pub stream_id: u16,
+}
+
+impl DoorbellEvent {
+    ///whether the doorbell rings for the endpoint `endpoint` handles
+    pub fn is_for(&self, endpoint: &EndpointStateMachine) -> bool {
+        self.device_address as u64 == endpoint.device_address()
+            && self.endpoint_address as u64 == endpoint.endpoint_address()
+    }
+}
+
+impl From<u32> for DoorbellEvent {
+    fn from(doorbell: u32) -> DoorbellEvent {
+        use Doorbell as D;
+        DoorbellEvent {
+            device_address: ((doorbell & u32::from(D::DeviceAddress))
+                >> D::DeviceAddressPhase as u32) as u8,
+            endpoint_address: ((doorbell & u32::from(D::EndpointAddress))
+                >> D::EndpointAddressPhase as u32) as u8,
+            stream_id: ((doorbell & u32::from(D::StreamId)) >> D::StreamIDPhase as u32) as u16,
+        }
+    }
+}
+
+impl From<DoorbellEvent> for u32 {
+    fn from(event: DoorbellEvent) -> u32 {
+        use Doorbell as D;
+        (event.device_address as u32) << D::DeviceAddressPhase as u32
+            | (event.endpoint_address as u32) << D::EndpointAddressPhase as u32
+            | (event.stream_id as u32) << D::StreamIDPhase as u32
+    }
+}
+
+#[derive(Default)]
+struct DoorbellQueue {
+    events: std::collections::VecDeque<DoorbellEvent>,
+    #[cfg(feature = "async")]
+    waker: Option<core::task::Waker>,
+}
+
+///queues the doorbells rung on a controller until the handler of their endpoint is ready for
+///them, the controller's doorbell handler pushing and endpoint handlers taking their own
+#[derive(Default)]
+pub struct DoorbellStream {
+    queue: std::sync::Mutex<DoorbellQueue>,
+}
+
+impl DoorbellStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, DoorbellQueue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    ///queues the doorbell words passed to the controller's doorbell handler
+    pub fn push(&self, doorbells: &[u32]) {
+        let mut queue = self.queue();
+        queue
+            .events
+            .extend(doorbells.iter().copied().map(DoorbellEvent::from));
+        #[cfg(feature = "async")]
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    ///the oldest queued doorbell, whichever endpoint it is for
+    pub fn pop(&self) -> Option<DoorbellEvent> {
+        self.queue().events.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///the number of queued doorbells for `endpoint`
+    pub fn pending_for(&self, endpoint: &EndpointStateMachine) -> usize {
+        self.queue()
+            .events
+            .iter()
+            .filter(|event| event.is_for(endpoint))
+            .count()
+    }
+
+    ///takes the queued doorbells for `endpoint`, oldest first, leaving the others queued
+    pub fn take_for(&self, endpoint: &EndpointStateMachine) -> Vec<DoorbellEvent> {
+        let mut queue = self.queue();
+        let (taken, kept) = queue
+            .events
+            .drain(..)
+            .partition(|event| event.is_for(endpoint));
+        queue.events = kept;
+        taken.into()
+    }
+
+    ///hands the queued doorbells for `endpoint` to its `process_doorbell`, returning how many
+    ///were processed; on error the doorbells not processed yet are queued again
+    pub fn dispatch(&self, endpoint: &EndpointStateMachine) -> Result<usize, UsbError> {
+        let events = self.take_for(endpoint);
+        for (processed, event) in events.iter().enumerate() {
+            if let Err(err) = endpoint.process_doorbell((*event).into()) {
+                let mut queue = self.queue();
+                for event in events[processed..].iter().rev() {
+                    queue.events.push_front(*event);
+                }
+                return Err(err);
+            }
+        }
+        Ok(events.len())
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for DoorbellStream {
+    type Item = DoorbellEvent;
+    fn poll_next(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Self::Item>> {
+        let mut queue = self.queue();
+        match queue.events.pop_front() {
+            Some(event) => core::task::Poll::Ready(Some(event)),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                core::task::Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageCommand {
+    ControlStatus = 3840,
+    StatusPhase = 8,
+    Data0RootPort = 15,
+    RootPortPhase = 0,
+    Data0DeviceAddress = 255,
+    Data0EndpointAddress = 65280,
+    Data0StreamId = 4294901760,
+    Data0StreamIdPhase = 16,
+}
+
+impl From<MessageCommand> for u32 {
+    fn from(mask: MessageCommand) -> u32 {
+        mask as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ControllerState {
+    Off = 0,
+    Paused = 1,
+    Active = 2,
+    Other(u32),
+}
+
+impl ControllerState {
+    ///the state a controller command moves the controller to, `None` if it is not allowed in
+    ///this state
+    pub fn after(self, command: MessageType) -> Option<ControllerState> {
+        use ControllerState as CS;
+        use MessageType as MT;
+        match (self, command) {
+            (CS::Off, MT::ControllerPowerOn) => Some(CS::Paused),
+            (CS::Paused, MT::ControllerStart) => Some(CS::Active),
+            (CS::Active, MT::ControllerPause) => Some(CS::Paused),
+            (CS::Paused, MT::ControllerPowerOff) => Some(CS::Off),
+            _ => None,
+        }
+    }
+}
+
+impl From<u32> for ControllerState {
+    fn from(num: u32) -> ControllerState {
+        use ControllerState as CS;
+        match num {
+            0 => CS::Off,
+            1 => CS::Paused,
+            2 => CS::Active,
+            other => CS::Other(other),
+        }
+    }
+}
+
+impl From<ControllerState> for u32 {
+    fn from(state: ControllerState) -> u32 {
+        use ControllerState as CS;
+        match state {
+            CS::Off => 0,
+            CS::Paused => 1,
+            CS::Active => 2,
+            CS::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PortState {
+    Off = 0,
+    Powered = 1,
+    Suspended = 2,
+    Active = 3,
+    Other(u32),
+}
+
+impl From<u32> for PortState {
+    fn from(num: u32) -> PortState {
+        use PortState as PS;
+        match num {
+            0 => PS::Off,
+            1 => PS::Powered,
+            2 => PS::Suspended,
+            3 => PS::Active,
+            other => PS::Other(other),
+        }
+    }
+}
+
+impl From<PortState> for u32 {
+    fn from(state: PortState) -> u32 {
+        use PortState as PS;
+        match state {
+            PS::Off => 0,
+            PS::Powered => 1,
+            PS::Suspended => 2,
+            PS::Active => 3,
+            PS::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortMessageEvent {
+    Data0PortNumber = 15,
+    Data0PortNumberPhase = 0,
+}
+
+impl From<PortMessageEvent> for u32 {
+    fn from(mask: PortMessageEvent) -> u32 {
+        mask as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortStatusCommand {
+    Powered = 1,
+    Overcurrent = 2,
+    Connected = 4,
+    LinkState = 240,
+    Speed = 1792,
+    SpeedPhase = 8,
+    OvercurrentChange = 131072,
+    ConnectChange = 262144,
+    LinkStateChange = 1048576,
+    ChangeMask = 1441792,
+}
+
+impl From<PortStatusCommand> for u32 {
+    fn from(mask: PortStatusCommand) -> u32 {
+        mask as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum DeviceState {
+    Destroyed = 0,
+    Paused = 1,
+    Active = 2,
+    Other(u32),
+}
+
+impl From<u32> for DeviceState {
+    fn from(num: u32) -> DeviceState {
+        use DeviceState as DS;
+        match num {
+            0 => DS::Destroyed,
+            1 => DS::Paused,
+            2 => DS::Active,
+            other => DS::Other(other),
+        }
+    }
+}
+
+impl From<DeviceState> for u32 {
+    fn from(state: DeviceState) -> u32 {
+        use DeviceState as DS;
+        match state {
+            DS::Destroyed => 0,
+            DS::Paused => 1,
+            DS::Active => 2,
+            DS::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum EndpointState {
+    Destroyed = 0,
+    Halted = 1,
+    Paused = 2,
+    Active = 3,
+    Other(u32),
+}
+
+impl From<u32> for EndpointState {
+    fn from(num: u32) -> EndpointState {
+        use EndpointState as ES;
+        match num {
+            0 => ES::Destroyed,
+            1 => ES::Halted,
+            2 => ES::Paused,
+            3 => ES::Active,
+            other => ES::Other(other),
+        }
+    }
+}
+
+impl From<EndpointState> for u32 {
+    fn from(state: EndpointState) -> u32 {
+        use EndpointState as ES;
+        match state {
+            ES::Destroyed => 0,
+            ES::Halted => 1,
+            ES::Paused => 2,
+            ES::Active => 3,
+            ES::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum EndpointCreateCommand {
+    Data1Descriptor = 18446744073709551615,
+    Data1DescriptorPhase = 0,
+}
+
+impl From<EndpointCreateCommand> for u64 {
+    fn from(mask: EndpointCreateCommand) -> u64 {
+        mask as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum EndpointUpdateCommand {
+    Data1Descriptor = 18446744073709551615,
+    Data1DescriptorPhase = 0,
+}
+
+impl From<EndpointUpdateCommand> for u64 {
+    fn from(mask: EndpointUpdateCommand) -> u64 {
+        mask as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointResetCommand {
+    Data1ClearState = 1,
+}
+
+impl From<EndpointResetCommand> for u64 {
+    fn from(mask: EndpointResetCommand) -> u64 {
+        mask as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum EndpointSetNExtTransferCommand {
+    Data1Address = 18446744073709551615,
+    Data1AddressPhase = 0,
+}
+
+impl From<EndpointSetNExtTransferCommand> for u64 {
+    fn from(mask: EndpointSetNExtTransferCommand) -> u64 {
+        mask as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum TransferCompletionMessage {
+    Status = 3840,
+    StatusPhase = 8,
+    DeviceAddress = 16711680,
+    DeviceAddressPhase = 16,
+    EndpointAddress = 4278190080,
+    EndpointAddressPhase = 24,
+    Data0TransferLength = 268435455,
+    Data0TransferLengthPhase = 0,
+    Data1TransferStructure = 18446744073709551615,
+}
+
+impl From<TransferCompletionMessage> for u64 {
+    fn from(mask: TransferCompletionMessage) -> u64 {
+        mask as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PortType {
+    Standard = 0,
+    Captive = 1,
+    Internal = 2,
+    Accessory = 3,
+    ExpressCard = 4,
+    Count = 5,
+    Other(u32),
+}
+
+impl From<u32> for PortType {
+    fn from(num: u32) -> PortType {
+        use PortType as PT;
+        match num {
+            0 => PT::Standard,
+            1 => PT::Captive,
+            2 => PT::Internal,
+            3 => PT::Accessory,
+            4 => PT::ExpressCard,
+            5 => PT::Count,
+            other => PT::Other(other),
+        }
+    }
+}
+
+impl From<PortType> for u32 {
+    fn from(ty: PortType) -> u32 {
+        use PortType as PT;
+        match ty {
+            PT::Standard => 0,
+            PT::Captive => 1,
+            PT::Internal => 2,
+            PT::Accessory => 3,
+            PT::ExpressCard => 4,
+            PT::Count => 5,
+            PT::Other(other) => other,
+        }
+    }
+}
+
+///what a root port of a user space controller offers, see `ControllerCapabilities`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PortCapabilities {
+    number: u8,
+    port_type: PortType,
+    speeds: Vec<DeviceSpeed>,
+    max_power: u16,
+}
+
+impl PortCapabilities {
+    ///port `number`, counting from 1
+    pub fn new(number: u8, port_type: PortType) -> Self {
+        Self {
+            number,
+            port_type,
+            speeds: Vec::new(),
+            max_power: 0,
+        }
+    }
+
+    pub fn speed(mut self, speed: DeviceSpeed) -> Self {
+        if !self.speeds.contains(&speed) {
+            self.speeds.push(speed);
+        }
+        self
+    }
+
+    pub fn speeds(self, speeds: &[DeviceSpeed]) -> Self {
+        speeds.iter().fold(self, |port, speed| port.speed(*speed))
+    }
+
+    ///the power the port supplies in mA
+    pub fn max_power(mut self, max_power: u16) -> Self {
+        self.max_power = max_power;
+        self
+    }
+
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn port_type(&self) -> PortType {
+        self.port_type
+    }
+
+    pub fn supported_speeds(&self) -> &[DeviceSpeed] {
+        &self.speeds
+    }
+
+    pub fn supports(&self, speed: DeviceSpeed) -> bool {
+        self.speeds.contains(&speed)
+    }
+}
+
+///the capability messages a user space controller is created with, a controller capabilities
+///message followed by one port capabilities message per port, see `ControllerBuilder`
+///
+///the speeds of a port are not part of the messages, they are checked for consistency and kept
+///to answer attaches with, see `PortCapabilities::supports`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ControllerCapabilities {
+    command_timeout_threshold: u8,
+    connection_latency: u8,
+    ports: Vec<PortCapabilities>,
+}
+
+impl ControllerCapabilities {
+    const CONTROL_PORT_COUNT_PHASE: u32 = 16;
+    const CONTROL_PORT_NUMBER_PHASE: u32 = 16;
+    const CONTROL_INTERNAL_CONNECTOR: u32 = 1 << 23;
+    const CONTROL_CONNECTOR_TYPE_PHASE: u32 = 24;
+    const DATA0_CONNECTION_LATENCY_PHASE: u32 = 4;
+    ///port numbers and the port count share a 4 bit field
+    pub const MAX_PORTS: usize = 15;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///how many command timeouts the kernel driver tolerates, 0 through 3
+    pub fn command_timeout_threshold(mut self, threshold: u8) -> Self {
+        self.command_timeout_threshold = threshold;
+        self
+    }
+
+    ///the connection latency in log2 of milliseconds, 0 through 15
+    pub fn connection_latency(mut self, latency: u8) -> Self {
+        self.connection_latency = latency;
+        self
+    }
+
+    ///adds a port, replacing one with the same number
+    pub fn port(mut self, port: PortCapabilities) -> Self {
+        self.ports.retain(|other| other.number != port.number);
+        self.ports.push(port);
+        self.ports.sort_by_key(|port| port.number);
+        self
+    }
+
+    pub fn ports(&self) -> &[PortCapabilities] {
+        &self.ports
+    }
+
+    pub fn port_capabilities(&self, number: u8) -> Option<&PortCapabilities> {
+        self.ports.iter().find(|port| port.number == number)
+    }
+
+    ///checks the capabilities against the message format and the speeds a port has to offer
+    ///alongside each other
+    pub fn validate(&self) -> Result<(), CapabilitiesError> {
+        use CapabilitiesError as CE;
+        if self.command_timeout_threshold > 3 {
+            return Err(CE::CommandTimeoutThreshold(self.command_timeout_threshold));
+        }
+        if self.connection_latency > 15 {
+            return Err(CE::ConnectionLatency(self.connection_latency));
+        }
+        if self.ports.is_empty() {
+            return Err(CE::NoPorts);
+        }
+        if self.ports.len() > Self::MAX_PORTS {
+            return Err(CE::TooManyPorts(self.ports.len()));
+        }
+        //ports are kept sorted and unique, so they have to be exactly 1..=count
+        for (index, port) in self.ports.iter().enumerate() {
+            let expected = index as u8 + 1;
+            if port.number != expected {
+                return Err(CE::MissingPort(expected));
+            }
+            if matches!(port.port_type, PortType::Count | PortType::Other(_)) {
+                return Err(CE::InvalidPortType(port.number, port.port_type));
+            }
+            if port.speeds.is_empty() {
+                return Err(CE::NoSpeeds(port.number));
+            }
+            for speed in &port.speeds {
+                let required = match speed {
+                    DeviceSpeed::None | DeviceSpeed::Other(_) => {
+                        return Err(CE::InvalidSpeed(port.number, *speed))
+                    }
+                    DeviceSpeed::Low | DeviceSpeed::Full => continue,
+                    DeviceSpeed::High => DeviceSpeed::Full,
+                    //SuperSpeed ports carry a USB 2.0 pair next to the enhanced one
+                    DeviceSpeed::Super => DeviceSpeed::High,
+                    DeviceSpeed::SuperPlus => DeviceSpeed::Super,
+                    DeviceSpeed::SuperPlusBy2 => DeviceSpeed::SuperPlus,
+                };
+                if !port.supports(required) {
+                    return Err(CE::MissingSpeed {
+                        port: port.number,
+                        speed: *speed,
+                        required,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///the messages to create the controller with, after `validate`
+    pub fn messages(&self) -> Result<Vec<IOUSBHostCIMessage>, CapabilitiesError> {
+        self.validate()?;
+        let valid = Message::CONTROL_VALID;
+        let controller = IOUSBHostCIMessage {
+            control: u32::from(MessageType::ControllerCapabilities)
+                | valid
+                | (self.ports.len() as u32) << Self::CONTROL_PORT_COUNT_PHASE,
+            data0: self.command_timeout_threshold as u32
+                | (self.connection_latency as u32) << Self::DATA0_CONNECTION_LATENCY_PHASE,
+            data1: 0,
+        };
+        let ports = self.ports.iter().map(|port| {
+            let internal = match port.port_type {
+                PortType::Internal => Self::CONTROL_INTERNAL_CONNECTOR,
+                _ => 0,
+            };
+            IOUSBHostCIMessage {
+                control: u32::from(MessageType::PortCapabilities)
+                    | valid
+                    | (port.number as u32) << Self::CONTROL_PORT_NUMBER_PHASE
+                    | internal
+                    | u32::from(port.port_type) << Self::CONTROL_CONNECTOR_TYPE_PHASE,
+                data0: port.max_power as u32,
+                data1: 0,
+            }
+        });
+        Ok(core::iter::once(controller).chain(ports).collect())
+    }
+
+    ///`messages` laid out as the capabilities data the controller interface is initialized with
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CapabilitiesError> {
+        Ok(self
+            .messages()?
+            .iter()
+            .flat_map(|msg| {
+                msg.control
+                    .to_ne_bytes()
+                    .into_iter()
+                    .chain(msg.data0.to_ne_bytes())
+                    .chain(msg.data1.to_ne_bytes())
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CapabilitiesError {
+    NoPorts,
+    TooManyPorts(usize),
+    ///ports have to be numbered 1 through the port count without gaps
+    MissingPort(u8),
+    InvalidPortType(u8, PortType),
+    NoSpeeds(u8),
+    InvalidSpeed(u8, DeviceSpeed),
+    ///`speed` is offered without `required`, which ports offering it have to support as well
+    MissingSpeed {
+        port: u8,
+        speed: DeviceSpeed,
+        required: DeviceSpeed,
+    },
+    CommandTimeoutThreshold(u8),
+    ConnectionLatency(u8),
+}
+
+impl core::fmt::Display for CapabilitiesError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use CapabilitiesError as CE;
+        match self {
+            CE::NoPorts => write!(f, "the controller has no ports"),
+            CE::TooManyPorts(count) => write!(
+                f,
+                "{count} ports exceed the {} a controller can have",
+                ControllerCapabilities::MAX_PORTS
+            ),
+            CE::MissingPort(port) => write!(f, "port {port} is missing"),
+            CE::InvalidPortType(port, port_type) => {
+                write!(f, "port {port} has the invalid type {port_type:?}")
+            }
+            CE::NoSpeeds(port) => write!(f, "port {port} supports no speed"),
+            CE::InvalidSpeed(port, speed) => {
+                write!(f, "port {port} lists the invalid speed {speed:?}")
+            }
+            CE::MissingSpeed {
+                port,
+                speed,
+                required,
+            } => write!(
+                f,
+                "port {port} supports {speed:?} but not {required:?}, which it requires"
+            ),
+            CE::CommandTimeoutThreshold(threshold) => {
+                write!(f, "command timeout threshold {threshold} is above 3")
+            }
+            CE::ConnectionLatency(latency) => {
+                write!(f, "connection latency {latency} is above 15")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //answers `transition` with `result`, returning the statuses it responded with
+    fn transition_with(
+        inspected: Result<(), UsbError>,
+        received: MessageType,
+        command: MessageType,
+        state: ControllerState,
+        result: Result<(), UsbError>,
+    ) -> (Result<ControllerState, UsbError>, Vec<MessageStatus>) {
+        let mut responses = Vec::new();
+        let res = answer_transition(inspected, received, command, state, |status| {
+            responses.push(status);
+            result
+        });
+        (res, responses)
+    }
+
+    #[test]
+    fn transition_succeeds_once_answered() {
+        let (res, responses) = transition_with(
+            Ok(()),
+            MessageType::ControllerPowerOn,
+            MessageType::ControllerPowerOn,
+            ControllerState::Off,
+            Ok(()),
+        );
+        assert_eq!(res, Ok(ControllerState::Paused));
+        assert_eq!(responses, [MessageStatus::Success]);
+    }
+
+    #[test]
+    fn transition_fails_when_the_answer_fails() {
+        let (res, responses) = transition_with(
+            Ok(()),
+            MessageType::ControllerStart,
+            MessageType::ControllerStart,
+            ControllerState::Paused,
+            Err(UsbError::Failure),
+        );
+        assert_eq!(res, Err(UsbError::Failure));
+        assert_eq!(responses, [MessageStatus::Success]);
+    }
+
+    #[test]
+    fn transition_answers_rejected_commands() {
+        let (res, responses) = transition_with(
+            Err(UsbError::InvalidArgument),
+            MessageType::ControllerStart,
+            MessageType::ControllerStart,
+            ControllerState::Paused,
+            Ok(()),
+        );
+        assert_eq!(res, Err(UsbError::InvalidArgument));
+        assert_eq!(responses, [MessageStatus::NotPermitted]);
+
+        let (res, responses) = transition_with(
+            Ok(()),
+            MessageType::ControllerPause,
+            MessageType::ControllerStart,
+            ControllerState::Paused,
+            Ok(()),
+        );
+        assert_eq!(res, Err(UsbError::InvalidArgument));
+        assert_eq!(responses, [MessageStatus::BadArgument]);
+
+        let (res, responses) = transition_with(
+            Ok(()),
+            MessageType::ControllerStart,
+            MessageType::ControllerStart,
+            ControllerState::Off,
+            Ok(()),
+        );
+        assert_eq!(
+            res,
+            Err(UsbError::IllegalTransition(
+                ControllerState::Off,
+                MessageType::ControllerStart
+            ))
+        );
+        assert_eq!(responses, [MessageStatus::NotPermitted]);
+    }
+
+    fn trace() -> CommandTrace {
+        CommandTrace {
+            capacity: 8,
+            commands: std::collections::VecDeque::new(),
+        }
+    }
+
+    fn command(message_type: MessageType) -> IOUSBHostCIMessage {
+        IOUSBHostCIMessage {
+            control: u32::from(message_type) | Message::CONTROL_VALID,
+            data0: 0,
+            data1: 0,
+        }
+    }
+
+    #[test]
+    fn successful_response_is_not_failed() {
+        let raw = command(MessageType::ControllerPowerOn);
+        let Some(message) = Message::new(&raw) else {
+            unreachable!()
+        };
+        let mut trace = trace();
+        let received = Instant::now();
+        trace.push(
+            message.inner.as_ptr() as usize,
+            TracedCommand::new(&message, received),
+        );
+        trace.responded(&message, MessageStatus::Success, &Ok(()), received);
+        let (_, traced) = &trace.commands[0];
+        assert_eq!(trace.commands.len(), 1);
+        assert_eq!(traced.response, Some(MessageStatus::Success));
+        assert_eq!(traced.latency, Some(Duration::ZERO));
+        assert!(!traced.response_failed);
+    }
+
+    #[test]
+    fn failed_response_is_failed() {
+        let raw = command(MessageType::DeviceCreate);
+        let Some(message) = Message::new(&raw) else {
+            unreachable!()
+        };
+        let mut trace = trace();
+        trace.responded(
+            &message,
+            MessageStatus::Success,
+            &Err(UsbError::Failure),
+            Instant::now(),
+        );
+        let (_, traced) = &trace.commands[0];
+        assert_eq!(traced.message_type, MessageType::DeviceCreate);
+        assert_eq!(traced.response, Some(MessageStatus::Success));
+        assert_eq!(traced.latency, None);
+        assert!(traced.response_failed);
+    }
+}
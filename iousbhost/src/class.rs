@@ -0,0 +1,221 @@
+use crate::*;
+use core::marker::PhantomData;
+use core::ptr;
+use core::ptr::NonNull;
+use iousbhost_sys::*;
+
+impl HostInterface<'_> {
+    const GET_DESCRIPTOR: u8 = 6;
+
+    ///reads the HID report descriptor of this interface, sized from the interface's HID class
+    ///descriptor
+    pub fn hid_report_descriptor(&self) -> Result<HidReportDescriptor, UsbError> {
+        let interface = self.interface_descriptor().ok_or(UsbError::InvalidObject)?;
+        let config = self
+            .configuration_descriptor()
+            .ok_or(UsbError::NoConfiguration)?;
+        let hid = unsafe {
+            IOUSBGetNextAssociatedDescriptorWithType(
+                config.inner.as_ref(),
+                interface.inner.as_ptr() as *const IOUSBDescriptorHeader,
+                ptr::null(),
+                DescriptorType::HID.into(),
+            )
+        };
+        let hid = HidDescriptor::new(hid).ok_or(UsbError::NotSupported)?;
+        let length = hid
+            .report_descriptor_length()
+            .ok_or(UsbError::NotSupported)?;
+
+        let request_type = u8::from(DeviceRequestType::DirectionIn)
+            | u8::from(DeviceRequestType::RecipientInterface);
+        let request = DeviceRequest::new(
+            DeviceRequestType::Other(request_type),
+            Self::GET_DESCRIPTOR,
+            (u8::from(DescriptorType::Report) as u16) << 8,
+            interface.interface_number() as u16,
+            length,
+        );
+
+        let bytes = self.shared.device_request_in(request)?;
+        Ok(HidReportDescriptor { bytes })
+    }
+}
+
+impl UsbDevice<'_> {
+    const HUB_CLASS: u8 = 9;
+    const CLASS_OTHER: u8 = 0x23;
+    const PORT_TEST: u16 = 21;
+
+    ///puts a downstream port of this USB 2.0 hub into an electrical test mode with
+    ///SET_FEATURE(PORT_TEST), the hub has to be reset to leave it again
+    pub fn set_port_test_mode(&self, port: u8, selector: TestSelector) -> Result<(), UsbError> {
+        let device = self
+            .device_descriptor()
+            .ok_or(UsbError::NoDeviceDescriptor)?;
+        if device.device_class() != Self::HUB_CLASS {
+            return Err(UsbError::NotSupported);
+        }
+
+        let request = DeviceRequest::feature(
+            true,
+            Self::CLASS_OTHER,
+            Self::PORT_TEST,
+            ((u8::from(selector) as u16) << 8) | port as u16,
+        );
+        self.send_device_request(request)
+    }
+}
+
+///the HID class descriptor (type 33) which follows a HID interface descriptor
+struct HidDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBDescriptorHeader>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl HidDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDescriptorHeader) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDescriptorHeader)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        let len = unsafe { self.inner.as_ref().bLength } as usize;
+        unsafe { core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, len) }
+    }
+
+    ///wDescriptorLength of the first report descriptor listed
+    fn report_descriptor_length(&self) -> Option<u16> {
+        let bytes = self.bytes();
+        let count = *bytes.get(5)? as usize;
+        bytes
+            .get(6..)?
+            .chunks_exact(3)
+            .take(count)
+            .find(|entry| entry[0] == u8::from(DescriptorType::Report))
+            .map(|entry| u16::from_le_bytes([entry[1], entry[2]]))
+    }
+}
+
+pub struct HidReportDescriptor {
+    bytes: Vec<u8>,
+}
+
+impl HidReportDescriptor {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = HidItem<'_>> {
+        HidItems { bytes: &self.bytes }
+    }
+}
+
+pub struct HidItems<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for HidItems<'a> {
+    type Item = HidItem<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let prefix = *self.bytes.first()?;
+        let (item, len) = if prefix == HidItem::LONG_ITEM_PREFIX {
+            let size = *self.bytes.get(1)? as usize;
+            let tag = *self.bytes.get(2)?;
+            let data = self.bytes.get(3..3 + size)?;
+            (
+                HidItem {
+                    item_type: HidItemType::Long,
+                    tag,
+                    data,
+                },
+                3 + size,
+            )
+        } else {
+            let size = match prefix & 3 {
+                3 => 4,
+                size => size as usize,
+            };
+            let data = self.bytes.get(1..1 + size)?;
+            (
+                HidItem {
+                    item_type: ((prefix >> 2) & 3).into(),
+                    tag: prefix >> 4,
+                    data,
+                },
+                1 + size,
+            )
+        };
+        self.bytes = &self.bytes[len..];
+        Some(item)
+    }
+}
+
+pub struct HidItem<'a> {
+    item_type: HidItemType,
+    tag: u8,
+    data: &'a [u8],
+}
+
+impl HidItem<'_> {
+    const LONG_ITEM_PREFIX: u8 = 0xfe;
+
+    pub fn item_type(&self) -> HidItemType {
+        self.item_type
+    }
+
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    ///the item data as a little endian unsigned value
+    pub fn value(&self) -> u32 {
+        self.data
+            .iter()
+            .take(4)
+            .rev()
+            .fold(0, |acc, byte| (acc << 8) | *byte as u32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HidItemType {
+    Main = 0,
+    Global = 1,
+    Local = 2,
+    Reserved = 3,
+    Long = 4,
+}
+
+impl From<u8> for HidItemType {
+    fn from(num: u8) -> HidItemType {
+        use HidItemType as HIT;
+        match num {
+            0 => HIT::Main,
+            1 => HIT::Global,
+            2 => HIT::Local,
+            3 => HIT::Reserved,
+            _ => HIT::Long,
+        }
+    }
+}
+
+impl From<HidItemType> for u8 {
+    fn from(ty: HidItemType) -> u8 {
+        use HidItemType as HIT;
+        match ty {
+            HIT::Main => 0,
+            HIT::Global => 1,
+            HIT::Local => 2,
+            HIT::Reserved => 3,
+            HIT::Long => 4,
+        }
+    }
+}
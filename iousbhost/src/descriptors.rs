@@ -0,0 +1,2475 @@
+use crate::*;
+use core::marker::PhantomData;
+use core::ptr;
+use core::ptr::NonNull;
+use iousbhost_sys::*;
+
+pub struct SuperSpeedCompanionDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBSuperSpeedEndpointCompanionDescriptor>,
+    pub(crate) lt: PhantomData<&'a IOUSBSuperSpeedEndpointCompanionDescriptor>,
+}
+
+pub struct SuperSpeedPlusCompanionDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor>,
+    pub(crate) lt: PhantomData<&'a IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor>,
+}
+
+impl SuperSpeedPlusCompanionDescriptor<'_> {
+    pub(crate) fn new(
+        ptr: *const IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor,
+    ) -> Option<Self> {
+        let ptr =
+            NonNull::new(ptr as *mut IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn bytes_per_interval(&self) -> u32 {
+        unsafe { self.inner.as_ref().dwBytesPerInterval }
+    }
+}
+
+impl SuperSpeedCompanionDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBSuperSpeedEndpointCompanionDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBSuperSpeedEndpointCompanionDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn max_burst(&self) -> u8 {
+        unsafe { self.inner.as_ref().bMaxBurst }
+    }
+
+    pub fn attributes(&self) -> u8 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+
+    pub fn bytes_per_interval(&self) -> u16 {
+        unsafe { self.inner.as_ref().wBytesPerInterval }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DescriptorType {
+    Device = 1,
+    Configuration = 2,
+    String = 3,
+    Interface = 4,
+    Endpoint = 5,
+    DeviceQualifier = 6,
+    OtherSpeedConfig = 7,
+    InterfacePower = 8,
+    OTG = 9,
+    Debug = 10,
+    InterfaceAssociation = 11,
+    CapabilityDescriptor = 15,
+    DeviceCapability = 16,
+    HID = 33,
+    Report = 34,
+    Physical = 35,
+    ClassSpecificInterface = 36,
+    ClassSpecificEndpoint = 37,
+    Hub = 41,
+    SuperSpeedHub = 42,
+    SuperSpeedEndpointCompanion = 48,
+    SuperSpeedPlusIsochronousEndpointCompanion = 49,
+    Other(u8),
+}
+
+impl From<u8> for DescriptorType {
+    fn from(num: u8) -> DescriptorType {
+        use DescriptorType as DT;
+        match num {
+            1 => DT::Device,
+            2 => DT::Configuration,
+            3 => DT::String,
+            4 => DT::Interface,
+            5 => DT::Endpoint,
+            6 => DT::DeviceQualifier,
+            7 => DT::OtherSpeedConfig,
+            8 => DT::InterfacePower,
+            9 => DT::OTG,
+            10 => DT::Debug,
+            11 => DT::InterfaceAssociation,
+            15 => DT::CapabilityDescriptor,
+            16 => DT::DeviceCapability,
+            33 => DT::HID,
+            34 => DT::Report,
+            35 => DT::Physical,
+            36 => DT::ClassSpecificInterface,
+            37 => DT::ClassSpecificEndpoint,
+            41 => DT::Hub,
+            42 => DT::SuperSpeedHub,
+            48 => DT::SuperSpeedEndpointCompanion,
+            49 => DT::SuperSpeedPlusIsochronousEndpointCompanion,
+            other => DT::Other(other),
+        }
+    }
+}
+
+impl From<DescriptorType> for u8 {
+    fn from(desc: DescriptorType) -> u8 {
+        use DescriptorType as DT;
+        match desc {
+            DT::Device => 1,
+            DT::Configuration => 2,
+            DT::String => 3,
+            DT::Interface => 4,
+            DT::Endpoint => 5,
+            DT::DeviceQualifier => 6,
+            DT::OtherSpeedConfig => 7,
+            DT::InterfacePower => 8,
+            DT::OTG => 9,
+            DT::Debug => 10,
+            DT::InterfaceAssociation => 11,
+            DT::CapabilityDescriptor => 15,
+            DT::DeviceCapability => 16,
+            DT::HID => 33,
+            DT::Report => 34,
+            DT::Physical => 35,
+            DT::ClassSpecificInterface => 36,
+            DT::ClassSpecificEndpoint => 37,
+            DT::Hub => 41,
+            DT::SuperSpeedHub => 42,
+            DT::SuperSpeedEndpointCompanion => 48,
+            DT::SuperSpeedPlusIsochronousEndpointCompanion => 49,
+            DT::Other(o) => o,
+        }
+    }
+}
+
+///USB language identifier as used by string descriptors, the low 10 bits holding the primary
+///language and the upper 6 the sublanguage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LangId(pub u16);
+
+impl LangId {
+    pub const EN_US: LangId = LangId(0x0409);
+    pub const EN_GB: LangId = LangId(0x0809);
+    pub const DE_DE: LangId = LangId(0x0407);
+    pub const FR_FR: LangId = LangId(0x040c);
+    pub const ES_ES: LangId = LangId(0x0c0a);
+    pub const IT_IT: LangId = LangId(0x0410);
+    pub const NL_NL: LangId = LangId(0x0413);
+    pub const PT_BR: LangId = LangId(0x0416);
+    pub const RU_RU: LangId = LangId(0x0419);
+    pub const SV_SE: LangId = LangId(0x041d);
+    pub const JA_JP: LangId = LangId(0x0411);
+    pub const KO_KR: LangId = LangId(0x0412);
+    pub const ZH_CN: LangId = LangId(0x0804);
+    pub const ZH_TW: LangId = LangId(0x0404);
+
+    pub fn primary_language(&self) -> u16 {
+        self.0 & 0x3ff
+    }
+
+    pub fn sublanguage(&self) -> u8 {
+        (self.0 >> 10) as u8
+    }
+
+    ///reads the language ids out of string descriptor zero, `None` if `bytes` is not a string
+    ///descriptor
+    pub fn parse_string_descriptor_zero(bytes: &[u8]) -> Option<Vec<LangId>> {
+        let (&length, rest) = bytes.split_first()?;
+        let (&descriptor_type, rest) = rest.split_first()?;
+        if DescriptorType::from(descriptor_type) != DescriptorType::String || length < 2 {
+            return None;
+        }
+        let ids = &rest[..rest.len().min(length as usize - 2)];
+        Some(
+            ids.chunks_exact(2)
+                .map(|id| LangId(u16::from_le_bytes([id[0], id[1]])))
+                .collect(),
+        )
+    }
+}
+
+impl From<u16> for LangId {
+    fn from(id: u16) -> LangId {
+        LangId(id)
+    }
+}
+
+impl From<LangId> for u16 {
+    fn from(id: LangId) -> u16 {
+        id.0
+    }
+}
+
+impl From<LangId> for u64 {
+    fn from(id: LangId) -> u64 {
+        id.0 as u64
+    }
+}
+
+///NOTE: this is commonly referred to as `altsetting`
+pub struct InterfaceDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBInterfaceDescriptor>,
+    lt: PhantomData<&'a IOUSBInterfaceDescriptor>,
+}
+
+impl InterfaceDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBInterfaceDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBInterfaceDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn interface_number(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterfaceNumber }
+    }
+
+    pub fn alternate_setting(&self) -> u8 {
+        unsafe { self.inner.as_ref().bAlternateSetting }
+    }
+
+    pub fn endpoint_count(&self) -> u8 {
+        unsafe { self.inner.as_ref().bNumEndpoints }
+    }
+
+    pub fn interface_class(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterfaceClass }
+    }
+
+    pub fn interface_subclass(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterfaceSubClass }
+    }
+
+    pub fn interface_protocol(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterfaceProtocol }
+    }
+
+    pub fn interface(&self) -> u8 {
+        unsafe { self.inner.as_ref().iInterface }
+    }
+
+    ///walks the class-specific interface and endpoint descriptors between this interface and the
+    ///next one in `config`
+    pub fn class_specific_descriptors<'a>(
+        &'a self,
+        config: &'a ConfigurationDescriptor<'_>,
+    ) -> impl Iterator<Item = ClassSpecificDescriptor<'a>> {
+        ClassSpecificDescriptors {
+            config_descriptor: unsafe { config.inner.as_ref() },
+            assoc_descriptor: self.inner.as_ptr() as *const IOUSBDescriptorHeader,
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+}
+
+pub struct ClassSpecificDescriptors<'a> {
+    config_descriptor: *const IOUSBConfigurationDescriptor,
+    current_descriptor: *const IOUSBDescriptorHeader,
+    assoc_descriptor: *const IOUSBDescriptorHeader,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for ClassSpecificDescriptors<'a> {
+    type Item = ClassSpecificDescriptor<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = unsafe {
+                IOUSBGetNextAssociatedDescriptor(
+                    self.config_descriptor,
+                    self.assoc_descriptor,
+                    self.current_descriptor,
+                )
+            };
+            if next.is_null() {
+                return None;
+            }
+            self.current_descriptor = next;
+            if let Some(desc) = ClassSpecificDescriptor::new(next) {
+                return Some(desc);
+            }
+        }
+    }
+}
+
+///a raw class-specific (CS_INTERFACE or CS_ENDPOINT) descriptor, the layout of everything after
+///the subtype is defined by the class specification
+pub struct ClassSpecificDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBDescriptorHeader>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl ClassSpecificDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDescriptorHeader) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDescriptorHeader)?;
+        match DescriptorType::from(unsafe { ptr.as_ref().bDescriptorType }) {
+            DescriptorType::ClassSpecificInterface | DescriptorType::ClassSpecificEndpoint => {
+                Some(Self {
+                    inner: ptr,
+                    lt: PhantomData,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    ///the class-defined subtype, `None` if the descriptor is too short to contain one
+    pub fn descriptor_subtype(&self) -> Option<u8> {
+        self.bytes().get(2).copied()
+    }
+
+    ///the whole descriptor including the header
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, self.length() as usize)
+        }
+    }
+}
+
+pub struct DeviceDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceDescriptor>,
+    lt: PhantomData<&'a IOUSBDeviceDescriptor>,
+}
+
+impl Drop for DeviceDescriptor<'_> {
+    fn drop(&mut self) {}
+}
+
+impl<'a> DeviceDescriptor<'a> {
+    ///views a device descriptor read elsewhere, such as part of an archived dump
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DescriptorError> {
+        if bytes.len() < core::mem::size_of::<IOUSBDeviceDescriptor>() {
+            return Err(DescriptorError::TooShort(bytes.len()));
+        }
+        if bytes[1] != u8::from(DescriptorType::Device) {
+            return Err(DescriptorError::WrongType(bytes[1].into()));
+        }
+        Self::new(bytes.as_ptr() as *const IOUSBDeviceDescriptor)
+            .ok_or(DescriptorError::TooShort(0))
+    }
+}
+
+impl DeviceDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self.inner.as_ptr() as *const u8,
+                core::mem::size_of::<IOUSBDeviceDescriptor>(),
+            )
+        }
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn bcd_usb(&self) -> u16 {
+        unsafe { self.inner.as_ref().bcdUSB }
+    }
+
+    pub fn device_class(&self) -> u8 {
+        unsafe { self.inner.as_ref().bDeviceClass }
+    }
+
+    pub fn device_subclass(&self) -> u8 {
+        unsafe { self.inner.as_ref().bDeviceSubClass }
+    }
+
+    pub fn device_protocol(&self) -> u8 {
+        unsafe { self.inner.as_ref().bDeviceProtocol }
+    }
+
+    pub fn max_packet_size(&self) -> u8 {
+        unsafe { self.inner.as_ref().bMaxPacketSize0 }
+    }
+
+    pub fn vendor_id(&self) -> u16 {
+        unsafe { self.inner.as_ref().idVendor }
+    }
+
+    pub fn product_id(&self) -> u16 {
+        unsafe { self.inner.as_ref().idProduct }
+    }
+
+    pub fn bcd_device(&self) -> u16 {
+        unsafe { self.inner.as_ref().bcdDevice }
+    }
+
+    pub fn manufacturer(&self) -> u8 {
+        unsafe { self.inner.as_ref().iManufacturer }
+    }
+
+    pub fn product(&self) -> u8 {
+        unsafe { self.inner.as_ref().iProduct }
+    }
+
+    pub fn serial_number(&self) -> u8 {
+        unsafe { self.inner.as_ref().iSerialNumber }
+    }
+
+    pub fn configuration_count(&self) -> u8 {
+        unsafe { self.inner.as_ref().bNumConfigurations }
+    }
+}
+
+///a current in milliamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Milliamps(pub u32);
+
+impl core::fmt::Display for Milliamps {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}mA", self.0)
+    }
+}
+
+impl From<Milliamps> for u32 {
+    fn from(current: Milliamps) -> u32 {
+        current.0
+    }
+}
+
+///a voltage in millivolts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millivolts(pub u32);
+
+impl core::fmt::Display for Millivolts {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}mV", self.0)
+    }
+}
+
+impl From<Millivolts> for u32 {
+    fn from(voltage: Millivolts) -> u32 {
+        voltage.0
+    }
+}
+
+///a power in milliwatts, or an energy in milliwatt hours for battery capacities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Milliwatts(pub u32);
+
+impl core::fmt::Display for Milliwatts {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}mW", self.0)
+    }
+}
+
+impl From<Milliwatts> for u32 {
+    fn from(power: Milliwatts) -> u32 {
+        power.0
+    }
+}
+
+pub struct ConfigurationDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBConfigurationDescriptor>,
+    lt: PhantomData<&'a IOUSBConfigurationDescriptor>,
+}
+
+impl<'a> ConfigurationDescriptor<'a> {
+    const LENGTH: usize = 9;
+
+    ///views a full configuration descriptor blob, such as an archived dump or the output of
+    ///`descriptor_bytes`, so it can be walked offline with the same iterators
+    ///
+    ///every descriptor in the blob has to lie within wTotalLength, and interface, endpoint and
+    ///interface association descriptors have to be long enough to be read as such
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DescriptorError> {
+        if bytes.len() < Self::LENGTH {
+            return Err(DescriptorError::TooShort(bytes.len()));
+        }
+        if bytes[1] != u8::from(DescriptorType::Configuration) {
+            return Err(DescriptorError::WrongType(bytes[1].into()));
+        }
+        let total_length = u16::from_le_bytes([bytes[2], bytes[3]]);
+        if (total_length as usize) < Self::LENGTH || total_length as usize > bytes.len() {
+            return Err(DescriptorError::TotalLength {
+                total_length,
+                len: bytes.len(),
+            });
+        }
+
+        let mut offset = 0;
+        while offset < total_length as usize {
+            let length = bytes[offset] as usize;
+            if length < 2 || offset + length > total_length as usize {
+                return Err(DescriptorError::Malformed { offset });
+            }
+            let min_length = match DescriptorType::from(bytes[offset + 1]) {
+                DescriptorType::Interface => 9,
+                DescriptorType::Endpoint => 7,
+                DescriptorType::InterfaceAssociation => 8,
+                _ => 2,
+            };
+            if length < min_length {
+                return Err(DescriptorError::Malformed { offset });
+            }
+            offset += length;
+        }
+
+        Self::new(bytes.as_ptr() as *const IOUSBConfigurationDescriptor)
+            .ok_or(DescriptorError::TooShort(0))
+    }
+}
+
+impl ConfigurationDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBConfigurationDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBConfigurationDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn descriptors(&self) -> impl Iterator<Item = DescriptorHeader<'_>> {
+        Descriptors {
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn descriptors_with_type(
+        &self,
+        descriptor_type: u8,
+    ) -> impl Iterator<Item = DescriptorHeader<'_>> {
+        TypedDescriptors {
+            descriptor_type,
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn associated_descriptors(
+        &self,
+        descriptor: &DescriptorHeader<'_>,
+    ) -> impl Iterator<Item = DescriptorHeader<'_>> {
+        AssociatedDescriptors {
+            assoc_descriptor: descriptor.inner.as_ptr(),
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn associated_descriptors_with_type(
+        &self,
+        descriptor: &DescriptorHeader<'_>,
+        descriptor_type: u8,
+    ) -> impl Iterator<Item = DescriptorHeader<'_>> {
+        TypedAssociatedDescriptors {
+            descriptor_type,
+            assoc_descriptor: descriptor.inner.as_ptr(),
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn interface_descriptors(&self) -> impl Iterator<Item = InterfaceDescriptor<'_>> {
+        InterfaceDescriptors {
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn interface_association_descriptors(
+        &self,
+    ) -> impl Iterator<Item = InterfaceAssociationDescriptor<'_>> {
+        InterfaceAssociationDescriptors {
+            config_descriptor: self.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    ///the endpoints of `interface`, which has to be part of this configuration
+    pub fn endpoint_descriptors(
+        &self,
+        interface: &InterfaceDescriptor<'_>,
+    ) -> impl Iterator<Item = EndpointDescriptor<'_>> {
+        EndpointDescriptors {
+            config_descriptor: self.inner.as_ptr(),
+            interface_descriptor: interface.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn total_length(&self) -> u16 {
+        unsafe { self.inner.as_ref().wTotalLength }
+    }
+
+    ///the whole configuration blob, wTotalLength bytes
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self.inner.as_ptr() as *const u8,
+                self.total_length() as usize,
+            )
+        }
+    }
+
+    pub fn interface_count(&self) -> u8 {
+        unsafe { self.inner.as_ref().bNumInterfaces }
+    }
+
+    pub fn configuration_value(&self) -> u8 {
+        unsafe { self.inner.as_ref().bConfigurationValue }
+    }
+
+    pub fn configuration(&self) -> u8 {
+        unsafe { self.inner.as_ref().iConfiguration }
+    }
+
+    pub fn attributes(&self) -> u8 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+
+    pub fn max_power(&self) -> u8 {
+        unsafe { self.inner.as_ref().MaxPower }
+    }
+
+    pub fn max_power_milliamps(&self, usb_device_speed: u32) -> u32 {
+        unsafe { IOUSBGetConfigurationMaxPowerMilliAmps(usb_device_speed, self.inner.as_ref()) }
+    }
+
+    ///the maximum power drawn in this configuration, scaled for the speed `device` is operating
+    ///at, `None` if the speed could not be read
+    pub fn max_power_ma(&self, device: &UsbDevice<'_>) -> Option<Milliamps> {
+        let speed = device.usb_device_speed()?;
+        Some(Milliamps(self.max_power_milliamps(speed)))
+    }
+
+    ///the configuration's string descriptor (`iConfiguration`) read from `device`, `None` if the
+    ///configuration has no description
+    pub fn description(&self, device: &UsbDevice<'_>) -> Result<Option<String>, UsbError> {
+        match self.configuration() {
+            0 => Ok(None),
+            index => device.string_descriptor(index as u64, None).map(Some),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DescriptorError {
+    ///the blob is shorter than the descriptor it should hold
+    TooShort(usize),
+    ///the blob does not start with the expected descriptor type
+    WrongType(DescriptorType),
+    ///wTotalLength is shorter than the configuration descriptor or longer than the blob
+    TotalLength { total_length: u16, len: usize },
+    ///the descriptor at `offset` has a bLength below 2 or runs past wTotalLength
+    Malformed { offset: usize },
+}
+
+impl core::fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DescriptorError::TooShort(len) => {
+                write!(f, "{len} bytes are too short for the descriptor")
+            }
+            DescriptorError::WrongType(ty) => {
+                write!(f, "unexpected descriptor type {ty:?}")
+            }
+            DescriptorError::TotalLength { total_length, len } => {
+                write!(
+                    f,
+                    "wTotalLength {total_length} does not fit a {len} byte blob"
+                )
+            }
+            DescriptorError::Malformed { offset } => {
+                write!(f, "malformed descriptor at offset {offset}")
+            }
+        }
+    }
+}
+
+pub struct Descriptors<'a> {
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+pub struct DescriptorHeader<'a> {
+    pub(crate) inner: NonNull<IOUSBDescriptorHeader>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DescriptorHeader<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDescriptorHeader) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDescriptorHeader)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    ///the whole descriptor including the header
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, self.length() as usize)
+        }
+    }
+
+    pub fn otg_descriptor(&self) -> Option<OtgDescriptor<'_>> {
+        match self.descriptor_type() {
+            DescriptorType::OTG if self.length() >= OtgDescriptor::MIN_LENGTH => {
+                Some(OtgDescriptor {
+                    inner: self.inner,
+                    lt: PhantomData,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn debug_descriptor(&self) -> Option<DebugDescriptor<'_>> {
+        match self.descriptor_type() {
+            DescriptorType::Debug if self.length() >= DebugDescriptor::LENGTH => {
+                Some(DebugDescriptor {
+                    inner: self.inner,
+                    lt: PhantomData,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct OtgDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBDescriptorHeader>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl OtgDescriptor<'_> {
+    const MIN_LENGTH: u8 = 3;
+    const SRP_SUPPORT: u8 = 1 << 0;
+    const HNP_SUPPORT: u8 = 1 << 1;
+    const ADP_SUPPORT: u8 = 1 << 2;
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        let len = unsafe { self.inner.as_ref().bLength } as usize;
+        unsafe { core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, len) }
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn attributes(&self) -> u8 {
+        self.bytes()[2]
+    }
+
+    pub fn srp_support(&self) -> bool {
+        self.attributes() & Self::SRP_SUPPORT != 0
+    }
+
+    pub fn hnp_support(&self) -> bool {
+        self.attributes() & Self::HNP_SUPPORT != 0
+    }
+
+    pub fn adp_support(&self) -> bool {
+        self.attributes() & Self::ADP_SUPPORT != 0
+    }
+
+    ///only present on OTG 2.0 and later descriptors
+    pub fn bcd_otg(&self) -> Option<u16> {
+        let bytes = self.bytes();
+        Some(u16::from_le_bytes([*bytes.get(3)?, *bytes.get(4)?]))
+    }
+}
+
+pub struct DebugDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBDescriptorHeader>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DebugDescriptor<'_> {
+    const LENGTH: u8 = 4;
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, Self::LENGTH as usize)
+        }
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn debug_in_endpoint(&self) -> u8 {
+        self.bytes()[2]
+    }
+
+    pub fn debug_out_endpoint(&self) -> u8 {
+        self.bytes()[3]
+    }
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = DescriptorHeader<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next =
+            unsafe { IOUSBGetNextDescriptor(self.config_descriptor, self.current_descriptor) };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next;
+        DescriptorHeader::new(next)
+    }
+}
+
+pub struct TypedDescriptors<'a> {
+    pub(crate) descriptor_type: u8,
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for TypedDescriptors<'a> {
+    type Item = DescriptorHeader<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextDescriptorWithType(
+                self.config_descriptor,
+                self.current_descriptor,
+                self.descriptor_type,
+            )
+        };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next;
+        DescriptorHeader::new(next)
+    }
+}
+
+pub struct AssociatedDescriptors<'a> {
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) assoc_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for AssociatedDescriptors<'a> {
+    type Item = DescriptorHeader<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextAssociatedDescriptor(
+                self.config_descriptor,
+                self.assoc_descriptor,
+                self.current_descriptor,
+            )
+        };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next;
+        DescriptorHeader::new(next)
+    }
+}
+
+pub struct TypedAssociatedDescriptors<'a> {
+    pub(crate) descriptor_type: u8,
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) assoc_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for TypedAssociatedDescriptors<'a> {
+    type Item = DescriptorHeader<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextAssociatedDescriptorWithType(
+                self.config_descriptor,
+                self.assoc_descriptor,
+                self.current_descriptor,
+                self.descriptor_type,
+            )
+        };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next;
+        DescriptorHeader::new(next)
+    }
+}
+
+pub struct InterfaceAssociationDescriptors<'a> {
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for InterfaceAssociationDescriptors<'a> {
+    type Item = InterfaceAssociationDescriptor<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextInterfaceAssociationDescriptor(
+                self.config_descriptor,
+                self.current_descriptor,
+            )
+        };
+
+        if next.is_null() {
+            return None;
+        }
+
+        self.current_descriptor = next as *const IOUSBDescriptorHeader;
+        InterfaceAssociationDescriptor::new(next)
+    }
+}
+
+pub struct InterfaceAssociationDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBInterfaceAssociationDescriptor>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl InterfaceAssociationDescriptor<'_> {
+    pub(crate) fn new(raw: *const IOUSBInterfaceAssociationDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(raw as *mut IOUSBInterfaceAssociationDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    ///whether the interface `interface_number` is part of this function
+    pub fn contains(&self, interface_number: u8) -> bool {
+        let first = self.first_interface();
+        interface_number >= first && (interface_number - first) < self.interface_count()
+    }
+
+    ///the interface descriptors of `config` grouped into this function, alternate settings
+    ///included
+    pub fn interfaces<'c>(
+        &self,
+        config: &'c ConfigurationDescriptor<'_>,
+    ) -> impl Iterator<Item = InterfaceDescriptor<'c>> + 'c {
+        let (first, count) = (self.first_interface(), self.interface_count());
+        InterfaceDescriptors {
+            config_descriptor: config.inner.as_ptr(),
+            current_descriptor: ptr::null(),
+            lt: PhantomData,
+        }
+        .filter(move |interface| {
+            let number = interface.interface_number();
+            number >= first && (number - first) < count
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn first_interface(&self) -> u8 {
+        unsafe { self.inner.as_ref().bFirstInterface }
+    }
+
+    pub fn interface_count(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterfaceCount }
+    }
+
+    pub fn function_class(&self) -> u8 {
+        unsafe { self.inner.as_ref().bFunctionClass }
+    }
+
+    pub fn function_subclass(&self) -> u8 {
+        unsafe { self.inner.as_ref().bFunctionSubClass }
+    }
+
+    pub fn function(&self) -> u8 {
+        unsafe { self.inner.as_ref().iFunction }
+    }
+}
+
+pub struct InterfaceDescriptors<'a> {
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for InterfaceDescriptors<'a> {
+    type Item = InterfaceDescriptor<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextInterfaceDescriptor(self.config_descriptor, self.current_descriptor)
+        };
+
+        if next.is_null() {
+            return None;
+        }
+
+        let desc = InterfaceDescriptor::new(next)?;
+        self.current_descriptor = next as *const IOUSBDescriptorHeader;
+        Some(desc)
+    }
+}
+
+pub struct EndpointDescriptors<'a> {
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) interface_descriptor: *const IOUSBInterfaceDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for EndpointDescriptors<'a> {
+    type Item = EndpointDescriptor<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextEndpointDescriptor(
+                self.config_descriptor,
+                self.interface_descriptor,
+                self.current_descriptor,
+            )
+        };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next as *const IOUSBDescriptorHeader;
+        EndpointDescriptor::new(next)
+    }
+}
+
+pub struct CapabilityDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBBOSDescriptor>,
+    lt: PhantomData<&'a IOUSBBOSDescriptor>,
+}
+
+impl<'a> CapabilityDescriptor<'a> {
+    const LENGTH: usize = 5;
+
+    ///views a full BOS descriptor blob so its capabilities can be walked offline
+    ///
+    ///every capability has to lie within wTotalLength and be long enough to be read as the
+    ///capability type it claims to be
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DescriptorError> {
+        if bytes.len() < Self::LENGTH {
+            return Err(DescriptorError::TooShort(bytes.len()));
+        }
+        if bytes[1] != u8::from(DescriptorType::CapabilityDescriptor) {
+            return Err(DescriptorError::WrongType(bytes[1].into()));
+        }
+        let total_length = u16::from_le_bytes([bytes[2], bytes[3]]);
+        if (total_length as usize) < Self::LENGTH || total_length as usize > bytes.len() {
+            return Err(DescriptorError::TotalLength {
+                total_length,
+                len: bytes.len(),
+            });
+        }
+
+        let mut offset = bytes[0] as usize;
+        if offset < Self::LENGTH || offset > total_length as usize {
+            return Err(DescriptorError::Malformed { offset: 0 });
+        }
+        while offset < total_length as usize {
+            let length = bytes[offset] as usize;
+            if length < 3 || offset + length > total_length as usize {
+                return Err(DescriptorError::Malformed { offset });
+            }
+            let min_length = match DeviceCapabilityType::from(bytes[offset + 2]) {
+                DeviceCapabilityType::Usb2Extension => 7,
+                DeviceCapabilityType::SuperSpeed => 10,
+                DeviceCapabilityType::ContainerID | DeviceCapabilityType::Platform => 20,
+                DeviceCapabilityType::SuperSpeedPlus => 12,
+                DeviceCapabilityType::Billboard => 44,
+                _ => 3,
+            };
+            if length < min_length {
+                return Err(DescriptorError::Malformed { offset });
+            }
+            offset += length;
+        }
+
+        Self::new(bytes.as_ptr() as *const IOUSBBOSDescriptor).ok_or(DescriptorError::TooShort(0))
+    }
+}
+
+impl CapabilityDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBBOSDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBBOSDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn capabilities(&self) -> impl Iterator<Item = Capability<'_>> {
+        let current_descriptor = ptr::null();
+        Capabilities {
+            current_descriptor,
+            bos_descriptor: unsafe { self.inner.as_ref() },
+            lt: PhantomData,
+        }
+    }
+
+    pub fn capabilities_with_type(
+        &self,
+        capability_type: u8,
+    ) -> impl Iterator<Item = Capability<'_>> {
+        let current_descriptor = ptr::null();
+        TypedCapabilities {
+            current_descriptor,
+            capability_type,
+            bos_descriptor: unsafe { self.inner.as_ref() },
+            lt: PhantomData,
+        }
+    }
+
+    pub fn usb_20_extension_device_capability_descriptor(
+        &self,
+    ) -> Option<DeviceCapabilityUsb2Extension<'_>> {
+        let ptr = unsafe { IOUSBGetUSB20ExtensionDeviceCapabilityDescriptor(self.inner.as_ref()) };
+        DeviceCapabilityUsb2Extension::new(ptr)
+    }
+
+    pub fn super_speed_device_capability_descriptor(&self) -> Option<DeviceCapabilitySS<'_>> {
+        let ptr = unsafe { IOUSBGetSuperSpeedDeviceCapabilityDescriptor(self.inner.as_ref()) };
+        DeviceCapabilitySS::new(ptr)
+    }
+
+    pub fn super_speed_plus_capability_descriptor(&self) -> Option<DeviceCapabilitySSP<'_>> {
+        DeviceCapabilitySSP::new(unsafe {
+            IOUSBGetSuperSpeedPlusDeviceCapabilityDescriptor(self.inner.as_ref())
+        })
+    }
+
+    pub fn container_id_descriptor(&self) -> Option<DeviceCapabilityContainerId<'_>> {
+        let ptr = unsafe { IOUSBGetContainerIDDescriptor(self.inner.as_ref()) };
+        DeviceCapabilityContainerId::new(ptr)
+    }
+
+    pub fn platform_capability_descriptor(
+        &self,
+        uuid: Option<&str>,
+    ) -> Option<PlatformCapabilityDescriptor<'_>> {
+        let ptr = unsafe {
+            match uuid {
+                Some(uuid) => IOUSBGetPlatformCapabilityDescriptorWithUUID(
+                    self.inner.as_ref(),
+                    uuid.as_bytes().as_ptr() as *mut u8,
+                ),
+                None => IOUSBGetPlatformCapabilityDescriptor(self.inner.as_ref()),
+            }
+        };
+        PlatformCapabilityDescriptor::new(ptr)
+    }
+
+    pub fn billboard_descriptor(&self) -> Option<DeviceCapabilityBillboard> {
+        DeviceCapabilityBillboard::new(unsafe { IOUSBGetBillboardDescriptor(self.inner.as_ref()) })
+    }
+
+    pub fn power_delivery_capability(&self) -> Option<PowerDeliveryCapability> {
+        self.capabilities_with_type(DeviceCapabilityType::PowerDelivery.into())
+            .find_map(|capability| capability.power_delivery())
+    }
+
+    ///one entry per battery the device reports
+    pub fn battery_info_capabilities(&self) -> Vec<BatteryInfoCapability> {
+        self.capabilities_with_type(DeviceCapabilityType::BatteryInfo.into())
+            .filter_map(|capability| capability.battery_info())
+            .collect()
+    }
+
+    pub fn pd_consumer_port_capability(&self) -> Option<PdConsumerPortCapability> {
+        self.capabilities_with_type(DeviceCapabilityType::PdConsumerPort.into())
+            .find_map(|capability| capability.pd_consumer_port())
+    }
+
+    ///collects the commonly checked capabilities into an owned summary
+    pub fn summary(&self) -> BosSummary {
+        let (lpm, besl_lpm) = match self.usb_20_extension_device_capability_descriptor() {
+            Some(usb2) => (
+                usb2.attributes() & (1 << 1) != 0,
+                usb2.attributes() & (1 << 2) != 0,
+            ),
+            None => (false, false),
+        };
+        let super_speed =
+            self.super_speed_device_capability_descriptor()
+                .map(|ss| SuperSpeedSummary {
+                    speeds_supported: ss.speeds_supported(),
+                    u1_exit_latency: ss.u1_dev_exit_lat(),
+                    u2_exit_latency: ss.u2_dev_exit_lat(),
+                });
+        let sublink_speeds = self
+            .super_speed_plus_capability_descriptor()
+            .map(|ssp| {
+                ssp.sublink_speed_attributes()
+                    .map(SublinkSpeed::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let container_id = self
+            .container_id_descriptor()
+            .map(|container| *container.container_id());
+        let platform_capabilities = self
+            .capabilities_with_type(DeviceCapabilityType::Platform.into())
+            .filter_map(|capability| {
+                let uuid = capability.bytes().get(4..20)?;
+                <[u8; 16]>::try_from(uuid).ok()
+            })
+            .collect();
+
+        BosSummary {
+            lpm,
+            besl_lpm,
+            super_speed,
+            sublink_speeds,
+            container_id,
+            platform_capabilities,
+        }
+    }
+
+    pub fn pd_provider_port_capability(&self) -> Option<PdProviderPortCapability> {
+        self.capabilities_with_type(DeviceCapabilityType::PdProviderPort.into())
+            .find_map(|capability| capability.pd_provider_port())
+    }
+}
+
+pub struct DeviceCapabilityUsb2Extension<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilityUSB2Extension>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilityUsb2Extension<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilityUSB2Extension) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityUSB2Extension)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn attributes(&self) -> u32 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+}
+
+pub struct DeviceCapabilitySS<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilitySuperSpeedUSB>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilitySS<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilitySuperSpeedUSB) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilitySuperSpeedUSB)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn attributes(&self) -> u8 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+
+    pub fn speeds_supported(&self) -> u16 {
+        unsafe { self.inner.as_ref().wSpeedsSupported }
+    }
+
+    pub fn functionality_support(&self) -> u8 {
+        unsafe { self.inner.as_ref().bFunctionalitySupport }
+    }
+
+    pub fn u1_dev_exit_lat(&self) -> u8 {
+        unsafe { self.inner.as_ref().bU1DevExitLat }
+    }
+
+    pub fn u2_dev_exit_lat(&self) -> u16 {
+        unsafe { self.inner.as_ref().wU2DevExitLat }
+    }
+
+    pub fn dev_exit_lat(&self) -> (u8, u16) {
+        let ptr = unsafe { self.inner.as_ref() };
+        (ptr.bU1DevExitLat, ptr.wU2DevExitLat)
+    }
+}
+
+pub struct DeviceCapabilitySSP<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilitySuperSpeedPlusUSB>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilitySSP<'_> {
+    const HEADER_LENGTH: usize = 12;
+
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilitySuperSpeedPlusUSB) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilitySuperSpeedPlusUSB)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn attributes(&self) -> u32 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+
+    pub fn functionality_support(&self) -> u16 {
+        unsafe { self.inner.as_ref().wFunctionalitySupport }
+    }
+
+    ///bounded by both the sublink speed attribute count in bmAttributes and bLength
+    pub fn sublink_speed_attributes(&self) -> impl Iterator<Item = u32> {
+        //bits 4:0 hold the number of sublink speed attributes minus one
+        let count = (self.attributes() & 0x1f) as usize + 1;
+        let fits = (self.length() as usize).saturating_sub(Self::HEADER_LENGTH) / 4;
+        let ptr = unsafe { self.inner.as_ref() };
+        let ptr = ptr::addr_of!(ptr.bmSublinkSpeedAttr);
+        SublinkSpeedAttrs {
+            inner: ptr as *const u32,
+            remaining: count.min(fits),
+            lt: PhantomData,
+        }
+    }
+}
+
+///the capabilities of a BOS descriptor most checks are after, see `UsbDevice::bos`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BosSummary {
+    ///link power management, from the USB 2.0 extension
+    pub lpm: bool,
+    ///BESL link power management, from the USB 2.0 extension
+    pub besl_lpm: bool,
+    pub super_speed: Option<SuperSpeedSummary>,
+    pub sublink_speeds: Vec<SublinkSpeed>,
+    pub container_id: Option<[u8; 16]>,
+    ///the UUIDs of the platform capabilities
+    pub platform_capabilities: Vec<[u8; 16]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SuperSpeedSummary {
+    ///bit 0 low, 1 full, 2 high and 3 SuperSpeed
+    pub speeds_supported: u16,
+    ///in microseconds
+    pub u1_exit_latency: u8,
+    ///in microseconds
+    pub u2_exit_latency: u16,
+}
+
+///a decoded SuperSpeedPlus sublink speed attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SublinkSpeed {
+    ///the sublink speed attribute id, shared by the receive and transmit entries of a speed
+    pub id: u8,
+    pub bits_per_second: u64,
+    pub symmetric: bool,
+    pub transmit: bool,
+    ///the link protocol is SuperSpeedPlus rather than SuperSpeed
+    pub super_speed_plus: bool,
+}
+
+impl From<u32> for SublinkSpeed {
+    fn from(attr: u32) -> SublinkSpeed {
+        let exponent = (attr >> 4) & 3;
+        let mantissa = (attr >> 16) as u64;
+        SublinkSpeed {
+            id: (attr & 0xf) as u8,
+            bits_per_second: mantissa * 1000u64.pow(exponent),
+            symmetric: attr & (1 << 6) == 0,
+            transmit: attr & (1 << 7) != 0,
+            super_speed_plus: (attr >> 14) & 3 == 1,
+        }
+    }
+}
+
+pub struct SublinkSpeedAttrs<'a> {
+    pub(crate) inner: *const u32,
+    remaining: usize,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for SublinkSpeedAttrs<'a> {
+    type Item = u32;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.inner.is_null() {
+            return None;
+        }
+        let item = unsafe { self.inner.read_unaligned() };
+        self.inner = unsafe { self.inner.add(1) };
+        self.remaining -= 1;
+        Some(item)
+    }
+}
+
+pub struct DeviceCapabilityContainerId<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilityContainerID>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilityContainerId<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilityContainerID) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityContainerID)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn reserved_id(&self) -> u8 {
+        unsafe { self.inner.as_ref().bReservedID }
+    }
+
+    pub fn container_id(&self) -> &[u8; 16] {
+        unsafe { &self.inner.as_ref().containerID }
+    }
+}
+
+pub struct PlatformCapabilityDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBPlatformCapabilityDescriptor>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl PlatformCapabilityDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBPlatformCapabilityDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBPlatformCapabilityDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn platform_capability_uuid(&self) -> &[u8; 16] {
+        unsafe { &self.inner.as_ref().PlatformCapabilityUUID }
+    }
+}
+
+pub struct DeviceCapabilityBillboard<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilityBillboard>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilityBillboard<'_> {
+    const HEADER_LENGTH: usize = 44;
+
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilityBillboard) -> Option<Self> {
+        let inner = NonNull::new(ptr as *mut IOUSBDeviceCapabilityBillboard)?;
+        Some(Self {
+            inner,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    ///index of string descriptor providing a URL for detailed information about the product and
+    ///supported modes
+    pub fn additional_info_url(&self) -> u8 {
+        unsafe { self.inner.as_ref().iAdditionalInfoURL }
+    }
+
+    pub fn alternate_modes_count(&self) -> u8 {
+        unsafe { self.inner.as_ref().bNumberOfAlternateModes }
+    }
+
+    pub fn preferred_alternate_mode(&self) -> u8 {
+        unsafe { self.inner.as_ref().bPreferredAlternateMode }
+    }
+
+    pub fn connection_power(&self) -> u16 {
+        unsafe { self.inner.as_ref().vCONNPower }
+    }
+
+    pub fn configured(&self) -> &[u8; 32] {
+        unsafe { &self.inner.as_ref().bmConfigured }
+    }
+
+    pub fn bcd_version(&self) -> u16 {
+        unsafe { self.inner.as_ref().bcdVersion }
+    }
+
+    pub fn additional_failure_info(&self) -> u8 {
+        unsafe { self.inner.as_ref().bAdditionalFailureInfo }
+    }
+
+    ///the configuration state of alternate mode `index` from bmConfigured
+    pub fn alternate_mode_state(&self, index: u8) -> AlternateModeState {
+        let byte = self
+            .configured()
+            .get(index as usize / 4)
+            .copied()
+            .unwrap_or(0);
+        ((byte >> ((index % 4) * 2)) & 3).into()
+    }
+
+    ///reads the additional info URL and every alternate mode's description from `device`'s
+    ///string descriptors
+    pub fn resolve(&self, device: &UsbDevice<'_>) -> Result<BillboardInfo, UsbError> {
+        let string = |index: u8| match index {
+            0 => Ok(None),
+            index => device.string_descriptor(index as u64, None).map(Some),
+        };
+
+        let mut alternate_modes = Vec::new();
+        for (index, alt) in self.alt_configurations().enumerate() {
+            alternate_modes.push(BillboardAlternateMode {
+                svid: alt.svid(),
+                alternate_mode: alt.altenate_mode(),
+                description: string(alt.alternate_mode_setting())?,
+                state: self.alternate_mode_state(index as u8),
+            });
+        }
+
+        Ok(BillboardInfo {
+            additional_info_url: string(self.additional_info_url())?,
+            preferred_alternate_mode: self.preferred_alternate_mode(),
+            connection_power: self.connection_power(),
+            bcd_version: self.bcd_version(),
+            additional_failure_info: self.additional_failure_info(),
+            alternate_modes,
+        })
+    }
+
+    ///bounded by both bNumberOfAlternateModes and bLength
+    pub fn alt_configurations(
+        &self,
+    ) -> impl Iterator<Item = DeviceCapabilityBillboardAltConfiguration<'_>> {
+        let fits = (self.length() as usize).saturating_sub(Self::HEADER_LENGTH) / 4;
+        let configs = unsafe { &self.inner.as_ref().pAltConfigurations };
+        DeviceCapabilityBillboardAltConfigurations {
+            inner: configs.as_ptr(),
+            remaining: (self.alternate_modes_count() as usize).min(fits),
+            lt: PhantomData,
+        }
+    }
+}
+
+///a billboard capability with its string descriptors resolved, see
+///`DeviceCapabilityBillboard::resolve`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BillboardInfo {
+    pub additional_info_url: Option<String>,
+    pub preferred_alternate_mode: u8,
+    ///VCONN power, as the raw vCONNPower field
+    pub connection_power: u16,
+    pub bcd_version: u16,
+    pub additional_failure_info: u8,
+    pub alternate_modes: Vec<BillboardAlternateMode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BillboardAlternateMode {
+    pub svid: u16,
+    pub alternate_mode: u8,
+    pub description: Option<String>,
+    pub state: AlternateModeState,
+}
+
+///the two bmConfigured bits of an alternate mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlternateModeState {
+    Unspecified = 0,
+    ///the mode was not attempted, or exited
+    NotAttempted = 1,
+    Unsuccessful = 2,
+    Configured = 3,
+}
+
+impl From<u8> for AlternateModeState {
+    fn from(bits: u8) -> AlternateModeState {
+        match bits & 3 {
+            1 => AlternateModeState::NotAttempted,
+            2 => AlternateModeState::Unsuccessful,
+            3 => AlternateModeState::Configured,
+            _ => AlternateModeState::Unspecified,
+        }
+    }
+}
+
+pub struct DeviceCapabilityBillboardAltConfigurations<'a> {
+    pub(crate) inner: *const IOUSBDeviceCapabilityBillboardAltConfig,
+    remaining: usize,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for DeviceCapabilityBillboardAltConfigurations<'a> {
+    type Item = DeviceCapabilityBillboardAltConfiguration<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let next = DeviceCapabilityBillboardAltConfiguration::new(self.inner)?;
+        self.inner = unsafe { self.inner.add(1) };
+        self.remaining -= 1;
+        Some(next)
+    }
+}
+
+pub struct DeviceCapabilityBillboardAltConfiguration<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilityBillboardAltConfig>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl DeviceCapabilityBillboardAltConfiguration<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilityBillboardAltConfig) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityBillboardAltConfig)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn svid(&self) -> u16 {
+        unsafe { self.inner.as_ref().wSVID }
+    }
+
+    pub fn altenate_mode(&self) -> u8 {
+        unsafe { self.inner.as_ref().bAltenateMode }
+    }
+
+    /// index for alternate mode settings
+    pub fn alternate_mode_setting(&self) -> u8 {
+        unsafe { self.inner.as_ref().iAlternateModeString }
+    }
+}
+
+pub struct CapabilityDescriptors<'a> {
+    pub(crate) inner: *const IOUSBBOSDescriptor,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+pub struct Capabilities<'a> {
+    bos_descriptor: *const IOUSBBOSDescriptor,
+    current_descriptor: *const IOUSBDeviceCapabilityDescriptorHeader,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for Capabilities<'a> {
+    type Item = Capability<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextCapabilityDescriptor(self.bos_descriptor, self.current_descriptor)
+        };
+
+        if next.is_null() {
+            return None;
+        }
+
+        self.current_descriptor = next;
+        Capability::new(next)
+    }
+}
+
+pub struct TypedCapabilities<'a> {
+    capability_type: u8,
+    bos_descriptor: *const IOUSBBOSDescriptor,
+    current_descriptor: *const IOUSBDeviceCapabilityDescriptorHeader,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for TypedCapabilities<'a> {
+    type Item = Capability<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextCapabilityDescriptorWithType(
+                self.bos_descriptor,
+                self.current_descriptor,
+                self.capability_type,
+            )
+        };
+
+        if next.is_null() {
+            return None;
+        }
+
+        self.current_descriptor = next;
+        Capability::new(next)
+    }
+}
+
+pub struct Capability<'a> {
+    pub(crate) inner: NonNull<IOUSBDeviceCapabilityDescriptorHeader>,
+    lt: PhantomData<&'a IOUSBDeviceCapabilityDescriptorHeader>,
+}
+
+impl Capability<'_> {
+    pub(crate) fn new(ptr: *const IOUSBDeviceCapabilityDescriptorHeader) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityDescriptorHeader)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn device_capability_type(&self) -> DeviceCapabilityType {
+        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        let len = self.length() as usize;
+        unsafe { core::slice::from_raw_parts(self.inner.as_ptr() as *const u8, len) }
+    }
+
+    ///the bytes of the capability if it is of type `ty`
+    fn bytes_of(&self, ty: DeviceCapabilityType) -> Option<&[u8]> {
+        (self.device_capability_type() == ty).then(|| self.bytes())
+    }
+
+    pub fn power_delivery(&self) -> Option<PowerDeliveryCapability> {
+        let bytes = self.bytes_of(DeviceCapabilityType::PowerDelivery)?;
+        Some(PowerDeliveryCapability {
+            attributes: u32_at(bytes, 4)?,
+            bcd_battery_charging: u16_at(bytes, 8)?,
+            bcd_power_delivery: u16_at(bytes, 10)?,
+            bcd_type_c: u16_at(bytes, 12)?,
+        })
+    }
+
+    pub fn battery_info(&self) -> Option<BatteryInfoCapability> {
+        let bytes = self.bytes_of(DeviceCapabilityType::BatteryInfo)?;
+        Some(BatteryInfoCapability {
+            battery: *bytes.get(3)?,
+            serial: *bytes.get(4)?,
+            manufacturer: *bytes.get(5)?,
+            battery_id: *bytes.get(6)?,
+            charged_threshold: Milliwatts(u32_at(bytes, 8)?),
+            weak_threshold: Milliwatts(u32_at(bytes, 12)?),
+            design_capacity: Milliwatts(u32_at(bytes, 16)?),
+            last_full_charge_capacity: Milliwatts(u32_at(bytes, 20)?),
+        })
+    }
+
+    pub fn pd_consumer_port(&self) -> Option<PdConsumerPortCapability> {
+        let bytes = self.bytes_of(DeviceCapabilityType::PdConsumerPort)?;
+        Some(PdConsumerPortCapability {
+            capabilities: u16_at(bytes, 4)?,
+            min_voltage: Millivolts(u16_at(bytes, 6)? as u32 * 50),
+            max_voltage: Millivolts(u16_at(bytes, 8)? as u32 * 50),
+            max_operating_power: Milliwatts(u32_at(bytes, 12)?.saturating_mul(10)),
+            max_peak_power: Milliwatts(u32_at(bytes, 16)?.saturating_mul(10)),
+            max_peak_power_time: core::time::Duration::from_millis(u32_at(bytes, 20)? as u64 * 100),
+        })
+    }
+
+    pub fn pd_provider_port(&self) -> Option<PdProviderPortCapability> {
+        let bytes = self.bytes_of(DeviceCapabilityType::PdProviderPort)?;
+        let count = *bytes.get(5)? as usize;
+        let power_data_objects = bytes
+            .get(6..)?
+            .chunks_exact(4)
+            .take(count)
+            .map(|pdo| PowerDataObject::from(u32::from_le_bytes([pdo[0], pdo[1], pdo[2], pdo[3]])))
+            .collect();
+        Some(PdProviderPortCapability {
+            capabilities: *bytes.get(4)?,
+            power_data_objects,
+        })
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    let field = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([field[0], field[1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let field = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+///the USB Power Delivery capability (type 6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PowerDeliveryCapability {
+    pub attributes: u32,
+    pub bcd_battery_charging: u16,
+    pub bcd_power_delivery: u16,
+    pub bcd_type_c: u16,
+}
+
+impl PowerDeliveryCapability {
+    pub fn battery_charging(&self) -> bool {
+        self.attributes & (1 << 1) != 0
+    }
+
+    pub fn usb_power_delivery(&self) -> bool {
+        self.attributes & (1 << 2) != 0
+    }
+
+    pub fn provider(&self) -> bool {
+        self.attributes & (1 << 3) != 0
+    }
+
+    pub fn consumer(&self) -> bool {
+        self.attributes & (1 << 4) != 0
+    }
+
+    pub fn charging_policy(&self) -> bool {
+        self.attributes & (1 << 5) != 0
+    }
+
+    pub fn type_c_current(&self) -> bool {
+        self.attributes & (1 << 6) != 0
+    }
+
+    pub fn ac_supply(&self) -> bool {
+        self.attributes & (1 << 8) != 0
+    }
+
+    pub fn battery(&self) -> bool {
+        self.attributes & (1 << 9) != 0
+    }
+}
+
+///the Battery Info capability (type 7), capacities and thresholds in milliwatt hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatteryInfoCapability {
+    ///string descriptor index of the battery's name
+    pub battery: u8,
+    ///string descriptor index of the battery's serial number
+    pub serial: u8,
+    ///string descriptor index of the battery's manufacturer
+    pub manufacturer: u8,
+    pub battery_id: u8,
+    pub charged_threshold: Milliwatts,
+    pub weak_threshold: Milliwatts,
+    pub design_capacity: Milliwatts,
+    pub last_full_charge_capacity: Milliwatts,
+}
+
+///the PD Consumer Port capability (type 8)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PdConsumerPortCapability {
+    ///bit 0 battery charging, bit 1 USB PD, bit 2 USB Type-C current
+    pub capabilities: u16,
+    pub min_voltage: Millivolts,
+    pub max_voltage: Millivolts,
+    pub max_operating_power: Milliwatts,
+    pub max_peak_power: Milliwatts,
+    pub max_peak_power_time: core::time::Duration,
+}
+
+///the PD Provider Port capability (type 9)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PdProviderPortCapability {
+    ///bit 0 battery charging, bit 1 USB PD, bit 2 USB Type-C current
+    pub capabilities: u8,
+    pub power_data_objects: Vec<PowerDataObject>,
+}
+
+///a source power data object advertised by a provider port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerDataObject {
+    Fixed {
+        voltage: Millivolts,
+        max_current: Milliamps,
+    },
+    Battery {
+        min_voltage: Millivolts,
+        max_voltage: Millivolts,
+        max_power: Milliwatts,
+    },
+    Variable {
+        min_voltage: Millivolts,
+        max_voltage: Millivolts,
+        max_current: Milliamps,
+    },
+    ///a programmable power supply augmented PDO
+    Programmable {
+        min_voltage: Millivolts,
+        max_voltage: Millivolts,
+        max_current: Milliamps,
+    },
+    Other(u32),
+}
+
+impl From<u32> for PowerDataObject {
+    fn from(pdo: u32) -> PowerDataObject {
+        use PowerDataObject as PDO;
+        let field = |shift: u32, bits: u32| (pdo >> shift) & ((1 << bits) - 1);
+        match pdo >> 30 {
+            0 => PDO::Fixed {
+                voltage: Millivolts(field(10, 10) * 50),
+                max_current: Milliamps(field(0, 10) * 10),
+            },
+            1 => PDO::Battery {
+                min_voltage: Millivolts(field(10, 10) * 50),
+                max_voltage: Millivolts(field(20, 10) * 50),
+                max_power: Milliwatts(field(0, 10) * 250),
+            },
+            2 => PDO::Variable {
+                min_voltage: Millivolts(field(10, 10) * 50),
+                max_voltage: Millivolts(field(20, 10) * 50),
+                max_current: Milliamps(field(0, 10) * 10),
+            },
+            _ if field(28, 2) == 0 => PDO::Programmable {
+                min_voltage: Millivolts(field(8, 8) * 100),
+                max_voltage: Millivolts(field(17, 8) * 100),
+                max_current: Milliamps(field(0, 7) * 50),
+            },
+            _ => PDO::Other(pdo),
+        }
+    }
+}
+
+impl<'a> Iterator for CapabilityDescriptors<'a> {
+    type Item = CapabilityDescriptor<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let ptr = unsafe { self.inner.add(1) };
+        let next = CapabilityDescriptor::new(ptr)?;
+        self.inner = ptr;
+        Some(next)
+    }
+}
+
+impl Drop for CapabilityDescriptor<'_> {
+    fn drop(&mut self) {}
+}
+
+pub struct EndpointDescriptor<'a> {
+    pub(crate) inner: NonNull<IOUSBEndpointDescriptor>,
+    lt: PhantomData<&'a IOUSBEndpointDescriptor>,
+}
+
+impl EndpointDescriptor<'_> {
+    pub(crate) fn new(ptr: *const IOUSBEndpointDescriptor) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBEndpointDescriptor)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub(crate) fn from_ref(descriptor: &IOUSBEndpointDescriptor) -> Self {
+        Self {
+            inner: NonNull::from(descriptor),
+            lt: PhantomData,
+        }
+    }
+    const SYNC_TYPE_MASK: u8 = 0x0c;
+    const SYNC_TYPE_PHASE: u8 = 2;
+    const USAGE_TYPE_MASK: u8 = 0x30;
+    const USAGE_TYPE_PHASE: u8 = 4;
+
+    pub fn length(&self) -> u8 {
+        unsafe { self.inner.as_ref().bLength }
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        unsafe { self.inner.as_ref().bDescriptorType }.into()
+    }
+
+    pub fn endpoint_address(&self) -> EndpointAddress {
+        EndpointAddress(unsafe { self.inner.as_ref().bEndpointAddress })
+    }
+
+    pub fn interval(&self) -> u8 {
+        unsafe { self.inner.as_ref().bInterval }
+    }
+
+    pub fn attributes(&self) -> u8 {
+        unsafe { self.inner.as_ref().bmAttributes }
+    }
+
+    pub fn max_packet_size(&self) -> u16 {
+        unsafe { self.inner.as_ref().wMaxPacketSize }
+    }
+
+    pub fn transfer_type(&self) -> EndpointType {
+        unsafe { IOUSBGetEndpointType(self.inner.as_ref()) }.into()
+    }
+
+    ///only meaningful for isochronous endpoints
+    pub fn sync_type(&self) -> SynchronizationType {
+        ((self.attributes() & Self::SYNC_TYPE_MASK) >> Self::SYNC_TYPE_PHASE).into()
+    }
+
+    ///only meaningful for isochronous and interrupt endpoints
+    pub fn usage_type(&self) -> UsageType {
+        ((self.attributes() & Self::USAGE_TYPE_MASK) >> Self::USAGE_TYPE_PHASE).into()
+    }
+
+    pub fn direction(&self) -> EndpointDirection {
+        self.endpoint_address().direction()
+    }
+
+    pub fn endpoint_number(&self) -> u8 {
+        unsafe { IOUSBGetEndpointNumber(self.inner.as_ref()) }
+    }
+
+    pub fn max_packet_size_with_device_speed(&self, usb_device_speed: u32) -> u16 {
+        unsafe { IOUSBGetEndpointMaxPacketSize(usb_device_speed, self.inner.as_ref()) }
+    }
+
+    ///companions may be `None` for endpoints which do not have them, such as on USB 2 devices
+    pub fn burst_size(
+        &self,
+        usb_device_speed: u32,
+        super_speed_companion: Option<&SuperSpeedCompanionDescriptor<'_>>,
+        super_speed_plus_companion: Option<&SuperSpeedPlusCompanionDescriptor<'_>>,
+    ) -> u32 {
+        unsafe {
+            IOUSBGetEndpointBurstSize(
+                usb_device_speed,
+                self.inner.as_ref(),
+                super_speed_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+                super_speed_plus_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+            )
+        }
+    }
+
+    pub fn multiplier(
+        &self,
+        usb_device_speed: u32,
+        super_speed_companion: Option<&SuperSpeedCompanionDescriptor<'_>>,
+        super_speed_plus_companion: Option<&SuperSpeedPlusCompanionDescriptor<'_>>,
+    ) -> u8 {
+        unsafe {
+            IOUSBGetEndpointMult(
+                usb_device_speed,
+                self.inner.as_ref(),
+                super_speed_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+                super_speed_plus_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+            )
+        }
+    }
+
+    pub fn interval_encoded_microframes(&self, usb_device_speed: u32) -> u32 {
+        unsafe { IOUSBGetEndpointIntervalEncodedMicroframes(usb_device_speed, self.inner.as_ref()) }
+    }
+
+    pub fn interval_microframes(&self, usb_device_speed: u32) -> u32 {
+        unsafe { IOUSBGetEndpointIntervalMicroframes(usb_device_speed, self.inner.as_ref()) }
+    }
+
+    pub fn interval_frames(&self, usb_device_speed: u32) -> u32 {
+        unsafe { IOUSBGetEndpointIntervalFrames(usb_device_speed, self.inner.as_ref()) }
+    }
+
+    pub fn max_streams_encoded(
+        &self,
+        usb_device_speed: u32,
+        super_speed_companion: Option<&SuperSpeedCompanionDescriptor<'_>>,
+    ) -> u32 {
+        unsafe {
+            IOUSBGetEndpointMaxStreamsEncoded(
+                usb_device_speed,
+                self.inner.as_ref(),
+                super_speed_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+            )
+        }
+    }
+
+    pub fn max_streams(
+        &self,
+        usb_device_speed: u32,
+        super_speed_companion: Option<&SuperSpeedCompanionDescriptor<'_>>,
+    ) -> u32 {
+        unsafe {
+            IOUSBGetEndpointMaxStreams(
+                usb_device_speed,
+                self.inner.as_ref(),
+                super_speed_companion.map_or(ptr::null_mut(), |desc| desc.inner.as_ptr()),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointDirection {
+    Out = 0,
+    In = 1,
+    Unknown = 2,
+}
+
+impl From<u8> for EndpointDirection {
+    fn from(num: u8) -> EndpointDirection {
+        use EndpointDirection as ED;
+        match num {
+            0 => ED::Out,
+            1 => ED::In,
+            _ => ED::Unknown,
+        }
+    }
+}
+
+impl From<EndpointDirection> for u8 {
+    fn from(direction: EndpointDirection) -> u8 {
+        use EndpointDirection as ED;
+        match direction {
+            ED::Out => 0,
+            ED::In => 1,
+            ED::Unknown => 2,
+        }
+    }
+}
+
+///bEndpointAddress, the endpoint number in the low nibble and the direction in bit 7
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EndpointAddress(pub u8);
+
+impl EndpointAddress {
+    ///the default control endpoint
+    pub const CONTROL: EndpointAddress = EndpointAddress(0);
+
+    const DIRECTION_MASK: u8 = 0x80;
+    const DIRECTION_PHASE: u8 = 7;
+    const NUMBER_MASK: u8 = 0x0f;
+
+    pub fn new(number: u8, direction: EndpointDirection) -> Self {
+        let direction = match direction {
+            EndpointDirection::In => Self::DIRECTION_MASK,
+            _ => 0,
+        };
+        Self((number & Self::NUMBER_MASK) | direction)
+    }
+
+    pub fn number(&self) -> u8 {
+        self.0 & Self::NUMBER_MASK
+    }
+
+    pub fn direction(&self) -> EndpointDirection {
+        ((self.0 & Self::DIRECTION_MASK) >> Self::DIRECTION_PHASE).into()
+    }
+
+    pub fn is_in(&self) -> bool {
+        self.0 & Self::DIRECTION_MASK != 0
+    }
+}
+
+impl core::fmt::Display for EndpointAddress {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let direction = if self.is_in() { "IN" } else { "OUT" };
+        write!(f, "{:#04x} {direction}", self.0)
+    }
+}
+
+impl From<u8> for EndpointAddress {
+    fn from(address: u8) -> EndpointAddress {
+        EndpointAddress(address)
+    }
+}
+
+impl From<EndpointAddress> for u8 {
+    fn from(address: EndpointAddress) -> u8 {
+        address.0
+    }
+}
+
+impl From<EndpointAddress> for u64 {
+    fn from(address: EndpointAddress) -> u64 {
+        address.0 as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointType {
+    Control = 0,
+    Isochronous = 1,
+    Bulk = 2,
+    Interrupt = 3,
+}
+
+impl From<u8> for EndpointType {
+    fn from(num: u8) -> EndpointType {
+        use EndpointType as ET;
+        match num & 3 {
+            0 => ET::Control,
+            1 => ET::Isochronous,
+            2 => ET::Bulk,
+            _ => ET::Interrupt,
+        }
+    }
+}
+
+impl From<EndpointType> for u8 {
+    fn from(ty: EndpointType) -> u8 {
+        use EndpointType as ET;
+        match ty {
+            ET::Control => 0,
+            ET::Isochronous => 1,
+            ET::Bulk => 2,
+            ET::Interrupt => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum UsageType {
+    Data = 0,
+    Feedback = 1,
+    ImplicitFeedbackData = 2,
+    Other(u8),
+}
+
+impl From<u8> for UsageType {
+    fn from(num: u8) -> UsageType {
+        use UsageType as UT;
+        match num {
+            0 => UT::Data,
+            1 => UT::Feedback,
+            2 => UT::ImplicitFeedbackData,
+            other => UT::Other(other),
+        }
+    }
+}
+
+impl From<UsageType> for u8 {
+    fn from(ty: UsageType) -> u8 {
+        use UsageType as UT;
+        match ty {
+            UT::Data => 0,
+            UT::Feedback => 1,
+            UT::ImplicitFeedbackData => 2,
+            UT::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum SynchronizationType {
+    None = 0,
+    Asynchronous = 1,
+    Adaptive = 2,
+    Synchronous = 3,
+    Other(u8),
+}
+
+impl From<u8> for SynchronizationType {
+    fn from(num: u8) -> SynchronizationType {
+        use SynchronizationType as ST;
+        match num {
+            0 => ST::None,
+            1 => ST::Asynchronous,
+            2 => ST::Adaptive,
+            3 => ST::Synchronous,
+            other => ST::Other(other),
+        }
+    }
+}
+
+impl From<SynchronizationType> for u8 {
+    fn from(ty: SynchronizationType) -> u8 {
+        use SynchronizationType as ST;
+        match ty {
+            ST::None => 0,
+            ST::Asynchronous => 1,
+            ST::Adaptive => 2,
+            ST::Synchronous => 3,
+            ST::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DeviceCapabilityType {
+    Wireless = 1,
+    Usb2Extension = 2,
+    SuperSpeed = 3,
+    ContainerID = 4,
+    Platform = 5,
+    PowerDelivery = 6,
+    BatteryInfo = 7,
+    PdConsumerPort = 8,
+    PdProviderPort = 9,
+    SuperSpeedPlus = 10,
+    PrecisionMeasurement = 11,
+    WirelessExt = 12,
+    Billboard = 13,
+    BillboardAltMode = 15,
+    Other(u8),
+}
+
+impl From<u8> for DeviceCapabilityType {
+    fn from(num: u8) -> DeviceCapabilityType {
+        use DeviceCapabilityType as DCT;
+        match num {
+            1 => DCT::Wireless,
+            2 => DCT::Usb2Extension,
+            3 => DCT::SuperSpeed,
+            4 => DCT::ContainerID,
+            5 => DCT::Platform,
+            6 => DCT::PowerDelivery,
+            7 => DCT::BatteryInfo,
+            8 => DCT::PdConsumerPort,
+            9 => DCT::PdProviderPort,
+            10 => DCT::SuperSpeedPlus,
+            11 => DCT::PrecisionMeasurement,
+            12 => DCT::WirelessExt,
+            13 => DCT::Billboard,
+            15 => DCT::BillboardAltMode,
+            other => DCT::Other(other),
+        }
+    }
+}
+
+impl From<DeviceCapabilityType> for u8 {
+    fn from(ty: DeviceCapabilityType) -> u8 {
+        use DeviceCapabilityType as DCT;
+        match ty {
+            DCT::Wireless => 1,
+            DCT::Usb2Extension => 2,
+            DCT::SuperSpeed => 3,
+            DCT::ContainerID => 4,
+            DCT::Platform => 5,
+            DCT::PowerDelivery => 6,
+            DCT::BatteryInfo => 7,
+            DCT::PdConsumerPort => 8,
+            DCT::PdProviderPort => 9,
+            DCT::SuperSpeedPlus => 10,
+            DCT::PrecisionMeasurement => 11,
+            DCT::WirelessExt => 12,
+            DCT::Billboard => 13,
+            DCT::BillboardAltMode => 15,
+            DCT::Other(other) => other,
+        }
+    }
+}
@@ -0,0 +1,2696 @@
+#[cfg(feature = "async")]
+use crate::future::{AsyncDataHandler, AsyncHandler, Completed};
+use crate::watch::{Devices, PortArrivals, ResumeWatch};
+use crate::*;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr;
+use core::ptr::NonNull;
+use iousbhost_sys::*;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub struct UsbDevice<'a> {
+    pub(crate) inner: NonNull<IOUSBHostDevice>,
+    pub(crate) shared: Rc<SharedObject>,
+    stats: Arc<TransferStats>,
+    profile_state: Rc<core::cell::RefCell<ProfileState<'a>>>,
+    lt: PhantomData<&'a ()>,
+}
+
+///what keeps the profile of a device in effect, see `UsbDevice::apply_profile`
+struct ProfileState<'a> {
+    //the interfaces the profile selects an alternate setting on, kept open so the setting stays
+    interfaces: Vec<HostInterface<'a>>,
+    //notes resets and wake ups, after which the profile is applied again before the next use
+    watch: Option<ResumeWatch>,
+}
+
+///handle that does not keep the device alive, upgrading only while some `UsbDevice` clone still
+///holds it and it has not been shut down
+pub struct WeakUsbDevice<'a> {
+    pub(crate) inner: NonNull<IOUSBHostDevice>,
+    shared: std::rc::Weak<SharedObject>,
+    stats: std::sync::Weak<TransferStats>,
+    profile_state: std::rc::Weak<core::cell::RefCell<ProfileState<'a>>>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> WeakUsbDevice<'a> {
+    pub fn upgrade(&self) -> Option<UsbDevice<'a>> {
+        let shared = self.shared.upgrade()?;
+        if shared.is_destroyed() {
+            return None;
+        }
+        Some(UsbDevice {
+            inner: self.inner,
+            shared,
+            stats: self.stats.upgrade()?,
+            profile_state: self.profile_state.upgrade()?,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.shared
+            .upgrade()
+            .map_or(false, |shared| !shared.is_destroyed())
+    }
+}
+
+impl Clone for WeakUsbDevice<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner,
+            shared: self.shared.clone(),
+            stats: self.stats.clone(),
+            profile_state: self.profile_state.clone(),
+            lt: PhantomData,
+        }
+    }
+}
+
+///clones share the device, its settings and its statistics, the device only being destroyed once
+///the last clone and everything opened from it has been dropped
+impl Clone for UsbDevice<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner,
+            shared: self.shared.clone(),
+            stats: self.stats.clone(),
+            profile_state: self.profile_state.clone(),
+            lt: PhantomData,
+        }
+    }
+}
+
+impl<'a> UsbDevice<'a> {
+    ///handle for caches and registries that does not keep the device open
+    pub fn downgrade(&self) -> WeakUsbDevice<'a> {
+        WeakUsbDevice {
+            inner: self.inner,
+            shared: Rc::downgrade(&self.shared),
+            stats: Arc::downgrade(&self.stats),
+            profile_state: Rc::downgrade(&self.profile_state),
+            lt: PhantomData,
+        }
+    }
+
+    ///opens the interfaces of the current configuration, each of which keeps the device alive;
+    ///`options` is either one `HostObjectInitOptions` for all of them or decides per interface
+    ///which ones to open, see `InterfaceOptions`
+    ///
+    ///interfaces that fail to open, such as ones a kernel driver owns, are reported as errors
+    ///without ending the walk, so the interfaces that are free can still be claimed
+    ///
+    ///the interfaces a profile keeps open are handed out again instead of being opened twice
+    pub fn interfaces(
+        &self,
+        options: impl InterfaceOptions + 'a,
+    ) -> Option<impl Iterator<Item = Result<HostInterface<'a>, InterfaceOpenError>>> {
+        if let Err(err) = self.refresh_profile() {
+            crate::diag!(warn, "failed to apply the device profile again: {:?}", err);
+        }
+        let held = self.profile_state.borrow().interfaces.clone();
+        self.open_interfaces(options, held)
+    }
+
+    fn open_interfaces(
+        &self,
+        options: impl InterfaceOptions + 'a,
+        held: Vec<HostInterface<'a>>,
+    ) -> Option<Interfaces<'a>> {
+        let current_descriptor = ptr::null();
+        Some(Interfaces {
+            device: self.shared.clone(),
+            options: Box::new(options),
+            queue: self.queue(),
+            current_descriptor,
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            held,
+            lt: PhantomData,
+        })
+    }
+}
+
+impl UsbDevice<'_> {
+    pub(crate) fn new(
+        service: io_service_t,
+        options: HostObjectInitOptions,
+        queue: &Queue,
+        main_port: mach_port_t,
+        matcher: Option<&DeviceMatcher>,
+    ) -> Result<Self, UsbError> {
+        OpenCandidate::new(OpenTarget::Device, service, matcher, options).check()?;
+        //NOTE: this asks for exclusive access for the device
+        //
+        //it might be beneficial to use this with IOKit inorder to query without claiming exclusive
+        //ownership
+        let host_device = IOUSBHostDevice::alloc();
+        let mut err = NSErr::new();
+        let dev = unsafe {
+            host_device.initWithIOService_options_queue_error_interestHandler_(
+                service,
+                options.into(),
+                queue.inner.clone(),
+                &mut *err,
+                0 as *mut c_void,
+            )
+        };
+        if err.is_err() {
+            return Err(err.into());
+        }
+        let ptr = NonNull::new(dev as *mut IOUSBHostDevice).ok_or(UsbError::InvalidObject)?;
+        Ok(Self::from_object(ptr, service, queue.clone(), main_port))
+    }
+
+    fn from_object(
+        ptr: NonNull<IOUSBHostDevice>,
+        service: io_service_t,
+        queue: Queue,
+        main_port: mach_port_t,
+    ) -> Self {
+        let (stats, pipe_stats) = register_device_stats(service);
+        let shared = SharedObject::new(
+            IOUSBHostObject(unsafe { ptr.as_ref() }.0),
+            queue,
+            main_port,
+            None,
+            pipe_stats,
+            stats.clone(),
+        );
+
+        Self {
+            inner: ptr,
+            shared,
+            stats,
+            profile_state: Rc::new(core::cell::RefCell::new(ProfileState {
+                interfaces: Vec::new(),
+                watch: None,
+            })),
+            lt: PhantomData,
+        }
+    }
+
+    ///the framework object behind this handle, for calling selectors the crate does not wrap
+    ///
+    ///the pointer is borrowed, it stays valid while some handle to the device is alive and must
+    ///not be released or destroyed through it
+    pub fn as_raw(&self) -> NonNull<IOUSBHostDevice> {
+        self.inner
+    }
+
+    ///takes over a device opened outside of the crate, which is destroyed once the last clone of
+    ///the handle and everything opened from it is dropped
+    ///
+    ///# Safety
+    ///`raw` has to be an initialized `IOUSBHostDevice` that nothing else destroys or releases
+    pub unsafe fn from_raw(raw: NonNull<IOUSBHostDevice>) -> Self {
+        let object = raw.as_ref();
+        let service = object.ioService();
+        let queue = Queue::new(object.queue());
+        //the framework does not tell which main port the device was found through
+        Self::from_object(raw, service, queue, 0)
+    }
+
+    ///when enabled every device request is checked with `validate_request` before it is sent,
+    ///failing with `UsbError::InvalidRequest` instead of reaching the device
+    pub fn set_request_validation(&self, enabled: bool) {
+        self.shared.validate_requests.set(enabled);
+    }
+
+    ///checks `request` against its data stage and against what the device reports about itself
+    pub fn validate_request(
+        &self,
+        request: &DeviceRequest,
+        data: Option<&[u8]>,
+    ) -> Result<(), RequestError> {
+        request.validate(data.map(|data| data.len()))?;
+
+        if request.request_type() == DeviceRequest::STANDARD_DEVICE_OUT
+            && request.request() == DeviceRequest::SET_CONFIGURATION
+        {
+            let value = request.value() as u8;
+            let exists = value == 0
+                || self
+                    .configurations()
+                    .filter_map(Result::ok)
+                    .any(|config| config.configuration_value() == value);
+            if !exists {
+                return Err(RequestError::UnknownConfiguration(value));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_request(&self, request: &DeviceRequest, data: Option<&[u8]>) -> Result<(), UsbError> {
+        if self.shared.closing.get() {
+            return Err(UsbError::Terminated);
+        }
+        self.refresh_profile()?;
+        if self.shared.validate_requests.get() {
+            self.validate_request(request, data)
+                .map_err(UsbError::InvalidRequest)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn send_device_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: &[u8],
+    ) -> Result<u64, UsbError> {
+        self.check_request(&request, Some(data))?;
+        self.send_device_data(
+            request,
+            MutData::with_data(data).raw(),
+            self.shared.request_timeout(),
+        )
+    }
+
+    ///`send_device_request_with_data` giving up with `UsbError::OperationTimedOut` once `timeout`
+    ///passed instead of blocking until the device answers
+    pub fn send_device_request_with_data_timeout(
+        &self,
+        request: DeviceRequest,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<u64, UsbError> {
+        self.check_request(&request, Some(data))?;
+        self.send_device_data(
+            request,
+            MutData::with_data(data).raw(),
+            timeout_secs(timeout),
+        )
+    }
+
+    ///`send_device_request` giving up with `UsbError::OperationTimedOut` once `timeout` passed
+    pub fn send_device_request_timeout(
+        &self,
+        request: DeviceRequest,
+        timeout: Duration,
+    ) -> Result<(), UsbError> {
+        self.check_request(&request, None)?;
+        self.send_device_data(request, MutData::zeroed(0).raw(), timeout_secs(timeout))
+            .map(|_| ())
+    }
+
+    fn send_device_data(
+        &self,
+        request: DeviceRequest,
+        data: NSMutableData,
+        timeout: f64,
+    ) -> Result<u64, UsbError> {
+        self.shared.send_device_data(request, data, timeout)
+    }
+
+    pub fn send_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        self.check_request(&request, None)?;
+        let timeout = self.shared.request_timeout();
+        if timeout > 0.0 {
+            return self
+                .send_device_data(request, MutData::zeroed(0).raw(), timeout)
+                .map(|_| ());
+        }
+        let _turn = self.shared.gate.enter();
+        let recording = self.stats.begin(request.is_in());
+        let mut err = NSErr::new();
+        let res = if !unsafe {
+            self.inner
+                .as_ref()
+                .sendDeviceRequest_error_(request.into(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        };
+        recording.finish(res.map(|_| 0));
+        res
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_device_request_with_data<'s>(
+        &'s self,
+        request: DeviceRequest,
+        data: &'s [u8],
+    ) -> Transfer<impl core::future::Future<Output = Result<u64, UsbError>> + 's> {
+        let this: &'s UsbDevice<'s> = self;
+        Transfer::new(|id| this.enqueue_device_request_with_data_inner(request, data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_request_with_data_inner(
+        &self,
+        request: DeviceRequest,
+        data: &[u8],
+        id: TransferId,
+    ) -> Result<u64, UsbError> {
+        self.check_request(&request, Some(data))?;
+        self.enqueue_device_data(request, MutData::with_data(data).raw(), id)
+            .await?
+            .result()
+    }
+
+    ///sends `request` with the data stage read into `data`, handing the buffer back in the
+    ///`TransferResult` whether the request succeeded or not; only requests refused before they
+    ///were submitted, see `set_request_validation`, fail
+    #[cfg(feature = "async")]
+    pub fn enqueue_device_read<'s>(
+        &'s self,
+        request: DeviceRequest,
+        data: IoData,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s UsbDevice<'s> = self;
+        Transfer::new(|id| this.enqueue_device_read_inner(request, data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_read_inner(
+        &self,
+        request: DeviceRequest,
+        data: IoData,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        self.check_request(&request, Some(data.as_slice()))?;
+        let res = self.enqueue_device_data(request, data.as_raw(), id).await;
+        Ok(TransferResult::completed(data, res, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_data(
+        &self,
+        request: DeviceRequest,
+        data: NSMutableData,
+        id: TransferId,
+    ) -> Result<Completed, UsbError> {
+        let _turn = self.shared.gate.enter_async().await;
+        let recording = self.stats.begin_as(id, request.is_in());
+        let handler = AsyncDataHandler::with_raw(self.inner, data, |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueDeviceRequest_data_completionTimeout_error_completionHandler_(
+                    request.into(),
+                    data,
+                    0.0,
+                    &mut *err,
+                    cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.shared.executor())
+        .recorded(recording);
+
+        handler.await
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_device_request<'s>(
+        &'s self,
+        request: DeviceRequest,
+    ) -> Transfer<impl core::future::Future<Output = Result<(), UsbError>> + 's> {
+        let this: &'s UsbDevice<'s> = self;
+        Transfer::new(|id| this.enqueue_device_request_inner(request, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_request_inner(
+        &self,
+        request: DeviceRequest,
+        id: TransferId,
+    ) -> Result<(), UsbError> {
+        self.check_request(&request, None)?;
+        let _turn = self.shared.gate.enter_async().await;
+        let recording = self.stats.begin_as(id, request.is_in());
+        let handler = AsyncHandler::new(self.inner, |dev, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueDeviceRequest_error_completionHandler_(request.into(), &mut *err, cb)
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.shared.executor())
+        .recorded(recording);
+        handler.await?.result().map(|_| ())
+    }
+
+    ///counters for every device request sent through this handle
+    pub fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    ///rejects any further device request and transfer on the device's pipes with
+    ///`UsbError::Terminated`, waits up to `timeout` for the ones in flight to complete and aborts
+    ///those still running after that, returning how many had to be aborted; the device is then
+    ///destroyed
+    ///
+    ///requests count as finished once their completion handler ran, whether or not their future
+    ///is still polled, so nothing has to drive them while this blocks
+    pub fn shutdown(&self, timeout: std::time::Duration) -> Result<u64, UsbError> {
+        if self.shared.is_destroyed() {
+            return Ok(0);
+        }
+        self.shared.closing.set(true);
+
+        let deadline = Instant::now() + timeout;
+        let pipes = &self.shared.pipe_stats;
+        self.stats.wait_idle(timeout);
+        pipes.wait_idle(deadline.saturating_duration_since(Instant::now()));
+        let requests = self.stats.snapshot().in_flight();
+        let transfers = pipes.snapshot().in_flight();
+        if requests > 0 {
+            self.abort_device_requests(AbortOption::Asynchronous)?;
+        }
+        if requests + transfers > 0 {
+            crate::diag!(
+                warn,
+                "aborting {} device requests and {} pipe transfers still in flight at shutdown",
+                requests,
+                transfers
+            );
+        }
+        //destroying the device aborts whatever its pipes still have in flight
+        self.shared.destroy();
+        Ok(requests + transfers)
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shared.is_destroyed()
+    }
+
+    ///shuts this handle down, reopens the device with `DeviceCapture`, which terminates the
+    ///kernel drivers attached to it, resets it and opens it again once it is back, as firmware
+    ///updaters do before switching a device into its bootloader
+    ///
+    ///clones of this handle and everything opened from it stop working; `timeout` bounds both the
+    ///shutdown and the wait for a device to show up again at the same port, found by its
+    ///locationID as it may come back as a new registry entry with other ids
+    pub fn reset_and_capture(
+        self,
+        timeout: std::time::Duration,
+        mut progress: impl FnMut(ResetStage),
+    ) -> Result<Self, UsbError> {
+        let service = self.io_service().inner;
+        let main_port = self.shared.main_port;
+        let queue = self.queue();
+        let profile = self.profile();
+        let location_id = self
+            .io_service()
+            .number_property(c"locationID")
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or(UsbError::InvalidObject)?;
+        //watching before the reset, so the device can not come back unnoticed
+        let arrivals = PortArrivals::new(main_port, location_id)?;
+
+        progress(ResetStage::Releasing);
+        //the service has to outlive the host object that handed it out
+        unsafe { IOObjectRetain(service) };
+        if let Err(err) = self.shutdown(timeout) {
+            unsafe { IOObjectRelease(service) };
+            return Err(err);
+        }
+        drop(self);
+
+        progress(ResetStage::Capturing);
+        let captured = Self::new(
+            service,
+            HostObjectInitOptions::DeviceCapture,
+            &queue,
+            main_port,
+            None,
+        );
+        unsafe { IOObjectRelease(service) };
+        let captured = captured?;
+
+        progress(ResetStage::Resetting);
+        captured.reset()?;
+        captured.shared.destroy();
+        drop(captured);
+
+        progress(ResetStage::Reopening);
+        let service = arrivals
+            .next(Instant::now() + timeout)
+            .ok_or(UsbError::OperationTimedOut)?;
+        let device = Self::new(
+            service,
+            HostObjectInitOptions::DeviceCapture,
+            &queue,
+            main_port,
+            None,
+        );
+        unsafe { IOObjectRelease(service) };
+        let device = device?;
+        if let Some(profile) = profile {
+            device.apply_profile(profile)?;
+        }
+        Ok(device)
+    }
+
+    ///the string descriptor at `index` decoded to UTF-8
+    pub fn string_descriptor(
+        &self,
+        index: u64,
+        language_id: Option<LangId>,
+    ) -> Result<String, UsbError> {
+        self.string_descriptor_raw(index, language_id)
+            .map(|string| string_from_ns(&string))
+    }
+
+    ///`string_descriptor` as the `NSString` IOUSBHost returns
+    pub fn string_descriptor_raw(
+        &self,
+        index: u64,
+        language_id: Option<LangId>,
+    ) -> Result<NSString, UsbError> {
+        let mut err = NSErr::new();
+        let descriptor = unsafe {
+            match language_id {
+                Some(id) => self.inner.as_ref().stringWithIndex_languageID_error_(
+                    index,
+                    id.into(),
+                    &mut *err,
+                ),
+                _ => self.inner.as_ref().stringWithIndex_error_(index, &mut *err),
+            }
+        };
+
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(descriptor)
+        }
+    }
+
+    //returns the current frame number, but also updates the host time aligned with the time which
+    //the frame number was last updated
+    pub fn frame_number(&self, time: &mut HostTime) -> u64 {
+        unsafe { self.inner.as_ref().frameNumberWithTime_(&mut time.inner) }
+    }
+
+    ///samples the frame number together with its host time every `interval` on the device's
+    ///queue, the stream ends once the device is destroyed
+    ///
+    ///samples the consumer falls behind on are dropped oldest first
+    #[cfg(feature = "async")]
+    pub fn frame_numbers(&self, interval: std::time::Duration) -> FrameNumbers {
+        let state = Arc::new(std::sync::Mutex::new(FrameState::default()));
+        let object = self.shared.object;
+        let _: *mut objc::runtime::Object = unsafe { objc::msg_send![object.0, retain] };
+        let ticker = Box::new(FrameTicker {
+            object,
+            destroyed: self.shared.destroyed.clone(),
+            queue: unsafe { self.inner.as_ref().queue() },
+            interval: interval.as_nanos().min(i64::MAX as u128) as i64,
+            state: state.clone(),
+            executor: self.shared.executor(),
+        });
+        unsafe {
+            dispatch_async_f(
+                ticker.queue,
+                Box::into_raw(ticker) as *mut c_void,
+                Some(FrameTicker::tick),
+            )
+        };
+        FrameNumbers { state }
+    }
+
+    ///a buffer of `capacity` bytes mapped for the device, see `IoData`
+    pub fn io_data(&self, capacity: u64) -> Result<IoData, UsbError> {
+        let mut err = NSErr::new();
+        let data = unsafe {
+            self.inner
+                .as_ref()
+                .ioDataWithCapacity_error_(capacity, &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            IoData::new(data, capacity)
+        }
+    }
+
+    pub fn abort_device_requests(&self, abort_option: AbortOption) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .abortDeviceRequestsWithOption_error_(abort_option.into(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_interface(&self, interface_number: u8) -> Option<InterfaceDescriptor<'_>> {
+        self.interface_descriptors()?
+            .find(|interface| interface.interface_number() == interface_number)
+    }
+
+    ///the first interface of the current configuration with the given class triple
+    pub fn get_interface_by_class(
+        &self,
+        class: u8,
+        subclass: u8,
+        protocol: u8,
+    ) -> Option<InterfaceDescriptor<'_>> {
+        self.interface_descriptors()?.find(|interface| {
+            interface.interface_class() == class
+                && interface.interface_subclass() == subclass
+                && interface.interface_protocol() == protocol
+        })
+    }
+
+    ///the alternate setting `alternate_setting` of the interface `interface_number`
+    pub fn get_interface_by_alt(
+        &self,
+        interface_number: u8,
+        alternate_setting: u8,
+    ) -> Option<InterfaceDescriptor<'_>> {
+        self.interface_descriptors()?.find(|interface| {
+            interface.interface_number() == interface_number
+                && interface.alternate_setting() == alternate_setting
+        })
+    }
+
+    pub fn interface_descriptors(&self) -> Option<impl Iterator<Item = InterfaceDescriptor<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(InterfaceDescriptors {
+            current_descriptor,
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            lt: PhantomData,
+        })
+    }
+
+    pub fn interface_association_descriptors(
+        &self,
+    ) -> Option<impl Iterator<Item = InterfaceAssociationDescriptor<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(InterfaceAssociationDescriptors {
+            current_descriptor,
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            lt: PhantomData,
+        })
+    }
+
+    ///the interface association grouping the interface `interface_number` into a function, `None`
+    ///if the interface stands on its own
+    pub fn function_for_interface(
+        &self,
+        interface_number: u8,
+    ) -> Option<InterfaceAssociationDescriptor<'_>> {
+        self.interface_association_descriptors()?
+            .find(|association| association.contains(interface_number))
+    }
+
+    pub fn descriptors(&self) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(Descriptors {
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            current_descriptor,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn descriptors_with_type(
+        &self,
+        descriptor_type: u8,
+    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(TypedDescriptors {
+            descriptor_type,
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            current_descriptor,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn associated_descriptors(
+        &self,
+        descriptor: &DescriptorHeader<'_>,
+    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(AssociatedDescriptors {
+            assoc_descriptor: unsafe { descriptor.inner.as_ref() },
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            current_descriptor,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn associated_descriptors_with_type(
+        &self,
+        descriptor: &DescriptorHeader<'_>,
+        descriptor_type: u8,
+    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
+        let current_descriptor = ptr::null();
+        Some(TypedAssociatedDescriptors {
+            assoc_descriptor: unsafe { descriptor.inner.as_ref() },
+            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
+            current_descriptor,
+            descriptor_type,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn io_service(&self) -> IoService {
+        IoService::from_raw(unsafe { self.inner.as_ref().ioService() })
+    }
+
+    pub fn queue(&self) -> Queue {
+        Queue::new(unsafe { self.inner.as_ref().queue() })
+    }
+
+    ///the operating speed from the `USBSpeed` registry property, as the `kUSBDeviceSpeed*` value
+    ///the descriptor parsing helpers expect
+    pub(crate) fn usb_device_speed(&self) -> Option<u32> {
+        let speed = self.io_service().number_property(c"USBSpeed")?;
+        u32::try_from(speed).ok()
+    }
+
+    pub fn devices<'a, const N: usize>(
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        device_class: Option<u8>,
+        device_subclass: Option<u8>,
+        device_protocol: Option<u8>,
+        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
+        options: HostObjectInitOptions,
+    ) -> Result<impl Iterator<Item = UsbDevice<'a>>, UsbError> {
+        Context::with_shared(|context| {
+            Self::devices_in::<N>(
+                context,
+                vendor_id,
+                product_id,
+                bcd_device,
+                device_class,
+                device_subclass,
+                device_protocol,
+                speed,
+                options,
+            )
+        })
+    }
+
+    ///enumerates the matching devices through `context`'s main port
+    pub fn devices_in<'a, const N: usize>(
+        context: &Context,
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        device_class: Option<u8>,
+        device_subclass: Option<u8>,
+        device_protocol: Option<u8>,
+        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
+        options: HostObjectInitOptions,
+    ) -> Result<impl Iterator<Item = UsbDevice<'a>>, UsbError> {
+        let dict = Self::create_matching_dictionary(
+            vendor_id,
+            product_id,
+            bcd_device,
+            device_class,
+            device_subclass,
+            device_protocol,
+            speed, /* product_ids */
+        )?;
+
+        let mut iter = 0;
+
+        let err = unsafe { IOServiceGetMatchingServices(context.main_port(), dict, &mut iter) };
+
+        if err != 0 {
+            return Err(err.into());
+        }
+
+        let queue = context.device_queue();
+
+        Ok(Devices {
+            queue,
+            inner: iter,
+            main_port: context.main_port(),
+            pool: context.queue_pool().cloned(),
+            matcher: DeviceMatcher {
+                vendor_id,
+                product_id,
+                bcd_device,
+                device_class,
+                device_subclass,
+                device_protocol,
+                speed,
+            },
+            options,
+            lt: PhantomData,
+        })
+    }
+
+    pub(crate) fn create_matching_dictionary(
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        device_class: Option<u8>,
+        device_subclass: Option<u8>,
+        device_protocol: Option<u8>,
+        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
+    ) -> Result<CFMutableDictionaryRef, UsbError> {
+        let vendor_id = NSNum::try_from(vendor_id)?;
+        let product_id = NSNum::try_from(product_id)?;
+        let bcd_device = NSNum::try_from(bcd_device)?;
+        let device_class = NSNum::try_from(device_class)?;
+        let device_subclass = NSNum::try_from(device_subclass)?;
+        let device_protocol = NSNum::try_from(device_protocol)?;
+        let speed = NSNum::try_from(speed)?;
+
+        let dict = unsafe {
+            IOUSBHostDevice::createMatchingDictionaryWithVendorID_productID_bcdDevice_deviceClass_deviceSubclass_deviceProtocol_speed_productIDArray_(
+            vendor_id.into(),
+            product_id.into(),
+            bcd_device.into(),
+            device_class.into(),
+            device_subclass.into(),
+            device_protocol.into(),
+            speed.into(),
+            NSArray(ptr::null_mut())
+        )
+        };
+
+        if dict.is_null() {
+            return Err(IoReturnError::NoMemory.into());
+        }
+
+        Ok(dict)
+    }
+
+    pub fn device<const N: usize>(
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        device_class: Option<u8>,
+        device_subclass: Option<u8>,
+        device_protocol: Option<u8>,
+        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
+        options: HostObjectInitOptions,
+    ) -> Result<Self, UsbError> {
+        Context::with_shared(|context| {
+            Self::device_in::<N>(
+                context,
+                vendor_id,
+                product_id,
+                bcd_device,
+                device_class,
+                device_subclass,
+                device_protocol,
+                speed,
+                options,
+            )
+        })
+    }
+
+    ///opens the first matching device found through `context`'s main port
+    pub fn device_in<const N: usize>(
+        context: &Context,
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        device_class: Option<u8>,
+        device_subclass: Option<u8>,
+        device_protocol: Option<u8>,
+        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
+        options: HostObjectInitOptions,
+    ) -> Result<Self, UsbError> {
+        let dict = Self::create_matching_dictionary(
+            vendor_id,
+            product_id,
+            bcd_device,
+            device_class,
+            device_subclass,
+            device_protocol,
+            speed, /* product_ids */
+        )?;
+        let service = unsafe { IOServiceGetMatchingService(context.main_port(), dict) };
+        let queue = context.queue_for(service);
+        let matcher = DeviceMatcher {
+            vendor_id,
+            product_id,
+            bcd_device,
+            device_class,
+            device_subclass,
+            device_protocol,
+            speed,
+        };
+        Self::new(
+            service,
+            options,
+            &queue,
+            context.main_port(),
+            Some(&matcher),
+        )
+    }
+}
+
+impl<'a> UsbDevice<'a> {
+    ///resets the device, applying its profile again afterwards
+    pub fn reset(&self) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe { self.inner.as_ref().resetWithError_(&mut *err) } {
+            return Err(err.into());
+        }
+        self.reapply_profile()
+    }
+
+    ///sets the configuration, alternate settings and timeouts of `profile` and keeps it in effect:
+    ///the interfaces it selects alternate settings on stay open, handed out by `interfaces`, and
+    ///it is applied again after resets and once the device woke up
+    ///
+    ///if any step fails the previous configuration and alternate settings are restored and the
+    ///previous profile kept
+    pub fn apply_profile(&self, profile: DeviceProfile) -> Result<(), UsbError> {
+        let previous = self
+            .active_configuration()
+            .map(|config| config.configuration_value());
+        let mut state = self.profile_state.borrow_mut();
+        //the alternate settings changed so far along with the ones they replaced
+        let mut changed = Vec::new();
+        if let Err(err) = self.configure_profile(&profile, previous, &mut state, &mut changed) {
+            //best effort, the error that got us here is the one worth reporting
+            for (interface, alternate_setting) in changed.iter().rev() {
+                let _ = interface.select_alternate_setting(*alternate_setting);
+            }
+            let reconfigured = profile.configuration.is_some() && profile.configuration != previous;
+            if let (true, Some(previous)) = (reconfigured, previous) {
+                let _ = self.configure(previous as u64, Some(true));
+                if let Some(profile) = self.profile() {
+                    let _ = self.configure_profile(
+                        &profile,
+                        Some(previous),
+                        &mut state,
+                        &mut Vec::new(),
+                    );
+                }
+            }
+            return Err(err);
+        }
+        //interfaces the previous profile needed and this one does not are let go
+        state.interfaces.retain(|interface| {
+            interface.interface_descriptor().is_some_and(|desc| {
+                profile
+                    .alternate_setting_for(desc.interface_number())
+                    .is_some()
+            })
+        });
+        if state.watch.is_none() {
+            match ResumeWatch::new(self.shared.main_port, self.io_service().inner) {
+                Ok(watch) => state.watch = Some(watch),
+                Err(err) => crate::diag!(
+                    warn,
+                    "the profile is not applied again on wake up, watching the device failed: {:?}",
+                    err
+                ),
+            }
+        }
+        drop(state);
+        self.set_serialize_requests(profile.serialize_requests);
+        *self.shared.profile.borrow_mut() = Some(profile);
+        Ok(())
+    }
+
+    ///opens the first device matching `matcher` through `context` with `profile` applied, failing
+    ///without handing the device out if it can not be applied
+    pub fn device_with_profile(
+        context: &Context,
+        matcher: DeviceMatcher,
+        options: HostObjectInitOptions,
+        profile: DeviceProfile,
+    ) -> Result<Self, UsbError> {
+        let device = Self::device_in::<0>(
+            context,
+            matcher.vendor_id,
+            matcher.product_id,
+            matcher.bcd_device,
+            matcher.device_class,
+            matcher.device_subclass,
+            matcher.device_protocol,
+            matcher.speed,
+            options,
+        )?;
+        device.apply_profile(profile)?;
+        Ok(device)
+    }
+
+    fn configure_profile(
+        &self,
+        profile: &DeviceProfile,
+        active: Option<u8>,
+        state: &mut ProfileState<'a>,
+        changed: &mut Vec<(HostInterface<'a>, u8)>,
+    ) -> Result<(), UsbError> {
+        if let Some(value) = profile.configuration {
+            if active != Some(value) {
+                //setting a configuration ends the interfaces of the previous one
+                state.interfaces.clear();
+                self.configure(value as u64, Some(true))?;
+            }
+        }
+        if profile.alternate_settings.is_empty() {
+            return Ok(());
+        }
+
+        let held = |number: u8, interfaces: &[HostInterface<'a>]| {
+            interfaces.iter().any(|interface| {
+                interface
+                    .interface_descriptor()
+                    .is_some_and(|desc| desc.interface_number() == number)
+            })
+        };
+        let missing: Vec<u8> = profile
+            .alternate_settings
+            .iter()
+            .map(|(number, _)| *number)
+            .filter(|number| !held(*number, &state.interfaces))
+            .collect();
+        if !missing.is_empty() {
+            //interfaces the profile leaves alone may well be owned by someone else
+            let options = move |desc: &InterfaceDescriptor<'_>| {
+                (desc.alternate_setting() == 0 && missing.contains(&desc.interface_number()))
+                    .then_some(HostObjectInitOptions::None)
+            };
+            let interfaces = self
+                .open_interfaces(options, Vec::new())
+                .ok_or(UsbError::NoConfiguration)?;
+            for interface in interfaces {
+                state.interfaces.push(interface?);
+            }
+        }
+
+        for interface in &state.interfaces {
+            let Some(desc) = interface.interface_descriptor() else {
+                continue;
+            };
+            let (number, current) = (desc.interface_number(), desc.alternate_setting());
+            if let Some(alternate_setting) = profile.alternate_setting_for(number) {
+                interface.select_alternate_setting(alternate_setting)?;
+                changed.push((interface.clone(), current));
+            }
+        }
+        Ok(())
+    }
+
+    ///applies the device's profile again, e.g. after it woke up, reopening the interfaces it keeps;
+    ///does nothing without one
+    pub fn reapply_profile(&self) -> Result<(), UsbError> {
+        match self.profile() {
+            Some(profile) => {
+                self.profile_state.borrow_mut().interfaces.clear();
+                self.apply_profile(profile)
+            }
+            None => Ok(()),
+        }
+    }
+
+    //applies the profile again if the device was reset or woke up since it was last applied
+    fn refresh_profile(&self) -> Result<(), UsbError> {
+        let stale = self
+            .profile_state
+            .borrow()
+            .watch
+            .as_ref()
+            .is_some_and(ResumeWatch::take);
+        if stale {
+            self.reapply_profile()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl UsbDevice<'_> {
+    pub fn profile(&self) -> Option<DeviceProfile> {
+        self.shared.profile.borrow().clone()
+    }
+
+    ///when enabled device requests, and control requests on the pipes of the device, are sent
+    ///one at a time, in the order they were made; blocking requests wait on the calling thread for
+    ///the ones ahead of them, so they must not be made while this thread still has to drive an
+    ///async request of the device to completion
+    pub fn set_serialize_requests(&self, enabled: bool) {
+        self.shared.gate.set_enabled(enabled);
+    }
+
+    pub fn serialize_requests(&self) -> bool {
+        self.shared.gate.is_enabled()
+    }
+
+    ///forwards the completions of async transfers on this device and its pipes, and the samples
+    ///of `frame_numbers`, to `executor` instead of waking their tasks on the device's dispatch
+    ///queue; `None` wakes them on the queue again
+    ///
+    ///only transfers and `frame_numbers` streams started after this are affected
+    #[cfg(feature = "async")]
+    pub fn set_completion_executor(&self, executor: Option<Arc<dyn Executor>>) {
+        *self
+            .shared
+            .executor
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = executor;
+    }
+
+    #[cfg(feature = "async")]
+    pub fn completion_executor(&self) -> Option<Arc<dyn Executor>> {
+        self.shared.executor()
+    }
+
+    ///keeps the profile applied across changes reported by `events`, reapplying it once the
+    ///device was reset or resumed
+    pub fn handle_event(&self, event: DeviceEvent) -> Result<(), UsbError> {
+        match event {
+            DeviceEvent::ResetComplete | DeviceEvent::Resumed => self.reapply_profile(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn configure(&self, val: u64, match_interfaces: Option<bool>) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        let configured = unsafe {
+            match match_interfaces {
+                Some(mtch) => self
+                    .inner
+                    .as_ref()
+                    .configureWithValue_matchInterfaces_error_(val, mtch, &mut *err),
+                None => self
+                    .inner
+                    .as_ref()
+                    .configureWithValue_error_(val, &mut *err),
+            }
+        };
+        if !configured {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn device_descriptor(&self) -> Option<DeviceDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().deviceDescriptor() };
+        DeviceDescriptor::new(ptr)
+    }
+
+    ///a stable hash over the device descriptor, the configuration descriptors and the serial
+    ///number string, identifying this exact device across reconnects and hosts; devices without
+    ///a serial number share the fingerprint of their model and firmware
+    pub fn fingerprint(&self) -> Result<Fingerprint, UsbError> {
+        DeviceDescriptorSnapshot::capture(self).map(|snapshot| snapshot.fingerprint())
+    }
+
+    pub fn capability_descriptors(&self) -> impl Iterator<Item = CapabilityDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().capabilityDescriptors() };
+        CapabilityDescriptors {
+            inner: ptr,
+            lt: PhantomData,
+        }
+    }
+
+    ///an owned summary of the device's BOS descriptor, `None` for devices without one
+    pub fn bos(&self) -> Option<BosSummary> {
+        self.capability_descriptors()
+            .next()
+            .map(|bos| bos.summary())
+    }
+
+    pub fn configuration_descriptor(&self) -> Option<ConfigurationDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().configurationDescriptor() };
+        ConfigurationDescriptor::new(ptr)
+    }
+
+    ///the configuration descriptor at `index`, which ranges over the device descriptor's
+    ///configuration count rather than configuration values
+    pub fn configuration_descriptor_at(
+        &self,
+        index: u8,
+    ) -> Result<ConfigurationDescriptor<'_>, UsbError> {
+        let dev = IOUSBHostObject(unsafe { self.inner.as_ref().0 });
+        configuration_descriptor_at(&dev, index)
+    }
+
+    pub fn configuration_descriptor_with_value(
+        &self,
+        val: u64,
+    ) -> Result<ConfigurationDescriptor<'_>, UsbError> {
+        let mut err = NSErr::new();
+        let desc = unsafe {
+            self.inner
+                .as_ref()
+                .configurationDescriptorWithConfigurationValue_error_(val, &mut *err)
+        };
+
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            ConfigurationDescriptor::new(desc).ok_or(UsbError::NoConfiguration)
+        }
+    }
+
+    pub fn configurations(
+        &self,
+    ) -> impl Iterator<Item = Result<ConfigurationDescriptor<'_>, UsbError>> {
+        let count = self
+            .device_descriptor()
+            .map_or(0, |desc| desc.configuration_count());
+        ConfigurationDescriptors {
+            configuration_count: count,
+            idx: 0,
+            dev: IOUSBHostObject(unsafe { self.inner.as_ref().0 }),
+            lt: PhantomData,
+        }
+    }
+
+    const GET_CONFIGURATION: u8 = 8;
+
+    ///the configuration the device is currently in, asking the device with GET_CONFIGURATION when
+    ///the framework has not cached one, `None` if the device is unconfigured
+    pub fn active_configuration(&self) -> Option<ConfigurationDescriptor<'_>> {
+        if let Some(desc) = self.configuration_descriptor() {
+            return Some(desc);
+        }
+
+        let request = DeviceRequest::new(
+            DeviceRequestType::DirectionIn,
+            Self::GET_CONFIGURATION,
+            0,
+            0,
+            1,
+        );
+        let value = *self.send_device_request_in(request).ok()?.first()?;
+        if value == 0 {
+            return None;
+        }
+        self.configuration_descriptor_with_value(value as u64).ok()
+    }
+
+    const GET_DESCRIPTOR: u8 = 6;
+
+    ///the languages the device's strings are available in, read from string descriptor zero
+    pub fn languages(&self) -> Result<Vec<LangId>, UsbError> {
+        let request = DeviceRequest::new(
+            DeviceRequestType::DirectionIn,
+            Self::GET_DESCRIPTOR,
+            (u8::from(DescriptorType::String) as u16) << 8,
+            0,
+            255,
+        );
+        let bytes = self.send_device_request_in(request)?;
+        LangId::parse_string_descriptor_zero(&bytes).ok_or(UsbError::InvalidValue)
+    }
+
+    ///sends a device-to-host request and returns the bytes received in the data stage
+    fn send_device_request_in(&self, request: DeviceRequest) -> Result<Vec<u8>, UsbError> {
+        self.refresh_profile()?;
+        self.shared.device_request_in(request)
+    }
+
+    ///issues GET_STATUS to the device and decodes the status word
+    pub fn status(&self) -> Result<DeviceStatus, UsbError> {
+        let request = DeviceRequest::get_status(RECIPIENT_DEVICE, 0);
+        let bytes = self.send_device_request_in(request)?;
+        parse_status(&bytes).map(DeviceStatus::from)
+    }
+
+    const DEVICE_REMOTE_WAKEUP: u16 = 1;
+
+    ///allows or forbids the device to wake the host, through SET_FEATURE / CLEAR_FEATURE
+    ///(DEVICE_REMOTE_WAKEUP)
+    pub fn set_remote_wakeup(&self, enabled: bool) -> Result<(), UsbError> {
+        let request =
+            DeviceRequest::feature(enabled, RECIPIENT_DEVICE, Self::DEVICE_REMOTE_WAKEUP, 0);
+        self.send_device_request(request)
+    }
+
+    ///whether remote wakeup is currently enabled, as reported by GET_STATUS
+    pub fn remote_wakeup(&self) -> Result<bool, UsbError> {
+        self.status().map(|status| status.remote_wakeup)
+    }
+
+    const TEST_MODE: u16 = 2;
+
+    ///puts the device into an electrical test mode with SET_FEATURE(TEST_MODE), the device only
+    ///leaves it again when it is power cycled
+    pub fn enter_test_mode(&self, selector: TestSelector) -> Result<(), UsbError> {
+        let request = DeviceRequest::feature(
+            true,
+            RECIPIENT_DEVICE,
+            Self::TEST_MODE,
+            (u8::from(selector) as u16) << 8,
+        );
+        self.send_device_request(request)
+    }
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.as_ref().deviceAddress() }
+    }
+}
+
+pub(crate) const RECIPIENT_DEVICE: u8 = 0;
+pub(crate) const RECIPIENT_ENDPOINT: u8 = 2;
+
+///reads the little endian status word of a GET_STATUS data stage
+pub(crate) fn parse_status(bytes: &[u8]) -> Result<u16, UsbError> {
+    match bytes {
+        [low, high, ..] => Ok(u16::from_le_bytes([*low, *high])),
+        _ => Err(UsbError::InvalidValue),
+    }
+}
+
+///the test patterns of the TEST_MODE feature, used for electrical compliance testing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TestSelector {
+    J = 1,
+    K = 2,
+    Se0Nak = 3,
+    Packet = 4,
+    ForceEnable = 5,
+}
+
+impl From<TestSelector> for u8 {
+    fn from(selector: TestSelector) -> u8 {
+        use TestSelector as TS;
+        match selector {
+            TS::J => 1,
+            TS::K => 2,
+            TS::Se0Nak => 3,
+            TS::Packet => 4,
+            TS::ForceEnable => 5,
+        }
+    }
+}
+
+///the status word of a device as returned by GET_STATUS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceStatus {
+    pub self_powered: bool,
+    pub remote_wakeup: bool,
+}
+
+impl From<u16> for DeviceStatus {
+    fn from(status: u16) -> DeviceStatus {
+        DeviceStatus {
+            self_powered: status & 1 != 0,
+            remote_wakeup: status & 2 != 0,
+        }
+    }
+}
+
+///the status word of an endpoint as returned by GET_STATUS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EndpointStatus {
+    pub halted: bool,
+}
+
+impl From<u16> for EndpointStatus {
+    fn from(status: u16) -> EndpointStatus {
+        EndpointStatus {
+            halted: status & 1 != 0,
+        }
+    }
+}
+
+///settings applied together to a device by `UsbDevice::apply_profile`, for applications opening
+///many identical devices
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DeviceProfile {
+    configuration: Option<u8>,
+    //(interface number, alternate setting)
+    alternate_settings: Vec<(u8, u8)>,
+    //(interface number, idle timeout)
+    idle_timeouts: Vec<(u8, Duration)>,
+    request_timeout: Option<Duration>,
+    serialize_requests: bool,
+}
+
+impl DeviceProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///the configuration value to set
+    pub fn configuration(mut self, value: u8) -> Self {
+        self.configuration = Some(value);
+        self
+    }
+
+    pub fn alternate_setting(mut self, interface_number: u8, alternate_setting: u8) -> Self {
+        self.alternate_settings
+            .retain(|(number, _)| *number != interface_number);
+        self.alternate_settings
+            .push((interface_number, alternate_setting));
+        self
+    }
+
+    ///set on the interface every time it is opened
+    pub fn idle_timeout(mut self, interface_number: u8, timeout: Duration) -> Self {
+        self.idle_timeouts
+            .retain(|(number, _)| *number != interface_number);
+        self.idle_timeouts.push((interface_number, timeout));
+        self
+    }
+
+    ///the completion timeout of synchronous device requests and pipe transfers that do not pass
+    ///one, instead of waiting forever
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    ///see `UsbDevice::set_serialize_requests`
+    pub fn serialize_requests(mut self, enabled: bool) -> Self {
+        self.serialize_requests = enabled;
+        self
+    }
+
+    pub fn configuration_value(&self) -> Option<u8> {
+        self.configuration
+    }
+
+    pub fn alternate_setting_for(&self, interface_number: u8) -> Option<u8> {
+        self.alternate_settings
+            .iter()
+            .find(|(number, _)| *number == interface_number)
+            .map(|(_, alternate_setting)| *alternate_setting)
+    }
+
+    pub fn idle_timeout_for(&self, interface_number: u8) -> Option<Duration> {
+        self.idle_timeouts
+            .iter()
+            .find(|(number, _)| *number == interface_number)
+            .map(|(_, timeout)| *timeout)
+    }
+
+    pub fn default_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    pub fn serializes_requests(&self) -> bool {
+        self.serialize_requests
+    }
+}
+
+///the steps of `UsbDevice::reset_and_capture`, reported as each one starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResetStage {
+    ///shutting the original handle down
+    Releasing,
+    ///reopening the device with `DeviceCapture`
+    Capturing,
+    Resetting,
+    ///waiting for the device to come back and opening it again
+    Reopening,
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct FrameState {
+    samples: std::collections::VecDeque<(u64, HostTime)>,
+    waker: Option<core::task::Waker>,
+    closed: bool,
+}
+
+///the timer behind `FrameNumbers`, rescheduling itself on the device's queue until the stream is
+///dropped or the device destroyed
+///
+///it runs on the queue, so it holds a retained host object and the device's destroyed flag rather
+///than the device's shared state, which only the thread owning the device touches
+#[cfg(feature = "async")]
+struct FrameTicker {
+    object: IOUSBHostObject,
+    destroyed: Arc<core::sync::atomic::AtomicBool>,
+    queue: dispatch_queue_t,
+    interval: i64,
+    state: Arc<std::sync::Mutex<FrameState>>,
+    //the device's executor when the stream was created
+    executor: Option<Arc<dyn Executor>>,
+}
+
+#[cfg(feature = "async")]
+impl FrameTicker {
+    const BACKLOG: usize = 64;
+
+    fn wake(&self, waker: core::task::Waker) {
+        match &self.executor {
+            Some(executor) => executor.execute(Box::new(move || waker.wake())),
+            None => waker.wake(),
+        }
+    }
+
+    extern "C" fn tick(context: *mut c_void) {
+        let ticker = unsafe { Box::from_raw(context as *mut FrameTicker) };
+        {
+            let mut state = ticker
+                .state
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if state.closed {
+                return;
+            }
+            if ticker.destroyed.load(core::sync::atomic::Ordering::Acquire) {
+                state.closed = true;
+                if let Some(waker) = state.waker.take() {
+                    ticker.wake(waker);
+                }
+                return;
+            }
+
+            let mut time = HostTime { inner: 0 };
+            let frame = unsafe { ticker.object.frameNumberWithTime_(&mut time.inner) };
+            if state.samples.len() == Self::BACKLOG {
+                state.samples.pop_front();
+            }
+            state.samples.push_back((frame, time));
+            if let Some(waker) = state.waker.take() {
+                ticker.wake(waker);
+            }
+        }
+
+        let queue = ticker.queue;
+        let when = unsafe { dispatch_time(0, ticker.interval) };
+        unsafe {
+            dispatch_after_f(
+                when,
+                queue,
+                Box::into_raw(ticker) as *mut c_void,
+                Some(Self::tick),
+            )
+        };
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for FrameTicker {
+    fn drop(&mut self) {
+        let _: () = unsafe { objc::msg_send![self.object.0, release] };
+    }
+}
+
+///a stream of `(frame_number, HostTime)` samples, see `UsbDevice::frame_numbers`
+#[cfg(feature = "async")]
+pub struct FrameNumbers {
+    state: Arc<std::sync::Mutex<FrameState>>,
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for FrameNumbers {
+    type Item = (u64, HostTime);
+    fn poll_next(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Self::Item>> {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match state.samples.pop_front() {
+            Some(sample) => core::task::Poll::Ready(Some(sample)),
+            None if state.closed => core::task::Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                core::task::Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for FrameNumbers {
+    fn drop(&mut self) {
+        //the ticker frees itself the next time it fires
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .closed = true;
+    }
+}
+
+type DeviceJob = Box<dyn FnOnce(&UsbDevice<'static>) + Send>;
+
+///owns a `UsbDevice` on a dedicated thread and runs the commands sent to it by `DeviceClient`s in
+///order, so the device never leaves the thread it was opened on
+pub struct DeviceActor {
+    commands: std::sync::mpsc::Sender<DeviceJob>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl DeviceActor {
+    ///opens the device with `open` on the actor thread, returning once it has been opened
+    pub fn spawn<F>(open: F) -> Result<Self, UsbError>
+    where
+        F: FnOnce() -> Result<UsbDevice<'static>, UsbError> + Send + 'static,
+    {
+        let (commands, jobs) = std::sync::mpsc::channel::<DeviceJob>();
+        let (opened_tx, opened) = std::sync::mpsc::sync_channel(1);
+        let thread = std::thread::spawn(move || {
+            let dev = match open() {
+                Ok(dev) => {
+                    let _ = opened_tx.send(Ok(()));
+                    dev
+                }
+                Err(err) => {
+                    let _ = opened_tx.send(Err(err));
+                    return;
+                }
+            };
+            //runs until every client and the actor itself have been dropped
+            for job in jobs {
+                job(&dev);
+            }
+        });
+        opened.recv().map_err(|_| UsbError::Terminated)??;
+        Ok(Self { commands, thread })
+    }
+
+    pub fn client(&self) -> DeviceClient {
+        DeviceClient {
+            commands: self.commands.clone(),
+        }
+    }
+
+    ///waits for the outstanding clients to be dropped and the device to be destroyed
+    pub fn join(self) {
+        drop(self.commands);
+        let _ = self.thread.join();
+    }
+}
+
+///`Send` handle to a device owned by a `DeviceActor`, every call blocking until the actor has run
+///it
+#[derive(Clone)]
+pub struct DeviceClient {
+    commands: std::sync::mpsc::Sender<DeviceJob>,
+}
+
+impl DeviceClient {
+    ///runs `f` against the device on the actor thread, failing with `UsbError::Terminated` once
+    ///the actor has exited
+    pub fn with<R, F>(&self, f: F) -> Result<R, UsbError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&UsbDevice<'static>) -> R + Send + 'static,
+    {
+        let (reply, result) = std::sync::mpsc::sync_channel(1);
+        self.commands
+            .send(Box::new(move |dev| {
+                let _ = reply.send(f(dev));
+            }))
+            .map_err(|_| UsbError::Terminated)?;
+        result.recv().map_err(|_| UsbError::Terminated)
+    }
+
+    pub fn send_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        self.with(move |dev| dev.send_device_request(request))?
+    }
+
+    pub fn send_device_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: Vec<u8>,
+    ) -> Result<u64, UsbError> {
+        self.with(move |dev| dev.send_device_request_with_data(request, &data))?
+    }
+
+    pub fn reset(&self) -> Result<(), UsbError> {
+        self.with(|dev| dev.reset())?
+    }
+
+    pub fn configure(&self, val: u64, match_interfaces: Option<bool>) -> Result<(), UsbError> {
+        self.with(move |dev| dev.configure(val, match_interfaces))?
+    }
+
+    pub fn stats(&self) -> Result<StatsSnapshot, UsbError> {
+        self.with(|dev| dev.stats())
+    }
+}
+
+///`IN|Vendor|Interface req=0x42 val=0x0001 idx=0x0002 len=64`
+impl core::fmt::Debug for DeviceRequest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let direction = match self.direction() {
+            DeviceRequestDirectionValue::In => "IN",
+            DeviceRequestDirectionValue::Out => "OUT",
+        };
+        let kind = match self.kind() {
+            DeviceRequestTypeValue::Standard => "Standard",
+            DeviceRequestTypeValue::Class => "Class",
+            DeviceRequestTypeValue::Vendor => "Vendor",
+            DeviceRequestTypeValue::Other(_) => "Reserved",
+        };
+        write!(
+            f,
+            "{direction}|{kind}|{:?} req={:#04x} val={:#06x} idx={:#06x} len={}",
+            self.recipient(),
+            self.request(),
+            self.value(),
+            self.index(),
+            self.length()
+        )
+    }
+}
+
+impl From<DeviceRequest> for IOUSBDeviceRequest {
+    fn from(req: DeviceRequest) -> IOUSBDeviceRequest {
+        req.inner
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct DeviceRequest {
+    pub(crate) inner: IOUSBDeviceRequest,
+}
+
+impl DeviceRequest {
+    pub fn new(
+        request_type: DeviceRequestType,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        let inner = IOUSBDeviceRequest {
+            bmRequestType: request_type.into(),
+            bRequest: request,
+            wValue: value,
+            wIndex: index,
+            wLength: length,
+        };
+        Self { inner }
+    }
+
+    pub fn request_type(&self) -> u8 {
+        self.inner.bmRequestType
+    }
+
+    pub fn request(&self) -> u8 {
+        self.inner.bRequest
+    }
+
+    pub fn value(&self) -> u16 {
+        self.inner.wValue
+    }
+
+    pub fn index(&self) -> u16 {
+        self.inner.wIndex
+    }
+
+    pub fn length(&self) -> u16 {
+        self.inner.wLength
+    }
+
+    pub fn direction(&self) -> DeviceRequestDirectionValue {
+        (self.request_type() >> 7).into()
+    }
+
+    ///the type bits of bmRequestType, `Other(3)` being the reserved type
+    pub fn kind(&self) -> DeviceRequestTypeValue {
+        (((self.request_type() >> 5) & 3) as u32).into()
+    }
+
+    pub fn recipient(&self) -> DeviceRequestRecipientValue {
+        ((self.request_type() & 0x1f) as u32).into()
+    }
+
+    ///a chapter 9 request to `recipient`, its direction given by the request
+    pub fn standard(
+        request: StandardRequest,
+        recipient: DeviceRequestRecipientValue,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        Self::with_type(
+            request.direction(),
+            DeviceRequestTypeValue::Standard,
+            recipient,
+            request.into(),
+            value,
+            index,
+            length,
+        )
+    }
+
+    ///a class request, in debug builds asserting `request` is not also a standard request code;
+    ///requests that reuse one on purpose, as HID's GET_REPORT does, are built with
+    ///`class_unchecked`
+    pub fn class(
+        direction: DeviceRequestDirectionValue,
+        recipient: DeviceRequestRecipientValue,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        debug_assert!(
+            StandardRequest::try_from(request).is_err(),
+            "class request {request:#04x} collides with a standard request code"
+        );
+        Self::class_unchecked(direction, recipient, request, value, index, length)
+    }
+
+    ///`class` without the assertion, for class codes that share their number with a standard
+    ///request
+    pub fn class_unchecked(
+        direction: DeviceRequestDirectionValue,
+        recipient: DeviceRequestRecipientValue,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        Self::with_type(
+            direction,
+            DeviceRequestTypeValue::Class,
+            recipient,
+            request,
+            value,
+            index,
+            length,
+        )
+    }
+
+    ///`class` for vendor requests
+    pub fn vendor(
+        direction: DeviceRequestDirectionValue,
+        recipient: DeviceRequestRecipientValue,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        debug_assert!(
+            StandardRequest::try_from(request).is_err(),
+            "vendor request {request:#04x} collides with a standard request code"
+        );
+        Self::vendor_unchecked(direction, recipient, request, value, index, length)
+    }
+
+    ///`class_unchecked` for vendor requests
+    pub fn vendor_unchecked(
+        direction: DeviceRequestDirectionValue,
+        recipient: DeviceRequestRecipientValue,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        Self::with_type(
+            direction,
+            DeviceRequestTypeValue::Vendor,
+            recipient,
+            request,
+            value,
+            index,
+            length,
+        )
+    }
+
+    fn with_type(
+        direction: DeviceRequestDirectionValue,
+        kind: DeviceRequestTypeValue,
+        recipient: DeviceRequestRecipientValue,
+        request: u8,
+        value: u16,
+        index: u16,
+        length: u16,
+    ) -> Self {
+        let request_type = (u8::from(direction) << 7)
+            | ((u32::from(kind) as u8 & 3) << 5)
+            | u32::from(recipient) as u8;
+        Self::new(request_type.into(), request, value, index, length)
+    }
+
+    const DIRECTION_IN: u8 = 0x80;
+    const STANDARD_DEVICE_OUT: u8 = 0;
+    const GET_STATUS: u8 = 0;
+    const CLEAR_FEATURE: u8 = 1;
+    const SET_FEATURE: u8 = 3;
+    const SET_CONFIGURATION: u8 = 9;
+
+    ///a standard GET_STATUS request to `recipient` (the low bits of bmRequestType)
+    pub(crate) fn get_status(recipient: u8, index: u16) -> Self {
+        Self::new(
+            (Self::DIRECTION_IN | recipient).into(),
+            Self::GET_STATUS,
+            0,
+            index,
+            2,
+        )
+    }
+
+    ///a standard SET_FEATURE (or CLEAR_FEATURE when `set` is false) request to `recipient`
+    pub(crate) fn feature(set: bool, recipient: u8, feature: u16, index: u16) -> Self {
+        let request = if set {
+            Self::SET_FEATURE
+        } else {
+            Self::CLEAR_FEATURE
+        };
+        Self::new(recipient.into(), request, feature, index, 0)
+    }
+
+    pub(crate) fn is_in(&self) -> bool {
+        self.request_type() & Self::DIRECTION_IN != 0
+    }
+
+    ///checks the data stage described by the setup packet against the length of the buffer the
+    ///request is sent with, `None` meaning no buffer at all
+    pub fn validate(&self, data_len: Option<usize>) -> Result<(), RequestError> {
+        let length = self.length();
+        match data_len {
+            None if length > 0 => Err(RequestError::MissingDataStage(length)),
+            Some(len) if length == 0 && len > 0 => Err(RequestError::UnexpectedDataStage(len)),
+            Some(len) if self.request_type() & Self::DIRECTION_IN != 0 && len < length as usize => {
+                Err(RequestError::LengthMismatch {
+                    length,
+                    buffer: len,
+                })
+            }
+            Some(len)
+                if self.request_type() & Self::DIRECTION_IN == 0 && len != length as usize =>
+            {
+                Err(RequestError::LengthMismatch {
+                    length,
+                    buffer: len,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestError {
+    ///wLength announces a data stage of this many bytes but no buffer was given
+    MissingDataStage(u16),
+    ///a buffer of this many bytes was given but wLength is zero
+    UnexpectedDataStage(usize),
+    ///the buffer cannot hold (IN) or does not match (OUT) wLength
+    LengthMismatch { length: u16, buffer: usize },
+    ///SET_CONFIGURATION with a value none of the device's configurations have
+    UnknownConfiguration(u8),
+}
+
+impl core::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RequestError::MissingDataStage(length) => {
+                write!(f, "wLength is {length} but no data buffer was provided")
+            }
+            RequestError::UnexpectedDataStage(len) => {
+                write!(f, "wLength is 0 but a {len} byte buffer was provided")
+            }
+            RequestError::LengthMismatch { length, buffer } => {
+                write!(f, "wLength is {length} but the buffer is {buffer} bytes")
+            }
+            RequestError::UnknownConfiguration(value) => {
+                write!(f, "the device has no configuration with value {value}")
+            }
+        }
+    }
+}
+
+pub struct UsbHostObject<'a> {
+    pub(crate) inner: NonNull<IOUSBHostObject>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl Drop for UsbHostObject<'_> {
+    fn drop(&mut self) {
+        unsafe { self.inner.as_ref().destroy() }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct DescriptorOptions {
+    descriptor_type: DescriptorType,
+    length: u64,
+    language_options: Option<LanguageOptions>,
+}
+
+impl DescriptorOptions {
+    pub fn new(
+        descriptor_type: DescriptorType,
+        length: u64,
+        language_options: Option<LanguageOptions>,
+    ) -> Self {
+        Self {
+            descriptor_type,
+            length,
+            language_options,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct LanguageOptions {
+    index: u64,
+    language_id: LangId,
+    request_options: Option<RequestOptions>,
+}
+
+impl LanguageOptions {
+    pub fn new(index: u64, language_id: LangId, request_options: Option<RequestOptions>) -> Self {
+        Self {
+            index,
+            language_id,
+            request_options,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RequestOptions {
+    request_type: DeviceRequestTypeValue,
+    request_recipient: DeviceRequestRecipientValue,
+}
+
+impl RequestOptions {
+    pub fn new(
+        request_type: DeviceRequestTypeValue,
+        request_recipient: DeviceRequestRecipientValue,
+    ) -> Self {
+        Self {
+            request_type,
+            request_recipient,
+        }
+    }
+}
+
+impl UsbHostObject<'_> {
+    pub fn send_device_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: &mut [u8],
+    ) -> Result<u64, UsbError> {
+        let data = MutData::with_data(data).raw();
+        let mut err = NSErr::new();
+        let mut transferred = 0;
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .sendDeviceRequest_data_bytesTransferred_completionTimeout_error_(
+                    request.into(),
+                    data,
+                    &mut transferred,
+                    0.0,
+                    &mut *err,
+                )
+        } {
+            Err(err.into())
+        } else {
+            Ok(transferred)
+        }
+    }
+
+    pub fn send_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .sendDeviceRequest_error_(request.into(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_device_request_with_data<'s>(
+        &'s self,
+        request: DeviceRequest,
+        data: &'s [u8],
+    ) -> Transfer<impl core::future::Future<Output = Result<u64, UsbError>> + 's> {
+        let this: &'s UsbHostObject<'s> = self;
+        Transfer::new(|_| this.enqueue_device_request_with_data_inner(request, data))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_request_with_data_inner(
+        &self,
+        request: DeviceRequest,
+        data: &[u8],
+    ) -> Result<u64, UsbError> {
+        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueDeviceRequest_data_completionTimeout_error_completionHandler_(
+                    request.into(),
+                    data,
+                    0.0,
+                    &mut *err,
+                    cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        });
+
+        handler.await?.result()
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_device_request<'s>(
+        &'s self,
+        request: DeviceRequest,
+    ) -> Transfer<impl core::future::Future<Output = Result<(), UsbError>> + 's> {
+        let this: &'s UsbHostObject<'s> = self;
+        Transfer::new(|_| this.enqueue_device_request_inner(request))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_device_request_inner(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        let handler = AsyncHandler::new(self.inner, |dev, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueDeviceRequest_error_completionHandler_(request.into(), &mut *err, cb)
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        });
+        handler.await?.result().map(|_| ())
+    }
+
+    pub fn abort_device_requests(&self, option: AbortOption) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .abortDeviceRequestsWithOption_error_(option.into(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn descriptor(&self, options: DescriptorOptions) -> Result<DescriptorHeader<'_>, UsbError> {
+        let (desc, _) = self.fetch_descriptor(options)?;
+        DescriptorHeader::new(desc).ok_or(UsbError::InvalidObject)
+    }
+
+    ///fetches a whole descriptor without knowing its size up front: the header is requested
+    ///first to learn `bLength`, or `wTotalLength` for configuration and BOS descriptors, and the
+    ///descriptor is then requested again at that size
+    pub fn descriptor_bytes(
+        &self,
+        descriptor_type: DescriptorType,
+        language_options: Option<LanguageOptions>,
+    ) -> Result<Vec<u8>, UsbError> {
+        let has_total_length = matches!(
+            descriptor_type,
+            DescriptorType::Configuration
+                | DescriptorType::OtherSpeedConfig
+                | DescriptorType::CapabilityDescriptor
+        );
+        let header_length = if has_total_length { 4 } else { 2 };
+        let (header, length) = self.fetch_descriptor(DescriptorOptions::new(
+            descriptor_type,
+            header_length,
+            language_options,
+        ))?;
+        let header = unsafe { core::slice::from_raw_parts(header as *const u8, length as usize) };
+        let full_length = match header {
+            [_, _, low, high, ..] if has_total_length => u16::from_le_bytes([*low, *high]) as u64,
+            [length, ..] => *length as u64,
+            [] => return Err(UsbError::InvalidValue),
+        };
+
+        let (desc, length) = self.fetch_descriptor(DescriptorOptions::new(
+            descriptor_type,
+            full_length,
+            language_options,
+        ))?;
+        let bytes = unsafe { core::slice::from_raw_parts(desc as *const u8, length as usize) };
+        Ok(bytes.to_vec())
+    }
+
+    fn fetch_descriptor(
+        &self,
+        options: DescriptorOptions,
+    ) -> Result<(*const IOUSBDescriptorHeader, u64), UsbError> {
+        let mut err = NSErr::new();
+        let DescriptorOptions {
+            descriptor_type,
+            mut length,
+            language_options,
+        } = options;
+        let descriptor_type: u8 = descriptor_type.into();
+        let desc = unsafe {
+            match language_options {
+                Some(LanguageOptions {
+                    index,
+                    language_id,
+                    request_options:
+                        Some(RequestOptions {
+                            request_type,
+                            request_recipient,
+                        }),
+                }) => self
+                    .inner
+                    .as_ref()
+                    .descriptorWithType_length_index_languageID_requestType_requestRecipient_error_(
+                        descriptor_type as u32,
+                        &mut length,
+                        index,
+                        language_id.into(),
+                        request_type.into(),
+                        request_recipient.into(),
+                        &mut *err,
+                    ),
+                Some(LanguageOptions {
+                    index,
+                    language_id,
+                    request_options: None,
+                }) => self
+                    .inner
+                    .as_ref()
+                    .descriptorWithType_length_index_languageID_error_(
+                        descriptor_type as u32,
+                        &mut length,
+                        index,
+                        language_id.into(),
+                        &mut *err,
+                    ),
+                None => self.inner.as_ref().descriptorWithType_length_error_(
+                    descriptor_type as u32,
+                    &mut length,
+                    &mut *err,
+                ),
+            }
+        };
+
+        if err.is_err() {
+            Err(err.into())
+        } else if desc.is_null() {
+            Err(UsbError::NotSupported)
+        } else {
+            Ok((desc as *const IOUSBDescriptorHeader, length))
+        }
+    }
+
+    ///the string descriptor at `index` decoded to UTF-8
+    pub fn string_descriptor(
+        &self,
+        index: u64,
+        language_id: Option<LangId>,
+    ) -> Result<String, UsbError> {
+        self.string_descriptor_raw(index, language_id)
+            .map(|string| string_from_ns(&string))
+    }
+
+    ///`string_descriptor` as the `NSString` IOUSBHost returns
+    pub fn string_descriptor_raw(
+        &self,
+        index: u64,
+        language_id: Option<LangId>,
+    ) -> Result<NSString, UsbError> {
+        let mut err = NSErr::new();
+        let desc = unsafe {
+            match language_id {
+                Some(id) => self.inner.as_ref().stringWithIndex_languageID_error_(
+                    index,
+                    id.into(),
+                    &mut *err,
+                ),
+                None => self.inner.as_ref().stringWithIndex_error_(index, &mut *err),
+            }
+        };
+
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(desc)
+        }
+    }
+
+    pub fn configuration_descriptors(
+        &self,
+    ) -> impl Iterator<Item = Result<ConfigurationDescriptor<'_>, UsbError>> {
+        let count = self
+            .device_descriptor()
+            .map_or(0, |desc| desc.configuration_count());
+        let idx = 0;
+        ConfigurationDescriptors {
+            configuration_count: count,
+            idx,
+            dev: unsafe { IOUSBHostObject(self.inner.as_ref().0) },
+            lt: PhantomData,
+        }
+    }
+
+    pub fn configuration_descriptor_at(
+        &self,
+        index: u8,
+    ) -> Result<ConfigurationDescriptor<'_>, UsbError> {
+        configuration_descriptor_at(unsafe { self.inner.as_ref() }, index)
+    }
+
+    pub fn configuration_descriptor_with_value(
+        &self,
+        val: u64,
+    ) -> Result<ConfigurationDescriptor<'_>, UsbError> {
+        let mut err = NSErr::new();
+        let desc = unsafe {
+            self.inner
+                .as_ref()
+                .configurationDescriptorWithConfigurationValue_error_(val, &mut *err)
+        };
+
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            ConfigurationDescriptor::new(desc).ok_or(UsbError::NoConfiguration)
+        }
+    }
+
+    //returns the current frame number, but also updates the host time aligned with the time which
+    //the frame number was last updated
+    pub fn frame_number(&self, time: &mut HostTime) -> u64 {
+        unsafe { self.inner.as_ref().frameNumberWithTime_(&mut time.inner) }
+    }
+
+    ///a buffer of `capacity` bytes mapped for the device, see `IoData`
+    pub fn io_data(&self, capacity: u64) -> Result<IoData, UsbError> {
+        let mut err = NSErr::new();
+        let data = unsafe {
+            self.inner
+                .as_ref()
+                .ioDataWithCapacity_error_(capacity, &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            IoData::new(data, capacity)
+        }
+    }
+
+    pub fn queue(&self) -> Queue {
+        Queue::new(unsafe { self.inner.as_ref().queue() })
+    }
+
+    pub fn device_descriptor(&self) -> Option<DeviceDescriptor> {
+        let ptr = unsafe { self.inner.as_ref().deviceDescriptor() };
+        DeviceDescriptor::new(ptr)
+    }
+
+    pub fn capability_descriptors(&self) -> impl Iterator<Item = CapabilityDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().capabilityDescriptors() };
+        CapabilityDescriptors {
+            inner: ptr,
+            lt: PhantomData,
+        }
+    }
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.as_ref().deviceAddress() }
+    }
+}
+
+fn configuration_descriptor_at<'a>(
+    dev: &IOUSBHostObject,
+    index: u8,
+) -> Result<ConfigurationDescriptor<'a>, UsbError> {
+    let device = unsafe { dev.deviceDescriptor().as_ref() }.ok_or(UsbError::NoDeviceDescriptor)?;
+    if index >= device.bNumConfigurations {
+        return Err(UsbError::NoConfiguration);
+    }
+
+    let mut err = NSErr::new();
+    let ptr = unsafe { dev.configurationDescriptorWithIndex_error_(index as u64, &mut *err) };
+
+    if err.is_err() {
+        Err(err.into())
+    } else {
+        ConfigurationDescriptor::new(ptr).ok_or(UsbError::NoConfiguration)
+    }
+}
+
+pub struct ConfigurationDescriptors<'a> {
+    dev: IOUSBHostObject,
+    idx: u8,
+    configuration_count: u8,
+    lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for ConfigurationDescriptors<'a> {
+    type Item = Result<ConfigurationDescriptor<'a>, UsbError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.configuration_count {
+            return None;
+        }
+
+        let desc = configuration_descriptor_at(&self.dev, self.idx);
+        self.idx += 1;
+        Some(desc)
+    }
+}
+
+///the bRequest codes of the standard requests in chapter 9 of the USB 2.0 and 3.x specifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum StandardRequest {
+    GetStatus = 0,
+    ClearFeature = 1,
+    SetFeature = 3,
+    SetAddress = 5,
+    GetDescriptor = 6,
+    SetDescriptor = 7,
+    GetConfiguration = 8,
+    SetConfiguration = 9,
+    GetInterface = 10,
+    SetInterface = 11,
+    SynchFrame = 12,
+    SetSel = 48,
+    SetIsochDelay = 49,
+}
+
+impl StandardRequest {
+    ///the direction of the request's data stage, OUT for requests without one
+    pub fn direction(self) -> DeviceRequestDirectionValue {
+        use StandardRequest as SR;
+        match self {
+            SR::GetStatus
+            | SR::GetDescriptor
+            | SR::GetConfiguration
+            | SR::GetInterface
+            | SR::SynchFrame => DeviceRequestDirectionValue::In,
+            _ => DeviceRequestDirectionValue::Out,
+        }
+    }
+}
+
+impl From<StandardRequest> for u8 {
+    fn from(request: StandardRequest) -> u8 {
+        request as u8
+    }
+}
+
+impl TryFrom<u8> for StandardRequest {
+    type Error = u8;
+    fn try_from(num: u8) -> Result<StandardRequest, u8> {
+        use StandardRequest as SR;
+        Ok(match num {
+            0 => SR::GetStatus,
+            1 => SR::ClearFeature,
+            3 => SR::SetFeature,
+            5 => SR::SetAddress,
+            6 => SR::GetDescriptor,
+            7 => SR::SetDescriptor,
+            8 => SR::GetConfiguration,
+            9 => SR::SetConfiguration,
+            10 => SR::GetInterface,
+            11 => SR::SetInterface,
+            12 => SR::SynchFrame,
+            48 => SR::SetSel,
+            49 => SR::SetIsochDelay,
+            other => return Err(other),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceRequestDirectionValue {
+    Out = 0,
+    In = 1,
+}
+
+impl From<u8> for DeviceRequestDirectionValue {
+    fn from(num: u8) -> DeviceRequestDirectionValue {
+        use DeviceRequestDirectionValue as DRDV;
+        match num {
+            0 => DRDV::Out,
+            _ => DRDV::In,
+        }
+    }
+}
+
+impl From<DeviceRequestDirectionValue> for u8 {
+    fn from(direction: DeviceRequestDirectionValue) -> u8 {
+        use DeviceRequestDirectionValue as DRDV;
+        match direction {
+            DRDV::Out => 0,
+            DRDV::In => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum DeviceRequestTypeValue {
+    Standard = 0,
+    Class = 1,
+    Vendor = 2,
+    Other(u32),
+}
+
+impl From<DeviceRequestTypeValue> for u32 {
+    fn from(req: DeviceRequestTypeValue) -> u32 {
+        use DeviceRequestTypeValue as DRTV;
+        match req {
+            DRTV::Standard => 0,
+            DRTV::Class => 1,
+            DRTV::Vendor => 2,
+            DRTV::Other(other) => other,
+        }
+    }
+}
+
+impl From<u32> for DeviceRequestTypeValue {
+    fn from(num: u32) -> DeviceRequestTypeValue {
+        use DeviceRequestTypeValue as DRTV;
+        match num {
+            0 => DRTV::Standard,
+            1 => DRTV::Class,
+            2 => DRTV::Vendor,
+            other => DRTV::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceRequestRecipientValue {
+    Device = 0,
+    Interface = 1,
+    Endpoint = 2,
+    Other = 3,
+}
+
+impl From<DeviceRequestRecipientValue> for u32 {
+    fn from(val: DeviceRequestRecipientValue) -> u32 {
+        use DeviceRequestRecipientValue as DRRV;
+        match val {
+            DRRV::Device => 0,
+            DRRV::Interface => 1,
+            DRRV::Endpoint => 2,
+            DRRV::Other => 3,
+        }
+    }
+}
+
+impl From<u32> for DeviceRequestRecipientValue {
+    fn from(num: u32) -> DeviceRequestRecipientValue {
+        use DeviceRequestRecipientValue as DRRV;
+        //the reserved recipients 4..=31 fall under other, as masking them would alias a real one
+        match num {
+            0 => DRRV::Device,
+            1 => DRRV::Interface,
+            2 => DRRV::Endpoint,
+            _ => DRRV::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DeviceRequestType {
+    Size = 8,
+    DirectionPhase = 7,
+    DirectionOut = 0,
+    DirectionIn = 128,
+    TypePhase = 5,
+    TypeClass = 32,
+    TypeVendor = 64,
+    RecipientInterface = 1,
+    RecipientEndpoint = 2,
+    RecipientOther = 3,
+    Other(u8),
+}
+
+impl From<DeviceRequestType> for u8 {
+    fn from(req_ty: DeviceRequestType) -> u8 {
+        use DeviceRequestType as DRT;
+        match req_ty {
+            DRT::Size => 8,
+            DRT::DirectionPhase => 7,
+            DRT::DirectionOut => 0,
+            DRT::DirectionIn => 128,
+            DRT::TypePhase => 5,
+            DRT::TypeClass => 32,
+            DRT::TypeVendor => 64,
+            DRT::RecipientInterface => 1,
+            DRT::RecipientEndpoint => 2,
+            DRT::RecipientOther => 3,
+            DRT::Other(other) => other,
+        }
+    }
+}
+
+impl From<u8> for DeviceRequestType {
+    fn from(num: u8) -> DeviceRequestType {
+        use DeviceRequestType as DRT;
+        match num {
+            8 => DRT::Size,
+            7 => DRT::DirectionPhase,
+            0 => DRT::DirectionOut,
+            128 => DRT::DirectionIn,
+            5 => DRT::TypePhase,
+            32 => DRT::TypeClass,
+            64 => DRT::TypeVendor,
+            1 => DRT::RecipientInterface,
+            2 => DRT::RecipientEndpoint,
+            3 => DRT::RecipientOther,
+            other => DRT::Other(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_to(request_type: u8) -> DeviceRequest {
+        DeviceRequest::new(DeviceRequestType::Other(request_type), 0, 0, 0, 0)
+    }
+
+    #[test]
+    fn recipient_decodes_the_defined_recipients() {
+        use DeviceRequestRecipientValue as DRRV;
+        assert_eq!(request_to(0x80).recipient(), DRRV::Device);
+        assert_eq!(request_to(0x81).recipient(), DRRV::Interface);
+        assert_eq!(request_to(0x02).recipient(), DRRV::Endpoint);
+        assert_eq!(request_to(0x23).recipient(), DRRV::Other);
+    }
+
+    #[test]
+    fn reserved_recipients_are_other() {
+        use DeviceRequestRecipientValue as DRRV;
+        assert_eq!(request_to(0x05).recipient(), DRRV::Other);
+        assert_eq!(request_to(0x06).recipient(), DRRV::Other);
+        assert_eq!(request_to(0x9f).recipient(), DRRV::Other);
+    }
+}
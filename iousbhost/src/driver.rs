@@ -0,0 +1,350 @@
+use crate::*;
+use std::collections::{BTreeMap, HashMap};
+
+pub const HID_CLASS: u8 = 0x03;
+pub const CDC_CLASS: u8 = 0x02;
+pub const CDC_DATA_CLASS: u8 = 0x0a;
+pub const MASS_STORAGE_CLASS: u8 = 0x08;
+pub const VENDOR_SPECIFIC_CLASS: u8 = 0xff;
+
+///which interfaces a driver is bound to: the device has to match `device`, the interface the
+///interface fields, every `None` field matching anything
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DriverMatcher {
+    pub device: DeviceMatcher,
+    pub interface_class: Option<u8>,
+    pub interface_subclass: Option<u8>,
+    pub interface_protocol: Option<u8>,
+}
+
+impl DriverMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///interfaces of `class` on any device, such as `HID_CLASS`
+    pub fn class(class: u8) -> Self {
+        Self::new().interface_class(class)
+    }
+
+    ///every interface of the devices with this vendor and product id
+    pub fn vid_pid(vendor_id: u16, product_id: u16) -> Self {
+        Self::new().device(
+            DeviceMatcher::new()
+                .vendor_id(vendor_id)
+                .product_id(product_id),
+        )
+    }
+
+    pub fn device(mut self, device: DeviceMatcher) -> Self {
+        self.device = device;
+        self
+    }
+
+    pub fn interface_class(mut self, class: u8) -> Self {
+        self.interface_class = Some(class);
+        self
+    }
+
+    pub fn interface_subclass(mut self, subclass: u8) -> Self {
+        self.interface_subclass = Some(subclass);
+        self
+    }
+
+    pub fn interface_protocol(mut self, protocol: u8) -> Self {
+        self.interface_protocol = Some(protocol);
+        self
+    }
+
+    ///checks the interface fields only, see `matches_device` for the device part
+    pub fn matches(&self, interface: &InterfaceDescriptor<'_>) -> bool {
+        let matches = |expected: Option<u8>, actual: u8| expected.map_or(true, |e| e == actual);
+        matches(self.interface_class, interface.interface_class())
+            && matches(self.interface_subclass, interface.interface_subclass())
+            && matches(self.interface_protocol, interface.interface_protocol())
+    }
+
+    ///checks `device` against the device part from its registry properties, as
+    ///`OpenCandidate::matches` does
+    pub fn matches_device(&self, device: &UsbDevice<'_>) -> bool {
+        self.device.matches_service(&device.io_service())
+    }
+}
+
+///a class driver bound to some interfaces of a device by a `DriverRegistry`, holding the device
+///and interfaces it was probed with for as long as it likes
+pub trait Driver {
+    ///the device is gone or the registry lets go of it, called right before the driver is dropped
+    fn detach(&mut self) {}
+}
+
+type Probe<'a> = Box<
+    dyn FnMut(&UsbDevice<'a>, Vec<HostInterface<'a>>) -> Result<Box<dyn Driver + 'a>, UsbError>
+        + 'a,
+>;
+
+struct RegisteredDriver<'a> {
+    name: &'static str,
+    matcher: DriverMatcher,
+    probe: Probe<'a>,
+}
+
+struct BoundDriver<'a> {
+    name: &'static str,
+    interfaces: Vec<u8>,
+    driver: Box<dyn Driver + 'a>,
+}
+
+impl Drop for BoundDriver<'_> {
+    fn drop(&mut self) {
+        self.driver.detach();
+    }
+}
+
+///what a `DriverRegistry::poll` did
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DriverEvent {
+    ///`driver` was probed successfully with `interfaces` of the device `id`
+    Bound {
+        id: u64,
+        driver: &'static str,
+        interfaces: Vec<u8>,
+    },
+    ///the probe of `driver` failed, its interfaces were closed again
+    ProbeFailed {
+        id: u64,
+        driver: &'static str,
+        error: UsbError,
+    },
+    ///the device `id` is gone and `driver` was detached
+    Unbound { id: u64, driver: &'static str },
+}
+
+///instantiates class drivers for the devices a `UsbManager` opens
+///
+///each interface of a device's current configuration goes to the first registered driver whose
+///matcher it fits, together with the other interfaces of its function if an interface
+///association groups them, as CDC does; a driver is probed once per device with every interface
+///it was given, interfaces no driver wants are not opened
+///
+///the manager opens every device matching the device part of some driver's matcher, so a matcher
+///without one opens every device
+pub struct DriverRegistry<'a> {
+    manager: UsbManager<'a>,
+    options: HostObjectInitOptions,
+    drivers: Vec<RegisteredDriver<'a>>,
+    //the drivers bound to each device seen, empty for devices no driver wanted
+    bound: HashMap<u64, Vec<BoundDriver<'a>>>,
+}
+
+impl<'a> DriverRegistry<'a> {
+    ///`options` are used to open the interfaces handed to drivers
+    pub fn new(manager: UsbManager<'a>, options: HostObjectInitOptions) -> Self {
+        Self {
+            manager,
+            options,
+            drivers: Vec::new(),
+            bound: HashMap::new(),
+        }
+    }
+
+    ///registers a driver, earlier registrations taking precedence; `probe` is called with the
+    ///device and the interfaces claimed for the driver and returns the driver instance
+    pub fn register<F>(&mut self, name: &'static str, matcher: DriverMatcher, probe: F)
+    where
+        F: FnMut(&UsbDevice<'a>, Vec<HostInterface<'a>>) -> Result<Box<dyn Driver + 'a>, UsbError>
+            + 'a,
+    {
+        self.manager.on(matcher.device, |_| {});
+        self.drivers.push(RegisteredDriver {
+            name,
+            matcher,
+            probe: Box::new(probe),
+        });
+    }
+
+    pub fn manager(&self) -> &UsbManager<'a> {
+        &self.manager
+    }
+
+    ///the names of the drivers bound to the device `id`
+    pub fn drivers(&self, id: u64) -> impl Iterator<Item = &'static str> + '_ {
+        self.bound
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|bound| bound.name)
+    }
+
+    ///the interface numbers the driver `name` was given on the device `id`
+    pub fn interfaces_of(&self, id: u64, name: &str) -> Option<&[u8]> {
+        self.bound
+            .get(&id)?
+            .iter()
+            .find(|bound| bound.name == name)
+            .map(|bound| bound.interfaces.as_slice())
+    }
+
+    ///polls the manager, binding drivers to arrived devices and detaching the drivers of departed
+    ///ones
+    pub fn poll(&mut self) -> Result<Vec<DriverEvent>, UsbError> {
+        self.manager.poll()?;
+        let mut events = Vec::new();
+
+        let departed: Vec<u64> = self
+            .bound
+            .keys()
+            .filter(|id| self.manager.device(**id).is_none())
+            .copied()
+            .collect();
+        for id in departed {
+            for bound in self.bound.remove(&id).into_iter().flatten() {
+                events.push(DriverEvent::Unbound {
+                    id,
+                    driver: bound.name,
+                });
+            }
+        }
+
+        let arrived: Vec<(u64, UsbDevice<'a>)> = self
+            .manager
+            .devices()
+            .filter(|(id, _)| !self.bound.contains_key(id))
+            .map(|(id, device)| (id, device.clone()))
+            .collect();
+        for (id, device) in arrived {
+            let bound = self.bind(id, &device, &mut events);
+            self.bound.insert(id, bound);
+        }
+        Ok(events)
+    }
+
+    //which driver each interface number of the current configuration goes to
+    fn claims(&self, device: &UsbDevice<'a>) -> BTreeMap<u8, usize> {
+        let mut claims = BTreeMap::new();
+        let Some(interfaces) = device.interface_descriptors() else {
+            return claims;
+        };
+        //the manager opens devices for the union of the matchers, so each driver's device part
+        //still has to be checked
+        let candidates: Vec<bool> = self
+            .drivers
+            .iter()
+            .map(|driver| driver.matcher.matches_device(device))
+            .collect();
+        let functions: Vec<(u8, u8)> = device
+            .interface_association_descriptors()
+            .into_iter()
+            .flatten()
+            .map(|function| (function.first_interface(), function.interface_count()))
+            .collect();
+        for interface in interfaces {
+            let number = interface.interface_number();
+            if interface.alternate_setting() != 0 || claims.contains_key(&number) {
+                continue;
+            }
+            let Some(driver) = self
+                .drivers
+                .iter()
+                .zip(&candidates)
+                .position(|(driver, candidate)| *candidate && driver.matcher.matches(&interface))
+            else {
+                continue;
+            };
+            let function = functions
+                .iter()
+                .find(|(first, count)| (*first..first.saturating_add(*count)).contains(&number));
+            match function {
+                Some((first, count)) => {
+                    for number in *first..first.saturating_add(*count) {
+                        claims.entry(number).or_insert(driver);
+                    }
+                }
+                None => {
+                    claims.insert(number, driver);
+                }
+            }
+        }
+        claims
+    }
+
+    fn bind(
+        &mut self,
+        id: u64,
+        device: &UsbDevice<'a>,
+        events: &mut Vec<DriverEvent>,
+    ) -> Vec<BoundDriver<'a>> {
+        let claims = self.claims(device);
+        if claims.is_empty() {
+            return Vec::new();
+        }
+
+        let mut grouped: BTreeMap<usize, Vec<(u8, HostInterface<'a>)>> = BTreeMap::new();
+        //only the claimed interfaces are opened, those held by kernel drivers are left to them
+        let options = self.options;
+        let claimed: Vec<u8> = claims.keys().copied().collect();
+        let per_interface = move |descriptor: &InterfaceDescriptor<'_>| {
+            (descriptor.alternate_setting() == 0
+                && claimed.contains(&descriptor.interface_number()))
+            .then_some(options)
+        };
+        let interfaces = device.interfaces(per_interface).into_iter().flatten();
+        for interface in interfaces.filter_map(Result::ok) {
+            let Some(number) = interface
+                .interface_descriptor()
+                .map(|descriptor| descriptor.interface_number())
+            else {
+                continue;
+            };
+            let Some(driver) = claims.get(&number) else {
+                continue;
+            };
+            grouped
+                .entry(*driver)
+                .or_default()
+                .push((number, interface));
+        }
+
+        let mut bound = Vec::new();
+        for (driver, interfaces) in grouped {
+            let registered = &mut self.drivers[driver];
+            let (numbers, interfaces): (Vec<u8>, Vec<HostInterface<'a>>) =
+                interfaces.into_iter().unzip();
+            match (registered.probe)(device, interfaces) {
+                Ok(instance) => {
+                    events.push(DriverEvent::Bound {
+                        id,
+                        driver: registered.name,
+                        interfaces: numbers.clone(),
+                    });
+                    bound.push(BoundDriver {
+                        name: registered.name,
+                        interfaces: numbers,
+                        driver: instance,
+                    });
+                }
+                Err(error) => {
+                    crate::diag!(
+                        debug,
+                        "driver {} failed to probe device {:#x}: {:?}",
+                        registered.name,
+                        id,
+                        error
+                    );
+                    events.push(DriverEvent::ProbeFailed {
+                        id,
+                        driver: registered.name,
+                        error,
+                    });
+                }
+            }
+        }
+        bound
+    }
+
+    ///detaches every driver, then shuts the manager's devices down, see `UsbManager::shutdown`
+    pub fn shutdown(&mut self, timeout: std::time::Duration) -> Result<(), UsbError> {
+        self.bound.clear();
+        self.manager.shutdown(timeout)
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::mpsc::{SendError, Sender};
+
+///the wakeup of a task whose transfer completed, handed to an `Executor` to run
+pub type Notification = Box<dyn FnOnce() + Send>;
+
+///runs completion notifications away from the dispatch queue the framework completes transfers
+///on, see `UsbDevice::set_completion_executor`
+///
+///with one set the queue only hands notifications over, so a waker that blocks or takes locks of
+///its own can not stall the completions behind it
+pub trait Executor: Send + Sync {
+    fn execute(&self, notification: Notification);
+}
+
+///for a thread of the application draining the channel and calling what it receives
+impl Executor for Sender<Notification> {
+    fn execute(&self, notification: Notification) {
+        //once nobody receives, a dropped notification would leave its task pending forever
+        if let Err(SendError(notification)) = self.send(notification) {
+            notification();
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Executor for tokio::runtime::Handle {
+    fn execute(&self, notification: Notification) {
+        self.spawn(async move { notification() });
+    }
+}
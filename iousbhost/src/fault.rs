@@ -0,0 +1,247 @@
+use crate::*;
+use std::time::Duration;
+
+///how a `FaultInjector` decides which transfers are hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultMode {
+    ///each transfer is hit with the configured probability, drawn from a generator seeded with
+    ///the value so the same seed reproduces the same sequence
+    Random(u64),
+    ///hits are spread evenly, a rate of 0.25 hits every fourth transfer
+    Deterministic,
+}
+
+///the faults injected into the transfers of a pipe so applications can exercise their retry and
+///recovery paths, see `HostPipe::set_fault_injector`; rates are fractions of transfers in `0..=1`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultInjector {
+    mode: FaultMode,
+    failure_rate: f64,
+    errors: Vec<UsbError>,
+    short_packet_rate: f64,
+    short_packet_len: u64,
+    delay_rate: f64,
+    delay: Duration,
+}
+
+impl FaultInjector {
+    pub fn new(mode: FaultMode) -> Self {
+        Self {
+            mode,
+            failure_rate: 0.0,
+            errors: Vec::new(),
+            short_packet_rate: 0.0,
+            short_packet_len: 0,
+            delay_rate: 0.0,
+            delay: Duration::ZERO,
+        }
+    }
+
+    ///fails `rate` of the transfers without submitting them, cycling through `errors`, with
+    ///`UsbError::Failure` if it is empty
+    pub fn fail(mut self, rate: f64, errors: &[UsbError]) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self.errors = errors.to_vec();
+        self
+    }
+
+    ///reports at most `len` bytes transferred for `rate` of the transfers that report a length
+    pub fn short_packets(mut self, rate: f64, len: u64) -> Self {
+        self.short_packet_rate = rate.clamp(0.0, 1.0);
+        self.short_packet_len = len;
+        self
+    }
+
+    ///holds back the completion of `rate` of the transfers by `delay`
+    pub fn delay(mut self, rate: f64, delay: Duration) -> Self {
+        self.delay_rate = rate.clamp(0.0, 1.0);
+        self.delay = delay;
+        self
+    }
+
+    pub fn mode(&self) -> FaultMode {
+        self.mode
+    }
+}
+
+///the faults drawn for a single transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Fault {
+    pub(crate) error: Option<UsbError>,
+    pub(crate) short_len: Option<u64>,
+    pub(crate) delay: Option<Duration>,
+}
+
+impl Fault {
+    pub(crate) fn shorten(&self, len: u64) -> u64 {
+        self.short_len.map_or(len, |short| len.min(short))
+    }
+
+    ///runs `transfer` unless the fault fails it, then holds back the completion
+    pub(crate) fn apply<T>(
+        &self,
+        transfer: impl FnOnce() -> Result<T, UsbError>,
+    ) -> Result<T, UsbError> {
+        let res = match self.error {
+            Some(err) => Err(err),
+            None => transfer(),
+        };
+        if let Some(delay) = self.delay {
+            std::thread::sleep(delay);
+        }
+        res
+    }
+
+    ///`apply` for an async request, a short packet cutting down the length its completion reports
+    #[cfg(feature = "async")]
+    pub(crate) async fn apply_async(
+        &self,
+        transfer: impl core::future::Future<Output = Result<crate::future::Completed, UsbError>>,
+    ) -> Result<crate::future::Completed, UsbError> {
+        let res = match self.error {
+            Some(err) => Err(err),
+            None => transfer.await.map(|done| crate::future::Completed {
+                bytes: self.shorten(done.bytes),
+                ..done
+            }),
+        };
+        if let Some(delay) = self.delay {
+            Sleep::new(delay).await;
+        }
+        res
+    }
+}
+
+///a `FaultInjector` together with how far into its sequence a pipe is
+pub(crate) struct FaultState {
+    injector: FaultInjector,
+    rng: u64,
+    transfers: u64,
+    failures: u64,
+}
+
+impl FaultState {
+    pub(crate) fn new(injector: FaultInjector) -> Self {
+        let rng = match injector.mode {
+            //xorshift never leaves zero
+            FaultMode::Random(seed) => seed.max(1),
+            FaultMode::Deterministic => 0,
+        };
+        Self {
+            injector,
+            rng,
+            transfers: 0,
+            failures: 0,
+        }
+    }
+
+    pub(crate) fn injector(&self) -> &FaultInjector {
+        &self.injector
+    }
+
+    fn hit(&mut self, rate: f64) -> bool {
+        match self.injector.mode {
+            FaultMode::Random(_) => {
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 7;
+                self.rng ^= self.rng << 17;
+                ((self.rng >> 11) as f64 / (1u64 << 53) as f64) < rate
+            }
+            //hit whenever the running total of `rate` crosses another whole transfer
+            FaultMode::Deterministic => {
+                (self.transfers as f64 * rate).floor()
+                    < ((self.transfers + 1) as f64 * rate).floor()
+            }
+        }
+    }
+
+    ///draws the faults for the next transfer
+    pub(crate) fn next(&mut self) -> Fault {
+        let error = self.hit(self.injector.failure_rate).then(|| {
+            let errors = &self.injector.errors;
+            let err = match errors.len() {
+                0 => UsbError::Failure,
+                len => errors[(self.failures % len as u64) as usize],
+            };
+            self.failures += 1;
+            err
+        });
+        let short_len = self
+            .hit(self.injector.short_packet_rate)
+            .then_some(self.injector.short_packet_len);
+        let delay = self
+            .hit(self.injector.delay_rate)
+            .then_some(self.injector.delay);
+        self.transfers += 1;
+        Fault {
+            error,
+            short_len,
+            delay,
+        }
+    }
+}
+
+///resolves after `delay` without needing a runtime, the wakeup is scheduled on a global dispatch
+///queue instead of a thread of its own
+#[cfg(feature = "async")]
+struct Sleep {
+    deadline: std::time::Instant,
+    waker: Option<Arc<std::sync::Mutex<core::task::Waker>>>,
+}
+
+#[cfg(feature = "async")]
+impl Sleep {
+    fn new(delay: Duration) -> Self {
+        Self {
+            deadline: std::time::Instant::now() + delay,
+            waker: None,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl core::future::Future for Sleep {
+    type Output = ();
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<()> {
+        let now = std::time::Instant::now();
+        if now >= self.deadline {
+            return core::task::Poll::Ready(());
+        }
+        match &self.waker {
+            Some(waker) => {
+                *waker
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) = cx.waker().clone();
+            }
+            None => {
+                let waker = Arc::new(std::sync::Mutex::new(cx.waker().clone()));
+                let remaining = (self.deadline - now).as_nanos().min(i64::MAX as u128) as i64;
+                unsafe {
+                    let when =
+                        dispatch::ffi::dispatch_time(dispatch::ffi::DISPATCH_TIME_NOW, remaining);
+                    dispatch::ffi::dispatch_after_f(
+                        when,
+                        dispatch::ffi::dispatch_get_global_queue(0, 0),
+                        Arc::into_raw(waker.clone()) as *mut core::ffi::c_void,
+                        wake_sleep,
+                    )
+                };
+                self.waker = Some(waker);
+            }
+        }
+        core::task::Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+extern "C" fn wake_sleep(context: *mut core::ffi::c_void) {
+    //takes back the reference handed to `dispatch_after_f`
+    let waker = unsafe { Arc::from_raw(context as *const std::sync::Mutex<core::task::Waker>) };
+    waker
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .wake_by_ref();
+}
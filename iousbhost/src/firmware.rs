@@ -0,0 +1,336 @@
+use crate::*;
+
+pub mod bin;
+pub mod ihex;
+
+pub use ihex::HexError;
+
+///bytes to be written to the device starting at `address`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    ///the address right after the segment, `None` if that is past the 32 bit address space
+    pub fn end(&self) -> Option<u32> {
+        u32::try_from(self.data.len())
+            .ok()
+            .and_then(|len| self.address.checked_add(len))
+    }
+
+    ///splits the segment into chunks of at most `max_len` bytes that never cross a multiple of
+    ///`max_len` in the address space, so with the max packet size every chunk but the first
+    ///starts packet aligned
+    pub fn chunks(&self, max_len: usize) -> Chunks<'_> {
+        Chunks {
+            address: self.address,
+            data: &self.data,
+            max_len: max_len.max(1),
+        }
+    }
+}
+
+///a piece of a `Segment` small enough for a single transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chunk<'s> {
+    pub address: u32,
+    pub data: &'s [u8],
+}
+
+///iterator returned by `Segment::chunks`
+#[derive(Debug, Clone)]
+pub struct Chunks<'s> {
+    address: u32,
+    data: &'s [u8],
+    max_len: usize,
+}
+
+impl<'s> Iterator for Chunks<'s> {
+    type Item = Chunk<'s>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let to_boundary = self.max_len - self.address as usize % self.max_len;
+        let (data, rest) = self.data.split_at(to_boundary.min(self.data.len()));
+        let chunk = Chunk {
+            address: self.address,
+            data,
+        };
+        self.address = self.address.wrapping_add(data.len() as u32);
+        self.data = rest;
+        Some(chunk)
+    }
+}
+
+///the chunks of every segment in order, see `Segment::chunks`
+pub fn chunks(segments: &[Segment], max_len: usize) -> impl Iterator<Item = Chunk<'_>> {
+    segments
+        .iter()
+        .flat_map(move |segment| segment.chunks(max_len))
+}
+
+///how far a download got, reported after every chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Progress {
+    ///where the chunk just written started
+    pub address: u32,
+    pub written: usize,
+    pub total: usize,
+}
+
+///sends every chunk of `segments` with `send`, at most `max_len` bytes at a time, calling
+///`progress` after each one; the engine behind the download helpers
+pub fn download<E>(
+    segments: &[Segment],
+    max_len: usize,
+    mut send: impl FnMut(Chunk<'_>) -> Result<(), E>,
+    mut progress: impl FnMut(Progress),
+) -> Result<(), E> {
+    let total = segments.iter().map(|segment| segment.data.len()).sum();
+    let mut written = 0;
+    for chunk in chunks(segments, max_len) {
+        send(chunk)?;
+        written += chunk.data.len();
+        progress(Progress {
+            address: chunk.address,
+            written,
+            total,
+        });
+    }
+    Ok(())
+}
+
+///downloads `segments` with a vendor OUT request `request` per chunk, wValue and wIndex holding
+///the low and high half of the chunk's address as most vendor loaders expect
+pub fn download_control(
+    device: &UsbDevice<'_>,
+    request: u8,
+    segments: &[Segment],
+    max_len: usize,
+    progress: impl FnMut(Progress),
+) -> Result<(), UsbError> {
+    download(
+        segments,
+        max_len.min(u16::MAX as usize),
+        |chunk| {
+            let request = DeviceRequest::new(
+                DeviceRequestType::TypeVendor,
+                request,
+                chunk.address as u16,
+                (chunk.address >> 16) as u16,
+                chunk.data.len() as u16,
+            );
+            device
+                .send_device_request_with_data(request, chunk.data)
+                .map(|_| ())
+        },
+        progress,
+    )
+}
+
+///streams `segments` to a bulk OUT pipe in max packet sized chunks, for loaders that take the
+///image as a plain byte stream
+pub fn download_bulk(
+    pipe: &HostPipe<'_>,
+    segments: &[Segment],
+    progress: impl FnMut(Progress),
+) -> Result<(), UsbError> {
+    let max_len = pipe.max_packet_size().unwrap_or(64) as usize;
+    download(
+        segments,
+        max_len,
+        |chunk| pipe.send_io_request(chunk.data).map(|_| ()),
+        progress,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FirmwareError {
+    Hex(HexError),
+    Usb(UsbError),
+    ///the RAM load request only carries 16 bit addresses
+    AddressOutOfRange(u32),
+    ///reading the firmware file failed
+    Io(std::io::ErrorKind),
+}
+
+impl From<HexError> for FirmwareError {
+    fn from(err: HexError) -> FirmwareError {
+        FirmwareError::Hex(err)
+    }
+}
+
+impl From<UsbError> for FirmwareError {
+    fn from(err: UsbError) -> FirmwareError {
+        FirmwareError::Usb(err)
+    }
+}
+
+impl core::fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FirmwareError::Hex(err) => write!(f, "invalid firmware image: {err}"),
+            FirmwareError::Usb(err) => write!(f, "firmware download failed: {err:?}"),
+            FirmwareError::AddressOutOfRange(address) => {
+                write!(
+                    f,
+                    "address {address:#x} is out of the 16 bit RAM load range"
+                )
+            }
+            FirmwareError::Io(kind) => write!(f, "failed to read the firmware file: {kind}"),
+        }
+    }
+}
+
+///Cypress EZ-USB parts, which differ in where their CPU control and status register lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EzUsbChip {
+    An21,
+    Fx,
+    Fx2,
+    Fx2Lp,
+}
+
+impl EzUsbChip {
+    ///the address of CPUCS, whose lowest bit holds the 8051 in reset
+    pub fn cpucs(self) -> u16 {
+        match self {
+            EzUsbChip::An21 | EzUsbChip::Fx => 0x7f92,
+            EzUsbChip::Fx2 | EzUsbChip::Fx2Lp => 0xe600,
+        }
+    }
+}
+
+///the vendor request the boot ROM of EZ-USB parts answers with reads and writes of internal RAM
+pub const RAM_LOAD_REQUEST: u8 = 0xa0;
+//the largest data stage sent per RAM write
+const RAM_LOAD_CHUNK: usize = 1024;
+
+///writes `data` to the device's RAM at `address` with the 0xA0 vendor request, one request per
+///1 KiB
+pub fn write_ram(device: &UsbDevice<'_>, address: u16, data: &[u8]) -> Result<(), UsbError> {
+    let mut address = address;
+    for chunk in data.chunks(RAM_LOAD_CHUNK) {
+        let request = DeviceRequest::new(
+            DeviceRequestType::TypeVendor,
+            RAM_LOAD_REQUEST,
+            address,
+            0,
+            chunk.len() as u16,
+        );
+        device.send_device_request_with_data(request, chunk)?;
+        address = address.wrapping_add(chunk.len() as u16);
+    }
+    Ok(())
+}
+
+///holds the 8051 in reset, or lets it run from what was loaded into RAM
+pub fn set_cpu_reset(device: &UsbDevice<'_>, chip: EzUsbChip, reset: bool) -> Result<(), UsbError> {
+    write_ram(device, chip.cpucs(), &[reset as u8])
+}
+
+///loads `segments` into the RAM of an EZ-USB device and starts the CPU on them, the usual
+///bring-up of blank EZ-USB devices; `progress` is called after every request
+///
+///the device usually disconnects and enumerates again as whatever the firmware makes of it, so
+///this handle is of no further use once this returns; only internal RAM is reachable this way,
+///external RAM needs a second stage loader
+pub fn load_ram(
+    device: &UsbDevice<'_>,
+    chip: EzUsbChip,
+    segments: &[Segment],
+    progress: impl FnMut(Progress),
+) -> Result<(), FirmwareError> {
+    for segment in segments {
+        let in_range = segment.end().map_or(false, |end| end <= 0x1_0000);
+        if !in_range {
+            return Err(FirmwareError::AddressOutOfRange(segment.address));
+        }
+    }
+    set_cpu_reset(device, chip, true)?;
+    download(
+        segments,
+        RAM_LOAD_CHUNK,
+        |chunk| write_ram(device, chunk.address as u16, chunk.data),
+        progress,
+    )?;
+    //the firmware can take the device off the bus before the request completes, which means it
+    //started rather than that the load failed
+    match set_cpu_reset(device, chip, false) {
+        Ok(())
+        | Err(UsbError::Terminated)
+        | Err(UsbError::IoReturn(
+            IoReturnError::NoDevice | IoReturnError::NotAttached | IoReturnError::Aborted,
+        )) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+///`load_ram` with the segments of an Intel HEX file
+pub fn load_ihex(
+    device: &UsbDevice<'_>,
+    chip: EzUsbChip,
+    hex: &str,
+    progress: impl FnMut(Progress),
+) -> Result<(), FirmwareError> {
+    let segments = ihex::parse(hex)?;
+    load_ram(device, chip, &segments, progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(segment: &Segment, max_len: usize) -> Vec<(u32, usize)> {
+        segment
+            .chunks(max_len)
+            .map(|chunk| (chunk.address, chunk.data.len()))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_stop_at_multiples_of_max_len() {
+        let segment = Segment {
+            address: 0x30,
+            data: vec![0; 0x90],
+        };
+        assert_eq!(
+            spans(&segment, 0x40),
+            vec![(0x30, 0x10), (0x40, 0x40), (0x80, 0x40)]
+        );
+    }
+
+    #[test]
+    fn aligned_chunks_are_max_len() {
+        let segment = Segment {
+            address: 0x100,
+            data: vec![0; 0x50],
+        };
+        assert_eq!(spans(&segment, 0x40), vec![(0x100, 0x40), (0x140, 0x10)]);
+    }
+
+    #[test]
+    fn chunks_of_an_empty_segment() {
+        assert_eq!(spans(&Segment::default(), 0x40), Vec::new());
+    }
+
+    #[test]
+    fn chunks_keep_the_data_in_order() {
+        let segment = Segment {
+            address: 0x3e,
+            data: (0..5).collect(),
+        };
+        let data: Vec<_> = segment
+            .chunks(0x40)
+            .flat_map(|chunk| chunk.data.iter().copied())
+            .collect();
+        assert_eq!(data, segment.data);
+        assert_eq!(
+            spans(&segment, 0),
+            vec![(0x3e, 1), (0x3f, 1), (0x40, 1), (0x41, 1), (0x42, 1)]
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use super::{FirmwareError, Segment};
+
+///a raw binary image, loaded as a single segment starting at `base`
+pub fn parse(image: &[u8], base: u32) -> Segment {
+    Segment {
+        address: base,
+        data: image.to_vec(),
+    }
+}
+
+///reads a raw binary image from `path`, see `parse`
+pub fn read(path: impl AsRef<std::path::Path>, base: u32) -> Result<Segment, FirmwareError> {
+    let image = std::fs::read(path).map_err(|err| FirmwareError::Io(err.kind()))?;
+    Ok(Segment {
+        address: base,
+        data: image,
+    })
+}
@@ -0,0 +1,213 @@
+use super::{FirmwareError, Segment};
+
+///a malformed Intel HEX file, lines counted from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HexError {
+    ///the line does not start with `:`
+    MissingStartCode(usize),
+    ///odd number of digits or a character that is not a hex digit
+    InvalidDigit(usize),
+    ///the byte count does not match the length of the line
+    LengthMismatch(usize),
+    ChecksumMismatch(usize),
+    ///record types 00 to 05 are defined
+    UnknownRecordType {
+        line: usize,
+        record_type: u8,
+    },
+    ///the file ends without an end of file record
+    MissingEndOfFile,
+}
+
+impl core::fmt::Display for HexError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HexError::MissingStartCode(line) => write!(f, "line {line} does not start with ':'"),
+            HexError::InvalidDigit(line) => write!(f, "line {line} has an invalid hex digit"),
+            HexError::LengthMismatch(line) => {
+                write!(f, "line {line} is not as long as its byte count says")
+            }
+            HexError::ChecksumMismatch(line) => write!(f, "line {line} has a bad checksum"),
+            HexError::UnknownRecordType { line, record_type } => {
+                write!(f, "line {line} has unknown record type {record_type:02x}")
+            }
+            HexError::MissingEndOfFile => write!(f, "missing end of file record"),
+        }
+    }
+}
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+fn decode_line(line: &str, number: usize) -> Result<Vec<u8>, HexError> {
+    let digits = line
+        .strip_prefix(':')
+        .ok_or(HexError::MissingStartCode(number))?;
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return Err(HexError::InvalidDigit(number));
+    }
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&digits[idx..idx + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| HexError::InvalidDigit(number))?;
+    //byte count, address, record type, data and checksum
+    match bytes.first() {
+        Some(count) if bytes.len() == *count as usize + 5 => {}
+        _ => return Err(HexError::LengthMismatch(number)),
+    }
+    if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+        return Err(HexError::ChecksumMismatch(number));
+    }
+    Ok(bytes)
+}
+
+///the data records of an Intel HEX file, records continuing where the previous one ended merged
+///into a single segment; start address records are ignored
+pub fn parse(text: &str) -> Result<Vec<Segment>, HexError> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut base = 0u32;
+    for (idx, line) in text.lines().enumerate() {
+        let number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = decode_line(line, number)?;
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            DATA => {
+                let address = base.wrapping_add(offset);
+                match segments.last_mut() {
+                    Some(last) if last.end() == Some(address) => last.data.extend_from_slice(data),
+                    _ => segments.push(Segment {
+                        address,
+                        data: data.to_vec(),
+                    }),
+                }
+            }
+            END_OF_FILE => return Ok(segments),
+            EXTENDED_SEGMENT_ADDRESS | EXTENDED_LINEAR_ADDRESS if data.len() == 2 => {
+                let value = u16::from_be_bytes([data[0], data[1]]) as u32;
+                base = if bytes[3] == EXTENDED_SEGMENT_ADDRESS {
+                    value << 4
+                } else {
+                    value << 16
+                };
+            }
+            EXTENDED_SEGMENT_ADDRESS | EXTENDED_LINEAR_ADDRESS => {
+                return Err(HexError::LengthMismatch(number))
+            }
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => {}
+            record_type => {
+                return Err(HexError::UnknownRecordType {
+                    line: number,
+                    record_type,
+                })
+            }
+        }
+    }
+    Err(HexError::MissingEndOfFile)
+}
+
+///reads and parses the Intel HEX file at `path`
+pub fn read(path: impl AsRef<std::path::Path>) -> Result<Vec<Segment>, FirmwareError> {
+    let text = std::fs::read_to_string(path).map_err(|err| FirmwareError::Io(err.kind()))?;
+    Ok(parse(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_contiguous_data_records() {
+        let hex = ":03010000010203F6\n:020103000405F1\n:0102000006F7\n:00000001FF\n";
+        assert_eq!(
+            parse(hex),
+            Ok(vec![
+                Segment {
+                    address: 0x0100,
+                    data: vec![1, 2, 3, 4, 5],
+                },
+                Segment {
+                    address: 0x0200,
+                    data: vec![6],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn extended_linear_address_sets_the_upper_half() {
+        let hex = ":020000040001F9\n:02001000AABB89\n:0400000500000100F6\n:00000001FF\n";
+        assert_eq!(
+            parse(hex),
+            Ok(vec![Segment {
+                address: 0x0001_0010,
+                data: vec![0xaa, 0xbb],
+            }])
+        );
+    }
+
+    #[test]
+    fn extended_segment_address_is_a_paragraph() {
+        let hex = ":020000021200EA\n:01000400CC2F\n:00000001FF\n";
+        assert_eq!(
+            parse(hex),
+            Ok(vec![Segment {
+                address: 0x0001_2004,
+                data: vec![0xcc],
+            }])
+        );
+    }
+
+    #[test]
+    fn extended_address_needs_two_bytes() {
+        assert_eq!(
+            parse(":0100000401FA\n:00000001FF\n"),
+            Err(HexError::LengthMismatch(1))
+        );
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        assert_eq!(
+            parse(":03010000010203F6\n:020103000405F2\n:00000001FF\n"),
+            Err(HexError::ChecksumMismatch(2))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_end_of_file() {
+        assert_eq!(
+            parse(":03010000010203F6\n"),
+            Err(HexError::MissingEndOfFile)
+        );
+        assert_eq!(parse(""), Err(HexError::MissingEndOfFile));
+    }
+
+    #[test]
+    fn ignores_records_after_end_of_file() {
+        assert_eq!(parse(":00000001FF\n:0102000006F7\n"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse("00000001FF"), Err(HexError::MissingStartCode(1)));
+        assert_eq!(parse(":0000001FF"), Err(HexError::InvalidDigit(1)));
+        assert_eq!(parse(":02010000010203F6"), Err(HexError::LengthMismatch(1)));
+        assert_eq!(
+            parse(":00000006FA"),
+            Err(HexError::UnknownRecordType {
+                line: 1,
+                record_type: 6,
+            })
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use crate::*;
+use atomic_waker::AtomicWaker;
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicU64, Ordering};
+use core::task::{Context, Poll};
+use iousbhost_sys::*;
+use std::sync::Arc;
+
+///drives a future for every element of a set concurrently, resolving with their outputs in order
+pub(crate) struct JoinAll<F: Future> {
+    futures: Vec<Option<Pin<Box<F>>>>,
+    outputs: Vec<Option<F::Output>>,
+}
+
+impl<F: Future> JoinAll<F> {
+    pub(crate) fn new(futures: impl IntoIterator<Item = F>) -> Self {
+        let futures: Vec<_> = futures.into_iter().map(|fut| Some(Box::pin(fut))).collect();
+        let outputs = futures.iter().map(|_| None).collect();
+        Self { futures, outputs }
+    }
+}
+
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut pending = false;
+        for (slot, output) in this.futures.iter_mut().zip(this.outputs.iter_mut()) {
+            if let Some(fut) = slot {
+                match fut.as_mut().poll(cx) {
+                    Poll::Ready(out) => {
+                        *output = Some(out);
+                        *slot = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(this.outputs.iter_mut().filter_map(Option::take).collect())
+        }
+    }
+}
+
+///the `IOUSBHostCompletionHandler` block, called with the request's IOReturn and how many bytes
+///were transferred
+pub(crate) type Callback = block::Block<(kern_return_t, u64), ()>;
+
+///what the completion handler of a request was called with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Completed {
+    pub(crate) status: kern_return_t,
+    pub(crate) bytes: u64,
+    ///mach absolute time the handler ran at
+    pub(crate) ticks: u64,
+}
+
+impl Completed {
+    ///the transferred length, or the IOReturn the request failed with
+    pub(crate) fn result(&self) -> Result<u64, UsbError> {
+        if self.status == 0 {
+            Ok(self.bytes)
+        } else {
+            Err(self.status.into())
+        }
+    }
+}
+
+///what a transfer's future and its completion handler share, handed over through atomics alone
+///so a completion never waits on the task polling it or the other way around
+#[derive(Default)]
+pub(crate) struct Completion {
+    finished: AtomicBool,
+    status: AtomicI32,
+    bytes: AtomicU64,
+    ticks: AtomicU64,
+    waker: AtomicWaker,
+    //wakes the task off the framework's queue when set
+    executor: Option<Arc<dyn Executor>>,
+    //the buffer of a data request, retained until both the future and the framework let go of
+    //the completion, so dropping the future while the request runs frees nothing in use
+    buffer: AtomicPtr<objc::runtime::Object>,
+    //finished by the handler, so the statistics see the request until the framework is done
+    //with it even if its future was dropped
+    recording: std::sync::Mutex<Option<Recording>>,
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        let buffer = *self.buffer.get_mut();
+        if !buffer.is_null() {
+            let _: () = unsafe { objc::msg_send![buffer, release] };
+        }
+    }
+}
+
+fn gen_callback(completion: Arc<Completion>) -> block::RcBlock<(kern_return_t, u64), ()> {
+    let handler = move |status: kern_return_t, bytes: u64| completion.finish(status, bytes);
+    block::ConcreteBlock::new(handler).copy()
+}
+
+impl Completion {
+    fn finish(self: &Arc<Self>, status: kern_return_t, bytes: u64) {
+        self.ticks
+            .store(unsafe { mach_absolute_time() }, Ordering::Relaxed);
+        self.status.store(status, Ordering::Relaxed);
+        self.bytes.store(bytes, Ordering::Relaxed);
+        if let Some(recording) = self.take_recording() {
+            recording.finish(if status == 0 {
+                Ok(bytes)
+            } else {
+                Err(status.into())
+            });
+        }
+        self.finished.store(true, Ordering::Release);
+        match &self.executor {
+            Some(executor) => {
+                let completion = self.clone();
+                executor.execute(Box::new(move || completion.waker.wake()));
+            }
+            None => self.waker.wake(),
+        }
+    }
+
+    //the setters are only called before the request is submitted, while the future holds the
+    //only reference
+    fn set_executor(self: &mut Arc<Self>, executor: Option<Arc<dyn Executor>>) {
+        if let Some(completion) = Arc::get_mut(self) {
+            completion.executor = executor;
+        }
+    }
+
+    fn set_recording(self: &mut Arc<Self>, recording: Recording) {
+        if let Some(completion) = Arc::get_mut(self) {
+            *completion
+                .recording
+                .get_mut()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(recording);
+        }
+    }
+
+    fn take_recording(&self) -> Option<Recording> {
+        self.recording
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+    }
+
+    fn hold(&self, data: NSMutableData) {
+        let _: *mut objc::runtime::Object = unsafe { objc::msg_send![data.0, retain] };
+        let previous = self.buffer.swap(data.0, Ordering::AcqRel);
+        if !previous.is_null() {
+            let _: () = unsafe { objc::msg_send![previous, release] };
+        }
+    }
+
+    fn completed(&self) -> Option<Completed> {
+        self.finished.load(Ordering::Acquire).then(|| Completed {
+            status: self.status.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            ticks: self.ticks.load(Ordering::Relaxed),
+        })
+    }
+
+    ///submits the request through `submit` on the first poll, afterwards only checks whether
+    ///the completion handler ran; the waker of every poll replaces the previous one, as the task
+    ///may have moved to another thread or executor since
+    ///
+    ///resolves with what the handler was called with, errors are the ones of the submission
+    fn poll(
+        self: &Arc<Self>,
+        submitted: &AtomicBool,
+        cx: &mut Context<'_>,
+        submit: impl FnOnce(*mut c_void) -> Option<UsbError>,
+    ) -> Poll<Result<Completed, UsbError>> {
+        if let Some(completed) = self.completed() {
+            return Poll::Ready(Ok(completed));
+        }
+        self.waker.register(cx.waker());
+        if submitted.swap(true, Ordering::Relaxed) {
+            //the handler may have run before the new waker was in place
+            if let Some(completed) = self.completed() {
+                return Poll::Ready(Ok(completed));
+            }
+            return Poll::Pending;
+        }
+        //the framework copies the block, so this reference can go once it was submitted
+        let handler = gen_callback(self.clone());
+        match submit(&*handler as *const Callback as *mut c_void) {
+            Some(err) => {
+                //counted as failed right away, the handler never runs
+                if let Some(recording) = self.take_recording() {
+                    recording.finish(Err(err));
+                }
+                Poll::Ready(Err(err))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+///a transfer future around a real `Completion` whose handler is called by the caller instead of
+///the framework, so the benches and tests can measure and exercise the handoff without a device
+#[doc(hidden)]
+#[derive(Default)]
+pub struct DetachedCompletion {
+    completion: Arc<Completion>,
+    submitted: AtomicBool,
+}
+
+impl DetachedCompletion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///what the framework would call once the request completed, it may be sent to any thread
+    pub fn handle(&self) -> CompletionHandle {
+        CompletionHandle(self.completion.clone())
+    }
+}
+
+impl Future for DetachedCompletion {
+    type Output = Result<u64, UsbError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.completion
+            .poll(&self.submitted, cx, |_| None)
+            .map(|completed| completed.and_then(|completed| completed.result()))
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct CompletionHandle(Arc<Completion>);
+
+impl CompletionHandle {
+    ///runs the completion handler with `status` and the transferred length
+    pub fn complete(&self, status: kern_return_t, bytes: u64) {
+        self.0.finish(status, bytes);
+    }
+}
+
+///used for handling async events which sends data
+pub(crate) struct AsyncDataHandler<
+    'a,
+    F: Fn(&'a T, NSMutableData, *mut c_void) -> Option<UsbError>,
+    T,
+> {
+    dev: &'a T,
+    data: NSMutableData,
+    cb_handler: F,
+    submitted: AtomicBool,
+    completion: Arc<Completion>,
+}
+
+impl<'a, T, F: Fn(&'a T, NSMutableData, *mut c_void) -> Option<UsbError>>
+    AsyncDataHandler<'a, F, T>
+{
+    pub(crate) fn new(dev: NonNull<T>, data: &[u8], cb_handler: F) -> Self {
+        Self::with_raw(dev, MutData::with_data(data).raw(), cb_handler)
+    }
+
+    ///sends `data` as is instead of a copy, for buffers such as `IoData`
+    pub(crate) fn with_raw(dev: NonNull<T>, data: NSMutableData, cb_handler: F) -> Self {
+        let dev = unsafe { dev.as_ref() };
+        Self {
+            dev,
+            cb_handler,
+            data,
+            submitted: AtomicBool::new(false),
+            completion: Arc::default(),
+        }
+    }
+
+    ///forwards the completion to `executor`, see `UsbDevice::set_completion_executor`
+    pub(crate) fn deliver_on(mut self, executor: Option<Arc<dyn Executor>>) -> Self {
+        self.completion.set_executor(executor);
+        self
+    }
+
+    ///finishes `recording` with what the completion handler reports
+    pub(crate) fn recorded(mut self, recording: Recording) -> Self {
+        self.completion.set_recording(recording);
+        self
+    }
+}
+
+impl<'a, T, F: Fn(&'a T, NSMutableData, *mut c_void) -> Option<UsbError>> Future
+    for AsyncDataHandler<'a, F, T>
+{
+    type Output = Result<Completed, UsbError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.completion.poll(&self.submitted, cx, |handler| {
+            self.completion.hold(self.data);
+            (self.cb_handler)(self.dev, self.data, handler)
+        })
+    }
+}
+
+///used for handling async events which does not send data
+pub(crate) struct AsyncHandler<'a, F: Fn(&'a T, *mut c_void) -> Option<UsbError>, T> {
+    dev: &'a T,
+    cb_handler: F,
+    submitted: AtomicBool,
+    completion: Arc<Completion>,
+}
+
+impl<'a, T, F: Fn(&'a T, *mut c_void) -> Option<UsbError>> AsyncHandler<'a, F, T> {
+    pub(crate) fn new(dev: NonNull<T>, cb_handler: F) -> Self {
+        let dev = unsafe { dev.as_ref() };
+        Self {
+            dev,
+            cb_handler,
+            submitted: AtomicBool::new(false),
+            completion: Arc::default(),
+        }
+    }
+
+    ///forwards the completion to `executor`, see `UsbDevice::set_completion_executor`
+    pub(crate) fn deliver_on(mut self, executor: Option<Arc<dyn Executor>>) -> Self {
+        self.completion.set_executor(executor);
+        self
+    }
+
+    ///finishes `recording` with what the completion handler reports
+    pub(crate) fn recorded(mut self, recording: Recording) -> Self {
+        self.completion.set_recording(recording);
+        self
+    }
+}
+
+impl<'a, T, F: Fn(&'a T, *mut c_void) -> Option<UsbError>> Future for AsyncHandler<'a, F, T> {
+    type Output = Result<Completed, UsbError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.completion.poll(&self.submitted, cx, |handler| {
+            (self.cb_handler)(self.dev, handler)
+        })
+    }
+}
+
+type Submit = Box<dyn FnOnce(*mut c_void) -> Option<UsbError>>;
+
+///requests collected to be submitted together, one after the other from a single hop onto the
+///queue of the pipe, so neither another request of this process nor a completion handler run on
+///the queue ends up between them
+pub(crate) struct Batch {
+    queue: Queue,
+    //retained while requests are on their way to the queue
+    target: id,
+    requests: core::cell::RefCell<Vec<(Arc<Completion>, Submit)>>,
+}
+
+impl Batch {
+    pub(crate) fn new(queue: Queue, target: id) -> Self {
+        Self {
+            queue,
+            target,
+            requests: core::cell::RefCell::new(Vec::new()),
+        }
+    }
+
+    ///a request submitted through `submit` with the next `flush`, `data` being its buffer
+    pub(crate) fn request(
+        &self,
+        data: NSMutableData,
+        submit: impl FnOnce(*mut c_void) -> Option<UsbError> + 'static,
+    ) -> Batched<'_> {
+        Batched {
+            batch: self,
+            data,
+            submit: Some(Box::new(submit)),
+            completion: Arc::default(),
+        }
+    }
+
+    ///polls `requests` and submits whatever they queued in one go
+    pub(crate) fn submitting<F: Future + Unpin>(&self, requests: F) -> Submitting<'_, F> {
+        Submitting {
+            batch: self,
+            requests,
+        }
+    }
+
+    fn flush(&self) {
+        let requests = core::mem::take(&mut *self.requests.borrow_mut());
+        if requests.is_empty() {
+            return;
+        }
+        let _: *mut objc::runtime::Object = unsafe { objc::msg_send![self.target, retain] };
+        let queued = Box::new(Queued {
+            target: self.target,
+            requests,
+            _queue: self.queue.clone(),
+        });
+        let queue = self.queue.inner.0 as dispatch::ffi::dispatch_queue_t;
+        if queue.is_null() {
+            queued.submit();
+            return;
+        }
+        unsafe {
+            dispatch::ffi::dispatch_async_f(
+                queue,
+                Box::into_raw(queued) as *mut c_void,
+                submit_queued,
+            )
+        };
+    }
+}
+
+struct Queued {
+    target: id,
+    requests: Vec<(Arc<Completion>, Submit)>,
+    //kept alive until the requests were handed to the framework
+    _queue: Queue,
+}
+
+impl Queued {
+    fn submit(self) {
+        for (completion, submit) in self.requests {
+            //the framework copies the block, so this reference can go once it was submitted
+            let handler = gen_callback(completion.clone());
+            if let Some(err) = submit(&*handler as *const Callback as *mut c_void) {
+                //the handler never runs, the request completes with the submission error
+                completion.finish(err.into(), 0);
+            }
+        }
+        let _: () = unsafe { objc::msg_send![self.target, release] };
+    }
+}
+
+extern "C" fn submit_queued(context: *mut c_void) {
+    let queued = unsafe { Box::from_raw(context as *mut Queued) };
+    queued.submit();
+}
+
+///a request of a `Batch`, queued on its first poll and resolving with what its completion
+///handler was called with
+pub(crate) struct Batched<'b> {
+    batch: &'b Batch,
+    data: NSMutableData,
+    submit: Option<Submit>,
+    completion: Arc<Completion>,
+}
+
+impl Batched<'_> {
+    ///forwards the completion to `executor`, see `UsbDevice::set_completion_executor`
+    pub(crate) fn deliver_on(mut self, executor: Option<Arc<dyn Executor>>) -> Self {
+        self.completion.set_executor(executor);
+        self
+    }
+
+    ///finishes `recording` with what the completion handler reports
+    pub(crate) fn recorded(mut self, recording: Recording) -> Self {
+        self.completion.set_recording(recording);
+        self
+    }
+}
+
+impl Future for Batched<'_> {
+    type Output = Result<Completed, UsbError>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(completed) = self.completion.completed() {
+            return Poll::Ready(Ok(completed));
+        }
+        self.completion.waker.register(cx.waker());
+        if let Some(submit) = self.submit.take() {
+            self.completion.hold(self.data);
+            let completion = self.completion.clone();
+            self.batch.requests.borrow_mut().push((completion, submit));
+            return Poll::Pending;
+        }
+        match self.completion.completed() {
+            Some(completed) => Poll::Ready(Ok(completed)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+///polls the requests of a `Batch`, submitting the ones queued by every poll together
+pub(crate) struct Submitting<'b, F> {
+    batch: &'b Batch,
+    requests: F,
+}
+
+impl<F: Future + Unpin> Future for Submitting<'_, F> {
+    type Output = F::Output;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let polled = Pin::new(&mut self.requests).poll(cx);
+        self.batch.flush();
+        polled
+    }
+}
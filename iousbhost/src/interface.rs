@@ -0,0 +1,543 @@
+use crate::*;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr;
+use core::ptr::NonNull;
+use iousbhost_sys::*;
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub struct HostInterface<'a> {
+    pub(crate) inner: NonNull<IOUSBHostInterface>,
+    pub(crate) shared: Rc<SharedObject>,
+    lt: PhantomData<&'a ()>,
+}
+
+impl Clone for HostInterface<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner,
+            shared: self.shared.clone(),
+            lt: PhantomData,
+        }
+    }
+}
+
+impl<'a> HostInterface<'a> {
+    ///takes over an interface of `device` opened outside of the crate, which is destroyed along
+    ///with the last handle to it; the interface keeps the device alive like the ones
+    ///`UsbDevice::interfaces` opens
+    ///
+    ///# Safety
+    ///`raw` has to be an initialized `IOUSBHostInterface` of `device` that nothing else destroys
+    ///or releases
+    pub unsafe fn from_raw(raw: NonNull<IOUSBHostInterface>, device: &UsbDevice<'a>) -> Self {
+        let queue = Queue::new(raw.as_ref().queue());
+        Self::from_object(raw, queue, device.shared.clone())
+    }
+}
+
+impl HostInterface<'_> {
+    pub(crate) fn new(
+        ptr: *const IOUSBHostInterface,
+        queue: Queue,
+        device: Rc<SharedObject>,
+    ) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBHostInterface)?;
+        Some(Self::from_object(ptr, queue, device))
+    }
+
+    fn from_object(
+        ptr: NonNull<IOUSBHostInterface>,
+        queue: Queue,
+        device: Rc<SharedObject>,
+    ) -> Self {
+        let shared = SharedObject::new(
+            IOUSBHostObject(unsafe { ptr.as_ref() }.0),
+            queue,
+            device.main_port,
+            Some(device.clone()),
+            device.pipe_stats.clone(),
+            device.request_stats.clone(),
+        );
+        let interface = HostInterface {
+            inner: ptr,
+            shared,
+            lt: PhantomData,
+        };
+        interface.apply_idle_timeout();
+        interface
+    }
+
+    ///the framework object behind this handle, for calling selectors the crate does not wrap
+    ///
+    ///the pointer is borrowed, it stays valid while some handle to the interface is alive and must
+    ///not be released or destroyed through it
+    pub fn as_raw(&self) -> NonNull<IOUSBHostInterface> {
+        self.inner
+    }
+
+    //the idle timeout the device profile asks for, set on every interface opened
+    fn apply_idle_timeout(&self) {
+        let Some(number) = self
+            .interface_descriptor()
+            .map(|desc| desc.interface_number())
+        else {
+            return;
+        };
+        let timeout = self
+            .shared
+            .with_profile(|profile| profile.and_then(|profile| profile.idle_timeout_for(number)));
+        if let Some(timeout) = timeout {
+            if let Err(err) = self.set_idle_timeout(timeout.as_secs_f64()) {
+                crate::diag!(
+                    warn,
+                    "failed to set the profile's idle timeout on interface {number}: {err:?}"
+                );
+            }
+        }
+    }
+
+    pub fn idle_timeout(&self) -> f64 {
+        unsafe { self.inner.as_ref().idleTimeout() }
+    }
+
+    pub fn set_idle_timeout(&self, interval: f64) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if unsafe {
+            !self
+                .inner
+                .as_ref()
+                .setIdleTimeout_error_(interval, &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn configuration_descriptor(&self) -> Option<ConfigurationDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().configurationDescriptor() };
+        ConfigurationDescriptor::new(ptr)
+    }
+
+    pub fn interface_descriptor(&self) -> Option<InterfaceDescriptor<'_>> {
+        let ptr = unsafe { self.inner.as_ref().interfaceDescriptor() };
+        InterfaceDescriptor::new(ptr)
+    }
+
+    ///the interface's string descriptor (`iInterface`), `None` if the interface has no name
+    pub fn name(&self) -> Result<Option<String>, UsbError> {
+        let index = match self.interface_descriptor() {
+            Some(descriptor) => descriptor.interface(),
+            None => return Ok(None),
+        };
+        if index == 0 {
+            return Ok(None);
+        }
+
+        let mut err = NSErr::new();
+        let name = unsafe {
+            self.shared
+                .object
+                .stringWithIndex_error_(index as u64, &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(Some(string_from_ns(&name)))
+        }
+    }
+
+    pub fn create_matching_dictionary<const N: usize>(
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        bcd_device: Option<u16>,
+        interface_number: Option<u8>,
+        configuration_value: Option<u8>,
+        interface_class: Option<u8>,
+        interface_subclass: Option<u8>,
+        interface_protocol: Option<u8>,
+        speed: Option<u16>, /*product_ids: Option<[u16; N]>*/
+    ) -> Result<CFMutableDictionaryRef, UsbError> {
+        let vendor_id = NSNum::try_from(vendor_id)?;
+        let product_id = NSNum::try_from(product_id)?;
+        let bcd_device = NSNum::try_from(bcd_device)?;
+        let interface_number = NSNum::try_from(interface_number)?;
+        let configuration_value = NSNum::try_from(configuration_value)?;
+        let interface_class = NSNum::try_from(interface_class)?;
+        let interface_subclass = NSNum::try_from(interface_subclass)?;
+        let interface_protocol = NSNum::try_from(interface_protocol)?;
+        let speed = NSNum::try_from(speed)?;
+
+        let dict = unsafe {
+            IOUSBHostInterface::createMatchingDictionaryWithVendorID_productID_bcdDevice_interfaceNumber_configurationValue_interfaceClass_interfaceSubclass_interfaceProtocol_speed_productIDArray_(
+            vendor_id.into(),
+            product_id.into(),
+            bcd_device.into(),
+            interface_number.into(),
+            configuration_value.into(),
+            interface_class.into(),
+            interface_subclass.into(),
+            interface_protocol.into(),
+            speed.into(),
+            NSArray(ptr::null_mut()),
+        )
+        };
+
+        if dict.is_null() {
+            return Err(IoReturnError::NoMemory.into());
+        }
+        Ok(dict)
+    }
+
+    pub fn endpoint_descriptors(&self) -> Option<impl Iterator<Item = EndpointDescriptor<'_>>> {
+        let config_descriptor = unsafe { self.configuration_descriptor()?.inner.as_ref() };
+        let interface_descriptor = unsafe { self.interface_descriptor()?.inner.as_ref() };
+        let current_descriptor = ptr::null();
+        Some(EndpointDescriptors {
+            config_descriptor,
+            interface_descriptor,
+            current_descriptor,
+            lt: PhantomData,
+        })
+    }
+
+    ///finds the SuperSpeed and SuperSpeedPlus isochronous companions which follow `endpoint`,
+    ///either will be `None` when the device does not provide it
+    pub fn companion_descriptors(
+        &self,
+        endpoint: &EndpointDescriptor<'_>,
+    ) -> (
+        Option<SuperSpeedCompanionDescriptor<'_>>,
+        Option<SuperSpeedPlusCompanionDescriptor<'_>>,
+    ) {
+        let config_descriptor = match self.configuration_descriptor() {
+            Some(desc) => desc.inner.as_ptr() as *const IOUSBConfigurationDescriptor,
+            None => return (None, None),
+        };
+        let endpoint_descriptor = endpoint.inner.as_ptr() as *const IOUSBDescriptorHeader;
+        let super_speed = unsafe {
+            IOUSBGetNextAssociatedDescriptorWithType(
+                config_descriptor,
+                endpoint_descriptor,
+                ptr::null(),
+                DescriptorType::SuperSpeedEndpointCompanion.into(),
+            )
+        };
+        let super_speed_plus = unsafe {
+            IOUSBGetNextAssociatedDescriptorWithType(
+                config_descriptor,
+                endpoint_descriptor,
+                ptr::null(),
+                DescriptorType::SuperSpeedPlusIsochronousEndpointCompanion.into(),
+            )
+        };
+        (
+            SuperSpeedCompanionDescriptor::new(
+                super_speed as *const IOUSBSuperSpeedEndpointCompanionDescriptor,
+            ),
+            SuperSpeedPlusCompanionDescriptor::new(
+                super_speed_plus
+                    as *const IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor,
+            ),
+        )
+    }
+
+    pub fn pipes(&self) -> Option<impl Iterator<Item = HostPipe<'_>>> {
+        let config_descriptor = unsafe { self.configuration_descriptor()?.inner.as_ref() };
+        let interface_descriptor = unsafe { self.interface_descriptor()?.inner.as_ref() };
+        let current_descriptor = ptr::null();
+        Some(Pipes {
+            config_descriptor,
+            interface_descriptor,
+            current_descriptor,
+            interface: &self,
+        })
+    }
+
+    pub fn select_alternate_setting(&self, alternate_setting: u8) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .selectAlternateSetting_error_(alternate_setting as u64, &mut *err)
+        } {
+            return Err(err.into());
+        } else {
+            Ok(())
+        }
+    }
+
+    ///opens the pipe of `endpoint`, failing with `UsbError::EndpointUnavailable` if the endpoint
+    ///belongs to an alternate setting other than the selected one
+    pub fn open_pipe(&self, endpoint: &EndpointDescriptor<'_>) -> Result<HostPipe<'_>, UsbError> {
+        let address = endpoint.endpoint_address();
+        let selected = self.endpoint_descriptors().map_or(false, |mut endpoints| {
+            endpoints.any(|selected| {
+                selected.endpoint_address() == address
+                    && selected.attributes() == endpoint.attributes()
+                    && selected.max_packet_size() == endpoint.max_packet_size()
+            })
+        });
+        if !selected {
+            return Err(UsbError::EndpointUnavailable(address));
+        }
+        self.copy_pipe(address)
+    }
+
+    ///whether `copy_pipe` may open an endpoint that already has an open pipe, off by default
+    ///
+    ///aliased pipes share the endpoint, so a transfer, stall or abort on one shows on the others
+    pub fn set_allow_pipe_aliases(&self, allow: bool) {
+        self.shared.allow_pipe_aliases.set(allow);
+    }
+
+    pub fn allow_pipe_aliases(&self) -> bool {
+        self.shared.allow_pipe_aliases.get()
+    }
+
+    ///whether a pipe of this interface, or of a clone of it, is open on `address`
+    pub fn is_claimed(&self, address: EndpointAddress) -> bool {
+        self.shared.open_pipes(address.0) > 0
+    }
+
+    ///fails with `UsbError::EndpointClaimed` if the endpoint already has an open pipe, unless
+    ///aliases are allowed through `set_allow_pipe_aliases`
+    pub fn copy_pipe(&self, address: EndpointAddress) -> Result<HostPipe<'_>, UsbError> {
+        if self.is_claimed(address) && !self.allow_pipe_aliases() {
+            return Err(UsbError::EndpointClaimed(address));
+        }
+        let mut err = NSErr::new();
+        let pipe = unsafe {
+            self.inner
+                .as_ref()
+                .copyPipeWithAddress_error_(address.into(), &mut *err)
+        };
+
+        if err.is_err() {
+            return Err(err.into());
+        }
+        if pipe.0.is_null() {
+            return Err(UsbError::EndpointUnavailable(address));
+        }
+        //the copy is owned by the handle, which releases it on drop
+        HostPipe::new(pipe.0 as *const IOUSBHostPipe, self.shared.clone(), address)
+            .ok_or(UsbError::InvalidObject)
+    }
+}
+
+pub struct Pipes<'a> {
+    interface: &'a HostInterface<'a>,
+    config_descriptor: *const IOUSBConfigurationDescriptor,
+    interface_descriptor: *const IOUSBInterfaceDescriptor,
+    current_descriptor: *const IOUSBDescriptorHeader,
+}
+
+impl<'a> Iterator for Pipes<'a> {
+    type Item = HostPipe<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextEndpointDescriptor(
+                self.config_descriptor,
+                self.interface_descriptor,
+                self.current_descriptor,
+            )
+        };
+        if next.is_null() {
+            return None;
+        }
+        self.current_descriptor = next as *const IOUSBDescriptorHeader;
+
+        match self
+            .interface
+            .copy_pipe(EndpointAddress(unsafe { (*next).bEndpointAddress }))
+        {
+            Ok(pipe) => Some(pipe),
+            Err(e) => {
+                crate::diag!(warn, "err while enumerating pipes: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+///how `UsbDevice::interfaces` opens each interface, `None` leaving it closed; implemented by
+///`HostObjectInitOptions` for the same options everywhere and by closures deciding per interface,
+///so a single vendor interface can be claimed while the kernel keeps the others
+pub trait InterfaceOptions {
+    fn options_for(&self, interface: &InterfaceDescriptor<'_>) -> Option<HostObjectInitOptions>;
+}
+
+impl InterfaceOptions for HostObjectInitOptions {
+    fn options_for(&self, _: &InterfaceDescriptor<'_>) -> Option<HostObjectInitOptions> {
+        Some(*self)
+    }
+}
+
+impl<F> InterfaceOptions for F
+where
+    F: Fn(&InterfaceDescriptor<'_>) -> Option<HostObjectInitOptions>,
+{
+    fn options_for(&self, interface: &InterfaceDescriptor<'_>) -> Option<HostObjectInitOptions> {
+        self(interface)
+    }
+}
+
+pub struct Interfaces<'a> {
+    pub(crate) device: Rc<SharedObject>,
+    pub(crate) config_descriptor: *const IOUSBConfigurationDescriptor,
+    pub(crate) current_descriptor: *const IOUSBDescriptorHeader,
+    pub(crate) options: Box<dyn InterfaceOptions + 'a>,
+    pub(crate) queue: Queue,
+    //the interfaces the device profile keeps open
+    pub(crate) held: Vec<HostInterface<'a>>,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Interfaces<'a> {
+    fn open(
+        &self,
+        next: *const IOUSBInterfaceDescriptor,
+        options: HostObjectInitOptions,
+    ) -> Result<HostInterface<'a>, UsbError> {
+        let number = unsafe { (*next).bInterfaceNumber };
+        let held = self.held.iter().find(|interface| {
+            interface
+                .interface_descriptor()
+                .is_some_and(|desc| desc.interface_number() == number)
+        });
+        if let Some(interface) = held {
+            return Ok(interface.clone());
+        }
+        let configuration = unsafe { (*self.config_descriptor).bConfigurationValue };
+        let service = self.service(number, configuration)?;
+        let res = self.open_service(service, options);
+        unsafe { IOObjectRelease(service) };
+        res
+    }
+
+    //the interface the device published for `number` in `configuration`, looked up among the
+    //device's own registry children so an interface of another device is never picked up; the
+    //caller releases it
+    fn service(&self, number: u8, configuration: u8) -> Result<io_service_t, UsbError> {
+        let device = unsafe { self.device.object.ioService() };
+        let mut iterator = 0;
+        let err = unsafe {
+            IORegistryEntryGetChildIterator(device, c"IOService".as_ptr(), &mut iterator)
+        };
+        if err != 0 {
+            return Err(err.into());
+        }
+        let mut found = None;
+        loop {
+            let service = unsafe { IOIteratorNext(iterator) };
+            if service == 0 {
+                break;
+            }
+            let io_service = IoService::from_raw(service);
+            if io_service.number_property(c"bInterfaceNumber") == Some(number.into())
+                && io_service.number_property(c"bConfigurationValue") == Some(configuration.into())
+            {
+                found = Some(service);
+                break;
+            }
+            unsafe { IOObjectRelease(service) };
+        }
+        unsafe { IOObjectRelease(iterator) };
+        found.ok_or(IoReturnError::NotAttached.into())
+    }
+
+    fn open_service(
+        &self,
+        service: io_service_t,
+        options: HostObjectInitOptions,
+    ) -> Result<HostInterface<'a>, UsbError> {
+        OpenCandidate::new(OpenTarget::Interface, service, None, options).check()?;
+
+        let mut err = NSErr::new();
+        let interface = IOUSBHostInterface::alloc();
+        let interface = unsafe {
+            IIOUSBHostInterface::initWithIOService_options_queue_error_interestHandler_(
+                &interface,
+                service,
+                options.into(),
+                self.queue.inner,
+                &mut *err,
+                0 as *mut c_void,
+            )
+        };
+        if err.is_err() {
+            return Err(err.into());
+        }
+        HostInterface::new(
+            interface as *const IOUSBHostInterface,
+            self.queue.clone(),
+            self.device.clone(),
+        )
+        .ok_or(UsbError::InvalidObject)
+    }
+}
+
+///an interface `UsbDevice::interfaces` could not open, usually because a kernel driver such as
+///the HID driver owns it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterfaceOpenError {
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub error: UsbError,
+}
+
+impl From<InterfaceOpenError> for UsbError {
+    fn from(err: InterfaceOpenError) -> UsbError {
+        err.error
+    }
+}
+
+impl core::fmt::Display for InterfaceOpenError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "failed to open interface {} alt {}: {:?}",
+            self.interface_number, self.alternate_setting, self.error
+        )
+    }
+}
+
+impl<'a> Iterator for Interfaces<'a> {
+    type Item = Result<HostInterface<'a>, InterfaceOpenError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = unsafe {
+            IOUSBGetNextInterfaceDescriptor(self.config_descriptor, self.current_descriptor)
+        };
+
+        if next.is_null() {
+            return None;
+        }
+        //a failed interface is reported and the walk goes on with the next one
+        self.current_descriptor = next as *const IOUSBDescriptorHeader;
+
+        let options = InterfaceDescriptor::new(next)
+            .and_then(|descriptor| self.options.options_for(&descriptor));
+        let Some(options) = options else {
+            return self.next();
+        };
+
+        Some(self.open(next, options).map_err(|error| {
+            let (interface_number, alternate_setting) =
+                unsafe { ((*next).bInterfaceNumber, (*next).bAlternateSetting) };
+            crate::diag!(
+                debug,
+                "failed to open interface {} alt {}: {:?}",
+                interface_number,
+                alternate_setting,
+                error
+            );
+            InterfaceOpenError {
+                interface_number,
+                alternate_setting,
+                error,
+            }
+        }))
+    }
+}
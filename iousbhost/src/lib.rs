@@ -1,15 +1,73 @@
-#![feature(type_alias_impl_trait)]
+//malformed devices should surface as `Err`/`None`, never as a panic
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic, clippy::todo)]
 use core::ffi::c_void;
-use core::future::Future;
-use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
-use core::pin::Pin;
 use core::ptr;
-use core::ptr::NonNull;
-use core::task::{Context, Poll, Waker};
 use iousbhost_sys::*;
-
-#[derive(Debug)]
+use std::rc::Rc;
+use std::sync::Arc;
+
+///emits a diagnostic through `tracing` when the `tracing` feature is enabled, otherwise through `log`
+macro_rules! diag {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::$level!($($arg)+);
+    }};
+}
+pub(crate) use diag;
+
+#[cfg(feature = "async")]
+pub mod blocking;
+#[cfg(feature = "ci")]
+pub mod ci;
+#[cfg(feature = "class")]
+pub mod class;
+pub mod descriptors;
+pub mod device;
+pub mod driver;
+#[cfg(feature = "async")]
+pub mod executor;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod firmware;
+#[cfg(feature = "async")]
+mod future;
+pub mod interface;
+pub mod pipe;
+pub mod policy;
+///the types most programs need, `use iousbhost::prelude::*;` brings them in without the rest of
+///the crate root
+pub mod prelude;
+pub mod snapshot;
+pub mod watch;
+
+#[cfg(feature = "ci")]
+pub use ci::*;
+#[cfg(feature = "class")]
+pub use class::*;
+pub use descriptors::*;
+pub use device::*;
+pub use driver::*;
+#[cfg(feature = "async")]
+pub use executor::*;
+#[cfg(feature = "fault-injection")]
+pub use fault::*;
+#[cfg(feature = "async")]
+#[doc(hidden)]
+pub use future::{CompletionHandle, DetachedCompletion};
+pub use interface::*;
+pub use pipe::*;
+pub use policy::*;
+pub use snapshot::*;
+pub use watch::*;
+
+///the raw framework bindings, for what the safe wrappers do not cover, see `UsbDevice::as_raw`
+pub use iousbhost_sys as sys;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
 pub enum UsbError {
     InvalidAddress = 1,
     ProtectionFailure = 2,
@@ -61,11 +119,104 @@ pub enum UsbError {
     NotWaiting = 48,
     OperationTimedOut = 49,
     Unknown,
+    ///rejected by request validation before reaching the device
+    InvalidRequest(RequestError),
+    ///the device did not provide a device descriptor
+    NoDeviceDescriptor,
+    ///the device has no configuration, or none at the requested index or value
+    NoConfiguration,
+    ///the endpoint is not part of the interface's selected alternate setting
+    EndpointUnavailable(EndpointAddress),
+    ///the endpoint already has an open pipe, see `HostInterface::set_allow_pipe_aliases`
+    EndpointClaimed(EndpointAddress),
+    ///the open policy refused to open the device or interface, see `set_open_policy`
+    Vetoed,
+    ///the descriptors a pipe was to be adjusted to were rejected before reaching the framework
+    InvalidAdjust(AdjustError),
+    ///an IOKit error such as kIOReturnExclusiveAccess, which does not fit a kern_return_t variant
+    IoReturn(IoReturnError),
+    ///the controller can not take the command in its current state
+    #[cfg(feature = "ci")]
+    IllegalTransition(ControllerState, MessageType),
+    ///an EndpointCreate command was rejected for its endpoint descriptor
+    #[cfg(feature = "ci")]
+    InvalidEndpointDescriptor(EndpointDescriptorError),
+    ///a controller was to be created with capabilities that do not pass
+    ///`ControllerCapabilities::validate`
+    #[cfg(feature = "ci")]
+    InvalidCapabilities(CapabilitiesError),
 }
 
 impl From<UsbError> for kern_return_t {
-    fn from(_err: UsbError) -> i32 {
-        todo!()
+    fn from(err: UsbError) -> kern_return_t {
+        use UsbError as E;
+        let code = match err {
+            E::InvalidAddress => KERN_INVALID_ADDRESS,
+            E::ProtectionFailure => KERN_PROTECTION_FAILURE,
+            E::NoSpace => KERN_NO_SPACE,
+            E::InvalidArgument => KERN_INVALID_ARGUMENT,
+            E::Failure => KERN_FAILURE,
+            E::ResourceShortage => KERN_RESOURCE_SHORTAGE,
+            E::NotReceiver => KERN_NOT_RECEIVER,
+            E::NoAccess => KERN_NO_ACCESS,
+            E::MemoryFailure => KERN_MEMORY_FAILURE,
+            E::MemoryError => KERN_MEMORY_ERROR,
+            E::AlreadyInSet => KERN_ALREADY_IN_SET,
+            E::NotInSet => KERN_NOT_IN_SET,
+            E::NameExists => KERN_NAME_EXISTS,
+            E::Aborted => KERN_ABORTED,
+            E::InvalidName => KERN_INVALID_NAME,
+            E::InvalidTask => KERN_INVALID_TASK,
+            E::InvalidRight => KERN_INVALID_RIGHT,
+            E::InvalidValue => KERN_INVALID_VALUE,
+            E::UrefsOverflow => KERN_UREFS_OVERFLOW,
+            E::InvalidCapability => KERN_INVALID_CAPABILITY,
+            E::RightExists => KERN_RIGHT_EXISTS,
+            E::InvalidHost => KERN_INVALID_HOST,
+            E::MemoryPresent => KERN_MEMORY_PRESENT,
+            E::MemoryDataMoved => KERN_MEMORY_DATA_MOVED,
+            E::MemoryRestartCopy => KERN_MEMORY_RESTART_COPY,
+            E::InvalidProcessorSet => KERN_INVALID_PROCESSOR_SET,
+            E::PolicyLimit => KERN_POLICY_LIMIT,
+            E::InvalidPolicy => KERN_INVALID_POLICY,
+            E::InvalidObject => KERN_INVALID_OBJECT,
+            E::AlreadyWaiting => KERN_ALREADY_WAITING,
+            E::DefaultSet => KERN_DEFAULT_SET,
+            E::ExceptionProtected => KERN_EXCEPTION_PROTECTED,
+            E::InvalidLedger => KERN_INVALID_LEDGER,
+            E::InvalidMemoryControl => KERN_INVALID_MEMORY_CONTROL,
+            E::InvalidSecurity => KERN_INVALID_SECURITY,
+            E::NotDepressed => KERN_NOT_DEPRESSED,
+            E::Terminated => KERN_TERMINATED,
+            E::LockSetDestroyed => KERN_LOCK_SET_DESTROYED,
+            E::LockUnstable => KERN_LOCK_UNSTABLE,
+            E::LockOwned => KERN_LOCK_OWNED,
+            E::LockOwnedSelf => KERN_LOCK_OWNED_SELF,
+            E::SemaphoreDestroyed => KERN_SEMAPHORE_DESTROYED,
+            E::RpcServerTerminated => KERN_RPC_SERVER_TERMINATED,
+            E::RpcTerminateOrphan => KERN_RPC_TERMINATE_ORPHAN,
+            E::RpcContinueOrphan => KERN_RPC_CONTINUE_ORPHAN,
+            E::NotSupported => KERN_NOT_SUPPORTED,
+            E::NodeDown => KERN_NODE_DOWN,
+            E::NotWaiting => KERN_NOT_WAITING,
+            E::OperationTimedOut => KERN_OPERATION_TIMED_OUT,
+            E::Unknown => KERN_FAILURE,
+            E::InvalidRequest(_) => KERN_INVALID_ARGUMENT,
+            E::NoDeviceDescriptor => KERN_INVALID_OBJECT,
+            E::NoConfiguration => KERN_INVALID_OBJECT,
+            E::EndpointUnavailable(_) => KERN_INVALID_ARGUMENT,
+            E::EndpointClaimed(_) => KERN_NO_ACCESS,
+            E::Vetoed => KERN_NO_ACCESS,
+            E::InvalidAdjust(_) => KERN_INVALID_ARGUMENT,
+            E::IoReturn(err) => err.code(),
+            #[cfg(feature = "ci")]
+            E::IllegalTransition(..) => KERN_INVALID_ARGUMENT,
+            #[cfg(feature = "ci")]
+            E::InvalidEndpointDescriptor(_) => KERN_INVALID_ARGUMENT,
+            #[cfg(feature = "ci")]
+            E::InvalidCapabilities(_) => KERN_INVALID_ARGUMENT,
+        };
+        code as kern_return_t
     }
 }
 
@@ -122,4180 +273,1268 @@ impl From<kern_return_t> for UsbError {
             KERN_NODE_DOWN => E::NodeDown,
             KERN_NOT_WAITING => E::NotWaiting,
             KERN_OPERATION_TIMED_OUT => E::OperationTimedOut,
-            _ => E::Unknown,
-        }
-    }
+            _ => IoReturnError::from_code(err).map_or(E::Unknown, E::from),
+        }
+    }
+}
+
+///the IOReturn codes of the IOKit error space (`err_system(0x38)`) requests commonly fail with,
+///which overlap none of the kern_return_t codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoReturnError {
+    NoMemory,
+    NoResources,
+    ///the device is gone
+    NoDevice,
+    NotPrivileged,
+    BadArgument,
+    ///another client, often a kernel driver, has the device or interface open exclusively
+    ExclusiveAccess,
+    Unsupported,
+    IoError,
+    NotOpen,
+    Busy,
+    Timeout,
+    Offline,
+    NotReady,
+    NotAttached,
+    NotPermitted,
+    NoPower,
+    ///fewer bytes than requested arrived
+    Underrun,
+    ///more bytes than requested arrived
+    Overrun,
+    DeviceError,
+    Aborted,
+    NoBandwidth,
+    ///no handshake from the device
+    NotResponding,
+    ///kIOUSBPipeStalled, the endpoint answered with a STALL
+    Stalled,
+    ///an IOKit code without a variant of its own
+    Other(u32),
 }
 
-pub struct UsbDevice<'a> {
-    inner: NonNull<IOUSBHostDevice>,
-    lt: PhantomData<&'a ()>,
-}
+impl IoReturnError {
+    const SYSTEM_MASK: u32 = 0xfc00_0000;
+    const SYSTEM_IO: u32 = 0xe000_0000;
 
-impl Drop for UsbDevice<'_> {
-    fn drop(&mut self) {
-        unsafe { self.inner.as_ref().destroy() }
+    ///`None` for codes outside of the IOKit error space, such as kern_return_t codes
+    pub fn from_code(code: kern_return_t) -> Option<Self> {
+        use IoReturnError as E;
+        let code = code as u32;
+        if code & Self::SYSTEM_MASK != Self::SYSTEM_IO {
+            return None;
+        }
+        Some(match code {
+            0xe000_02bd => E::NoMemory,
+            0xe000_02be => E::NoResources,
+            0xe000_02c0 => E::NoDevice,
+            0xe000_02c1 => E::NotPrivileged,
+            0xe000_02c2 => E::BadArgument,
+            0xe000_02c5 => E::ExclusiveAccess,
+            0xe000_02c7 => E::Unsupported,
+            0xe000_02ca => E::IoError,
+            0xe000_02cd => E::NotOpen,
+            0xe000_02d5 => E::Busy,
+            0xe000_02d6 => E::Timeout,
+            0xe000_02d7 => E::Offline,
+            0xe000_02d8 => E::NotReady,
+            0xe000_02d9 => E::NotAttached,
+            0xe000_02e2 => E::NotPermitted,
+            0xe000_02e3 => E::NoPower,
+            0xe000_02e7 => E::Underrun,
+            0xe000_02e8 => E::Overrun,
+            0xe000_02e9 => E::DeviceError,
+            0xe000_02eb => E::Aborted,
+            0xe000_02ec => E::NoBandwidth,
+            0xe000_02ed => E::NotResponding,
+            0xe000_404f => E::Stalled,
+            other => E::Other(other),
+        })
     }
-}
 
-#[derive(Default, Clone, Copy)]
-pub enum HostObjectInitOptions {
-    #[default]
-    None = 0,
-    DeviceCapture = 1,
-}
+    pub fn code(&self) -> u32 {
+        use IoReturnError as E;
+        match self {
+            E::NoMemory => 0xe000_02bd,
+            E::NoResources => 0xe000_02be,
+            E::NoDevice => 0xe000_02c0,
+            E::NotPrivileged => 0xe000_02c1,
+            E::BadArgument => 0xe000_02c2,
+            E::ExclusiveAccess => 0xe000_02c5,
+            E::Unsupported => 0xe000_02c7,
+            E::IoError => 0xe000_02ca,
+            E::NotOpen => 0xe000_02cd,
+            E::Busy => 0xe000_02d5,
+            E::Timeout => 0xe000_02d6,
+            E::Offline => 0xe000_02d7,
+            E::NotReady => 0xe000_02d8,
+            E::NotAttached => 0xe000_02d9,
+            E::NotPermitted => 0xe000_02e2,
+            E::NoPower => 0xe000_02e3,
+            E::Underrun => 0xe000_02e7,
+            E::Overrun => 0xe000_02e8,
+            E::DeviceError => 0xe000_02e9,
+            E::Aborted => 0xe000_02eb,
+            E::NoBandwidth => 0xe000_02ec,
+            E::NotResponding => 0xe000_02ed,
+            E::Stalled => 0xe000_404f,
+            E::Other(code) => *code,
+        }
+    }
+}
+
+///timeouts and aborts become `OperationTimedOut` and `Aborted`, as the crate's own timeouts and
+///cancellations do, everything else is kept as `UsbError::IoReturn`
+impl From<IoReturnError> for UsbError {
+    fn from(err: IoReturnError) -> UsbError {
+        match err {
+            IoReturnError::Timeout => UsbError::OperationTimedOut,
+            IoReturnError::Aborted => UsbError::Aborted,
+            err => UsbError::IoReturn(err),
+        }
+    }
+}
+
+impl core::fmt::Display for IoReturnError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            IoReturnError::Stalled => write!(f, "kIOUSBPipeStalled"),
+            IoReturnError::Other(code) => write!(f, "IOReturn {code:#010x}"),
+            err => write!(f, "kIOReturn{:?}", err),
+        }
+    }
+}
+
+///a host object together with the queue its callbacks run on, shared by the handle that opened it
+///and everything derived from it so it is only destroyed once the last of them is gone
+///
+///it is only ever touched by the thread owning the handles, hence the `Rc` and cells; what runs on
+///the queue gets state of its own, such as the `destroyed` flag
+struct SharedObject {
+    object: IOUSBHostObject,
+    _queue: Queue,
+    //the IOKit main port the object was looked up through
+    main_port: mach_port_t,
+    //shared with the frame number timers running on the queue
+    destroyed: Arc<core::sync::atomic::AtomicBool>,
+    //an interface keeps the device it was opened from alive
+    parent: Option<Rc<SharedObject>>,
+    //the number of open pipes per endpoint address, only used by interfaces
+    pipes: core::cell::RefCell<std::collections::HashMap<u8, usize>>,
+    allow_pipe_aliases: core::cell::Cell<bool>,
+    //the profile applied to the device, only set on devices
+    profile: core::cell::RefCell<Option<DeviceProfile>>,
+    //what the transfers of the pipes opened from a device add up to, only used by devices
+    pipe_stats: Arc<TransferStats>,
+    //what the device requests of a device add up to, only used by devices
+    request_stats: Arc<TransferStats>,
+    //serializes the device requests of a device, also taken by control requests on its pipes
+    gate: RequestGate,
+    //whether device requests are checked with `UsbDevice::validate_request`, only used by devices
+    validate_requests: core::cell::Cell<bool>,
+    //set once `UsbDevice::shutdown` started, refusing new requests and pipe transfers, only used
+    //by devices
+    closing: core::cell::Cell<bool>,
+    //where completions are forwarded to instead of waking their tasks on the queue, only set on
+    //devices; requests and streams take a copy when they are created, the handlers running on
+    //the queue never read it
+    #[cfg(feature = "async")]
+    executor: std::sync::Mutex<Option<Arc<dyn Executor>>>,
+}
+
+impl SharedObject {
+    fn new(
+        object: IOUSBHostObject,
+        queue: Queue,
+        main_port: mach_port_t,
+        parent: Option<Rc<SharedObject>>,
+        pipe_stats: Arc<TransferStats>,
+        request_stats: Arc<TransferStats>,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            object,
+            _queue: queue,
+            main_port,
+            destroyed: Arc::new(core::sync::atomic::AtomicBool::new(false)),
+            parent,
+            pipes: core::cell::RefCell::new(std::collections::HashMap::new()),
+            allow_pipe_aliases: core::cell::Cell::new(false),
+            profile: core::cell::RefCell::new(None),
+            pipe_stats,
+            request_stats,
+            gate: RequestGate::default(),
+            validate_requests: core::cell::Cell::new(false),
+            closing: core::cell::Cell::new(false),
+            #[cfg(feature = "async")]
+            executor: std::sync::Mutex::new(None),
+        })
+    }
 
-impl From<HostObjectInitOptions> for IOUSBHostObjectInitOptions {
-    fn from(options: HostObjectInitOptions) -> IOUSBHostObjectInitOptions {
-        use HostObjectInitOptions as HOIO;
-        match options {
-            HOIO::None => 0,
-            HOIO::DeviceCapture => 1,
-        }
+    ///the queue the completion handlers of the object run on
+    #[cfg(feature = "async")]
+    fn queue(&self) -> Queue {
+        Queue::new(unsafe { self.object.queue() })
     }
-}
 
-impl UsbDevice<'_> {
-    fn new(
-        service: io_service_t,
-        options: HostObjectInitOptions,
-        queue: &Queue,
-    ) -> Result<Self, UsbError> {
-        //NOTE: this asks for exclusive access for the device
-        //
-        //it might be beneficial to use this with IOKit inorder to query without claiming exclusive
-        //ownership
-        let host_device = IOUSBHostDevice::alloc();
-        let mut err = NSErr::new();
-        let dev = unsafe {
-            host_device.initWithIOService_options_queue_error_interestHandler_(
-                service,
-                options.into(),
-                queue.inner.clone(),
-                &mut *err,
-                0 as *mut c_void,
-            )
-        };
-        if err.is_err() {
-            return Err(err.into());
-        }
-        //SAFETY: it shouldnt fail here as we already validated the pointer and ensured there was
-        //no error with initWithIOService
-        let ptr = unsafe { NonNull::new_unchecked(dev as *mut IOUSBHostDevice) };
+    fn is_destroyed(&self) -> bool {
+        self.destroyed.load(core::sync::atomic::Ordering::Acquire)
+    }
 
-        Ok(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
+    ///fails with `UsbError::Terminated` once the device this object is or belongs to is being
+    ///shut down
+    fn check_open(&self) -> Result<(), UsbError> {
+        match self.device().closing.get() {
+            true => Err(UsbError::Terminated),
+            false => Ok(()),
+        }
     }
 
-    pub fn send_device_request_with_data(
+    ///sends a device request on the default control pipe of the device this object is or belongs
+    ///to, waiting for the device's turn and counting it with the device's requests
+    fn send_device_data(
         &self,
         request: DeviceRequest,
-        data: &[u8],
+        data: NSMutableData,
+        timeout: f64,
     ) -> Result<u64, UsbError> {
-        let data = MutData::with_data(data).raw();
+        let device = self.device();
+        device.check_open()?;
+        let _turn = device.gate.enter();
+        let recording = device.request_stats.begin(request.is_in());
         let mut err = NSErr::new();
         let mut transferred = 0;
-        if !unsafe {
-            self.inner
-                .as_ref()
+        let res = if !unsafe {
+            device
+                .object
                 .sendDeviceRequest_data_bytesTransferred_completionTimeout_error_(
                     request.into(),
                     data,
                     &mut transferred,
-                    0.0,
+                    timeout,
                     &mut *err,
                 )
         } {
             Err(err.into())
         } else {
             Ok(transferred)
-        }
+        };
+        recording.finish(res);
+        res
     }
 
-    pub fn send_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendDeviceRequest_error_(request.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
+    ///sends a device-to-host request with the profile's timeout and returns the bytes received in
+    ///the data stage
+    fn device_request_in(&self, request: DeviceRequest) -> Result<Vec<u8>, UsbError> {
+        let length = request.length() as usize;
+        if self.device().validate_requests.get() {
+            request
+                .validate(Some(length))
+                .map_err(UsbError::InvalidRequest)?;
         }
+        let data = MutData::with_data(&vec![0; length]);
+        let transferred = self.send_device_data(request, data.inner, self.request_timeout())?;
+        let bytes = data.bytes();
+        Ok(bytes[..bytes.len().min(transferred as usize)].to_vec())
     }
 
-    pub async fn enqueue_device_request_with_data(
-        &self,
-        request: DeviceRequest,
-        data: &[u8],
-    ) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueDeviceRequest_data_completionTimeout_error_completionHandler_(
-                    request.into(),
-                    data,
-                    0.0,
-                    &mut *err,
-                    cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-
-        handler.await
+    ///the device this object is or belongs to
+    fn device(&self) -> &SharedObject {
+        match &self.parent {
+            Some(parent) => parent.device(),
+            None => self,
+        }
     }
 
-    pub async fn enqueue_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let handler = AsyncHandler::new(self.inner, |dev, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueDeviceRequest_error_completionHandler_(request.into(), &mut *err, cb)
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-        handler.await
+    #[cfg(feature = "async")]
+    fn executor(&self) -> Option<Arc<dyn Executor>> {
+        self.device()
+            .executor
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
     }
 
-    pub fn string_descriptor(
-        &self,
-        index: u64,
-        language_id: Option<u64>,
-    ) -> Result<NSString, UsbError> {
-        let mut err = NSErr::new();
-        let descriptor = unsafe {
-            match language_id {
-                Some(id) => self
-                    .inner
-                    .as_ref()
-                    .stringWithIndex_languageID_error_(index, id, &mut *err),
-                _ => self.inner.as_ref().stringWithIndex_error_(index, &mut *err),
-            }
-        };
-
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(descriptor)
+    ///runs `f` with the profile of the device this object is or belongs to
+    fn with_profile<R>(&self, f: impl FnOnce(Option<&DeviceProfile>) -> R) -> R {
+        match &self.parent {
+            Some(parent) => parent.with_profile(f),
+            None => f(self.profile.borrow().as_ref()),
         }
     }
 
-    //returns the current frame number, but also updates the host time aligned with the time which
-    //the frame number was last updated
-    pub fn frame_number(&self, time: &mut HostTime) -> u64 {
-        unsafe { self.inner.as_ref().frameNumberWithTime_(&mut time.inner) }
+    ///the completion timeout synchronous requests fall back to, 0 waiting forever
+    fn request_timeout(&self) -> f64 {
+        self.with_profile(|profile| {
+            profile
+                .and_then(|profile| profile.request_timeout)
+                .map_or(0.0, timeout_secs)
+        })
     }
 
-    pub fn io_data(&self, capacity: u64) -> Result<NSMutableData, UsbError> {
-        let mut err = NSErr::new();
-        let data = unsafe {
-            self.inner
-                .as_ref()
-                .ioDataWithCapacity_error_(capacity, &mut *err)
-        };
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(data)
-        }
+    fn open_pipes(&self, address: u8) -> usize {
+        self.pipes.borrow().get(&address).copied().unwrap_or(0)
     }
 
-    pub fn abort_device_requests(&self, abort_option: AbortOption) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .abortDeviceRequestsWithOption_error_(abort_option.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
+    fn claim_pipe(&self, address: u8) {
+        *self.pipes.borrow_mut().entry(address).or_insert(0) += 1;
     }
 
-    pub fn interfaces(
-        &self,
-        options: HostObjectInitOptions,
-    ) -> Option<impl Iterator<Item = HostInterface<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(Interfaces {
-            options,
-            queue: self.queue(),
-            current_descriptor,
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            lt: PhantomData,
-        })
+    fn release_pipe(&self, address: u8) {
+        let mut pipes = self.pipes.borrow_mut();
+        if let Some(count) = pipes.get_mut(&address) {
+            *count -= 1;
+            if *count == 0 {
+                pipes.remove(&address);
+            }
+        }
     }
 
-    pub fn get_interface(&self, interface_number: u8) -> Option<InterfaceDescriptor<'_>> {
-        self.interface_descriptors()?
-            .find(|interface| interface.interface_number() == interface_number)
+    fn destroy(&self) {
+        if !self
+            .destroyed
+            .swap(true, core::sync::atomic::Ordering::AcqRel)
+        {
+            unsafe { self.object.destroy() }
+        }
     }
+}
 
-    pub fn get_interface_by_value(&self, interface_number: u8) -> Option<InterfaceDescriptor<'_>> {
-        self.interface_descriptors()?
-            .find(|interface| interface.interface_number() == interface_number)
+impl Drop for SharedObject {
+    fn drop(&mut self) {
+        self.destroy();
     }
+}
 
-    pub fn interface_descriptors(&self) -> Option<impl Iterator<Item = InterfaceDescriptor<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(InterfaceDescriptors {
-            current_descriptor,
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            lt: PhantomData,
-        })
-    }
+///lets a single request through at a time once enabled, for devices that can not handle
+///overlapping requests; requests take their turns in the order they arrived, blocking ones
+///waiting on the calling thread for the requests ahead of them to finish
+#[derive(Default)]
+struct RequestGate {
+    enabled: core::sync::atomic::AtomicBool,
+    state: std::sync::Mutex<GateState>,
+    turn_changed: std::sync::Condvar,
+}
 
-    pub fn interface_association_descriptors(
-        &self,
-    ) -> Option<impl Iterator<Item = InterfaceAssociationDescriptor<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(InterfaceAssociationDescriptors {
-            current_descriptor,
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            lt: PhantomData,
-        })
-    }
+#[derive(Default)]
+struct GateState {
+    locked: bool,
+    next_id: u64,
+    //the requests waiting for their turn, the front one is next once the gate is unlocked
+    waiters: std::collections::VecDeque<GateWaiter>,
+}
 
-    pub fn descriptors(&self) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(Descriptors {
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            current_descriptor,
-            lt: PhantomData,
-        })
-    }
+struct GateWaiter {
+    id: u64,
+    //`None` for a blocking request waiting on the condvar
+    #[cfg(feature = "async")]
+    waker: Option<core::task::Waker>,
+}
 
-    pub fn descriptors_with_type(
-        &self,
-        descriptor_type: u8,
-    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(TypedDescriptors {
-            descriptor_type,
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            current_descriptor,
-            lt: PhantomData,
-        })
+impl GateState {
+    fn is_turn_of(&self, id: u64) -> bool {
+        !self.locked && self.waiters.front().is_some_and(|waiter| waiter.id == id)
     }
 
-    pub fn associated_descriptors(
-        &self,
-        descriptor: &DescriptorHeader<'_>,
-    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(AssociatedDescriptors {
-            assoc_descriptor: unsafe { descriptor.inner.as_ref() },
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            current_descriptor,
-            lt: PhantomData,
-        })
+    fn queue(&mut self, waiter: impl FnOnce(u64) -> GateWaiter) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiters.push_back(waiter(id));
+        id
     }
+}
 
-    pub fn associated_descriptors_with_type(
-        &self,
-        descriptor: &DescriptorHeader<'_>,
-        descriptor_type: u8,
-    ) -> Option<impl Iterator<Item = DescriptorHeader<'_>>> {
-        let current_descriptor = ptr::null();
-        Some(TypedAssociatedDescriptors {
-            assoc_descriptor: unsafe { descriptor.inner.as_ref() },
-            config_descriptor: unsafe { self.configuration_descriptor()?.inner.as_ref() },
-            current_descriptor,
-            descriptor_type,
-            lt: PhantomData,
-        })
+impl RequestGate {
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled
+            .store(enabled, core::sync::atomic::Ordering::Release);
+        if !enabled {
+            //whoever is still waiting goes through without a turn now
+            let state = self.lock();
+            #[cfg(feature = "async")]
+            for waker in state
+                .waiters
+                .iter()
+                .filter_map(|waiter| waiter.waker.as_ref())
+            {
+                waker.wake_by_ref();
+            }
+            drop(state);
+            self.turn_changed.notify_all();
+        }
     }
 
-    pub fn io_service(&self) -> IoService {
-        IoService::from_raw(unsafe { self.inner.as_ref().ioService() })
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(core::sync::atomic::Ordering::Acquire)
     }
 
-    pub fn queue(&self) -> Queue {
-        Queue::new(unsafe { self.inner.as_ref().queue() })
+    fn lock(&self) -> std::sync::MutexGuard<'_, GateState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     }
 
-    pub fn devices<'a, const N: usize>(
-        vendor_id: Option<u16>,
-        product_id: Option<u16>,
-        bcd_device: Option<u16>,
-        device_class: Option<u8>,
-        device_subclass: Option<u8>,
-        device_protocol: Option<u8>,
-        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
-        options: HostObjectInitOptions,
-    ) -> Result<impl Iterator<Item = UsbDevice<'a>>, UsbError> {
-        let dict = Self::create_matching_dictionary(
-            vendor_id,
-            product_id,
-            bcd_device,
-            device_class,
-            device_subclass,
-            device_protocol,
-            speed, /* product_ids */
-        )?;
-
-        let mut iter = 0;
-
-        let err = unsafe { IOServiceGetMatchingServices(kIOMasterPortDefault, dict, &mut iter) };
-
-        if err != 0 {
-            //uh oh...
+    ///waits for the turn of a blocking request, `None` when the gate is disabled
+    fn enter(&self) -> Option<GateGuard<'_>> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let mut state = self.lock();
+        if !state.locked && state.waiters.is_empty() {
+            state.locked = true;
+            return Some(GateGuard { gate: self });
+        }
+        let id = state.queue(|id| GateWaiter {
+            id,
+            #[cfg(feature = "async")]
+            waker: None,
+        });
+        loop {
+            if !self.is_enabled() {
+                self.leave(state, id);
+                return None;
+            }
+            if state.is_turn_of(id) {
+                state.waiters.pop_front();
+                state.locked = true;
+                return Some(GateGuard { gate: self });
+            }
+            state = self
+                .turn_changed
+                .wait(state)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
         }
-
-        let label = &0;
-        let attr = NSObject(ptr::null_mut());
-
-        let queue = Queue::new(unsafe { dispatch_queue_create(label, attr) });
-
-        Ok(Devices {
-            queue,
-            inner: iter,
-            options,
-            lt: PhantomData,
-        })
     }
 
-    fn create_matching_dictionary(
-        vendor_id: Option<u16>,
-        product_id: Option<u16>,
-        bcd_device: Option<u16>,
-        device_class: Option<u8>,
-        device_subclass: Option<u8>,
-        device_protocol: Option<u8>,
-        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
-    ) -> Result<CFMutableDictionaryRef, UsbError> {
-        let vendor_id: NSNum = vendor_id.into();
-        let product_id: NSNum = product_id.into();
-        let bcd_device: NSNum = bcd_device.into();
-        let device_class: NSNum = device_class.into();
-        let device_subclass: NSNum = device_subclass.into();
-        let device_protocol: NSNum = device_protocol.into();
-        let speed: NSNum = speed.into();
-
-        let dict = unsafe {
-            IOUSBHostDevice::createMatchingDictionaryWithVendorID_productID_bcdDevice_deviceClass_deviceSubclass_deviceProtocol_speed_productIDArray_(
-            vendor_id.into(),
-            product_id.into(),
-            bcd_device.into(),
-            device_class.into(),
-            device_subclass.into(),
-            device_protocol.into(),
-            speed.into(),
-            NSArray(ptr::null_mut())
-        )
-        };
-
-        if dict.is_null() {
-            //uh oh...
+    ///waits for the turn of an async request, `None` when the gate is disabled
+    #[cfg(feature = "async")]
+    fn enter_async(&self) -> GateEntry<'_> {
+        GateEntry {
+            gate: self,
+            id: None,
         }
-
-        Ok(dict)
     }
 
-    pub fn device<const N: usize>(
-        vendor_id: Option<u16>,
-        product_id: Option<u16>,
-        bcd_device: Option<u16>,
-        device_class: Option<u8>,
-        device_subclass: Option<u8>,
-        device_protocol: Option<u8>,
-        speed: Option<u16>, /*, product_ids: Option<[u16; N]>*/
-        options: HostObjectInitOptions,
-    ) -> Result<Self, UsbError> {
-        let dict = Self::create_matching_dictionary(
-            vendor_id,
-            product_id,
-            bcd_device,
-            device_class,
-            device_subclass,
-            device_protocol,
-            speed, /* product_ids */
-        )?;
-        let service = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, dict) };
-        let label = &0;
-        let attr = NSObject(ptr::null_mut());
-
-        let queue = Queue::new(unsafe { dispatch_queue_create(label, attr) });
-        Self::new(service, options, &queue)
+    ///takes a waiter out of the queue, handing the turn on if it was next
+    fn leave(&self, mut state: std::sync::MutexGuard<'_, GateState>, id: u64) {
+        let was_next = state.is_turn_of(id);
+        state.waiters.retain(|waiter| waiter.id != id);
+        if was_next {
+            self.pass_turn(&state);
+        }
     }
 
-    pub fn reset(&self) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.as_ref().resetWithError_(&mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
+    ///wakes the request whose turn it is now
+    fn pass_turn(&self, state: &GateState) {
+        if state.waiters.is_empty() {
+            return;
         }
+        #[cfg(feature = "async")]
+        if let Some(waker) = state.waiters.front().and_then(|next| next.waker.as_ref()) {
+            waker.wake_by_ref();
+            return;
+        }
+        //blocking waiters check whether they are at the front themselves
+        self.turn_changed.notify_all();
     }
+}
 
-    pub fn configure(&self, val: u64, match_interfaces: Option<bool>) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        let is_err = unsafe {
-            match match_interfaces {
-                Some(mtch) => self
-                    .inner
-                    .as_ref()
-                    .configureWithValue_matchInterfaces_error_(val, mtch, &mut *err),
-                None => self
-                    .inner
-                    .as_ref()
-                    .configureWithValue_error_(val, &mut *err),
+struct GateGuard<'g> {
+    gate: &'g RequestGate,
+}
+
+impl Drop for GateGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.gate.lock();
+        state.locked = false;
+        self.gate.pass_turn(&state);
+    }
+}
+
+#[cfg(feature = "async")]
+struct GateEntry<'g> {
+    gate: &'g RequestGate,
+    //the place in the queue once the entry had to wait
+    id: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<'g> core::future::Future for GateEntry<'g> {
+    type Output = Option<GateGuard<'g>>;
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let gate = this.gate;
+        let mut state = gate.lock();
+        if !gate.is_enabled() {
+            if let Some(id) = this.id.take() {
+                gate.leave(state, id);
+            }
+            return core::task::Poll::Ready(None);
+        }
+        let Some(id) = this.id else {
+            if !state.locked && state.waiters.is_empty() {
+                state.locked = true;
+                return core::task::Poll::Ready(Some(GateGuard { gate }));
             }
+            this.id = Some(state.queue(|id| GateWaiter {
+                id,
+                waker: Some(cx.waker().clone()),
+            }));
+            return core::task::Poll::Pending;
         };
-        if is_err {
-            Err(err.into())
-        } else {
-            Ok(())
+        if state.is_turn_of(id) {
+            state.waiters.pop_front();
+            state.locked = true;
+            this.id = None;
+            return core::task::Poll::Ready(Some(GateGuard { gate }));
+        }
+        //polled again before its turn, keeps a single waker for the entry
+        if let Some(waiter) = state.waiters.iter_mut().find(|waiter| waiter.id == id) {
+            match &mut waiter.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                waker => *waker = Some(cx.waker().clone()),
+            }
         }
+        core::task::Poll::Pending
     }
+}
 
-    pub fn device_descriptor(&self) -> Option<DeviceDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().deviceDescriptor() };
-        DeviceDescriptor::new(ptr)
+#[cfg(feature = "async")]
+impl Drop for GateEntry<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.gate.leave(self.gate.lock(), id);
+        }
     }
+}
 
-    pub fn capability_descriptors(&self) -> impl Iterator<Item = CapabilityDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().capabilityDescriptors() };
-        CapabilityDescriptors {
-            inner: ptr,
-            lt: PhantomData,
-        }
+///how a host object is opened, `IOUSBHostObjectInitOptions`
+///
+///every host object is opened for exclusive use by this process, the options decide what happens
+///to whoever is using the service already; capturing and seizing only apply to devices,
+///interfaces always open with `None`'s semantics
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostObjectInitOptions {
+    ///fails with `NoAccess` if a kernel driver or another process has the service open
+    #[default]
+    None = 0,
+    ///terminates the kernel drivers of the device and its interfaces, which match again once the
+    ///device is destroyed; needs root or the `com.apple.vm.device-access` entitlement
+    DeviceCapture = 1,
+    ///takes the device from another user space client holding it open, which is told to close
+    ///it, kernel drivers are left alone
+    DeviceSeize = 2,
+    ///both of the above, the device ends up with this process whoever had it
+    DeviceCaptureAndSeize = 3,
+}
+
+impl HostObjectInitOptions {
+    pub fn captures(&self) -> bool {
+        matches!(self, Self::DeviceCapture | Self::DeviceCaptureAndSeize)
+    }
+
+    pub fn seizes(&self) -> bool {
+        matches!(self, Self::DeviceSeize | Self::DeviceCaptureAndSeize)
     }
+}
 
-    pub fn configuration_descriptor(&self) -> Option<ConfigurationDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().configurationDescriptor() };
-        ConfigurationDescriptor::new(ptr)
+impl From<HostObjectInitOptions> for IOUSBHostObjectInitOptions {
+    fn from(options: HostObjectInitOptions) -> IOUSBHostObjectInitOptions {
+        use HostObjectInitOptions as HOIO;
+        match options {
+            HOIO::None => 0,
+            HOIO::DeviceCapture => 1,
+            HOIO::DeviceSeize => 2,
+            HOIO::DeviceCaptureAndSeize => 3,
+        }
     }
+}
 
-    pub fn device_address(&self) -> u64 {
-        unsafe { self.inner.as_ref().deviceAddress() }
+impl From<IOUSBHostObjectInitOptions> for HostObjectInitOptions {
+    fn from(options: IOUSBHostObjectInitOptions) -> HostObjectInitOptions {
+        use HostObjectInitOptions as HOIO;
+        match options & 3 {
+            1 => HOIO::DeviceCapture,
+            2 => HOIO::DeviceSeize,
+            3 => HOIO::DeviceCaptureAndSeize,
+            _ => HOIO::None,
+        }
     }
 }
 
+///a dispatch queue callbacks are delivered on, every `Queue` holds its own reference so it may
+///outlive the object it was taken from
 pub struct Queue {
     inner: dispatch_queue_t,
 }
 
 impl Queue {
+    ///retains `queue`, for queues owned by someone else such as the one an object reports
     fn new(queue: dispatch_queue_t) -> Self {
+        if !queue.0.is_null() {
+            unsafe { dispatch::ffi::dispatch_retain(queue.0 as dispatch::ffi::dispatch_object_t) };
+        }
         Self { inner: queue }
     }
-}
 
-struct Devices<'a> {
-    inner: io_service_t,
-    queue: Queue,
-    options: HostObjectInitOptions,
-    lt: PhantomData<&'a ()>,
+    ///takes over the reference of a queue we created, as `dispatch_queue_create` returns one
+    fn created(queue: dispatch_queue_t) -> Self {
+        Self { inner: queue }
+    }
 }
 
-impl<'a> Iterator for Devices<'a> {
-    type Item = UsbDevice<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        if unsafe { IOIteratorIsValid(self.inner) } == 0 {
-            match UsbDevice::new(self.inner, self.options, &self.queue) {
-                Ok(dev) => {
-                    let next = unsafe { IOIteratorNext(self.inner) };
-                    self.inner = next;
-                    Some(dev)
-                }
-                Err(e) => {
-                    println!("unexpected err when enumerating devices: {:?}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        }
+#[cfg(feature = "dispatch2")]
+impl Queue {
+    ///a reference to the queue for `dispatch2`, `None` for the null queue
+    pub fn to_dispatch2(&self) -> Option<dispatch2::DispatchRetained<dispatch2::DispatchQueue>> {
+        let queue = ptr::NonNull::new(self.inner.0 as *mut dispatch2::DispatchQueue)?;
+        Some(unsafe { dispatch2::DispatchRetained::retain(queue) })
     }
 }
 
-pub struct HostPipe<'a> {
-    inner: NonNull<IOUSBHostPipe>,
-    lt: PhantomData<&'a ()>,
+#[cfg(feature = "dispatch2")]
+impl From<&dispatch2::DispatchQueue> for Queue {
+    fn from(queue: &dispatch2::DispatchQueue) -> Queue {
+        let queue = queue as *const dispatch2::DispatchQueue as *mut _;
+        Queue::new(NSObject(queue))
+    }
 }
 
-impl HostPipe<'_> {
-    fn new(ptr: *const IOUSBHostPipe) -> Self {
-        let ptr = unsafe { NonNull::new_unchecked(ptr as *mut IOUSBHostPipe) };
-        Self {
-            inner: ptr,
-            lt: PhantomData,
-        }
+#[cfg(feature = "dispatch2")]
+impl From<dispatch2::DispatchRetained<dispatch2::DispatchQueue>> for Queue {
+    fn from(queue: dispatch2::DispatchRetained<dispatch2::DispatchQueue>) -> Queue {
+        let queue = dispatch2::DispatchRetained::into_raw(queue);
+        Queue::created(NSObject(queue.as_ptr() as *mut _))
     }
+}
 
-    #[allow(private_bounds)]
-    pub fn adjust(&self, descriptors: impl IntoRawSource) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .adjustPipeWithDescriptors_error_(descriptors.raw(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
+impl Clone for Queue {
+    fn clone(&self) -> Self {
+        Self::new(self.inner)
     }
+}
 
-    pub fn set_idle_timeout(&self, duration: f64) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .setIdleTimeout_error_(duration, &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
+impl Drop for Queue {
+    fn drop(&mut self) {
+        if !self.inner.0.is_null() {
+            unsafe {
+                dispatch::ffi::dispatch_release(self.inner.0 as dispatch::ffi::dispatch_object_t)
+            };
         }
     }
+}
 
-    pub fn clear_stall(&self) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.as_ref().clearStallWithError_(&mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
+///how a `QueuePool` picks the queue of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sharding {
+    ///every device takes the next queue in turn
+    RoundRobin,
+    ///devices are spread by the hash of their location ID, so a device keeps its queue across
+    ///reconnects to the same port; devices without one fall back to round robin
+    LocationId,
+}
 
-    pub fn send_control_request_with_data(
-        &self,
-        request: DeviceRequest,
-        data: &mut [u8],
-    ) -> Result<u64, UsbError> {
-        let data = MutData::with_data(data).raw();
-        let mut err = NSErr::new();
-        let mut transferred = 0;
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendControlRequest_data_bytesTransferred_completionTimeout_error_(
-                    request.into(),
-                    data,
-                    &mut transferred,
-                    0.0,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(transferred)
-        }
+///a fixed set of serial queues the devices of a `Context` are spread over, so the completions of
+///many devices are handled on several cores without a queue per device
+pub struct QueuePool {
+    queues: Vec<Queue>,
+    sharding: Sharding,
+    next: core::sync::atomic::AtomicUsize,
+}
+
+impl QueuePool {
+    ///`count` new serial queues, at least one
+    pub fn new(count: usize, sharding: Sharding) -> Self {
+        let queues = (0..count.max(1))
+            .map(|_| {
+                let label = &0;
+                let attr = NSObject(ptr::null_mut());
+                Queue::created(unsafe { dispatch_queue_create(label, attr) })
+            })
+            .collect();
+        Self::with_queues(queues, sharding)
     }
 
-    pub fn send_control_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendControlRequest_error_(request.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
+    ///a pool over queues created elsewhere, such as ones with a QoS class; an empty list gets a
+    ///single new queue
+    pub fn with_queues(queues: Vec<Queue>, sharding: Sharding) -> Self {
+        if queues.is_empty() {
+            return Self::new(1, sharding);
+        }
+        Self {
+            queues,
+            sharding,
+            next: core::sync::atomic::AtomicUsize::new(0),
         }
     }
 
-    pub async fn enqueue_control_request_with_data(
-        &self,
-        request: DeviceRequest,
-        data: &mut [u8],
-    ) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueControlRequest_data_completionTimeout_error_completionHandler_(
-                    request.into(),
-                    data,
-                    0.0,
-                    &mut *err,
-                    cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
+    pub fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
 
-        handler.await
+    pub fn sharding(&self) -> Sharding {
+        self.sharding
     }
 
-    pub async fn enqueue_control_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let handler = AsyncHandler::new(self.inner, |dev, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueControlRequest_error_completionHandler_(request.into(), &mut *err, cb)
-            } {
-                Some(err.into())
-            } else {
-                None
+    ///the queue for a device at `location_id`
+    pub fn queue_for(&self, location_id: Option<u32>) -> Queue {
+        let index = match (self.sharding, location_id) {
+            //fibonacci hashing, location IDs differ mostly in their high nibbles
+            (Sharding::LocationId, Some(location_id)) => {
+                ((location_id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as usize
             }
-        });
-        handler.await
+            _ => self
+                .next
+                .fetch_add(1, core::sync::atomic::Ordering::Relaxed),
+        };
+        self.queues[index % self.queues.len()].clone()
     }
 
-    pub fn send_io_request(&self, data: &[u8]) -> Result<u64, UsbError> {
-        let mut err = NSErr::new();
-        let data = MutData::with_data(data).raw();
-        let mut transferred = 0;
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendIORequestWithData_bytesTransferred_completionTimeout_error_(
-                    data,
-                    &mut transferred,
-                    0.0,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(transferred)
-        }
+    ///the queue for the device behind `service`
+    pub(crate) fn queue_for_service(&self, service: io_service_t) -> Queue {
+        let location_id = IoService::from_raw(service)
+            .number_property(c"locationID")
+            .and_then(|location_id| u32::try_from(location_id).ok());
+        self.queue_for(location_id)
     }
+}
 
-    pub async fn enqueue_io_request(&self, data: &[u8]) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueIORequestWithData_completionTimeout_error_completionHandler_(
-                    data, 0.0, &mut *err, cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
+/*
+pub struct NSArr(NSArray);
 
-        handler.await
-    }
+impl<T, const N: usize> From<Option<[T; N]>> for NSArr {
+    fn from(arr: Option<[T; N]>) -> NSArr {
+        let ptr = if let Some(arr) = arr {
+            let alloc = NSMutableArray::alloc();
+            //alloc.initWithObjects_count_();
 
-    pub async fn enqueue_io_request_isochronous_frame(
-        &self,
-        data: &[u8],
-        frames: &mut [IsochronousFrame],
-        first_frame_number: u64,
-    ) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueIORequestWithData_frameList_frameListCount_firstFrameNumber_error_completionHandler_(
-                    data,
-                    frames.as_ptr() as *mut IOUSBHostIsochronousFrame,
-                    frames.len() as u64,
-                    first_frame_number,
-                    &mut *err,
-                    cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-
-        handler.await
-    }
-
-    pub async fn enqueue_io_request_isochronous_transaction(
-        &self,
-        data: &[u8],
-        transactions: &mut [IsochronousTransaction],
-        first_frame_number: u64,
-        options: IsochronousTransactionOptions,
-    ) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueIORequestWithData_transactionList_transactionListCount_firstFrameNumber_options_error_completionHandler_(
-                    data,
-                    transactions.as_ptr() as *mut IOUSBHostIsochronousTransaction,
-                    transactions.len() as u64,
-                    first_frame_number,
-                    options.into(),
-                    &mut *err,
-                    cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-
-        handler.await
-    }
-
-    pub fn send_io_request_isochronous_frame(
-        &self,
-        data: &[u8],
-        frames: &mut [IsochronousFrame],
-        first_frame_number: u64,
-    ) -> Result<(), UsbError> {
-        let data = MutData::with_data(data).raw();
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendIORequestWithData_frameList_frameListCount_firstFrameNumber_error_(
-                    data,
-                    frames.as_ptr() as *mut IOUSBHostIsochronousFrame,
-                    frames.len() as u64,
-                    first_frame_number,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn send_io_request_isochronous_transaction(
-        &self,
-        data: &[u8],
-        transactions: &mut [IsochronousTransaction],
-        first_frame_number: u64,
-        options: IsochronousTransactionOptions,
-    ) -> Result<(), UsbError> {
-        let data = MutData::with_data(data).raw();
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner.as_ref().sendIORequestWithData_transactionList_transactionListCount_firstFrameNumber_options_error_(data, transactions.as_ptr() as *mut IOUSBHostIsochronousTransaction, transactions.len() as u64, first_frame_number, options.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn abort(&self, abort: AbortOption) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .abortWithOption_error_(abort.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn enable_streams(&self) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.as_ref().enableStreamsWithError_(&mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn disable_streams(&self) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.as_ref().disableStreamsWithError_(&mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn copy_stream(&self, stream_id: u64) -> Result<HostStream, UsbError> {
-        let mut err = NSErr::new();
-        let stream = unsafe {
-            self.inner
-                .as_ref()
-                .copyStreamWithStreamID_error_(stream_id, &mut *err)
-        };
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(HostStream { inner: stream })
-        }
-    }
-
-    #[allow(private_interfaces)]
-    pub fn original_descriptors(
-        &self,
-    ) -> impl Iterator<Item = IoSourceDescriptor<'_>> + IntoRawSource {
-        let ptr = unsafe { self.inner.as_ref().originalDescriptors() };
-        IoSourceDescriptors {
-            inner: ptr,
-            lt: PhantomData,
-        }
-    }
-
-    #[allow(private_interfaces)]
-    pub fn descriptors(&self) -> impl Iterator<Item = IoSourceDescriptor<'_>> + IntoRawSource {
-        let ptr = unsafe { self.inner.as_ref().descriptors() };
-        IoSourceDescriptors {
-            inner: ptr,
-            lt: PhantomData,
-        }
-    }
-
-    pub fn idle_timeout(&self) -> f64 {
-        unsafe { self.inner.as_ref().idleTimeout() }
-    }
-}
-
-pub struct HostStream {
-    inner: IOUSBHostStream,
-}
-
-impl HostStream {
-    pub fn abort(&self, option: AbortOption) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.abortWithOption_error_(option.into(), &mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn send_io_request(&self, data: &mut [u8]) -> Result<u64, UsbError> {
-        let mut err = NSErr::new();
-        let data = MutData::with_data(data).raw();
-        let mut transferred = 0;
-        if !unsafe {
-            self.inner.sendIORequestWithData_bytesTransferred_error_(
-                data,
-                &mut transferred,
-                &mut *err,
-            )
-        } {
-            Err(err.into())
-        } else {
-            Ok(transferred)
-        }
-    }
-
-    pub async fn enqueue_io_request(&self, data: &[u8]) -> Result<(), UsbError> {
-        let ptr = unsafe {
-            NonNull::new_unchecked(&self.inner as *const IOUSBHostStream as *mut IOUSBHostStream)
-        };
-        let handler = AsyncDataHandler::new(ptr, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueIORequestWithData_error_completionHandler_(data, &mut *err, cb)
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-
-        handler.await
-    }
-
-    /*
-    fn host_pipe(&self) -> HostPipe {
-        HostPipe{
-            inner: unsafe { self.inner.hostPipe() }
-        }
-    }
-    */
-
-    pub fn stream_id(&self) -> u64 {
-        unsafe { self.inner.streamID() }
-    }
-}
-
-pub struct HostIoSource {
-    inner: IOUSBHostIOSource,
-}
-
-/*
-pub struct InterfacePropertyKey(NSString);
-pub struct DevicePropertyKey(NSString);
-pub struct MatchingPropertyKey(NSString);
-pub struct PropertyKey(NSString);
-*/
-
-impl HostIoSource {
-    /*
-    fn host_interface(&self) -> HostInterface {
-        HostInterface {
-            inner: unsafe{self.inner.hostInterface()}
-        }
-    }
-    */
-
-    pub fn device_address(&self) -> u64 {
-        unsafe { self.inner.deviceAddress() }
-    }
-
-    pub fn endpoint_address(&self) -> u64 {
-        unsafe { self.inner.endpointAddress() }
-    }
-}
-
-pub struct IoSourceDescriptors<'a> {
-    inner: *const IOUSBHostIOSourceDescriptors,
-    lt: PhantomData<&'a IOUSBHostIOSourceDescriptors>,
-}
-
-impl IntoRawSource for IoSourceDescriptors<'_> {
-    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors {
-        self.inner
-    }
-}
-
-impl<'a> Iterator for IoSourceDescriptors<'a> {
-    type Item = IoSourceDescriptor<'a>;
-    fn next(&mut self) -> Option<IoSourceDescriptor<'a>> {
-        let desc = IoSourceDescriptor::new(self.inner)?;
-        self.inner = unsafe { self.inner.add(1) };
-        Some(desc)
-    }
-}
-
-pub struct IoSourceDescriptor<'a> {
-    inner: NonNull<IOUSBHostIOSourceDescriptors>,
-    lt: PhantomData<&'a ()>,
-}
-
-trait IntoRawSource {
-    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors;
-}
-
-impl IntoRawSource for IoSourceDescriptor<'_> {
-    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors {
-        let ptr = unsafe { self.inner.as_ref() as *const IOUSBHostIOSourceDescriptors };
-        ptr
-    }
-}
-
-impl IoSourceDescriptor<'_> {
-    fn new(ptr: *const IOUSBHostIOSourceDescriptors) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBHostIOSourceDescriptors)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn bcd_usb(&self) -> u16 {
-        unsafe { self.inner.as_ref().bcdUSB }
-    }
-
-    pub fn endpoint_descriptor(&self) -> EndpointDescriptor<'_> {
-        let ptr = &unsafe { self.inner.as_ref().descriptor };
-        EndpointDescriptor::new(ptr).unwrap()
-    }
-
-    pub fn super_speed_companion_descriptor(&self) -> SuperSpeedCompanionDescriptor {
-        let ptr = &unsafe { self.inner.as_ref().ssCompanionDescriptor };
-        SuperSpeedCompanionDescriptor::new(ptr).unwrap()
-    }
-
-    pub fn super_speed_plus_companion_descriptor(&self) -> SuperSpeedPlusCompanionDescriptor {
-        let ptr = &unsafe { self.inner.as_ref().sspCompanionDescriptor };
-        SuperSpeedPlusCompanionDescriptor::new(ptr).unwrap()
-    }
-}
-
-pub struct SuperSpeedCompanionDescriptor<'a> {
-    inner: NonNull<IOUSBSuperSpeedEndpointCompanionDescriptor>,
-    lt: PhantomData<&'a IOUSBSuperSpeedEndpointCompanionDescriptor>,
-}
-
-pub struct SuperSpeedPlusCompanionDescriptor<'a> {
-    inner: NonNull<IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor>,
-    lt: PhantomData<&'a IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor>,
-}
-
-impl SuperSpeedPlusCompanionDescriptor<'_> {
-    fn new(ptr: *const IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor) -> Option<Self> {
-        let ptr =
-            NonNull::new(ptr as *mut IOUSBSuperSpeedPlusIsochronousEndpointCompanionDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn bytes_per_interval(&self) -> u32 {
-        unsafe { self.inner.as_ref().dwBytesPerInterval }
-    }
-}
-
-impl SuperSpeedCompanionDescriptor<'_> {
-    fn new(ptr: *const IOUSBSuperSpeedEndpointCompanionDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBSuperSpeedEndpointCompanionDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn max_burst(&self) -> u8 {
-        unsafe { self.inner.as_ref().bMaxBurst }
-    }
-
-    pub fn attributes(&self) -> u8 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-
-    pub fn bytes_per_interval(&self) -> u16 {
-        unsafe { self.inner.as_ref().wBytesPerInterval }
-    }
-}
-
-#[repr(u8)]
-#[derive(Clone, Copy)]
-pub enum DescriptorType {
-    Device = 1,
-    Configuration = 2,
-    String = 3,
-    Interface = 4,
-    Endpoint = 5,
-    DeviceQualifier = 6,
-    OtherSpeedConfig = 7,
-    InterfacePower = 8,
-    OTG = 9,
-    Debug = 10,
-    InterfaceAssociation = 11,
-    CapabilityDescriptor = 15,
-    DeviceCapability = 16,
-    HID = 33,
-    Report = 34,
-    Physical = 35,
-    Hub = 41,
-    SuperSpeedHub = 42,
-    SuperSpeedEndpointCompanion = 48,
-    SuperSpeedPlusIsochronousEndpointCompanion = 49,
-    Other(u8),
-}
-
-impl From<u8> for DescriptorType {
-    fn from(num: u8) -> DescriptorType {
-        use DescriptorType as DT;
-        match num {
-            1 => DT::Device,
-            2 => DT::Configuration,
-            3 => DT::String,
-            4 => DT::Interface,
-            5 => DT::Endpoint,
-            6 => DT::DeviceQualifier,
-            7 => DT::OtherSpeedConfig,
-            8 => DT::InterfacePower,
-            9 => DT::OTG,
-            10 => DT::Debug,
-            11 => DT::InterfaceAssociation,
-            15 => DT::CapabilityDescriptor,
-            16 => DT::DeviceCapability,
-            33 => DT::HID,
-            34 => DT::Report,
-            35 => DT::Physical,
-            41 => DT::Hub,
-            42 => DT::SuperSpeedHub,
-            48 => DT::SuperSpeedEndpointCompanion,
-            49 => DT::SuperSpeedPlusIsochronousEndpointCompanion,
-            other => DT::Other(other),
-        }
-    }
-}
-
-impl From<DescriptorType> for u8 {
-    fn from(desc: DescriptorType) -> u8 {
-        use DescriptorType as DT;
-        match desc {
-            DT::Device => 1,
-            DT::Configuration => 2,
-            DT::String => 3,
-            DT::Interface => 4,
-            DT::Endpoint => 5,
-            DT::DeviceQualifier => 6,
-            DT::OtherSpeedConfig => 7,
-            DT::InterfacePower => 8,
-            DT::OTG => 9,
-            DT::Debug => 10,
-            DT::InterfaceAssociation => 11,
-            DT::CapabilityDescriptor => 15,
-            DT::DeviceCapability => 16,
-            DT::HID => 33,
-            DT::Report => 34,
-            DT::Physical => 35,
-            DT::Hub => 41,
-            DT::SuperSpeedHub => 42,
-            DT::SuperSpeedEndpointCompanion => 48,
-            DT::SuperSpeedPlusIsochronousEndpointCompanion => 49,
-            DT::Other(o) => o,
-        }
-    }
-}
-
-impl From<DeviceRequest> for IOUSBDeviceRequest {
-    fn from(req: DeviceRequest) -> IOUSBDeviceRequest {
-        req.inner
-    }
-}
-
-#[derive(Clone, Copy)]
-pub struct DeviceRequest {
-    inner: IOUSBDeviceRequest,
-}
-
-impl DeviceRequest {
-    pub fn new(
-        request_type: DeviceRequestType,
-        request: u8,
-        value: u16,
-        index: u16,
-        length: u16,
-    ) -> Self {
-        let inner = IOUSBDeviceRequest {
-            bmRequestType: request_type.into(),
-            bRequest: request,
-            wValue: value,
-            wIndex: index,
-            wLength: length,
-        };
-        Self { inner }
-    }
-
-    pub fn request_type(&self) -> u8 {
-        self.inner.bmRequestType
-    }
-
-    pub fn request(&self) -> u8 {
-        self.inner.bRequest
-    }
-
-    pub fn value(&self) -> u16 {
-        self.inner.wValue
-    }
-
-    pub fn index(&self) -> u16 {
-        self.inner.wIndex
-    }
-
-    pub fn length(&self) -> u16 {
-        self.inner.wLength
-    }
-}
-
-pub struct HostInterface<'a> {
-    inner: NonNull<IOUSBHostInterface>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl HostInterface<'_> {
-    fn new(ptr: *const IOUSBHostInterface) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBHostInterface)?;
-        Some(HostInterface {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn idle_timeout(&self) -> f64 {
-        unsafe { self.inner.as_ref().idleTimeout() }
-    }
-
-    pub fn set_idle_timeout(&self, interval: f64) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if unsafe {
-            !self
-                .inner
-                .as_ref()
-                .setIdleTimeout_error_(interval, &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn configuration_descriptor(&self) -> Option<ConfigurationDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().configurationDescriptor() };
-        ConfigurationDescriptor::new(ptr)
-    }
-
-    pub fn interface_descriptor(&self) -> Option<InterfaceDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().interfaceDescriptor() };
-        InterfaceDescriptor::new(ptr)
-    }
-
-    pub fn create_matching_dictionary<const N: usize>(
-        vendor_id: Option<u16>,
-        product_id: Option<u16>,
-        bcd_device: Option<u16>,
-        interface_number: Option<u8>,
-        configuration_value: Option<u8>,
-        interface_class: Option<u8>,
-        interface_subclass: Option<u8>,
-        interface_protocol: Option<u8>,
-        speed: Option<u16>, /*product_ids: Option<[u16; N]>*/
-    ) -> Result<CFMutableDictionaryRef, UsbError> {
-        let vendor_id: NSNum = vendor_id.into();
-        let product_id: NSNum = product_id.into();
-        let bcd_device: NSNum = bcd_device.into();
-        let interface_number: NSNum = interface_number.into();
-        let configuration_value: NSNum = configuration_value.into();
-        let interface_class: NSNum = interface_class.into();
-        let interface_subclass: NSNum = interface_subclass.into();
-        let interface_protocol: NSNum = interface_protocol.into();
-        let speed: NSNum = speed.into();
-
-        let dict = unsafe {
-            IOUSBHostInterface::createMatchingDictionaryWithVendorID_productID_bcdDevice_interfaceNumber_configurationValue_interfaceClass_interfaceSubclass_interfaceProtocol_speed_productIDArray_(
-            vendor_id.into(),
-            product_id.into(),
-            bcd_device.into(),
-            interface_number.into(),
-            configuration_value.into(),
-            interface_class.into(),
-            interface_subclass.into(),
-            interface_protocol.into(),
-            speed.into(),
-            NSArray(ptr::null_mut()),
-        )
-        };
-
-        if dict.is_null() {
-            //uh oh...
-        }
-        Ok(dict)
-    }
-
-    pub fn endpoint_descriptors(&self) -> Option<impl Iterator<Item = EndpointDescriptor<'_>>> {
-        let config_descriptor = unsafe { self.configuration_descriptor()?.inner.as_ref() };
-        let interface_descriptor = unsafe { self.interface_descriptor()?.inner.as_ref() };
-        let current_descriptor = ptr::null();
-        Some(EndpointDescriptors {
-            config_descriptor,
-            interface_descriptor,
-            current_descriptor,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn pipes(&self) -> Option<impl Iterator<Item = HostPipe<'_>>> {
-        let config_descriptor = unsafe { self.configuration_descriptor()?.inner.as_ref() };
-        let interface_descriptor = unsafe { self.interface_descriptor()?.inner.as_ref() };
-        let current_descriptor = ptr::null();
-        Some(Pipes {
-            config_descriptor,
-            interface_descriptor,
-            current_descriptor,
-            interface: &self,
-        })
-    }
-
-    pub fn select_alternate_setting(&self, alternate_setting: u8) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .selectAlternateSetting_error_(alternate_setting as u64, &mut *err)
-        } {
-            return Err(err.into());
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn copy_pipe(&self, address: u64) -> Result<HostPipe<'_>, UsbError> {
-        let mut err = NSErr::new();
-        let pipe = unsafe {
-            self.inner
-                .as_ref()
-                .copyPipeWithAddress_error_(address, &mut *err)
-        };
-
-        if err.is_err() {
-            return Err(err.into());
-        } else {
-            Ok(HostPipe::new(&pipe))
-        }
-    }
-}
-
-pub struct Pipes<'a> {
-    interface: &'a HostInterface<'a>,
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    interface_descriptor: *const IOUSBInterfaceDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-}
-
-impl<'a> Iterator for Pipes<'a> {
-    type Item = HostPipe<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextEndpointDescriptor(
-                self.config_descriptor,
-                self.interface_descriptor,
-                self.current_descriptor,
-            )
-        };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next as *const IOUSBDescriptorHeader;
-
-        match self
-            .interface
-            .copy_pipe(unsafe { (*next).bEndpointAddress } as u64)
-        {
-            Ok(pipe) => Some(pipe),
-            Err(e) => {
-                println!("err while enumerating pipes: {:?}", e);
-                None
-            }
-        }
-    }
-}
-
-/*
-pub struct NSArr(NSArray);
-
-impl<T, const N: usize> From<Option<[T; N]>> for NSArr {
-    fn from(arr: Option<[T; N]>) -> NSArr {
-        let ptr = if let Some(arr) = arr {
-            let alloc = NSMutableArray::alloc();
-            //alloc.initWithObjects_count_();
-
-            todo!()
-        } else {
-            NSArray(ptr::null_mut())
-        };
-        todo!()
-    }
-}
-*/
-
-pub struct NSNum(NSNumber);
-
-impl From<Option<u16>> for NSNum {
-    fn from(opt: Option<u16>) -> NSNum {
-        NSNum(if let Some(num) = opt {
-            let alloc = NSNumber::alloc();
-            unsafe { alloc.initWithUnsignedShort_(num) }
-        } else {
-            NSNumber(ptr::null_mut())
-        })
-    }
-}
-
-impl From<Option<u8>> for NSNum {
-    fn from(opt: Option<u8>) -> NSNum {
-        NSNum(if let Some(num) = opt {
-            let alloc = NSNumber::alloc();
-            unsafe { alloc.initWithUnsignedChar_(num) }
-        } else {
-            NSNumber(ptr::null_mut())
-        })
-    }
-}
-
-impl From<NSNum> for NSNumber {
-    fn from(f: NSNum) -> NSNumber {
-        f.0
-    }
-}
-
-pub struct NSErr(NSError);
-
-impl NSErr {
-    pub fn new() -> Self {
-        Self(NSError(ptr::null_mut()))
-    }
-
-    pub fn is_err(&self) -> bool {
-        !self.0 .0.is_null()
-    }
-}
-
-impl From<NSErr> for UsbError {
-    fn from(err: NSErr) -> UsbError {
-        //NOTE: this is the same as `kern_return_t`
-        match unsafe { err.0.code() } {
-            _ => todo!(),
-        }
-    }
-}
-
-impl Deref for NSErr {
-    type Target = NSError;
-    fn deref(&self) -> &NSError {
-        &self.0
-    }
-}
-
-impl DerefMut for NSErr {
-    fn deref_mut(&mut self) -> &mut NSError {
-        &mut self.0
-    }
-}
-
-///NOTE: this is commonly referred to as `altsetting`
-pub struct InterfaceDescriptor<'a> {
-    inner: NonNull<IOUSBInterfaceDescriptor>,
-    lt: PhantomData<&'a IOUSBInterfaceDescriptor>,
-}
-
-impl InterfaceDescriptor<'_> {
-    fn new(ptr: *const IOUSBInterfaceDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBInterfaceDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn interface_number(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterfaceNumber }
-    }
-
-    pub fn alternate_setting(&self) -> u8 {
-        unsafe { self.inner.as_ref().bAlternateSetting }
-    }
-
-    pub fn endpoint_count(&self) -> u8 {
-        unsafe { self.inner.as_ref().bNumEndpoints }
-    }
-
-    pub fn interface_class(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterfaceClass }
-    }
-
-    pub fn interface_subclass(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterfaceSubClass }
-    }
-
-    pub fn interface_protocol(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterfaceProtocol }
-    }
-
-    pub fn interface(&self) -> u8 {
-        unsafe { self.inner.as_ref().iInterface }
-    }
-}
-
-pub struct DeviceDescriptor<'a> {
-    inner: NonNull<IOUSBDeviceDescriptor>,
-    lt: PhantomData<&'a IOUSBDeviceDescriptor>,
-}
-
-impl Drop for DeviceDescriptor<'_> {
-    fn drop(&mut self) {}
-}
-
-impl DeviceDescriptor<'_> {
-    fn new(ptr: *const IOUSBDeviceDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn bcd_usb(&self) -> u16 {
-        unsafe { self.inner.as_ref().bcdUSB }
-    }
-
-    pub fn device_class(&self) -> u8 {
-        unsafe { self.inner.as_ref().bDeviceClass }
-    }
-
-    pub fn device_subclass(&self) -> u8 {
-        unsafe { self.inner.as_ref().bDeviceSubClass }
-    }
-
-    pub fn device_protocol(&self) -> u8 {
-        unsafe { self.inner.as_ref().bDeviceProtocol }
-    }
-
-    pub fn max_packet_size(&self) -> u8 {
-        unsafe { self.inner.as_ref().bMaxPacketSize0 }
-    }
-
-    pub fn vendor_id(&self) -> u16 {
-        unsafe { self.inner.as_ref().idVendor }
-    }
-
-    pub fn product_id(&self) -> u16 {
-        unsafe { self.inner.as_ref().idProduct }
-    }
-
-    pub fn bcd_device(&self) -> u16 {
-        unsafe { self.inner.as_ref().bcdDevice }
-    }
-
-    pub fn manufacturer(&self) -> u8 {
-        unsafe { self.inner.as_ref().iManufacturer }
-    }
-
-    pub fn product(&self) -> u8 {
-        unsafe { self.inner.as_ref().iProduct }
-    }
-
-    pub fn serial_number(&self) -> u8 {
-        unsafe { self.inner.as_ref().iSerialNumber }
-    }
-
-    pub fn configuration_count(&self) -> u8 {
-        unsafe { self.inner.as_ref().bNumConfigurations }
-    }
-}
-
-pub struct ConfigurationDescriptor<'a> {
-    inner: NonNull<IOUSBConfigurationDescriptor>,
-    lt: PhantomData<&'a IOUSBConfigurationDescriptor>,
-}
-
-impl ConfigurationDescriptor<'_> {
-    fn new(ptr: *const IOUSBConfigurationDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBConfigurationDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn total_length(&self) -> u16 {
-        unsafe { self.inner.as_ref().wTotalLength }
-    }
-
-    pub fn interface_count(&self) -> u8 {
-        unsafe { self.inner.as_ref().bNumInterfaces }
-    }
-
-    pub fn configuration_value(&self) -> u8 {
-        unsafe { self.inner.as_ref().bConfigurationValue }
-    }
-
-    pub fn configuration(&self) -> u8 {
-        unsafe { self.inner.as_ref().iConfiguration }
-    }
-
-    pub fn attributes(&self) -> u8 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-
-    pub fn max_power(&self) -> u8 {
-        unsafe { self.inner.as_ref().MaxPower }
-    }
-
-    pub fn max_power_milliamps(&self, usb_device_speed: u32) -> u32 {
-        unsafe { IOUSBGetConfigurationMaxPowerMilliAmps(usb_device_speed, self.inner.as_ref()) }
-    }
-}
-
-pub struct Descriptors<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-pub struct DescriptorHeader<'a> {
-    inner: NonNull<IOUSBDescriptorHeader>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DescriptorHeader<'_> {
-    fn new(ptr: *const IOUSBDescriptorHeader) -> Self {
-        let ptr = unsafe { NonNull::new_unchecked(ptr as *mut IOUSBDescriptorHeader) };
-        Self {
-            inner: ptr,
-            lt: PhantomData,
-        }
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-}
-
-impl<'a> Iterator for Descriptors<'a> {
-    type Item = DescriptorHeader<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next =
-            unsafe { IOUSBGetNextDescriptor(self.config_descriptor, self.current_descriptor) };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next;
-        Some(DescriptorHeader::new(next))
-    }
-}
-
-pub struct TypedDescriptors<'a> {
-    descriptor_type: u8,
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for TypedDescriptors<'a> {
-    type Item = DescriptorHeader<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextDescriptorWithType(
-                self.config_descriptor,
-                self.current_descriptor,
-                self.descriptor_type,
-            )
-        };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next;
-        Some(DescriptorHeader::new(next))
-    }
-}
-
-pub struct AssociatedDescriptors<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    assoc_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for AssociatedDescriptors<'a> {
-    type Item = DescriptorHeader<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextAssociatedDescriptor(
-                self.config_descriptor,
-                self.assoc_descriptor,
-                self.current_descriptor,
-            )
-        };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next;
-        Some(DescriptorHeader::new(next))
-    }
-}
-
-pub struct TypedAssociatedDescriptors<'a> {
-    descriptor_type: u8,
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    assoc_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for TypedAssociatedDescriptors<'a> {
-    type Item = DescriptorHeader<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextAssociatedDescriptorWithType(
-                self.config_descriptor,
-                self.assoc_descriptor,
-                self.current_descriptor,
-                self.descriptor_type,
-            )
-        };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next;
-        Some(DescriptorHeader::new(next))
-    }
-}
-
-pub struct InterfaceAssociationDescriptors<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for InterfaceAssociationDescriptors<'a> {
-    type Item = InterfaceAssociationDescriptor<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextInterfaceAssociationDescriptor(
-                self.config_descriptor,
-                self.current_descriptor,
-            )
-        };
-
-        if next.is_null() {
-            return None;
-        }
-
-        self.current_descriptor = next as *const IOUSBDescriptorHeader;
-        InterfaceAssociationDescriptor::new(next)
-    }
-}
-
-pub struct InterfaceAssociationDescriptor<'a> {
-    inner: NonNull<IOUSBInterfaceAssociationDescriptor>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl InterfaceAssociationDescriptor<'_> {
-    fn new(raw: *const IOUSBInterfaceAssociationDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(raw as *mut IOUSBInterfaceAssociationDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn first_interface(&self) -> u8 {
-        unsafe { self.inner.as_ref().bFirstInterface }
-    }
-
-    pub fn interface_count(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterfaceCount }
-    }
-
-    pub fn function_class(&self) -> u8 {
-        unsafe { self.inner.as_ref().bFunctionClass }
-    }
-
-    pub fn function_subclass(&self) -> u8 {
-        unsafe { self.inner.as_ref().bFunctionSubClass }
-    }
-
-    pub fn function(&self) -> u8 {
-        unsafe { self.inner.as_ref().iFunction }
-    }
-}
-
-pub struct InterfaceDescriptors<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for InterfaceDescriptors<'a> {
-    type Item = InterfaceDescriptor<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextInterfaceDescriptor(self.config_descriptor, self.current_descriptor)
-        };
-
-        if next.is_null() {
-            return None;
-        }
-
-        let desc = InterfaceDescriptor::new(next)?;
-        self.current_descriptor = next as *const IOUSBDescriptorHeader;
-        Some(desc)
-    }
-}
-
-pub struct Interfaces<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    options: HostObjectInitOptions,
-    queue: Queue,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for Interfaces<'a> {
-    type Item = HostInterface<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextInterfaceDescriptor(self.config_descriptor, self.current_descriptor)
-        };
-
-        if next.is_null() {
-            return None;
-        }
-
-        let vendor_id = 0;
-        let product_id = 0;
-
-        match unsafe {
-            HostInterface::create_matching_dictionary::<0>(
-                Some(vendor_id),
-                Some(product_id),
-                None,
-                Some((*next).bInterfaceNumber),
-                Some((*self.config_descriptor).bConfigurationValue),
-                Some((*next).bInterfaceClass),
-                Some((*next).bInterfaceSubClass),
-                Some((*next).bInterfaceProtocol),
-                None,
-            )
-        } {
-            Ok(dict) => {
-                let service = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, dict) };
-
-                let mut err = NSErr::new();
-
-                let interface = IOUSBHostInterface::alloc();
-                let interface = unsafe {
-                    IIOUSBHostInterface::initWithIOService_options_queue_error_interestHandler_(
-                        &interface,
-                        service,
-                        self.options.into(),
-                        self.queue.inner,
-                        &mut *err,
-                        0 as *mut c_void,
-                    )
-                };
-
-                if err.is_err() {
-                    println!("error while enumerating interface descriptors: {:?}", err.0);
-                    return None;
-                }
-                let interface = HostInterface::new(interface as *const IOUSBHostInterface)?;
-                self.current_descriptor = next as *const IOUSBDescriptorHeader;
-                Some(interface)
-            }
-            Err(e) => {
-                println!("error while enumerating interface descriptors: {:?}", e);
-                None
-            }
-        }
-    }
-}
-
-pub struct EndpointDescriptors<'a> {
-    config_descriptor: *const IOUSBConfigurationDescriptor,
-    interface_descriptor: *const IOUSBInterfaceDescriptor,
-    current_descriptor: *const IOUSBDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for EndpointDescriptors<'a> {
-    type Item = EndpointDescriptor<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextEndpointDescriptor(
-                self.config_descriptor,
-                self.interface_descriptor,
-                self.current_descriptor,
-            )
-        };
-        if next.is_null() {
-            return None;
-        }
-        self.current_descriptor = next as *const IOUSBDescriptorHeader;
-        EndpointDescriptor::new(next)
-    }
-}
-
-pub struct CapabilityDescriptor<'a> {
-    inner: NonNull<IOUSBBOSDescriptor>,
-    lt: PhantomData<&'a IOUSBBOSDescriptor>,
-}
-
-impl CapabilityDescriptor<'_> {
-    fn new(ptr: *const IOUSBBOSDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBBOSDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn capabilities(&self) -> impl Iterator<Item = Capability<'_>> {
-        let current_descriptor = ptr::null();
-        Capabilities {
-            current_descriptor,
-            bos_descriptor: unsafe { self.inner.as_ref() },
-            lt: PhantomData,
-        }
-    }
-
-    pub fn capabilities_with_type(
-        &self,
-        capability_type: u8,
-    ) -> impl Iterator<Item = Capability<'_>> {
-        let current_descriptor = ptr::null();
-        TypedCapabilities {
-            current_descriptor,
-            capability_type,
-            bos_descriptor: unsafe { self.inner.as_ref() },
-            lt: PhantomData,
-        }
-    }
-
-    pub fn usb_20_extension_device_capability_descriptor(
-        &self,
-    ) -> Option<DeviceCapabilityUsb2Extension<'_>> {
-        let ptr = unsafe { IOUSBGetUSB20ExtensionDeviceCapabilityDescriptor(self.inner.as_ref()) };
-        DeviceCapabilityUsb2Extension::new(ptr)
-    }
-
-    pub fn super_speed_device_capability_descriptor(&self) -> Option<DeviceCapabilitySS<'_>> {
-        let ptr = unsafe { IOUSBGetSuperSpeedDeviceCapabilityDescriptor(self.inner.as_ref()) };
-        DeviceCapabilitySS::new(ptr)
-    }
-
-    pub fn super_speed_plus_capability_descriptor(&self) -> Option<DeviceCapabilitySSP<'_>> {
-        DeviceCapabilitySSP::new(unsafe {
-            IOUSBGetSuperSpeedPlusDeviceCapabilityDescriptor(self.inner.as_ref())
-        })
-    }
-
-    pub fn container_id_descriptor(&self) -> Option<DeviceCapabilityContainerId<'_>> {
-        let ptr = unsafe { IOUSBGetContainerIDDescriptor(self.inner.as_ref()) };
-        DeviceCapabilityContainerId::new(ptr)
-    }
-
-    pub fn platform_capability_descriptor(
-        &self,
-        uuid: Option<&str>,
-    ) -> Option<PlatformCapabilityDescriptor<'_>> {
-        let ptr = unsafe {
-            match uuid {
-                Some(uuid) => IOUSBGetPlatformCapabilityDescriptorWithUUID(
-                    self.inner.as_ref(),
-                    uuid.as_bytes().as_ptr() as *mut u8,
-                ),
-                None => IOUSBGetPlatformCapabilityDescriptor(self.inner.as_ref()),
-            }
-        };
-        PlatformCapabilityDescriptor::new(ptr)
-    }
-
-    pub fn billboard_descriptor(&self) -> Option<DeviceCapabilityBillboard> {
-        DeviceCapabilityBillboard::new(unsafe { IOUSBGetBillboardDescriptor(self.inner.as_ref()) })
-    }
-}
-
-pub struct DeviceCapabilityUsb2Extension<'a> {
-    inner: NonNull<IOUSBDeviceCapabilityUSB2Extension>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilityUsb2Extension<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilityUSB2Extension) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityUSB2Extension)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    pub fn attributes(&self) -> u32 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-}
-
-pub struct DeviceCapabilitySS<'a> {
-    inner: NonNull<IOUSBDeviceCapabilitySuperSpeedUSB>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilitySS<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilitySuperSpeedUSB) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilitySuperSpeedUSB)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    pub fn attributes(&self) -> u8 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-
-    pub fn speeds_supported(&self) -> u16 {
-        unsafe { self.inner.as_ref().wSpeedsSupported }
-    }
-
-    pub fn functionality_support(&self) -> u8 {
-        unsafe { self.inner.as_ref().bFunctionalitySupport }
-    }
-
-    pub fn u1_dev_exit_lat(&self) -> u8 {
-        unsafe { self.inner.as_ref().bU1DevExitLat }
-    }
-
-    pub fn u2_dev_exit_lat(&self) -> u16 {
-        unsafe { self.inner.as_ref().wU2DevExitLat }
-    }
-
-    pub fn dev_exit_lat(&self) -> (u8, u16) {
-        let ptr = unsafe { self.inner.as_ref() };
-        (ptr.bU1DevExitLat, ptr.wU2DevExitLat)
-    }
-}
-
-pub struct DeviceCapabilitySSP<'a> {
-    inner: NonNull<IOUSBDeviceCapabilitySuperSpeedPlusUSB>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilitySSP<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilitySuperSpeedPlusUSB) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilitySuperSpeedPlusUSB)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    pub fn attributes(&self) -> u32 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-
-    pub fn functionality_support(&self) -> u16 {
-        unsafe { self.inner.as_ref().wFunctionalitySupport }
-    }
-
-    pub fn sublink_speed_attributes(&self) -> impl Iterator<Item = u32> {
-        let ptr = unsafe { self.inner.as_ref() };
-        let ptr = ptr::addr_of!(ptr.bmSublinkSpeedAttr);
-        SublinkSpeedAttrs {
-            inner: ptr as *const u32,
-            lt: PhantomData,
-        }
-    }
-}
-
-pub struct SublinkSpeedAttrs<'a> {
-    inner: *const u32,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for SublinkSpeedAttrs<'a> {
-    type Item = u32;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.inner.is_null() {
-            return None;
-        }
-        let item = unsafe { self.inner.read_unaligned() };
-        self.inner = unsafe { self.inner.add(1) };
-        Some(item.clone())
-    }
-}
-
-pub struct DeviceCapabilityContainerId<'a> {
-    inner: NonNull<IOUSBDeviceCapabilityContainerID>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilityContainerId<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilityContainerID) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityContainerID)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    pub fn reserved_id(&self) -> u8 {
-        unsafe { self.inner.as_ref().bReservedID }
-    }
-
-    pub fn container_id(&self) -> &[u8; 16] {
-        unsafe { &self.inner.as_ref().containerID }
-    }
-}
-
-pub struct PlatformCapabilityDescriptor<'a> {
-    inner: NonNull<IOUSBPlatformCapabilityDescriptor>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl PlatformCapabilityDescriptor<'_> {
-    fn new(ptr: *const IOUSBPlatformCapabilityDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBPlatformCapabilityDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    pub fn platform_capability_uuid(&self) -> &[u8; 16] {
-        unsafe { &self.inner.as_ref().PlatformCapabilityUUID }
-    }
-}
-
-pub struct DeviceCapabilityBillboard<'a> {
-    inner: NonNull<IOUSBDeviceCapabilityBillboard>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilityBillboard<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilityBillboard) -> Option<Self> {
-        let inner = NonNull::new(ptr as *mut IOUSBDeviceCapabilityBillboard)?;
-        Some(Self {
-            inner,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-
-    ///index of string descriptor providing a URL for detailed information about the product and
-    ///supported modes
-    pub fn additional_info_url(&self) -> u8 {
-        unsafe { self.inner.as_ref().iAdditionalInfoURL }
-    }
-
-    pub fn alternate_modes_count(&self) -> u8 {
-        unsafe { self.inner.as_ref().bNumberOfAlternateModes }
-    }
-
-    pub fn preferred_alternate_mode(&self) -> u8 {
-        unsafe { self.inner.as_ref().bPreferredAlternateMode }
-    }
-
-    pub fn connection_power(&self) -> u16 {
-        unsafe { self.inner.as_ref().vCONNPower }
-    }
-
-    pub fn configured(&self) -> &[u8; 32] {
-        unsafe { &self.inner.as_ref().bmConfigured }
-    }
-
-    pub fn bcd_version(&self) -> u16 {
-        unsafe { self.inner.as_ref().bcdVersion }
-    }
-
-    pub fn additional_failure_info(&self) -> u8 {
-        unsafe { self.inner.as_ref().bAdditionalFailureInfo }
-    }
-
-    pub fn alt_configurations(
-        &self,
-    ) -> impl Iterator<Item = DeviceCapabilityBillboardAltConfiguration<'_>> {
-        let configs = unsafe { &self.inner.as_ref().pAltConfigurations };
-        DeviceCapabilityBillboardAltConfigurations {
-            inner: configs.as_ptr(),
-            lt: PhantomData,
-        }
-    }
-}
-
-pub struct DeviceCapabilityBillboardAltConfigurations<'a> {
-    inner: *const IOUSBDeviceCapabilityBillboardAltConfig,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for DeviceCapabilityBillboardAltConfigurations<'a> {
-    type Item = DeviceCapabilityBillboardAltConfiguration<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = DeviceCapabilityBillboardAltConfiguration::new(self.inner)?;
-        self.inner = unsafe { self.inner.add(1) };
-        Some(next)
-    }
-}
-
-pub struct DeviceCapabilityBillboardAltConfiguration<'a> {
-    inner: NonNull<IOUSBDeviceCapabilityBillboardAltConfig>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl DeviceCapabilityBillboardAltConfiguration<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilityBillboardAltConfig) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBDeviceCapabilityBillboardAltConfig)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-
-    pub fn svid(&self) -> u16 {
-        unsafe { self.inner.as_ref().wSVID }
-    }
-
-    pub fn altenate_mode(&self) -> u8 {
-        unsafe { self.inner.as_ref().bAltenateMode }
-    }
-
-    /// index for alternate mode settings
-    pub fn alternate_mode_setting(&self) -> u8 {
-        unsafe { self.inner.as_ref().iAlternateModeString }
-    }
-}
-
-pub struct CapabilityDescriptors<'a> {
-    inner: *const IOUSBBOSDescriptor,
-    lt: PhantomData<&'a ()>,
-}
-
-pub struct Capabilities<'a> {
-    bos_descriptor: *const IOUSBBOSDescriptor,
-    current_descriptor: *const IOUSBDeviceCapabilityDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for Capabilities<'a> {
-    type Item = Capability<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextCapabilityDescriptor(self.bos_descriptor, self.current_descriptor)
-        };
-
-        if next.is_null() {
-            return None;
-        }
-
-        self.current_descriptor = next;
-        Some(Capability::new(next))
-    }
-}
-
-pub struct TypedCapabilities<'a> {
-    capability_type: u8,
-    bos_descriptor: *const IOUSBBOSDescriptor,
-    current_descriptor: *const IOUSBDeviceCapabilityDescriptorHeader,
-    lt: PhantomData<&'a ()>,
-}
-
-impl<'a> Iterator for TypedCapabilities<'a> {
-    type Item = Capability<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = unsafe {
-            IOUSBGetNextCapabilityDescriptorWithType(
-                self.bos_descriptor,
-                self.current_descriptor,
-                self.capability_type,
-            )
-        };
-
-        if next.is_null() {
-            return None;
-        }
-
-        self.current_descriptor = next;
-        Some(Capability::new(next))
-    }
-}
-
-pub struct Capability<'a> {
-    inner: NonNull<IOUSBDeviceCapabilityDescriptorHeader>,
-    lt: PhantomData<&'a IOUSBDeviceCapabilityDescriptorHeader>,
-}
-
-impl Capability<'_> {
-    fn new(ptr: *const IOUSBDeviceCapabilityDescriptorHeader) -> Self {
-        let ptr =
-            unsafe { NonNull::new_unchecked(ptr as *mut IOUSBDeviceCapabilityDescriptorHeader) };
-        Self {
-            inner: ptr,
-            lt: PhantomData,
-        }
-    }
-
-    pub fn length(&self) -> u8 {
-        unsafe { self.inner.as_ref().bLength }
-    }
-
-    pub fn descriptor_type(&self) -> DescriptorType {
-        unsafe { self.inner.as_ref().bDescriptorType }.into()
-    }
-
-    pub fn device_capability_type(&self) -> DeviceCapabilityType {
-        unsafe { self.inner.as_ref().bDevCapabilityType }.into()
-    }
-}
-
-impl<'a> Iterator for CapabilityDescriptors<'a> {
-    type Item = CapabilityDescriptor<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let ptr = unsafe { self.inner.add(1) };
-        let next = CapabilityDescriptor::new(ptr)?;
-        self.inner = ptr;
-        Some(next)
-    }
-}
-
-impl Drop for CapabilityDescriptor<'_> {
-    fn drop(&mut self) {}
-}
-
-pub struct EndpointDescriptor<'a> {
-    inner: NonNull<IOUSBEndpointDescriptor>,
-    lt: PhantomData<&'a IOUSBEndpointDescriptor>,
-}
-
-impl EndpointDescriptor<'_> {
-    fn new(ptr: *const IOUSBEndpointDescriptor) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBEndpointDescriptor)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-    const SIZE: u8 = 7;
-
-    pub fn size(&self) -> u8 {
-        Self::SIZE
-    }
-
-    pub fn descriptor_type(&self) -> u8 {
-        unsafe { self.inner.as_ref().bDescriptorType }
-    }
-
-    pub fn endpoint_address(&self) -> u8 {
-        unsafe { self.inner.as_ref().bEndpointAddress }
-    }
-
-    pub fn interval(&self) -> u8 {
-        unsafe { self.inner.as_ref().bInterval }
-    }
-
-    pub fn attributes(&self) -> u8 {
-        unsafe { self.inner.as_ref().bmAttributes }
-    }
-
-    pub fn max_packet_size(&self) -> u16 {
-        unsafe { self.inner.as_ref().wMaxPacketSize }
-    }
-
-    pub fn synchronization_type(&self) -> SynchronizationType {
-        unsafe { IOUSBGetEndpointType(self.inner.as_ref()) }.into()
-    }
-
-    pub fn endpoint_direction(&self) -> EndpointDirection {
-        unsafe { IOUSBGetEndpointDirection(self.inner.as_ref()) }.into()
-    }
-
-    pub fn endpoint_number(&self) -> u8 {
-        unsafe { IOUSBGetEndpointNumber(self.inner.as_ref()) }
-    }
-
-    pub fn max_packet_size_with_device_speed(&self, usb_device_speed: u32) -> u16 {
-        unsafe { IOUSBGetEndpointMaxPacketSize(usb_device_speed, self.inner.as_ref()) }
-    }
-
-    pub fn burst_size(
-        &self,
-        usb_device_speed: u32,
-        super_speed_companion: &SuperSpeedCompanionDescriptor<'_>,
-        super_speed_plus_companion: &SuperSpeedPlusCompanionDescriptor<'_>,
-    ) -> u32 {
-        unsafe {
-            IOUSBGetEndpointBurstSize(
-                usb_device_speed,
-                self.inner.as_ref(),
-                super_speed_companion.inner.as_ref(),
-                super_speed_plus_companion.inner.as_ref(),
-            )
-        }
-    }
-
-    pub fn multiplier(
-        &self,
-        usb_device_speed: u32,
-        super_speed_companion: &SuperSpeedCompanionDescriptor<'_>,
-        super_speed_plus_companion: &SuperSpeedPlusCompanionDescriptor<'_>,
-    ) -> u8 {
-        unsafe {
-            IOUSBGetEndpointMult(
-                usb_device_speed,
-                self.inner.as_ref(),
-                super_speed_companion.inner.as_ref(),
-                super_speed_plus_companion.inner.as_ref(),
-            )
-        }
-    }
-
-    pub fn interval_encoded_microframes(&self, usb_device_speed: u32) -> u32 {
-        unsafe { IOUSBGetEndpointIntervalEncodedMicroframes(usb_device_speed, self.inner.as_ref()) }
-    }
-
-    pub fn interval_microframes(&self, usb_device_speed: u32) -> u32 {
-        unsafe { IOUSBGetEndpointIntervalMicroframes(usb_device_speed, self.inner.as_ref()) }
-    }
-
-    pub fn interval_frames(&self, usb_device_speed: u32) -> u32 {
-        unsafe { IOUSBGetEndpointIntervalFrames(usb_device_speed, self.inner.as_ref()) }
-    }
-
-    pub fn max_streams_encoded(
-        &self,
-        usb_device_speed: u32,
-        super_speed_companion: &SuperSpeedCompanionDescriptor<'_>,
-    ) -> u32 {
-        unsafe {
-            IOUSBGetEndpointMaxStreamsEncoded(
-                usb_device_speed,
-                self.inner.as_ref(),
-                super_speed_companion.inner.as_ref(),
-            )
-        }
-    }
-
-    pub fn max_streams(
-        &self,
-        usb_device_speed: u32,
-        super_speed_companion: &SuperSpeedCompanionDescriptor<'_>,
-    ) -> u32 {
-        unsafe {
-            IOUSBGetEndpointMaxStreams(
-                usb_device_speed,
-                self.inner.as_ref(),
-                super_speed_companion.inner.as_ref(),
-            )
-        }
-    }
-}
-
-pub struct UsbHostObject<'a> {
-    inner: NonNull<IOUSBHostObject>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl Drop for UsbHostObject<'_> {
-    fn drop(&mut self) {
-        unsafe { self.inner.as_ref().destroy() }
-    }
-}
-
-pub struct DescriptorOptions {
-    descriptor_type: DescriptorType,
-    length: u64,
-    language_options: Option<LanguageOptions>,
-}
-
-impl DescriptorOptions {
-    pub fn new(
-        descriptor_type: DescriptorType,
-        length: u64,
-        language_options: Option<LanguageOptions>,
-    ) -> Self {
-        Self {
-            descriptor_type,
-            length,
-            language_options,
-        }
-    }
-}
-
-pub struct LanguageOptions {
-    index: u64,
-    language_id: u64,
-    request_options: Option<RequestOptions>,
-}
-
-impl LanguageOptions {
-    pub fn new(index: u64, language_id: u64, request_options: Option<RequestOptions>) -> Self {
-        Self {
-            index,
-            language_id,
-            request_options,
-        }
-    }
-}
-
-pub struct RequestOptions {
-    request_type: DeviceRequestTypeValue,
-    request_recipient: DeviceRequestRecipientValue,
-}
-
-impl RequestOptions {
-    pub fn new(
-        request_type: DeviceRequestTypeValue,
-        request_recipient: DeviceRequestRecipientValue,
-    ) -> Self {
-        Self {
-            request_type,
-            request_recipient,
-        }
-    }
-}
-
-impl UsbHostObject<'_> {
-    pub fn send_device_request_with_data(
-        &self,
-        request: DeviceRequest,
-        data: &mut [u8],
-    ) -> Result<u64, UsbError> {
-        let data = MutData::with_data(data).raw();
-        let mut err = NSErr::new();
-        let mut transferred = 0;
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendDeviceRequest_data_bytesTransferred_completionTimeout_error_(
-                    request.into(),
-                    data,
-                    &mut transferred,
-                    0.0,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(transferred)
-        }
-    }
-
-    pub fn send_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .sendDeviceRequest_error_(request.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub async fn enqueue_device_request_with_data(
-        &self,
-        request: DeviceRequest,
-        data: &[u8],
-    ) -> Result<(), UsbError> {
-        let handler = AsyncDataHandler::new(self.inner, data, |dev, data, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueDeviceRequest_data_completionTimeout_error_completionHandler_(
-                    request.into(),
-                    data,
-                    0.0,
-                    &mut *err,
-                    cb,
-                )
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-
-        handler.await
-    }
-
-    pub async fn enqueue_device_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
-        let handler = AsyncHandler::new(self.inner, |dev, cb| {
-            let cb = unsafe { downcast_tait(cb) };
-            let mut err = NSErr::new();
-            if !unsafe {
-                dev.enqueueDeviceRequest_error_completionHandler_(request.into(), &mut *err, cb)
-            } {
-                Some(err.into())
-            } else {
-                None
-            }
-        });
-        handler.await
-    }
-
-    pub fn abort_device_requests(&self, option: AbortOption) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .as_ref()
-                .abortDeviceRequestsWithOption_error_(option.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn descriptor(&self, options: DescriptorOptions) -> Result<DescriptorHeader<'_>, UsbError> {
-        let mut err = NSErr::new();
-        let DescriptorOptions {
-            descriptor_type,
-            mut length,
-            language_options,
-        } = options;
-        let descriptor_type: u8 = descriptor_type.into();
-        let desc = unsafe {
-            match language_options {
-                Some(LanguageOptions {
-                    index,
-                    language_id,
-                    request_options:
-                        Some(RequestOptions {
-                            request_type,
-                            request_recipient,
-                        }),
-                }) => self
-                    .inner
-                    .as_ref()
-                    .descriptorWithType_length_index_languageID_requestType_requestRecipient_error_(
-                        descriptor_type as u32,
-                        &mut length,
-                        index,
-                        language_id,
-                        request_type.into(),
-                        request_recipient.into(),
-                        &mut *err,
-                    ),
-                Some(LanguageOptions {
-                    index,
-                    language_id,
-                    request_options: None,
-                }) => self
-                    .inner
-                    .as_ref()
-                    .descriptorWithType_length_index_languageID_error_(
-                        descriptor_type as u32,
-                        &mut length,
-                        index,
-                        language_id,
-                        &mut *err,
-                    ),
-                None => self.inner.as_ref().descriptorWithType_length_error_(
-                    descriptor_type as u32,
-                    &mut length,
-                    &mut *err,
-                ),
-            }
-        };
-
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(DescriptorHeader::new(desc))
-        }
-    }
-
-    pub fn string_descriptor(
-        &self,
-        index: u64,
-        language_id: Option<u64>,
-    ) -> Result<NSString, UsbError> {
-        let mut err = NSErr::new();
-        let desc = unsafe {
-            match language_id {
-                Some(id) => self
-                    .inner
-                    .as_ref()
-                    .stringWithIndex_languageID_error_(index, id, &mut *err),
-                None => self.inner.as_ref().stringWithIndex_error_(index, &mut *err),
-            }
-        };
-
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(desc)
-        }
-    }
-
-    pub fn configuration_descriptors(&self) -> impl Iterator<Item = ConfigurationDescriptor<'_>> {
-        let count = self.device_descriptor().unwrap().configuration_count();
-        let idx = 0;
-        ConfigurationDescriptors {
-            configuration_count: count,
-            idx,
-            dev: unsafe { self.inner.as_ref() },
-        }
-    }
-
-    pub fn configuration_descriptor_with_value(
-        &self,
-        val: u64,
-    ) -> Result<ConfigurationDescriptor<'_>, UsbError> {
-        let mut err = NSErr::new();
-        let desc = unsafe {
-            self.inner
-                .as_ref()
-                .configurationDescriptorWithConfigurationValue_error_(val, &mut *err)
-        };
-
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(ConfigurationDescriptor::new(desc).unwrap())
-        }
-    }
-
-    //returns the current frame number, but also updates the host time aligned with the time which
-    //the frame number was last updated
-    pub fn frame_number(&self, time: &mut HostTime) -> u64 {
-        unsafe { self.inner.as_ref().frameNumberWithTime_(&mut time.inner) }
-    }
-
-    pub fn io_data(&self, capacity: u64) -> Result<NSMutableData, UsbError> {
-        let mut err = NSErr::new();
-        let data = unsafe {
-            self.inner
-                .as_ref()
-                .ioDataWithCapacity_error_(capacity, &mut *err)
-        };
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(data)
-        }
-    }
-
-    pub fn queue(&self) -> Queue {
-        Queue::new(unsafe { self.inner.as_ref().queue() })
-    }
-
-    pub fn device_descriptor(&self) -> Option<DeviceDescriptor> {
-        let ptr = unsafe { self.inner.as_ref().deviceDescriptor() };
-        DeviceDescriptor::new(ptr)
-    }
-
-    pub fn capability_descriptors(&self) -> impl Iterator<Item = CapabilityDescriptor<'_>> {
-        let ptr = unsafe { self.inner.as_ref().capabilityDescriptors() };
-        CapabilityDescriptors {
-            inner: ptr,
-            lt: PhantomData,
-        }
-    }
-
-    pub fn device_address(&self) -> u64 {
-        unsafe { self.inner.as_ref().deviceAddress() }
-    }
-}
-
-pub enum AbortOption {
-    Asynchronous = 0,
-    Synchronous = 1,
-}
-
-impl From<AbortOption> for IOUSBHostAbortOption {
-    fn from(option: AbortOption) -> IOUSBHostAbortOption {
-        use AbortOption as AO;
-        match option {
-            AO::Asynchronous => 0,
-            AO::Synchronous => 1,
-        }
-    }
-}
-
-pub struct ConfigurationDescriptors<'a> {
-    dev: &'a IOUSBHostObject,
-    idx: u8,
-    configuration_count: u8,
-}
-
-impl<'a> Iterator for ConfigurationDescriptors<'a> {
-    type Item = ConfigurationDescriptor<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx == self.configuration_count - 1 {
-            return None;
-        }
-
-        let mut err = NSErr::new();
-        let ptr = unsafe {
-            self.dev
-                .configurationDescriptorWithIndex_error_(self.idx as u64, &mut *err)
-        };
-
-        if err.is_err() {
-            let err: UsbError = err.into();
-            println!("err while enumerating configuration descriptors: {:?}", err);
-            return None;
-        }
-
-        self.idx += 1;
-        ConfigurationDescriptor::new(ptr)
-    }
-}
-
-pub struct EndpointStateMachine {
-    inner: IOUSBHostCIEndpointStateMachine,
-}
-
-impl EndpointStateMachine {
-    pub fn inspect_command(&self, command: &Message<'_>) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .inspectCommand_error_(command.inner.as_ref(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn respond(&self, command: &Message<'_>, status: MessageStatus) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner.respondToCommand_status_error_(
-                command.inner.as_ref(),
-                status.into(),
-                &mut *err,
-            )
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn process_doorbell(&self, doorbell: u32) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe { self.inner.processDoorbell_error_(doorbell, &mut *err) } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn enqueue_transfer_completion_for_message(
-        &self,
-        message: &Message<'_>,
-        status: MessageStatus,
-        transfer_length: u64,
-    ) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .enqueueTransferCompletionForMessage_status_transferLength_error_(
-                    message.inner.as_ref(),
-                    status.into(),
-                    transfer_length,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn endpoint_state(&self) -> EndpointState {
-        unsafe { self.inner.endpointState() }.into()
-    }
-
-    pub fn device_address(&self) -> u64 {
-        unsafe { self.inner.deviceAddress() }
-    }
-
-    pub fn endpoint_address(&self) -> u64 {
-        unsafe { self.inner.endpointAddress() }
-    }
-
-    pub fn current_transfer_message(&mut self) -> Option<Message<'_>> {
-        Message::new(unsafe { self.inner.currentTransferMessage() })
-    }
-
-    pub fn controller_interface(&self) -> ControllerInterface {
-        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
-    }
-}
-
-pub struct Message<'a> {
-    inner: NonNull<IOUSBHostCIMessage>,
-    lt: PhantomData<&'a ()>,
-}
-
-impl Message<'_> {
-    fn new(ptr: *const IOUSBHostCIMessage) -> Option<Self> {
-        let ptr = NonNull::new(ptr as *mut IOUSBHostCIMessage)?;
-        Some(Self {
-            inner: ptr,
-            lt: PhantomData,
-        })
-    }
-    pub fn control(&self) -> u32 {
-        unsafe { self.inner.as_ref().control }
-    }
-
-    pub fn data_0(&self) -> u32 {
-        unsafe { self.inner.as_ref().data0 }
-    }
-
-    pub fn data_1(&self) -> u64 {
-        unsafe { self.inner.as_ref().data1 }
-    }
-
-    pub fn data(&self) -> (u32, u64) {
-        let msg = unsafe { self.inner.as_ref() };
-        (msg.data0, msg.data1)
-    }
-}
-
-pub struct ControllerInterface {
-    inner: IOUSBHostControllerInterface,
-}
-
-impl ControllerInterface {
-    fn new(inner: IOUSBHostControllerInterface) -> Self {
-        Self { inner }
-    }
-
-    pub fn enqueue_interrupts(
-        &self,
-        msg: &Message<'_>,
-        expedited: Option<bool>,
-        count: Option<u64>,
-    ) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        let is_err = unsafe {
-            match (expedited, count) {
-                (Some(expd), Some(count)) => self.inner.enqueueInterrupts_count_expedite_error_(
-                    msg.inner.as_ref(),
-                    count,
-                    expd,
-                    &mut *err,
-                ),
-                (Some(expd), None) => {
-                    self.inner
-                        .enqueueInterrupt_expedite_error_(msg.inner.as_ref(), expd, &mut *err)
-                }
-                (None, Some(count)) => {
-                    self.inner
-                        .enqueueInterrupts_count_error_(msg.inner.as_ref(), count, &mut *err)
-                }
-                (None, None) => self
-                    .inner
-                    .enqueueInterrupt_error_(msg.inner.as_ref(), &mut *err),
-            }
-        };
-        if is_err {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn message_description(&self, msg: &Message<'_>) -> NSString {
-        unsafe { self.inner.descriptionForMessage_(msg.inner.as_ref()) }
-    }
-
-    pub fn port_state_machine_for_command(
-        &self,
-        cmd: &Message<'_>,
-    ) -> Result<PortStateMachine, UsbError> {
-        let mut err = NSErr::new();
-        let res = unsafe {
-            self.inner
-                .getPortStateMachineForCommand_error_(cmd.inner.as_ref(), &mut *err)
-        };
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(PortStateMachine::new(res))
-        }
-    }
-
-    pub fn port_state_machine_for_port(&self, port: u64) -> Result<PortStateMachine, UsbError> {
-        let mut err = NSErr::new();
-        let res = unsafe {
-            self.inner
-                .getPortStateMachineForPort_error_(port, &mut *err)
-        };
-        if err.is_err() {
-            Err(err.into())
-        } else {
-            Ok(PortStateMachine::new(res))
-        }
-    }
-
-    pub fn port_capabilities(&self, port: u64) -> Option<Message<'_>> {
-        Message::new(unsafe { self.inner.capabilitiesForPort_(port) })
-    }
-
-    pub fn queue(&self) -> Queue {
-        Queue::new(unsafe { self.inner.queue() })
-    }
-
-    pub fn interupt_rate_hz(&self) -> u64 {
-        unsafe { self.inner.interruptRateHz() }
-    }
-
-    pub fn set_interrupt_rate_hz(&self, rate: u64) {
-        unsafe { self.inner.setInterruptRateHz_(rate) }
-    }
-
-    pub fn controller_state_machine(&self) -> ControllerStateMachine {
-        ControllerStateMachine::new(unsafe { self.inner.controllerStateMachine() })
-    }
-
-    pub fn capabilities(&self) -> Option<Message<'_>> {
-        Message::new(unsafe { self.inner.capabilities() })
-    }
-
-    pub fn uuid(&self) -> NSUUID {
-        unsafe { self.inner.uuid() }
-    }
-}
-
-pub struct ControllerStateMachine {
-    inner: IOUSBHostCIControllerStateMachine,
-}
-
-impl ControllerStateMachine {
-    fn new(inner: IOUSBHostCIControllerStateMachine) -> Self {
-        Self { inner }
-    }
-
-    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn respond(
-        &self,
-        cmd: &Message<'_>,
-        status: MessageStatus,
-        frame_timestamp: Option<(u64, u64)>,
-    ) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        let is_err = unsafe {
-            match frame_timestamp {
-                Some((frame, timestamp)) => {
-                    self.inner.respondToCommand_status_frame_timestamp_error_(
-                        cmd.inner.as_ref(),
-                        status.into(),
-                        frame,
-                        timestamp,
-                        &mut *err,
-                    )
-                }
-                None => self.inner.respondToCommand_status_error_(
-                    cmd.inner.as_ref(),
-                    status.into(),
-                    &mut *err,
-                ),
-            }
-        };
-        if is_err {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn enqueue_updated(&self, frame: u64, timestamp: u64) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .enqueueUpdatedFrame_timestamp_error_(frame, timestamp, &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn controller_state(&self) -> ControllerState {
-        unsafe { self.inner.controllerState() }.into()
-    }
-
-    pub fn controller_interface(&self) -> ControllerInterface {
-        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
-    }
-}
-
-pub struct PortStateMachine {
-    inner: IOUSBHostCIPortStateMachine,
-}
-
-impl PortStateMachine {
-    fn new(inner: IOUSBHostCIPortStateMachine) -> Self {
-        Self { inner }
-    }
-
-    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn respond(&self, cmd: &Message<'_>, status: MessageStatus) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .respondToCommand_status_error_(cmd.inner.as_ref(), status.into(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn update_link_state(
-        &self,
-        link_state: LinkState,
-        speed: DeviceSpeed,
-        inhibit_link_state_change: bool,
-    ) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .updateLinkState_speed_inhibitLinkStateChange_error_(
-                    link_state.into(),
-                    speed.into(),
-                    inhibit_link_state_change,
-                    &mut *err,
-                )
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
-
-    pub fn port_state(&self) -> PortState {
-        unsafe { self.inner.portState() }.into()
-    }
-
-    pub fn port_status(&self) -> PortStatus {
-        unsafe { self.inner.portStatus() }.into()
-    }
-
-    pub fn controller_interface(&self) -> ControllerInterface {
-        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
-    }
-
-    pub fn powered(&self) -> bool {
-        unsafe { self.inner.powered() }
-    }
-
-    pub fn set_powered(&self, powered: bool) {
-        unsafe { self.inner.setPowered_(powered) }
-    }
-
-    pub fn connected(&self) -> bool {
-        unsafe { self.inner.connected() }
-    }
-
-    pub fn set_connected(&self, connected: bool) {
-        unsafe { self.inner.setConnected_(connected) }
-    }
-
-    pub fn overcurrent(&self) -> bool {
-        unsafe { self.inner.overcurrent() }
-    }
-
-    pub fn set_overcurrent(&self, overcurrent: bool) {
-        unsafe { self.inner.setOvercurrent_(overcurrent) }
-    }
-
-    pub fn link_state(&self) -> LinkState {
-        unsafe { self.inner.linkState() }.into()
-    }
-
-    pub fn speed(&self) -> DeviceSpeed {
-        unsafe { self.inner.speed() }.into()
-    }
-}
-
-impl Drop for ControllerInterface {
-    fn drop(&mut self) {
-        unsafe { self.inner.destroy() }
+            todo!()
+        } else {
+            NSArray(ptr::null_mut())
+        };
+        todo!()
     }
 }
+*/
 
-pub struct DeviceStateMachine {
-    inner: IOUSBHostCIDeviceStateMachine,
-}
-
-impl DeviceStateMachine {
-    pub fn inspect_command(&self, cmd: &Message<'_>) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        if !unsafe {
-            self.inner
-                .inspectCommand_error_(cmd.inner.as_ref(), &mut *err)
-        } {
-            Err(err.into())
-        } else {
-            Ok(())
-        }
-    }
+pub struct NSNum(NSNumber);
 
-    pub fn respond(
-        &self,
-        cmd: &Message,
-        status: MessageStatus,
-        device_address: Option<u64>,
-    ) -> Result<(), UsbError> {
-        let mut err = NSErr::new();
-        let is_err = unsafe {
-            match device_address {
-                Some(addr) => self.inner.respondToCommand_status_deviceAddress_error_(
-                    cmd.inner.as_ref(),
-                    status.into(),
-                    addr,
-                    &mut *err,
-                ),
-                None => self.inner.respondToCommand_status_error_(
-                    cmd.inner.as_ref(),
-                    status.into(),
-                    &mut *err,
-                ),
-            }
-        };
-        if is_err {
-            Err(err.into())
-        } else {
-            Ok(())
+impl NSNum {
+    //`None` stays null, matching any value, so a number that failed to allocate must not be
+    //mistaken for it
+    fn new(opt: Option<NSNumber>) -> Result<NSNum, UsbError> {
+        match opt {
+            Some(num) if num.0.is_null() => Err(IoReturnError::NoMemory.into()),
+            Some(num) => Ok(NSNum(num)),
+            None => Ok(NSNum(NSNumber(ptr::null_mut()))),
         }
     }
+}
 
-    pub fn device_state(&self) -> DeviceState {
-        unsafe { self.inner.deviceState() }.into()
-    }
-
-    pub fn complete_route(&self) -> u64 {
-        unsafe { self.inner.completeRoute() }
+impl TryFrom<Option<u16>> for NSNum {
+    type Error = UsbError;
+    fn try_from(opt: Option<u16>) -> Result<NSNum, UsbError> {
+        NSNum::new(opt.map(|num| unsafe { NSNumber::alloc().initWithUnsignedShort_(num) }))
     }
+}
 
-    pub fn device_address(&self) -> u64 {
-        unsafe { self.inner.deviceAddress() }
+impl TryFrom<Option<u8>> for NSNum {
+    type Error = UsbError;
+    fn try_from(opt: Option<u8>) -> Result<NSNum, UsbError> {
+        NSNum::new(opt.map(|num| unsafe { NSNumber::alloc().initWithUnsignedChar_(num) }))
     }
+}
 
-    pub fn controller_interface(&self) -> ControllerInterface {
-        ControllerInterface::new(unsafe { self.inner.controllerInterface() })
+impl From<NSNum> for NSNumber {
+    fn from(f: NSNum) -> NSNumber {
+        f.0
     }
 }
 
-pub struct IoService {
-    inner: io_service_t,
-}
+///identifies a submitted transfer in logs and errors, ids grow with every submission across all
+///devices so transfers on different endpoints can be put in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TransferId(u64);
 
-impl IoService {
-    pub fn authorize(&self, options: u32) -> Result<(), i32> {
-        let res = unsafe { IOServiceAuthorize(self.inner, options) };
-        if res != 0 {
-            Err(res)
-        } else {
-            Ok(())
-        }
+impl TransferId {
+    pub(crate) fn next() -> Self {
+        static NEXT: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, core::sync::atomic::Ordering::Relaxed))
     }
 
-    fn from_raw(raw: io_service_t) -> Self {
-        Self { inner: raw }
+    pub fn get(&self) -> u64 {
+        self.0
     }
 }
 
-pub struct MutData {
-    inner: NSMutableData,
-}
-
-impl MutData {
-    pub fn with_data(data: &[u8]) -> Self {
-        let mut_data = NSMutableData::alloc();
-        let len = data.len() as u64;
-        unsafe {
-            mut_data.initWithCapacity_(len);
-            mut_data.appendBytes_length_(data.as_ptr() as *const c_void, len);
-        }
-        Self { inner: mut_data }
-    }
-
-    fn raw(self) -> NSMutableData {
-        self.inner
+impl core::fmt::Display for TransferId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}", self.0)
     }
 }
 
-type Callback = impl FnOnce();
+///an error together with the transfer it ended, see `Transfer::tagged`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransferError {
+    pub id: TransferId,
+    pub error: UsbError,
+}
 
-fn gen_callback(waker: Waker, finished: *const std::sync::Mutex<bool>) -> Callback {
-    move || {
-        let finished = &mut *unsafe { finished.as_ref().unwrap().lock().unwrap() };
-        *finished = true;
-        waker.wake()
+impl From<TransferError> for UsbError {
+    fn from(err: TransferError) -> UsbError {
+        err.error
     }
 }
 
-//NOTE: if we could get rid of either of these mutexes that would be great
-
-///used for handling async events which sends data
-struct AsyncDataHandler<'a, F: Fn(&'a T, NSMutableData, *mut Callback) -> Option<UsbError>, T> {
-    handler: std::sync::Mutex<*mut Callback>,
-    dev: &'a T,
-    data: NSMutableData,
-    cb_handler: F,
-    finished: std::sync::Mutex<bool>,
+impl core::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "transfer {} failed: {:?}", self.id, self.error)
+    }
 }
 
-impl<'a, T, F: Fn(&'a T, NSMutableData, *mut Callback) -> Option<UsbError>>
-    AsyncDataHandler<'a, F, T>
-{
-    fn new(dev: NonNull<T>, data: &[u8], cb_handler: F) -> Self {
-        let data = MutData::with_data(data).raw();
-        let dev = unsafe { dev.as_ref() };
+///what an async IN request such as `HostPipe::enqueue_read` hands back once it completed: the
+///buffer it received into and how it went
+#[derive(Debug)]
+pub struct TransferResult {
+    pub data: IoData,
+    ///how many bytes were transferred, none for failed requests
+    pub bytes: usize,
+    ///the length of `data` the request was submitted with
+    pub requested: usize,
+    ///`Status::Err` for requests that failed, or `Status::Underrun` for short packets on a pipe
+    ///treating them as errors, see `ShortPacketPolicy`; the buffer is handed back either way
+    pub status: Status,
+    ///when the completion handler ran, or when a blocking request returned
+    pub timestamp: HostTime,
+    pub id: TransferId,
+}
+
+impl TransferResult {
+    fn new(
+        data: IoData,
+        res: Result<usize, UsbError>,
+        timestamp: HostTime,
+        id: TransferId,
+    ) -> Self {
+        let (bytes, status) = match res {
+            Ok(bytes) => (bytes, Status::Ok),
+            Err(err) => (0, Status::Err(err)),
+        };
         Self {
-            dev,
-            cb_handler,
+            requested: data.len(),
             data,
-            handler: std::sync::Mutex::new(ptr::null_mut()),
-            finished: std::sync::Mutex::new(false),
+            bytes,
+            status,
+            timestamp,
+            id,
         }
     }
-}
 
-impl<'a, T, F: Fn(&'a T, NSMutableData, *mut Callback) -> Option<UsbError>> Future
-    for AsyncDataHandler<'a, F, T>
-{
-    type Output = Result<(), UsbError>;
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.finished.lock().as_deref() {
-            Ok(true) => Poll::Ready(Ok(())),
-            Ok(false) => {
-                let boxed = Box::new(gen_callback(cx.waker().clone(), &self.finished));
-                let handler = Box::into_raw(boxed);
-                let h = &mut *self.handler.lock().unwrap();
-                *h = handler;
-                if let Some(err) = (self.cb_handler)(self.dev, self.data, handler) {
-                    Poll::Ready(Err(err))
-                } else {
-                    Poll::Pending
-                }
-            }
-            _ => {
-                todo!()
+    //an async request that either completed or failed to be submitted, in which case it is
+    //stamped with the time of the failure
+    #[cfg(feature = "async")]
+    fn completed(data: IoData, res: Result<future::Completed, UsbError>, id: TransferId) -> Self {
+        match res {
+            Ok(done) => {
+                let res = done.result().map(|bytes| bytes as usize);
+                Self::new(data, res, HostTime::from_ticks(done.ticks), id)
             }
+            Err(err) => Self::new(data, Err(err), HostTime::now(), id),
         }
     }
-}
-
-///used for handling async events which does not send data
-struct AsyncHandler<'a, F: Fn(&'a T, *mut Callback) -> Option<UsbError>, T> {
-    handler: std::sync::Mutex<*mut Callback>,
-    dev: &'a T,
-    cb_handler: F,
-    finished: std::sync::Mutex<bool>,
-}
 
-impl<'a, T, F: Fn(&'a T, *mut Callback) -> Option<UsbError>> AsyncHandler<'a, F, T> {
-    fn new(dev: NonNull<T>, cb_handler: F) -> Self {
-        let dev = unsafe { dev.as_ref() };
-        Self {
-            dev,
-            cb_handler,
-            handler: std::sync::Mutex::new(ptr::null_mut()),
-            finished: std::sync::Mutex::new(false),
+    ///`Ok` with the transferred length unless the request failed
+    pub fn result(&self) -> Result<usize, UsbError> {
+        match self.status {
+            Status::Ok => Ok(self.bytes),
+            Status::Err(err) => Err(err),
+            status => Err(i32::from(status).into()),
         }
     }
-}
 
-impl<'a, T, F: Fn(&'a T, *mut Callback) -> Option<UsbError>> Future for AsyncHandler<'a, F, T> {
-    type Output = Result<(), UsbError>;
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.finished.lock().as_deref() {
-            Ok(true) => Poll::Ready(Ok(())),
-            Ok(false) => {
-                let boxed = Box::new(gen_callback(cx.waker().clone(), &self.finished));
-                let handler = Box::into_raw(boxed);
-                let h = &mut *self.handler.lock().unwrap();
-                *h = handler;
-                if let Some(err) = (self.cb_handler)(self.dev, handler) {
-                    Poll::Ready(Err(err))
-                } else {
-                    Poll::Pending
-                }
-            }
-            _ => {
-                todo!()
-            }
-        }
+    ///the bytes received
+    pub fn received(&self) -> &[u8] {
+        &self.data[..self.bytes.min(self.data.len())]
     }
-}
-
-/// SAFETY: i have no clue if this works.
-/// this might be breaking
-unsafe fn downcast_tait(tait: *mut Callback) -> *mut c_void {
-    tait as *mut dyn FnOnce() as *mut c_void
-}
 
-#[repr(transparent)]
-pub struct IsochronousFrame {
-    inner: IOUSBHostIsochronousFrame,
+    ///whether the request ended with a short packet before `requested` bytes were transferred,
+    ///whichever way the pipe's `ShortPacketPolicy` treats that
+    pub fn is_short(&self) -> bool {
+        let underrun = Status::Err(IoReturnError::Underrun.into());
+        (self.status.is_ok() && self.bytes < self.requested)
+            || self.status == Status::Underrun
+            || self.status == underrun
+    }
 }
 
-pub enum Status {
-    Ok,
-    Err(UsbError),
+///an `enqueue_*` transfer, resolving like the request it wraps; its id is known before it is
+///first polled and is the one the diagnostics of its submission and completion carry
+#[cfg(feature = "async")]
+pub struct Transfer<F> {
+    id: TransferId,
+    started: bool,
+    inner: F,
 }
 
-impl From<Status> for i32 {
-    fn from(status: Status) -> i32 {
-        match status {
-            Status::Ok => 0,
-            Status::Err(err) => err.into(),
+#[cfg(feature = "async")]
+impl<F> Transfer<F> {
+    //`inner` is built with the transfer's id, which it submits and records the request under
+    fn new(inner: impl FnOnce(TransferId) -> F) -> Self {
+        let id = TransferId::next();
+        Self {
+            id,
+            started: false,
+            inner: inner(id),
         }
     }
-}
 
-impl IsochronousFrame {
-    pub fn new(
-        status: Status,
-        request_count: u32,
-        complete_count: u32,
-        timestamp: HostTime,
-    ) -> Self {
-        let inner = IOUSBHostIsochronousFrame {
-            status: status.into(),
-            requestCount: request_count,
-            completeCount: complete_count,
-            reserved: 0,
-            timeStamp: timestamp.inner,
-        };
-        Self { inner }
+    pub fn id(&self) -> TransferId {
+        self.id
     }
 }
 
-#[repr(transparent)]
-pub struct IsochronousTransaction {
-    inner: IOUSBHostIsochronousTransaction,
-}
-
-impl IsochronousTransaction {
-    pub fn new(
-        status: Status,
-        request_count: u32,
-        offset: u32,
-        complete_count: u32,
-        timestamp: HostTime,
-        options: IsochronousTransactionOptions,
-    ) -> Self {
-        let inner = IOUSBHostIsochronousTransaction {
-            status: status.into(),
-            requestCount: request_count,
-            offset,
-            completeCount: complete_count,
-            timeStamp: timestamp.inner,
-            options: options.into(),
-        };
-        Self { inner }
+#[cfg(feature = "async")]
+impl<T, F: core::future::Future<Output = Result<T, UsbError>>> Transfer<F> {
+    ///resolves with the transfer's id attached to its error
+    pub async fn tagged(self) -> Result<T, TransferError> {
+        let id = self.id;
+        self.await.map_err(|error| TransferError { id, error })
     }
 }
 
-#[derive(Clone, Copy)]
-pub enum IsochronousTransactionOptions {
-    None = 0,
-    Wrap = 1,
-}
-
-impl From<IsochronousTransactionOptions> for IOUSBHostIsochronousTransferOptions {
-    fn from(options: IsochronousTransactionOptions) -> IOUSBHostIsochronousTransferOptions {
-        use IsochronousTransactionOptions as ITO;
-        match options {
-            ITO::None => 0,
-            ITO::Wrap => 1,
+#[cfg(feature = "async")]
+impl<T, F: core::future::Future<Output = Result<T, UsbError>>> core::future::Future
+    for Transfer<F>
+{
+    type Output = F::Output;
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        //SAFETY: `inner` is never moved out of the pinned transfer
+        let this = unsafe { self.get_unchecked_mut() };
+        if !this.started {
+            this.started = true;
+            crate::diag!(trace, "transfer {} started", this.id);
         }
+        //the outcome is logged where the request is recorded, under the same id
+        unsafe { core::pin::Pin::new_unchecked(&mut this.inner) }.poll(cx)
     }
 }
 
-pub struct HostTime {
-    inner: u64,
+//IOUSBHost's completion timeout in seconds, where 0 means waiting forever, so a zero `timeout`
+//is rounded up to a millisecond rather than turning into no timeout at all
+fn timeout_secs(timeout: std::time::Duration) -> f64 {
+    timeout.as_secs_f64().max(0.001)
 }
 
-impl From<std::time::Instant> for HostTime {
-    fn from(_instant: std::time::Instant) -> HostTime {
-        todo!()
+///copies the UTF-8 contents of `string`, an empty string for nil
+fn string_from_ns(string: &NSString) -> String {
+    if string.0.is_null() {
+        return String::new();
     }
+    let ptr = unsafe { string.UTF8String() };
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { core::ffi::CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
 }
 
-pub enum Exception {
-    Unknown = 0,
-    InvalidCapabilities = 1,
-    Terminated = 2,
-    CommandReadCollision = 3,
-    WriteFailed = 4,
-    Timeout = 5,
-    Failure = 6,
-    InvalidInterrupt = 7,
-    InterruptOverflow = 8,
-    DoorbellReadCollision = 9,
-    DoorbellOverflow = 10,
-    ProtocolError = 11,
-    FrameUpdateError = 12,
-}
-
-pub enum MessageType {
-    ControllerCapabilities = 0,
-    PortCapabilities = 1,
-    PortEvent = 8,
-    FrameNumberUpdate = 9,
-    FrameTimestampUpdate = 10,
-    ControllerPowerOn = 16,
-    ControllerPowerOff = 17,
-    ControllerStart = 18,
-    ControllerPause = 19,
-    ControllerFrameNumner = 20,
-    PortPowerOn = 24,
-    PortPowerOff = 25,
-    PortResume = 26,
-    PortSuspend = 27,
-    PortReset = 28,
-    PortDisable = 29,
-    PortStatus = 30,
-    DeviceCreate = 32,
-    DeviceDestroy = 33,
-    DeviceStart = 34,
-    DevicePause = 35,
-    DeviceUpdate = 36,
-    EndpointCreate = 40,
-    EndpointDesroy = 41,
-    EndpointPause = 43,
-    EndpointUpdate = 44,
-    EndpointRest = 45,
-    EndpointSetNextTransfer = 46,
-    CommandMax = 55,
-    SetupTransfer = 56,
-    NormalTransfer = 57,
-    StatusTransfer = 58,
-    IsochronousTransfer = 59,
-    Link = 60,
-    TransferComplete = 61,
-}
+pub struct NSErr(NSError);
 
-#[repr(u32)]
-pub enum MessageStatus {
-    Success = 1,
-    Offline = 2,
-    NotPermitted = 3,
-    BadArgument = 4,
-    Timeout = 5,
-    NoResources = 6,
-    EndpointStopped = 7,
-    ProtocolError = 8,
-    TransactionError = 9,
-    OverrunError = 10,
-    StallError = 11,
-    MissedServiceError = 12,
-    Error = 13,
-    Other(u32),
-}
+impl NSErr {
+    pub fn new() -> Self {
+        Self(NSError(ptr::null_mut()))
+    }
 
-impl From<MessageStatus> for u32 {
-    fn from(status: MessageStatus) -> u32 {
-        use MessageStatus as MS;
-        match status {
-            MS::Success => 1,
-            MS::Offline => 2,
-            MS::NotPermitted => 3,
-            MS::BadArgument => 4,
-            MS::Timeout => 5,
-            MS::NoResources => 6,
-            MS::EndpointStopped => 7,
-            MS::ProtocolError => 8,
-            MS::TransactionError => 9,
-            MS::OverrunError => 10,
-            MS::StallError => 11,
-            MS::MissedServiceError => 12,
-            MS::Error => 13,
-            MS::Other(other) => other,
-        }
+    pub fn is_err(&self) -> bool {
+        !self.0 .0.is_null()
     }
 }
 
-#[derive(Clone, Copy)]
-#[repr(u32)]
-pub enum DeviceSpeed {
-    None = 0,
-    Full = 1,
-    Low = 2,
-    High = 3,
-    Super = 4,
-    SuperPlus = 5,
-    SuperPlusBy2 = 6,
-    Other(u32),
+impl From<NSErr> for UsbError {
+    fn from(err: NSErr) -> UsbError {
+        //NOTE: this is the same as `kern_return_t`, IOReturn codes included
+        UsbError::from(unsafe { err.0.code() } as kern_return_t)
+    }
 }
 
-impl From<u32> for DeviceSpeed {
-    fn from(num: u32) -> DeviceSpeed {
-        use DeviceSpeed as DS;
-        match num {
-            0 => DS::None,
-            1 => DS::Full,
-            2 => DS::Low,
-            3 => DS::High,
-            4 => DS::Super,
-            5 => DS::SuperPlus,
-            6 => DS::SuperPlusBy2,
-            other => DS::Other(other),
-        }
+impl Deref for NSErr {
+    type Target = NSError;
+    fn deref(&self) -> &NSError {
+        &self.0
     }
 }
 
-impl From<DeviceSpeed> for u32 {
-    fn from(speed: DeviceSpeed) -> u32 {
-        use DeviceSpeed as DS;
-        match speed {
-            DS::None => 0,
-            DS::Full => 1,
-            DS::Low => 2,
-            DS::High => 3,
-            DS::Super => 4,
-            DS::SuperPlus => 5,
-            DS::SuperPlusBy2 => 6,
-            DS::Other(other) => other,
-        }
+impl DerefMut for NSErr {
+    fn deref_mut(&mut self) -> &mut NSError {
+        &mut self.0
     }
 }
 
-#[repr(u32)]
-pub enum LinkState {
-    U0 = 0,
-    U1 = 1,
-    U2 = 2,
-    U3 = 3,
-    Disabled = 4,
-    RxDetect = 5,
-    Inactive = 6,
-    Polling = 7,
-    Recovery = 8,
-    Reset = 9,
-    Compliance = 10,
-    Test = 11,
-    Resume = 15,
-    Other(u32),
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbortOption {
+    Asynchronous = 0,
+    Synchronous = 1,
 }
 
-impl From<u32> for LinkState {
-    fn from(num: u32) -> LinkState {
-        use LinkState as LS;
-        match num {
-            0 => LS::U0,
-            1 => LS::U1,
-            2 => LS::U2,
-            3 => LS::U3,
-            4 => LS::Disabled,
-            5 => LS::RxDetect,
-            6 => LS::Inactive,
-            7 => LS::Polling,
-            8 => LS::Recovery,
-            9 => LS::Reset,
-            10 => LS::Compliance,
-            11 => LS::Test,
-            15 => LS::Resume,
-            other => LS::Other(other),
+impl From<AbortOption> for IOUSBHostAbortOption {
+    fn from(option: AbortOption) -> IOUSBHostAbortOption {
+        use AbortOption as AO;
+        match option {
+            AO::Asynchronous => 0,
+            AO::Synchronous => 1,
         }
     }
 }
 
-impl From<LinkState> for u32 {
-    fn from(state: LinkState) -> u32 {
-        use LinkState as LS;
-        match state {
-            LS::U0 => 0,
-            LS::U1 => 1,
-            LS::U2 => 2,
-            LS::U3 => 3,
-            LS::Disabled => 4,
-            LS::RxDetect => 5,
-            LS::Inactive => 6,
-            LS::Polling => 7,
-            LS::Recovery => 8,
-            LS::Reset => 9,
-            LS::Compliance => 10,
-            LS::Test => 11,
-            LS::Resume => 15,
-            LS::Other(other) => other,
+impl From<IOUSBHostAbortOption> for AbortOption {
+    fn from(option: IOUSBHostAbortOption) -> AbortOption {
+        use AbortOption as AO;
+        match option {
+            1 => AO::Synchronous,
+            _ => AO::Asynchronous,
         }
     }
 }
 
-#[repr(u32)]
-pub enum PortStatus {
-    Powered = 1,
-    Overcurrent = 2,
-    Connected = 4,
-    LinkState = 240,
-    Speed = 1792,
-    SpeedPhase = 8,
-    OvercurrentChange = 131072,
-    ConnectChange = 262144,
-    LinkStateChange = 1048576,
-    Other(u32),
+pub struct IoService {
+    inner: io_service_t,
 }
 
-impl From<u32> for PortStatus {
-    fn from(num: u32) -> PortStatus {
-        use PortStatus as PS;
-        match num {
-            1 => PS::Powered,
-            2 => PS::Overcurrent,
-            4 => PS::Connected,
-            240 => PS::LinkState,
-            1792 => PS::Speed,
-            8 => PS::SpeedPhase,
-            131072 => PS::OvercurrentChange,
-            262144 => PS::ConnectChange,
-            1048576 => PS::LinkStateChange,
-            other => PS::Other(other),
+impl IoService {
+    pub fn authorize(&self, options: u32) -> Result<(), i32> {
+        let res = unsafe { IOServiceAuthorize(self.inner, options) };
+        if res != 0 {
+            Err(res)
+        } else {
+            Ok(())
         }
     }
-}
 
-pub enum Doorbell {
-    DeviceAddress = 255,
-    DeviceAddressPhase = 0,
-    EndpointAddress = 65280,
-    EndpointAddressPhase = 8,
-    StreamId = 4294901760,
-    StreamIDPhase = 16,
-}
-
-pub enum MessageCommand {
-    ControlStatus = 3840,
-    StatusPhase = 8,
-    Data0RootPort = 15,
-    RootPortPhase = 0,
-    Data0DeviceAddress = 255,
-    Data0EndpointAddress = 65280,
-    Data0StreamId = 4294901760,
-    Data0StreamIdPhase = 16,
-}
+    fn from_raw(raw: io_service_t) -> Self {
+        Self { inner: raw }
+    }
 
-#[repr(u32)]
-pub enum ControllerState {
-    Off = 0,
-    Paused = 1,
-    Active = 2,
-    Other(u32),
-}
+    ///reads a numeric property of the service's registry entry
+    fn number_property(&self, key: &core::ffi::CStr) -> Option<i64> {
+        unsafe {
+            let key =
+                CFStringCreateWithCString(kCFAllocatorDefault, key.as_ptr(), kCFStringEncodingUTF8);
+            if key.is_null() {
+                return None;
+            }
+            let value = IORegistryEntryCreateCFProperty(self.inner, key, kCFAllocatorDefault, 0);
+            CFRelease(key as CFTypeRef);
+            if value.is_null() {
+                return None;
+            }
 
-impl From<u32> for ControllerState {
-    fn from(num: u32) -> ControllerState {
-        use ControllerState as CS;
-        match num {
-            0 => CS::Off,
-            1 => CS::Paused,
-            2 => CS::Active,
-            other => CS::Other(other),
+            let mut number = 0i64;
+            let found = CFGetTypeID(value) == CFNumberGetTypeID()
+                && CFNumberGetValue(
+                    value as CFNumberRef,
+                    kCFNumberSInt64Type as CFNumberType,
+                    &mut number as *mut i64 as *mut c_void,
+                ) != 0;
+            CFRelease(value);
+            found.then_some(number)
         }
     }
-}
 
-#[repr(u32)]
-pub enum PortState {
-    Off = 0,
-    Powered = 1,
-    Suspended = 2,
-    Active = 3,
-    Other(u32),
-}
+    ///reads a string property of the service's registry entry
+    fn string_property(&self, key: &core::ffi::CStr) -> Option<String> {
+        unsafe {
+            let key =
+                CFStringCreateWithCString(kCFAllocatorDefault, key.as_ptr(), kCFStringEncodingUTF8);
+            if key.is_null() {
+                return None;
+            }
+            let value = IORegistryEntryCreateCFProperty(self.inner, key, kCFAllocatorDefault, 0);
+            CFRelease(key as CFTypeRef);
+            if value.is_null() {
+                return None;
+            }
 
-impl From<u32> for PortState {
-    fn from(num: u32) -> PortState {
-        use PortState as PS;
-        match num {
-            0 => PS::Off,
-            1 => PS::Powered,
-            2 => PS::Suspended,
-            3 => PS::Active,
-            other => PS::Other(other),
+            let mut string = None;
+            if CFGetTypeID(value) == CFStringGetTypeID() {
+                let value = value as CFStringRef;
+                let capacity = CFStringGetMaximumSizeForEncoding(
+                    CFStringGetLength(value),
+                    kCFStringEncodingUTF8,
+                ) + 1;
+                let mut buf = vec![0u8; capacity.max(1) as usize];
+                if CFStringGetCString(
+                    value,
+                    buf.as_mut_ptr() as *mut core::ffi::c_char,
+                    buf.len() as CFIndex,
+                    kCFStringEncodingUTF8,
+                ) != 0
+                {
+                    string = core::ffi::CStr::from_bytes_until_nul(&buf)
+                        .ok()
+                        .map(|s| s.to_string_lossy().into_owned());
+                }
+            }
+            CFRelease(value);
+            string
         }
     }
 }
 
-pub enum PortMessageEvent {
-    Data0PortNumber = 15,
-    Data0PortNumberPhase = 0,
-}
-
-pub enum PortStatusCommand {
-    Powered = 1,
-    Overcurrent = 2,
-    Connected = 4,
-    LinkState = 240,
-    Speed = 1792,
-    SpeedPhase = 8,
-    OvercurrentChange = 131072,
-    ConnectChange = 262144,
-    LinkStateChange = 1048576,
-    ChangeMask = 1441792,
-}
-
-#[repr(u32)]
-pub enum DeviceState {
-    Destroyed = 0,
-    Paused = 1,
-    Active = 2,
-    Other(u32),
+pub struct MutData {
+    inner: NSMutableData,
 }
 
-impl From<u32> for DeviceState {
-    fn from(num: u32) -> DeviceState {
-        use DeviceState as DS;
-        match num {
-            0 => DS::Destroyed,
-            1 => DS::Paused,
-            2 => DS::Active,
-            other => DS::Other(other),
+impl MutData {
+    pub fn with_data(data: &[u8]) -> Self {
+        let mut_data = NSMutableData::alloc();
+        let len = data.len() as u64;
+        unsafe {
+            mut_data.initWithCapacity_(len);
+            mut_data.appendBytes_length_(data.as_ptr() as *const c_void, len);
         }
+        Self { inner: mut_data }
     }
-}
-
-#[repr(u32)]
-pub enum EndpointState {
-    Destroyed = 0,
-    Halted = 1,
-    Paused = 2,
-    Active = 3,
-    Other(u32),
-}
 
-impl From<u32> for EndpointState {
-    fn from(num: u32) -> EndpointState {
-        use EndpointState as ES;
-        match num {
-            0 => ES::Destroyed,
-            1 => ES::Halted,
-            2 => ES::Paused,
-            3 => ES::Active,
-            other => ES::Other(other),
+    fn with_slices(slices: &[std::io::IoSlice<'_>]) -> Self {
+        let mut_data = NSMutableData::alloc();
+        let len = slices.iter().map(|slice| slice.len() as u64).sum();
+        unsafe {
+            mut_data.initWithCapacity_(len);
+            for slice in slices {
+                mut_data.appendBytes_length_(slice.as_ptr() as *const c_void, slice.len() as u64);
+            }
         }
+        Self { inner: mut_data }
     }
-}
 
-#[repr(u64)]
-pub enum EndpointCreateCommand {
-    Data1Descriptor = 18446744073709551615,
-    Data1DescriptorPhase = 0,
-}
-
-#[repr(u64)]
-pub enum EndpointUpdateCommand {
-    Data1Descriptor = 18446744073709551615,
-    Data1DescriptorPhase = 0,
-}
+    fn zeroed(len: usize) -> Self {
+        let mut_data = NSMutableData::alloc();
+        unsafe {
+            mut_data.initWithCapacity_(len as u64);
+            mut_data.setLength_(len as u64);
+        }
+        Self { inner: mut_data }
+    }
 
-pub enum EndpointResetCommand {
-    Data1ClearState = 1,
-}
+    fn raw(self) -> NSMutableData {
+        self.inner
+    }
 
-#[repr(u64)]
-pub enum EndpointSetNExtTransferCommand {
-    Data1Address = 18446744073709551615,
-    Data1AddressPhase = 0,
+    fn bytes(&self) -> &[u8] {
+        unsafe {
+            let len = self.inner.length() as usize;
+            if len == 0 {
+                return &[];
+            }
+            core::slice::from_raw_parts(self.inner.bytes() as *const u8, len)
+        }
+    }
 }
 
-#[repr(u64)]
-pub enum TransferCompletionMessage {
-    Status = 3840,
-    StatusPhase = 8,
-    DeviceAddress = 16711680,
-    DeviceAddressPhase = 16,
-    EndpointAddress = 4278190080,
-    EndpointAddressPhase = 24,
-    Data0TransferLength = 268435455,
-    Data0TransferLengthPhase = 0,
-    Data1TransferStructure = 18446744073709551615,
+///a buffer allocated by the framework for transfers through `io_data`, the memory is mapped for
+///the device so it can be sent without being copied
+///
+///the length starts out at the capacity and can be shrunk with `set_len` to send less, it never
+///grows past the capacity as that would move the data out of the mapped memory
+pub struct IoData {
+    inner: NSMutableData,
+    capacity: usize,
 }
 
-pub enum EndpointDirection {
-    Out = 0,
-    In = 1,
-    Unknown = 2,
-}
+impl IoData {
+    //a copy of `data` in memory of its own, for the transfers taking a slice
+    pub(crate) fn from_slice(data: &[u8]) -> Self {
+        Self {
+            inner: MutData::with_data(data).raw(),
+            capacity: data.len(),
+        }
+    }
 
-impl From<u8> for EndpointDirection {
-    fn from(num: u8) -> EndpointDirection {
-        use EndpointDirection as ED;
-        match num {
-            0 => ED::Out,
-            1 => ED::In,
-            _ => ED::Unknown,
+    //`data` is autoreleased by the framework, the retain is given back on drop
+    pub(crate) fn new(data: NSMutableData, capacity: u64) -> Result<Self, UsbError> {
+        if data.0.is_null() {
+            return Err(UsbError::ResourceShortage);
         }
+        let _: *mut objc::runtime::Object = unsafe { objc::msg_send![data.0, retain] };
+        let capacity = usize::try_from(capacity).map_err(|_| UsbError::InvalidArgument)?;
+        Ok(Self {
+            inner: data,
+            capacity,
+        })
     }
-}
 
-pub enum EndpointType {
-    Control = 0,
-    Isochronous = 1,
-    Bulk = 2,
-    Interrupt = 3,
-}
+    pub fn len(&self) -> usize {
+        unsafe { self.inner.length() as usize }
+    }
 
-#[repr(u8)]
-pub enum SynchronizationType {
-    None = 0,
-    Asynchronous = 1,
-    Adaptive = 2,
-    Synchronous = 3,
-    Other(u8),
-}
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-impl From<u8> for SynchronizationType {
-    fn from(num: u8) -> SynchronizationType {
-        use SynchronizationType as ST;
-        match num {
-            0 => ST::None,
-            1 => ST::Asynchronous,
-            2 => ST::Adaptive,
-            3 => ST::Synchronous,
-            other => ST::Other(other),
-        }
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
-}
 
-#[repr(u8)]
-pub enum DeviceCapabilityType {
-    Wireless = 1,
-    Usb2Extension = 2,
-    SuperSpeed = 3,
-    ContainerID = 4,
-    Platform = 5,
-    PowerDelivery = 6,
-    BatteryInfo = 7,
-    PdConsumerPort = 8,
-    PdProviderPort = 9,
-    SuperSpeedPlus = 10,
-    PrecisionMeasurement = 11,
-    WirelessExt = 12,
-    Billboard = 13,
-    BillboardAltMode = 15,
-    Other(u8),
-}
+    ///shrinks or grows the buffer up to its capacity, new bytes are zeroed
+    pub fn set_len(&mut self, len: usize) {
+        unsafe { self.inner.setLength_(len.min(self.capacity) as u64) }
+    }
 
-impl From<u8> for DeviceCapabilityType {
-    fn from(num: u8) -> DeviceCapabilityType {
-        use DeviceCapabilityType as DCT;
-        match num {
-            1 => DCT::Wireless,
-            2 => DCT::Usb2Extension,
-            3 => DCT::SuperSpeed,
-            4 => DCT::ContainerID,
-            5 => DCT::Platform,
-            6 => DCT::PowerDelivery,
-            7 => DCT::BatteryInfo,
-            8 => DCT::PdConsumerPort,
-            9 => DCT::PdProviderPort,
-            10 => DCT::SuperSpeedPlus,
-            11 => DCT::PrecisionMeasurement,
-            12 => DCT::WirelessExt,
-            13 => DCT::Billboard,
-            15 => DCT::BillboardAltMode,
-            other => DCT::Other(other),
+    pub fn as_slice(&self) -> &[u8] {
+        let len = self.len();
+        if len == 0 {
+            return &[];
         }
+        unsafe { core::slice::from_raw_parts(self.inner.bytes() as *const u8, len) }
     }
-}
-
-pub enum DeviceRequestDirectionValue {
-    Out = 0,
-    In = 1,
-}
-
-#[repr(u32)]
-pub enum DeviceRequestTypeValue {
-    Standard = 0,
-    Class = 1,
-    Vendor = 2,
-    Other(u32),
-}
 
-impl From<DeviceRequestTypeValue> for u32 {
-    fn from(req: DeviceRequestTypeValue) -> u32 {
-        use DeviceRequestTypeValue as DRTV;
-        match req {
-            DRTV::Standard => 0,
-            DRTV::Class => 1,
-            DRTV::Vendor => 2,
-            DRTV::Other(other) => other,
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        let len = self.len();
+        if len == 0 {
+            return &mut [];
         }
+        unsafe { core::slice::from_raw_parts_mut(self.inner.mutableBytes() as *mut u8, len) }
     }
-}
 
-pub enum DeviceRequestRecipientValue {
-    Device = 0,
-    Interface = 1,
-    Endpoint = 2,
-    Other = 3,
+    ///the underlying object, still owned by `self`
+    pub fn as_raw(&self) -> NSMutableData {
+        NSMutableData(self.inner.0)
+    }
 }
 
-impl From<DeviceRequestRecipientValue> for u32 {
-    fn from(val: DeviceRequestRecipientValue) -> u32 {
-        use DeviceRequestRecipientValue as DRRV;
-        match val {
-            DRRV::Device => 0,
-            DRRV::Interface => 1,
-            DRRV::Endpoint => 2,
-            DRRV::Other => 3,
-        }
+impl Deref for IoData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
     }
 }
 
-#[repr(u8)]
-pub enum DeviceRequestType {
-    Size = 8,
-    DirectionPhase = 7,
-    DirectionOut = 0,
-    DirectionIn = 128,
-    TypePhase = 5,
-    TypeClass = 32,
-    TypeVendor = 64,
-    RecipientInterface = 1,
-    RecipientEndpoint = 2,
-    RecipientOther = 3,
-    Other(u8),
+impl DerefMut for IoData {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
 }
 
-impl From<DeviceRequestType> for u8 {
-    fn from(req_ty: DeviceRequestType) -> u8 {
-        use DeviceRequestType as DRT;
-        match req_ty {
-            DRT::Size => 8,
-            DRT::DirectionPhase => 7,
-            DRT::DirectionOut => 0,
-            DRT::DirectionIn => 128,
-            DRT::TypePhase => 5,
-            DRT::TypeClass => 32,
-            DRT::TypeVendor => 64,
-            DRT::RecipientInterface => 1,
-            DRT::RecipientEndpoint => 2,
-            DRT::RecipientOther => 3,
-            DRT::Other(other) => other,
-        }
+impl core::fmt::Debug for IoData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IoData")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
     }
 }
 
-pub enum PortType {
-    Standard = 0,
-    Captive = 1,
-    Internal = 2,
-    Accessory = 3,
-    ExpressCard = 4,
-    Count = 5,
+impl Drop for IoData {
+    fn drop(&mut self) {
+        let _: () = unsafe { objc::msg_send![self.inner.0, release] };
+    }
 }
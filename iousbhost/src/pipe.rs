@@ -0,0 +1,2479 @@
+#[cfg(feature = "async")]
+use crate::future::{AsyncDataHandler, AsyncHandler, Batch, Completed, JoinAll};
+use crate::*;
+use core::marker::PhantomData;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+use iousbhost_sys::*;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub struct HostPipe<'a> {
+    pub(crate) inner: NonNull<IOUSBHostPipe>,
+    //the interface, and through it the device, the pipe was copied from
+    interface: Rc<SharedObject>,
+    //the endpoint this pipe holds a claim on in the interface
+    address: EndpointAddress,
+    stats: Arc<TransferStats>,
+    //serializes the transfers of this pipe when enabled
+    gate: RequestGate,
+    short_packets: core::cell::Cell<ShortPacketPolicy>,
+    #[cfg(feature = "fault-injection")]
+    faults: std::sync::Mutex<Option<FaultState>>,
+    lt: PhantomData<&'a ()>,
+}
+
+///how IN transfers ending with a short packet, before as many bytes as requested arrived, are
+///reported; protocols such as CDC end variable length transfers that way while others treat it
+///as data having been lost
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShortPacketPolicy {
+    ///the transfer succeeds with the bytes that arrived
+    #[default]
+    Accept,
+    ///the transfer fails with `IoReturnError::Underrun`
+    Underrun,
+}
+
+impl Drop for HostPipe<'_> {
+    fn drop(&mut self) {
+        self.interface.release_pipe(self.address.0);
+        let _: () = unsafe { objc::msg_send![self.inner.as_ptr() as id, release] };
+    }
+}
+
+impl<'a> HostPipe<'a> {
+    ///takes over a pipe of `interface` copied outside of the crate, which then counts as a claim
+    ///on its endpoint like the pipes `HostInterface::copy_pipe` opens
+    ///
+    ///# Safety
+    ///`raw` has to be an `IOUSBHostPipe` of `interface`, the handle retains it
+    pub unsafe fn from_raw(raw: NonNull<IOUSBHostPipe>, interface: &HostInterface<'a>) -> Self {
+        let _: *mut objc::runtime::Object = objc::msg_send![raw.as_ptr() as id, retain];
+        let address = EndpointAddress(raw.as_ref().endpointAddress() as u8);
+        Self::from_object(raw, interface.shared.clone(), address)
+    }
+}
+
+impl HostPipe<'_> {
+    pub(crate) fn new(
+        ptr: *const IOUSBHostPipe,
+        interface: Rc<SharedObject>,
+        address: EndpointAddress,
+    ) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBHostPipe)?;
+        Some(Self::from_object(ptr, interface, address))
+    }
+
+    fn from_object(
+        ptr: NonNull<IOUSBHostPipe>,
+        interface: Rc<SharedObject>,
+        address: EndpointAddress,
+    ) -> Self {
+        interface.claim_pipe(address.0);
+        let stats = Arc::new(TransferStats::with_parent(
+            interface.device().pipe_stats.clone(),
+        ));
+        Self {
+            inner: ptr,
+            interface,
+            address,
+            stats,
+            gate: RequestGate::default(),
+            short_packets: core::cell::Cell::new(ShortPacketPolicy::default()),
+            #[cfg(feature = "fault-injection")]
+            faults: std::sync::Mutex::new(None),
+            lt: PhantomData,
+        }
+    }
+
+    ///the framework object behind this handle, which holds a reference to it until dropped
+    pub fn as_raw(&self) -> NonNull<IOUSBHostPipe> {
+        self.inner
+    }
+
+    pub fn adjust(&self, descriptors: impl IntoRawSource) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .adjustPipeWithDescriptors_error_(descriptors.raw(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_idle_timeout(&self, duration: f64) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .setIdleTimeout_error_(duration, &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn clear_stall(&self) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe { self.inner.as_ref().clearStallWithError_(&mut *err) } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn send_control_request_with_data(
+        &self,
+        request: DeviceRequest,
+        data: &mut [u8],
+    ) -> Result<u64, UsbError> {
+        self.send_control_data(
+            request,
+            MutData::with_data(data).raw(),
+            self.interface.request_timeout(),
+        )
+    }
+
+    ///`send_control_request_with_data` giving up with `UsbError::OperationTimedOut` once
+    ///`timeout` passed instead of blocking until the device answers
+    pub fn send_control_request_with_data_timeout(
+        &self,
+        request: DeviceRequest,
+        data: &mut [u8],
+        timeout: Duration,
+    ) -> Result<u64, UsbError> {
+        self.send_control_data(
+            request,
+            MutData::with_data(data).raw(),
+            timeout_secs(timeout),
+        )
+    }
+
+    ///`send_control_request` giving up with `UsbError::OperationTimedOut` once `timeout` passed
+    pub fn send_control_request_timeout(
+        &self,
+        request: DeviceRequest,
+        timeout: Duration,
+    ) -> Result<(), UsbError> {
+        self.send_control_data(request, MutData::zeroed(0).raw(), timeout_secs(timeout))
+            .map(|_| ())
+    }
+
+    fn send_control_data(
+        &self,
+        request: DeviceRequest,
+        data: NSMutableData,
+        timeout: f64,
+    ) -> Result<u64, UsbError> {
+        let _device_turn = self.interface.device().gate.enter();
+        let _turn = self.gate.enter();
+        self.interface.check_open()?;
+        let recording = self.stats.begin(request.is_in());
+        let mut err = NSErr::new();
+        let mut transferred = 0;
+        let res = self.inject_len(|| {
+            if !unsafe {
+                self.inner
+                    .as_ref()
+                    .sendControlRequest_data_bytesTransferred_completionTimeout_error_(
+                        request.into(),
+                        data,
+                        &mut transferred,
+                        timeout,
+                        &mut *err,
+                    )
+            } {
+                Err(err.into())
+            } else {
+                Ok(transferred)
+            }
+        });
+        recording.finish(res);
+        res
+    }
+
+    pub fn send_control_request(&self, request: DeviceRequest) -> Result<(), UsbError> {
+        let timeout = self.interface.request_timeout();
+        if timeout > 0.0 {
+            return self
+                .send_control_data(request, MutData::zeroed(0).raw(), timeout)
+                .map(|_| ());
+        }
+        let _device_turn = self.interface.device().gate.enter();
+        let _turn = self.gate.enter();
+        self.interface.check_open()?;
+        let recording = self.stats.begin(request.is_in());
+        let mut err = NSErr::new();
+        let res = self.inject(|| {
+            if !unsafe {
+                self.inner
+                    .as_ref()
+                    .sendControlRequest_error_(request.into(), &mut *err)
+            } {
+                Err(err.into())
+            } else {
+                Ok(())
+            }
+        });
+        recording.finish(res.map(|_| 0));
+        res
+    }
+
+    ///sends `request` with `data` as its data stage, or reads the data stage into it, handing the
+    ///buffer back in the `TransferResult` whether the request succeeded or not
+    #[cfg(feature = "async")]
+    pub fn enqueue_control_request_with_data<'s>(
+        &'s self,
+        request: DeviceRequest,
+        data: IoData,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| this.enqueue_control_request_with_data_inner(request, data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_control_request_with_data_inner(
+        &self,
+        request: DeviceRequest,
+        data: IoData,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        let _device_turn = self.interface.device().gate.enter_async().await;
+        let _turn = self.gate.enter_async().await;
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, request.is_in());
+        let handler = AsyncDataHandler::with_raw(self.inner, data.as_raw(), |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueControlRequest_data_completionTimeout_error_completionHandler_(
+                    request.into(),
+                    data,
+                    0.0,
+                    &mut *err,
+                    cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(recording);
+
+        let res = self.inject_async(handler).await;
+        Ok(TransferResult::completed(data, res, id))
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_control_request<'s>(
+        &'s self,
+        request: DeviceRequest,
+    ) -> Transfer<impl core::future::Future<Output = Result<(), UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| this.enqueue_control_request_inner(request, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_control_request_inner(
+        &self,
+        request: DeviceRequest,
+        id: TransferId,
+    ) -> Result<(), UsbError> {
+        let _device_turn = self.interface.device().gate.enter_async().await;
+        let _turn = self.gate.enter_async().await;
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, request.is_in());
+        let handler = AsyncHandler::new(self.inner, |dev, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueControlRequest_error_completionHandler_(request.into(), &mut *err, cb)
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(recording);
+        self.inject_async(handler).await?.result().map(|_| ())
+    }
+
+    pub fn send_io_request(&self, data: &[u8]) -> Result<u64, UsbError> {
+        self.send_io_data(
+            MutData::with_data(data).raw(),
+            self.interface.request_timeout(),
+        )
+    }
+
+    ///`send_io_request` giving up with `UsbError::OperationTimedOut` once `timeout` passed
+    ///instead of blocking until the device answers
+    pub fn send_io_request_timeout(&self, data: &[u8], timeout: Duration) -> Result<u64, UsbError> {
+        self.send_io_data(MutData::with_data(data).raw(), timeout_secs(timeout))
+    }
+
+    ///sends the bytes of `data` as they are, or receives up to its length into it on an IN pipe,
+    ///returning how many bytes were transferred; with a buffer from `io_data` nothing is copied on
+    ///the way
+    pub fn send_io_request_data(&self, data: &mut IoData) -> Result<u64, UsbError> {
+        self.send_io_data(data.as_raw(), self.interface.request_timeout())
+    }
+
+    ///gathers `bufs` into a single request instead of concatenating them first
+    pub fn write_vectored(&self, bufs: &[std::io::IoSlice<'_>]) -> Result<u64, UsbError> {
+        self.send_io_data(
+            MutData::with_slices(bufs).raw(),
+            self.interface.request_timeout(),
+        )
+    }
+
+    ///reads into a single request sized to fit all of `bufs`, scattering what was received across
+    ///them in order
+    pub fn read_vectored(&self, bufs: &mut [std::io::IoSliceMut<'_>]) -> Result<u64, UsbError> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let data = MutData::zeroed(len);
+        let transferred = self.send_io_data(data.inner, self.interface.request_timeout())?;
+
+        let mut received = &data.bytes()[..(transferred as usize).min(len)];
+        for buf in bufs.iter_mut() {
+            if received.is_empty() {
+                break;
+            }
+            let count = buf.len().min(received.len());
+            buf[..count].copy_from_slice(&received[..count]);
+            received = &received[count..];
+        }
+        Ok(transferred)
+    }
+
+    ///`send_io_request_data` handing the buffer back with the transferred length, so short packets
+    ///show up in `TransferResult::is_short` even when the short packet policy accepts them
+    pub fn send_io_request_result(&self, data: IoData) -> TransferResult {
+        let id = TransferId::next();
+        let res = self.send_io_data_unchecked(data.as_raw(), self.interface.request_timeout(), id);
+        let result =
+            TransferResult::new(data, res.map(|bytes| bytes as usize), HostTime::now(), id);
+        self.check_short_result(result)
+    }
+
+    //fails a result ending with a short packet with `Status::Underrun` if the short packet policy
+    //says so
+    fn check_short_result(&self, mut result: TransferResult) -> TransferResult {
+        let short = self.is_in() && result.status.is_ok() && result.bytes < result.requested;
+        if short && self.short_packets.get() == ShortPacketPolicy::Underrun {
+            result.status = Status::Underrun;
+        }
+        result
+    }
+
+    fn send_io_data(&self, data: NSMutableData, timeout: f64) -> Result<u64, UsbError> {
+        let requested = unsafe { data.length() };
+        let id = TransferId::next();
+        let transferred = self.send_io_data_unchecked(data, timeout, id)?;
+        self.check_short(requested, transferred, id)
+    }
+
+    //`send_io_data` without the short packet policy
+    fn send_io_data_unchecked(
+        &self,
+        data: NSMutableData,
+        timeout: f64,
+        id: TransferId,
+    ) -> Result<u64, UsbError> {
+        let _turn = self.gate.enter();
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, self.is_in());
+        let mut err = NSErr::new();
+        let mut transferred = 0;
+        let res = self.inject_len(|| {
+            if !unsafe {
+                self.inner
+                    .as_ref()
+                    .sendIORequestWithData_bytesTransferred_completionTimeout_error_(
+                        data,
+                        &mut transferred,
+                        timeout,
+                        &mut *err,
+                    )
+            } {
+                Err(err.into())
+            } else {
+                Ok(transferred)
+            }
+        });
+        recording.finish(res);
+        res
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_io_request<'s>(
+        &'s self,
+        data: &'s [u8],
+    ) -> Transfer<impl core::future::Future<Output = Result<u64, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| this.enqueue_io_request_inner(data, id))
+    }
+
+    ///`send_io_request_result` as a future: the transfer owns `data` until it completed, so
+    ///dropping it early never leaves the framework writing into a buffer that was freed, and
+    ///hands it back in the `TransferResult`
+    #[cfg(feature = "async")]
+    pub fn enqueue_io_request_data<'s>(
+        &'s self,
+        data: IoData,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| this.enqueue_read_inner(data, id))
+    }
+
+    ///receives up to the length of `data` on an IN pipe, handing the buffer back in the
+    ///`TransferResult` whether the request succeeded or not, so it can be submitted again
+    #[cfg(feature = "async")]
+    pub fn enqueue_read<'s>(
+        &'s self,
+        data: IoData,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| this.enqueue_read_inner(data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_read_inner(
+        &self,
+        data: IoData,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        let res = self.enqueue_io_data(data.as_raw(), id).await;
+        Ok(self.check_short_result(TransferResult::completed(data, res, id)))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_io_request_inner(&self, data: &[u8], id: TransferId) -> Result<u64, UsbError> {
+        self.enqueue_io_data_checked(MutData::with_data(data).raw(), id)
+            .await
+    }
+
+    //`enqueue_io_data` with the short packet policy applied
+    #[cfg(feature = "async")]
+    async fn enqueue_io_data_checked(
+        &self,
+        data: NSMutableData,
+        id: TransferId,
+    ) -> Result<u64, UsbError> {
+        let requested = unsafe { data.length() };
+        let transferred = self.enqueue_io_data(data, id).await?.result()?;
+        self.check_short(requested, transferred, id)
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_io_data(
+        &self,
+        data: NSMutableData,
+        id: TransferId,
+    ) -> Result<Completed, UsbError> {
+        let _turn = self.gate.enter_async().await;
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, self.is_in());
+        let handler = AsyncDataHandler::with_raw(self.inner, data, |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueIORequestWithData_completionTimeout_error_completionHandler_(
+                    data, 0.0, &mut *err, cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(recording);
+
+        self.inject_async(handler).await
+    }
+
+    ///transfers `data` split across `frames`, handing the buffer back in the `TransferResult`
+    ///whether the request succeeded or not; each frame reports its own status and length
+    #[cfg(feature = "async")]
+    pub fn enqueue_io_request_isochronous_frame<'s>(
+        &'s self,
+        data: IoData,
+        frames: &'s mut [IsochronousFrame],
+        first_frame_number: u64,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| {
+            this.enqueue_io_request_isochronous_frame_inner(data, frames, first_frame_number, id)
+        })
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_io_request_isochronous_frame_inner(
+        &self,
+        data: IoData,
+        frames: &mut [IsochronousFrame],
+        first_frame_number: u64,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        let _turn = self.gate.enter_async().await;
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, self.is_in());
+        let handler = AsyncDataHandler::with_raw(self.inner, data.as_raw(), |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueIORequestWithData_frameList_frameListCount_firstFrameNumber_error_completionHandler_(
+                    data,
+                    frames.as_ptr() as *mut IOUSBHostIsochronousFrame,
+                    frames.len() as u64,
+                    first_frame_number,
+                    &mut *err,
+                    cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(recording);
+
+        let res = self.inject_async(handler).await;
+        Ok(TransferResult::completed(data, res, id))
+    }
+
+    ///`enqueue_io_request_isochronous_frame` with a transaction list
+    #[cfg(feature = "async")]
+    pub fn enqueue_io_request_isochronous_transaction<'s>(
+        &'s self,
+        data: IoData,
+        transactions: &'s mut [IsochronousTransaction],
+        first_frame_number: u64,
+        options: IsochronousTransactionOptions,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + 's> {
+        let this: &'s HostPipe<'s> = self;
+        Transfer::new(|id| {
+            this.enqueue_io_request_isochronous_transaction_inner(
+                data,
+                transactions,
+                first_frame_number,
+                options,
+                id,
+            )
+        })
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_io_request_isochronous_transaction_inner(
+        &self,
+        data: IoData,
+        transactions: &mut [IsochronousTransaction],
+        first_frame_number: u64,
+        options: IsochronousTransactionOptions,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        let _turn = self.gate.enter_async().await;
+        self.interface.check_open()?;
+        let recording = self.stats.begin_as(id, self.is_in());
+        let handler = AsyncDataHandler::with_raw(self.inner, data.as_raw(), |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueIORequestWithData_transactionList_transactionListCount_firstFrameNumber_options_error_completionHandler_(
+                    data,
+                    transactions.as_ptr() as *mut IOUSBHostIsochronousTransaction,
+                    transactions.len() as u64,
+                    first_frame_number,
+                    options.into(),
+                    &mut *err,
+                    cb,
+                )
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(recording);
+
+        let res = self.inject_async(handler).await;
+        Ok(TransferResult::completed(data, res, id))
+    }
+
+    pub fn send_io_request_isochronous_frame(
+        &self,
+        data: &[u8],
+        frames: &mut [IsochronousFrame],
+        first_frame_number: u64,
+    ) -> Result<(), UsbError> {
+        let _turn = self.gate.enter();
+        self.interface.check_open()?;
+        let recording = self.stats.begin(self.is_in());
+        let len = data.len() as u64;
+        let data = MutData::with_data(data).raw();
+        let mut err = NSErr::new();
+        let res = self.inject(|| {
+            if !unsafe {
+                self.inner
+                    .as_ref()
+                    .sendIORequestWithData_frameList_frameListCount_firstFrameNumber_error_(
+                        data,
+                        frames.as_ptr() as *mut IOUSBHostIsochronousFrame,
+                        frames.len() as u64,
+                        first_frame_number,
+                        &mut *err,
+                    )
+            } {
+                Err(err.into())
+            } else {
+                Ok(())
+            }
+        });
+        recording.finish(res.map(|_| len));
+        res
+    }
+
+    pub fn send_io_request_isochronous_transaction(
+        &self,
+        data: &[u8],
+        transactions: &mut [IsochronousTransaction],
+        first_frame_number: u64,
+        options: IsochronousTransactionOptions,
+    ) -> Result<(), UsbError> {
+        let _turn = self.gate.enter();
+        self.interface.check_open()?;
+        let recording = self.stats.begin(self.is_in());
+        let len = data.len() as u64;
+        let data = MutData::with_data(data).raw();
+        let mut err = NSErr::new();
+        let res = self.inject(|| {
+            if !unsafe {
+                self.inner.as_ref().sendIORequestWithData_transactionList_transactionListCount_firstFrameNumber_options_error_(data, transactions.as_ptr() as *mut IOUSBHostIsochronousTransaction, transactions.len() as u64, first_frame_number, options.into(), &mut *err)
+            } {
+                Err(err.into())
+            } else {
+                Ok(())
+            }
+        });
+        recording.finish(res.map(|_| len));
+        res
+    }
+
+    ///aborts every request on the pipe, including the requests of all of its streams, see
+    ///`abort_scope` to cancel a single stream only
+    pub fn abort(&self, abort: AbortOption) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe {
+            self.inner
+                .as_ref()
+                .abortWithOption_error_(abort.into(), &mut *err)
+        } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    ///aborts the requests of a single stream, leaving the other streams' transactions in flight
+    pub fn abort_stream(&self, stream_id: u64, option: AbortOption) -> Result<(), UsbError> {
+        self.copy_stream(stream_id)?.abort(option)
+    }
+
+    pub fn abort_scope(&self, scope: AbortScope, option: AbortOption) -> Result<(), UsbError> {
+        match scope {
+            AbortScope::Pipe => self.abort(option),
+            AbortScope::Stream(stream_id) => self.abort_stream(stream_id, option),
+        }
+    }
+
+    pub fn enable_streams(&self) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe { self.inner.as_ref().enableStreamsWithError_(&mut *err) } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn disable_streams(&self) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe { self.inner.as_ref().disableStreamsWithError_(&mut *err) } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn copy_stream(&self, stream_id: u64) -> Result<HostStream, UsbError> {
+        let mut err = NSErr::new();
+        let stream = unsafe {
+            self.inner
+                .as_ref()
+                .copyStreamWithStreamID_error_(stream_id, &mut *err)
+        };
+        if err.is_err() {
+            Err(err.into())
+        } else {
+            Ok(HostStream {
+                inner: stream,
+                interface: self.interface.clone(),
+                stats: Arc::new(TransferStats::with_parent(self.stats.clone())),
+                incoming: self.is_in(),
+            })
+        }
+    }
+
+    ///the descriptors the pipe was created with
+    pub fn original_descriptors(&self) -> Option<IoSourceDescriptors<'_>> {
+        IoSourceDescriptors::new(unsafe { self.inner.as_ref().originalDescriptors() })
+    }
+
+    ///the descriptors currently in effect, differing from the original ones after `adjust`
+    pub fn descriptors(&self) -> Option<IoSourceDescriptors<'_>> {
+        IoSourceDescriptors::new(unsafe { self.inner.as_ref().descriptors() })
+    }
+
+    pub fn idle_timeout(&self) -> f64 {
+        unsafe { self.inner.as_ref().idleTimeout() }
+    }
+
+    ///submits every transfer in `transfers` back to back with a single hop onto the pipe's queue,
+    ///taking one turn of a serialized pipe or device for the whole batch, and resolves once all of
+    ///them have completed with one status per transfer in the same order
+    ///
+    ///batches only send: if any transfer would receive, an IO request on an IN pipe or an IN
+    ///control request, none are submitted and every one fails with `UsbError::InvalidArgument`;
+    ///`enqueue_read` and `enqueue_control_request_with_data` hand back what they received
+    #[cfg(feature = "async")]
+    pub async fn enqueue_batch(
+        &self,
+        transfers: &[TransferSpec<'_>],
+    ) -> Vec<Result<u64, UsbError>> {
+        if let Err(err) = self.interface.check_open() {
+            return transfers.iter().map(|_| Err(err)).collect();
+        }
+        let incoming = transfers.iter().any(|transfer| match transfer {
+            TransferSpec::Io(_) => self.is_in(),
+            TransferSpec::Control(request, _) => request.is_in(),
+        });
+        if incoming {
+            return transfers
+                .iter()
+                .map(|_| Err(UsbError::InvalidArgument))
+                .collect();
+        }
+        let control = transfers
+            .iter()
+            .any(|transfer| matches!(transfer, TransferSpec::Control(..)));
+        let _device_turn = match control {
+            true => self.interface.device().gate.enter_async().await,
+            false => None,
+        };
+        let _turn = self.gate.enter_async().await;
+        let batch = Batch::new(self.interface.queue(), self.inner.as_ptr() as id);
+        let requests = JoinAll::new(
+            transfers
+                .iter()
+                .map(|transfer| self.enqueue_batched(&batch, *transfer)),
+        );
+        batch.submitting(requests).await
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_batched(
+        &self,
+        batch: &Batch,
+        transfer: TransferSpec<'_>,
+    ) -> Result<u64, UsbError> {
+        let pipe = self.inner;
+        let (incoming, requested, batched) = match transfer {
+            TransferSpec::Io(data) => {
+                let data = MutData::with_data(data).raw();
+                let batched = batch.request(data, move |cb| {
+                    let mut err = NSErr::new();
+                    if !unsafe {
+                        pipe.as_ref()
+                            .enqueueIORequestWithData_completionTimeout_error_completionHandler_(
+                                data, 0.0, &mut *err, cb,
+                            )
+                    } {
+                        Some(err.into())
+                    } else {
+                        None
+                    }
+                });
+                (self.is_in(), Some(unsafe { data.length() }), batched)
+            }
+            TransferSpec::Control(request, data) => {
+                let data = MutData::with_data(data).raw();
+                let batched = batch.request(data, move |cb| {
+                    let mut err = NSErr::new();
+                    if !unsafe {
+                        pipe.as_ref()
+                            .enqueueControlRequest_data_completionTimeout_error_completionHandler_(
+                                request.into(),
+                                data,
+                                0.0,
+                                &mut *err,
+                                cb,
+                            )
+                    } {
+                        Some(err.into())
+                    } else {
+                        None
+                    }
+                });
+                (request.is_in(), None, batched)
+            }
+        };
+        let recording = self.stats.begin(incoming);
+        let id = recording.id();
+        let batched = batched
+            .deliver_on(self.interface.executor())
+            .recorded(recording);
+        let transferred = self.inject_async(batched).await?.result()?;
+        match requested {
+            Some(requested) => self.check_short(requested, transferred, id),
+            None => Ok(transferred),
+        }
+    }
+
+    pub(crate) fn is_in(&self) -> bool {
+        self.endpoint_address().is_in()
+    }
+
+    pub fn endpoint_address(&self) -> EndpointAddress {
+        EndpointAddress(unsafe { self.inner.as_ref().endpointAddress() } as u8)
+    }
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.as_ref().deviceAddress() }
+    }
+
+    ///from the pipe's current descriptors, which `adjust` may have changed
+    pub fn transfer_type(&self) -> Option<EndpointType> {
+        Some(self.descriptors()?.endpoint_descriptor().transfer_type())
+    }
+
+    ///from the pipe's current descriptors, which `adjust` may have changed
+    pub fn max_packet_size(&self) -> Option<u16> {
+        Some(self.descriptors()?.endpoint_descriptor().max_packet_size())
+    }
+
+    ///bInterval of the pipe's current descriptors, which `adjust` may have changed
+    pub fn interval(&self) -> Option<u8> {
+        Some(self.descriptors()?.endpoint_descriptor().interval())
+    }
+
+    ///the `USBSpeed` registry property of the pipe's device
+    pub(crate) fn usb_device_speed(&self) -> Option<u32> {
+        let device = self.interface.device();
+        let speed = IoService::from_raw(unsafe { device.object.ioService() })
+            .number_property(c"USBSpeed")?;
+        u32::try_from(speed).ok()
+    }
+
+    ///issues GET_STATUS for this pipe's endpoint on the device's default control pipe
+    pub fn endpoint_status(&self) -> Result<EndpointStatus, UsbError> {
+        let address = u8::from(self.endpoint_address());
+        let request = DeviceRequest::get_status(RECIPIENT_ENDPOINT, address as u16);
+        let bytes = self.interface.device_request_in(request)?;
+        parse_status(&bytes).map(EndpointStatus::from)
+    }
+
+    ///counters for every transfer submitted through this pipe handle
+    pub fn stats(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    ///when enabled transfers on this pipe are submitted one at a time, see
+    ///`UsbDevice::set_serialize_requests`
+    pub fn set_serialized(&self, enabled: bool) {
+        self.gate.set_enabled(enabled);
+    }
+
+    pub fn is_serialized(&self) -> bool {
+        self.gate.is_enabled()
+    }
+
+    ///how the IN transfers of this pipe, blocking and async, treat short packets, accepting them by
+    ///default
+    pub fn set_short_packet_policy(&self, policy: ShortPacketPolicy) {
+        self.short_packets.set(policy);
+    }
+
+    pub fn short_packet_policy(&self) -> ShortPacketPolicy {
+        self.short_packets.get()
+    }
+
+    //applies the short packet policy to IN transfer `id`, which moved `transferred` of
+    //`requested` bytes
+    fn check_short(
+        &self,
+        requested: u64,
+        transferred: u64,
+        id: TransferId,
+    ) -> Result<u64, UsbError> {
+        if !self.is_in() || transferred >= requested {
+            return Ok(transferred);
+        }
+        match self.short_packets.get() {
+            ShortPacketPolicy::Accept => Ok(transferred),
+            ShortPacketPolicy::Underrun => {
+                crate::diag!(
+                    debug,
+                    "transfer {} ended with a short packet on endpoint {:#04x}: {} of {} bytes",
+                    id,
+                    self.address.0,
+                    transferred,
+                    requested
+                );
+                Err(IoReturnError::Underrun.into())
+            }
+        }
+    }
+
+    ///injects faults into the transfers made through this pipe from now on, `None` stops
+    ///injecting; the injector's sequence starts over every time it is set
+    #[cfg(feature = "fault-injection")]
+    pub fn set_fault_injector(&self, injector: Option<FaultInjector>) {
+        *self
+            .faults
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = injector.map(FaultState::new);
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub fn fault_injector(&self) -> Option<FaultInjector> {
+        self.faults
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .map(|state| state.injector().clone())
+    }
+
+    #[cfg(feature = "fault-injection")]
+    fn next_fault(&self) -> Option<Fault> {
+        self.faults
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_mut()
+            .map(FaultState::next)
+    }
+
+    //runs `transfer` through the pipe's fault injector, if one is set
+    fn inject<T>(&self, transfer: impl FnOnce() -> Result<T, UsbError>) -> Result<T, UsbError> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(fault) = self.next_fault() {
+            return fault.apply(transfer);
+        }
+        transfer()
+    }
+
+    //`inject` for transfers reporting a length, which injected short packets cut down
+    fn inject_len(
+        &self,
+        transfer: impl FnOnce() -> Result<u64, UsbError>,
+    ) -> Result<u64, UsbError> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(fault) = self.next_fault() {
+            return fault.apply(transfer).map(|len| fault.shorten(len));
+        }
+        transfer()
+    }
+
+    //`inject` for async requests, applied to what their completion reports so injected short
+    //packets cut down async transfers as well
+    #[cfg(feature = "async")]
+    async fn inject_async(
+        &self,
+        transfer: impl core::future::Future<Output = Result<Completed, UsbError>>,
+    ) -> Result<Completed, UsbError> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(fault) = self.next_fault() {
+            return fault.apply_async(transfer).await;
+        }
+        transfer.await
+    }
+}
+
+const LATENCY_BUCKETS: usize = 24;
+
+///a transfer `TransferStats::begin` counted as submitted; async transfers hand it to their
+///completion handler, so it is finished with the bytes the framework reports, and one dropped
+///without being finished, such as the one of a future dropped before it was submitted, counts as
+///failed instead of staying in flight forever
+///
+///the submission and the outcome are logged under the transfer's id
+pub(crate) struct Recording {
+    stats: Arc<TransferStats>,
+    id: TransferId,
+    start: Instant,
+    incoming: bool,
+    finished: bool,
+}
+
+impl Recording {
+    pub(crate) fn id(&self) -> TransferId {
+        self.id
+    }
+
+    pub(crate) fn finish(mut self, res: Result<u64, UsbError>) {
+        self.finished = true;
+        match res {
+            Ok(bytes) => crate::diag!(trace, "transfer {} completed with {} bytes", self.id, bytes),
+            Err(err) => crate::diag!(debug, "transfer {} failed: {:?}", self.id, err),
+        }
+        self.stats.record(self.start, self.incoming, res.ok());
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if !self.finished {
+            crate::diag!(debug, "transfer {} dropped before it completed", self.id);
+            self.stats.record(self.start, self.incoming, None);
+        }
+    }
+}
+
+///transfer counters kept by pipes and devices, updated with relaxed atomics so recording never
+///blocks a completion, unless someone waits in `wait_idle`
+#[derive(Default)]
+pub(crate) struct TransferStats {
+    submitted: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS],
+    //counts everything recorded here as well, pipes roll up into their device and devices into
+    //the process wide totals
+    parent: Option<Arc<TransferStats>>,
+    //`wait_idle` callers, woken whenever a transfer is recorded while there are any
+    waiting: core::sync::atomic::AtomicUsize,
+    idle: std::sync::Mutex<()>,
+    idle_changed: std::sync::Condvar,
+}
+
+impl TransferStats {
+    pub(crate) fn with_parent(parent: Arc<TransferStats>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    ///counts a transfer as submitted under a new id, it stays in flight until the returned
+    ///`Recording` finished or was dropped
+    pub(crate) fn begin(self: &Arc<Self>, incoming: bool) -> Recording {
+        self.begin_as(TransferId::next(), incoming)
+    }
+
+    ///`begin` for a `Transfer` that was handed its id before it was submitted
+    pub(crate) fn begin_as(self: &Arc<Self>, id: TransferId, incoming: bool) -> Recording {
+        crate::diag!(trace, "transfer {} submitted", id);
+        self.count_submitted();
+        Recording {
+            stats: self.clone(),
+            id,
+            start: Instant::now(),
+            incoming,
+            finished: false,
+        }
+    }
+
+    fn count_submitted(&self) {
+        self.submitted.fetch_add(1, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.count_submitted();
+        }
+    }
+
+    ///`bytes` is `None` when the transfer failed
+    fn record(&self, start: Instant, incoming: bool, bytes: Option<u64>) {
+        if let Some(parent) = &self.parent {
+            parent.record(start, incoming, bytes);
+        }
+        self.count(start, incoming, bytes);
+        core::sync::atomic::fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) > 0 {
+            let _idle = self
+                .idle
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            self.idle_changed.notify_all();
+        }
+    }
+
+    fn count(&self, start: Instant, incoming: bool, bytes: Option<u64>) {
+        let Some(bytes) = bytes else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        if incoming {
+            self.bytes_in.fetch_add(bytes, Ordering::Relaxed);
+        } else {
+            self.bytes_out.fetch_add(bytes, Ordering::Relaxed);
+        }
+        let micros = start.elapsed().as_micros().max(1) as u64;
+        let bucket = (micros.ilog2() as usize).min(LATENCY_BUCKETS - 1);
+        self.latency[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///blocks until no transfer is in flight or `timeout` passed, returning whether none is; the
+    ///completions wake it, so it works from any thread
+    pub(crate) fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        core::sync::atomic::fence(Ordering::SeqCst);
+        let mut idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let drained = loop {
+            if self.snapshot().in_flight() == 0 {
+                break true;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break false;
+            }
+            idle = self
+                .idle_changed
+                .wait_timeout(idle, left)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        };
+        drop(idle);
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+        drained
+    }
+
+    pub(crate) fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            submitted: self.submitted.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            latency: core::array::from_fn(|idx| self.latency[idx].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsSnapshot {
+    pub submitted: u64,
+    pub completed: u64,
+    pub failed: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    ///completion latency histogram, bucket `n` counting transfers that took
+    ///`2^n..2^(n + 1)` microseconds, the last bucket also holding everything slower
+    pub latency: [u64; LATENCY_BUCKETS],
+}
+
+impl StatsSnapshot {
+    ///transfers submitted but not yet completed or failed
+    pub fn in_flight(&self) -> u64 {
+        self.submitted
+            .saturating_sub(self.completed)
+            .saturating_sub(self.failed)
+    }
+
+    ///upper bound in microseconds of the bucket holding the `quantile` (0.0..=1.0) latency
+    pub fn latency_quantile(&self, quantile: f64) -> Option<u64> {
+        let total: u64 = self.latency.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let target = ((total as f64) * quantile.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.latency.iter().enumerate().find_map(|(idx, count)| {
+            seen += count;
+            (seen >= target).then(|| 1 << (idx + 1))
+        })
+    }
+
+    ///publishes the counters as `metrics` gauges labelled with `scope`
+    #[cfg(feature = "metrics")]
+    pub fn publish(&self, scope: &str) {
+        let scope = scope.to_string();
+        metrics::gauge!("iousbhost_transfers_submitted", "scope" => scope.clone())
+            .set(self.submitted as f64);
+        metrics::gauge!("iousbhost_transfers_completed", "scope" => scope.clone())
+            .set(self.completed as f64);
+        metrics::gauge!("iousbhost_transfers_failed", "scope" => scope.clone())
+            .set(self.failed as f64);
+        metrics::gauge!("iousbhost_bytes_in", "scope" => scope.clone()).set(self.bytes_in as f64);
+        metrics::gauge!("iousbhost_bytes_out", "scope" => scope).set(self.bytes_out as f64);
+    }
+}
+
+//the totals every device's counters roll up into
+fn crate_totals() -> &'static Arc<TransferStats> {
+    static TOTALS: std::sync::OnceLock<Arc<TransferStats>> = std::sync::OnceLock::new();
+    TOTALS.get_or_init(Arc::default)
+}
+
+struct RegisteredDevice {
+    info: DeviceMetrics,
+    requests: std::sync::Weak<TransferStats>,
+    pipes: std::sync::Weak<TransferStats>,
+}
+
+static DEVICES: std::sync::Mutex<Vec<RegisteredDevice>> = std::sync::Mutex::new(Vec::new());
+
+///the counters of a device opened from `service`, registered for `CrateMetricsSnapshot`; returns
+///the counters for its device requests and the ones its pipes roll up into
+pub(crate) fn register_device_stats(
+    service: io_service_t,
+) -> (Arc<TransferStats>, Arc<TransferStats>) {
+    let requests = Arc::new(TransferStats::with_parent(crate_totals().clone()));
+    let pipes = Arc::new(TransferStats::with_parent(crate_totals().clone()));
+    let io_service = IoService::from_raw(service);
+    let property = |key: &core::ffi::CStr| io_service.number_property(key);
+    let mut id = 0;
+    let info = DeviceMetrics {
+        registry_entry_id: (unsafe { IORegistryEntryGetRegistryEntryID(service, &mut id) } == 0)
+            .then_some(id),
+        vendor_id: property(c"idVendor").and_then(|id| u16::try_from(id).ok()),
+        product_id: property(c"idProduct").and_then(|id| u16::try_from(id).ok()),
+        location_id: property(c"locationID").and_then(|id| u32::try_from(id).ok()),
+        requests: StatsSnapshot::default(),
+        pipes: StatsSnapshot::default(),
+    };
+    let mut devices = DEVICES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    devices.retain(|device| device.requests.strong_count() > 0);
+    devices.push(RegisteredDevice {
+        info,
+        requests: Arc::downgrade(&requests),
+        pipes: Arc::downgrade(&pipes),
+    });
+    (requests, pipes)
+}
+
+///the counters of one open device, see `CrateMetricsSnapshot`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceMetrics {
+    pub registry_entry_id: Option<u64>,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub location_id: Option<u32>,
+    ///device requests sent through the device's handles
+    pub requests: StatsSnapshot,
+    ///transfers on every pipe opened from the device, closed ones included
+    pub pipes: StatsSnapshot,
+}
+
+///the transfer counters of the whole process as plain data, for exporting to Prometheus, StatsD
+///or whatever backend a service uses
+///
+///the totals only ever grow, closed devices drop out of the per device breakdown
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrateMetricsSnapshot {
+    ///every transfer since the process started
+    pub totals: StatsSnapshot,
+    ///the devices still open, in the order they were opened
+    pub devices: Vec<DeviceMetrics>,
+}
+
+impl CrateMetricsSnapshot {
+    pub fn capture() -> Self {
+        let mut devices = DEVICES
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        devices.retain(|device| device.requests.strong_count() > 0);
+        let devices = devices
+            .iter()
+            .filter_map(|device| {
+                let requests = device.requests.upgrade()?;
+                let pipes = device.pipes.upgrade()?;
+                Some(DeviceMetrics {
+                    requests: requests.snapshot(),
+                    pipes: pipes.snapshot(),
+                    ..device.info
+                })
+            })
+            .collect();
+        Self {
+            totals: crate_totals().snapshot(),
+            devices,
+        }
+    }
+}
+
+///a transfer of `HostPipe::enqueue_batch`, which only takes transfers sending data
+#[derive(Clone, Copy)]
+pub enum TransferSpec<'d> {
+    ///an IO request sending these bytes on an OUT pipe
+    Io(&'d [u8]),
+    ///an OUT control request with the bytes of its data stage, empty for requests without one
+    Control(DeviceRequest, &'d [u8]),
+}
+
+///what a `HostPipe::abort_scope` cancels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbortScope {
+    ///every request on the pipe, including those of all of its streams
+    Pipe,
+    ///only the requests of the stream with this id
+    Stream(u64),
+}
+
+pub struct HostStream {
+    pub(crate) inner: IOUSBHostStream,
+    interface: Rc<SharedObject>,
+    //counted towards the pipe the stream was copied from
+    stats: Arc<TransferStats>,
+    incoming: bool,
+}
+
+impl HostStream {
+    ///aborts the requests of this stream only, the pipe's other streams are not touched
+    pub fn abort(&self, option: AbortOption) -> Result<(), UsbError> {
+        let mut err = NSErr::new();
+        if !unsafe { self.inner.abortWithOption_error_(option.into(), &mut *err) } {
+            Err(err.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn send_io_request(&self, data: &mut [u8]) -> Result<u64, UsbError> {
+        self.interface.check_open()?;
+        let recording = self.stats.begin(self.incoming);
+        let mut err = NSErr::new();
+        let data = MutData::with_data(data).raw();
+        let mut transferred = 0;
+        let res = if !unsafe {
+            self.inner.sendIORequestWithData_bytesTransferred_error_(
+                data,
+                &mut transferred,
+                &mut *err,
+            )
+        } {
+            Err(err.into())
+        } else {
+            Ok(transferred)
+        };
+        recording.finish(res);
+        res
+    }
+
+    #[cfg(feature = "async")]
+    pub fn enqueue_io_request<'s>(
+        &'s self,
+        data: &'s [u8],
+    ) -> Transfer<impl core::future::Future<Output = Result<u64, UsbError>> + 's> {
+        Transfer::new(|id| self.enqueue_io_request_inner(data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_io_request_inner(&self, data: &[u8], id: TransferId) -> Result<u64, UsbError> {
+        self.interface.check_open()?;
+        let ptr = unsafe {
+            NonNull::new_unchecked(&self.inner as *const IOUSBHostStream as *mut IOUSBHostStream)
+        };
+        let handler = AsyncDataHandler::new(ptr, data, |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueIORequestWithData_error_completionHandler_(data, &mut *err, cb)
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(self.stats.begin_as(id, self.incoming));
+
+        handler.await?.result()
+    }
+
+    ///receives up to `buf.len()` bytes on an IN stream, returning how many arrived
+    pub fn read(&self, buf: &mut [u8]) -> Result<u64, UsbError> {
+        self.interface.check_open()?;
+        let recording = self.stats.begin(self.incoming);
+        let data = MutData::zeroed(buf.len());
+        let mut err = NSErr::new();
+        let mut transferred = 0;
+        if !unsafe {
+            self.inner.sendIORequestWithData_bytesTransferred_error_(
+                data.inner,
+                &mut transferred,
+                &mut *err,
+            )
+        } {
+            let err = err.into();
+            recording.finish(Err(err));
+            return Err(err);
+        }
+        recording.finish(Ok(transferred));
+
+        let count = (transferred as usize).min(buf.len());
+        buf[..count].copy_from_slice(&data.bytes()[..count]);
+        Ok(transferred)
+    }
+
+    ///receives up to `len` bytes on an IN stream
+    pub fn read_to_vec(&self, len: usize) -> Result<Vec<u8>, UsbError> {
+        let mut buf = vec![0; len];
+        let transferred = self.read(&mut buf)?;
+        buf.truncate(transferred as usize);
+        Ok(buf)
+    }
+
+    ///receives up to the length of `data` on an IN stream, handing the buffer back in the
+    ///`TransferResult` whether the request succeeded or not, as `HostPipe::enqueue_read` does
+    #[cfg(feature = "async")]
+    pub fn enqueue_read(
+        &self,
+        data: IoData,
+    ) -> Transfer<impl core::future::Future<Output = Result<TransferResult, UsbError>> + '_> {
+        Transfer::new(|id| self.enqueue_read_inner(data, id))
+    }
+
+    #[cfg(feature = "async")]
+    async fn enqueue_read_inner(
+        &self,
+        data: IoData,
+        id: TransferId,
+    ) -> Result<TransferResult, UsbError> {
+        self.interface.check_open()?;
+        let ptr = unsafe {
+            NonNull::new_unchecked(&self.inner as *const IOUSBHostStream as *mut IOUSBHostStream)
+        };
+        let handler = AsyncDataHandler::with_raw(ptr, data.as_raw(), |dev, data, cb| {
+            let mut err = NSErr::new();
+            if !unsafe {
+                dev.enqueueIORequestWithData_error_completionHandler_(data, &mut *err, cb)
+            } {
+                Some(err.into())
+            } else {
+                None
+            }
+        })
+        .deliver_on(self.interface.executor())
+        .recorded(self.stats.begin_as(id, self.incoming));
+
+        Ok(TransferResult::completed(data, handler.await, id))
+    }
+
+    /*
+    fn host_pipe(&self) -> HostPipe {
+        HostPipe{
+            inner: unsafe { self.inner.hostPipe() }
+        }
+    }
+    */
+
+    pub fn stream_id(&self) -> u64 {
+        unsafe { self.inner.streamID() }
+    }
+}
+
+///spreads submissions round robin across a set of streams of one pipe, as UAS style protocols
+///do, and records the order they complete in on every stream
+#[cfg(feature = "async")]
+pub struct StreamWriter {
+    streams: Vec<HostStream>,
+    next: core::sync::atomic::AtomicUsize,
+    //the tags completed on every stream, oldest first
+    completed: std::sync::Mutex<Vec<std::collections::VecDeque<u64>>>,
+}
+
+#[cfg(feature = "async")]
+impl StreamWriter {
+    ///`None` if `streams` is empty
+    pub fn new(streams: Vec<HostStream>) -> Option<Self> {
+        if streams.is_empty() {
+            return None;
+        }
+        let completed = streams.iter().map(|_| Default::default()).collect();
+        Some(Self {
+            streams,
+            next: core::sync::atomic::AtomicUsize::new(0),
+            completed: std::sync::Mutex::new(completed),
+        })
+    }
+
+    ///copies the streams with `stream_ids` from `pipe`, which must have streams enabled
+    pub fn from_pipe(
+        pipe: &HostPipe<'_>,
+        stream_ids: impl IntoIterator<Item = u64>,
+    ) -> Result<Self, UsbError> {
+        let streams = stream_ids
+            .into_iter()
+            .map(|id| pipe.copy_stream(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(streams).ok_or(UsbError::InvalidArgument)
+    }
+
+    pub fn streams(&self) -> &[HostStream] {
+        &self.streams
+    }
+
+    ///enqueues `data` on the next stream in turn, resolving with the id of the stream it went
+    ///out on once it completed
+    pub async fn submit(&self, tag: u64, data: &[u8]) -> Result<u64, UsbError> {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.streams.len();
+        let stream = &self.streams[idx];
+        stream.enqueue_io_request(data).await?;
+        self.completed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)[idx]
+            .push_back(tag);
+        Ok(stream.stream_id())
+    }
+
+    ///drains the tags completed on the stream `stream_id` in the order they completed
+    pub fn take_completed(&self, stream_id: u64) -> Vec<u64> {
+        let Some(idx) = self
+            .streams
+            .iter()
+            .position(|stream| stream.stream_id() == stream_id)
+        else {
+            return Vec::new();
+        };
+        self.completed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)[idx]
+            .drain(..)
+            .collect()
+    }
+}
+
+pub struct HostIoSource {
+    pub(crate) inner: IOUSBHostIOSource,
+}
+
+/*
+pub struct InterfacePropertyKey(NSString);
+pub struct DevicePropertyKey(NSString);
+pub struct MatchingPropertyKey(NSString);
+pub struct PropertyKey(NSString);
+*/
+
+impl HostIoSource {
+    /*
+    fn host_interface(&self) -> HostInterface {
+        HostInterface {
+            inner: unsafe{self.inner.hostInterface()}
+        }
+    }
+    */
+
+    pub fn device_address(&self) -> u64 {
+        unsafe { self.inner.deviceAddress() }
+    }
+
+    pub fn endpoint_address(&self) -> EndpointAddress {
+        EndpointAddress(unsafe { self.inner.endpointAddress() } as u8)
+    }
+}
+
+///the endpoint descriptor of a pipe together with the SuperSpeed companion descriptors that apply
+///to it, as consumed by `HostPipe::adjust`
+pub struct IoSourceDescriptors<'a> {
+    pub(crate) inner: NonNull<IOUSBHostIOSourceDescriptors>,
+    lt: PhantomData<&'a IOUSBHostIOSourceDescriptors>,
+}
+
+///anything `HostPipe::adjust` accepts as the new descriptors of a pipe
+pub trait IntoRawSource {
+    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors;
+}
+
+impl IntoRawSource for IoSourceDescriptors<'_> {
+    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors {
+        self.inner.as_ptr()
+    }
+}
+
+impl IoSourceDescriptors<'_> {
+    pub(crate) fn new(ptr: *const IOUSBHostIOSourceDescriptors) -> Option<Self> {
+        let ptr = NonNull::new(ptr as *mut IOUSBHostIOSourceDescriptors)?;
+        Some(Self {
+            inner: ptr,
+            lt: PhantomData,
+        })
+    }
+
+    pub fn bcd_usb(&self) -> u16 {
+        unsafe { self.inner.as_ref().bcdUSB }
+    }
+
+    pub fn endpoint_descriptor(&self) -> EndpointDescriptor<'_> {
+        let ptr = unsafe { &self.inner.as_ref().descriptor };
+        EndpointDescriptor::from_ref(ptr)
+    }
+
+    ///present from USB 3.0 on
+    pub fn super_speed_companion_descriptor(&self) -> Option<SuperSpeedCompanionDescriptor<'_>> {
+        if self.bcd_usb() < 0x0300 {
+            return None;
+        }
+        let ptr = unsafe { &self.inner.as_ref().ssCompanionDescriptor };
+        SuperSpeedCompanionDescriptor::new(ptr)
+    }
+
+    ///present on SuperSpeedPlus isochronous endpoints, flagged by bit 7 of the SuperSpeed
+    ///companion's attributes
+    pub fn super_speed_plus_companion_descriptor(
+        &self,
+    ) -> Option<SuperSpeedPlusCompanionDescriptor<'_>> {
+        let companion = self.super_speed_companion_descriptor()?;
+        if companion.attributes() & 0x80 == 0 {
+            return None;
+        }
+        let ptr = unsafe { &self.inner.as_ref().sspCompanionDescriptor };
+        SuperSpeedPlusCompanionDescriptor::new(ptr)
+    }
+}
+
+///owned copy of a pipe's descriptors, edited field by field and handed to `HostPipe::adjust`
+#[derive(Clone, Copy)]
+pub struct AdjustBuilder {
+    original: IOUSBHostIOSourceDescriptors,
+    inner: IOUSBHostIOSourceDescriptors,
+}
+
+impl IntoRawSource for AdjustBuilder {
+    fn raw(&self) -> *const IOUSBHostIOSourceDescriptors {
+        &self.inner
+    }
+}
+
+impl AdjustBuilder {
+    ///starts from the descriptors the pipe was created with
+    pub fn new(pipe: &HostPipe<'_>) -> Option<Self> {
+        let ptr = unsafe { pipe.inner.as_ref().originalDescriptors() };
+        let original = unsafe { ptr.as_ref() }.copied()?;
+        Some(Self {
+            original,
+            inner: original,
+        })
+    }
+
+    fn transfer_type(&self) -> EndpointType {
+        EndpointType::from(self.inner.descriptor.bmAttributes)
+    }
+
+    ///`bInterval`, an exponent for isochronous endpoints and high speed interrupt endpoints
+    pub fn interval(mut self, interval: u8) -> Result<Self, AdjustError> {
+        let valid = match self.transfer_type() {
+            EndpointType::Isochronous => (1..=16).contains(&interval),
+            EndpointType::Interrupt => interval >= 1,
+            _ => false,
+        };
+        if !valid {
+            return Err(AdjustError::InvalidInterval(interval));
+        }
+        self.inner.descriptor.bInterval = interval;
+        Ok(self)
+    }
+
+    ///`bInterval` of an interrupt endpoint polled every `interval` on a device running at `speed`,
+    ///the `USBSpeed` registry value: whole milliseconds up to 255 below high speed, 125µs times a
+    ///power of two up to 2^15 from high speed on
+    pub fn polling_interval(self, interval: Duration, speed: u32) -> Result<Self, AdjustError> {
+        if self.transfer_type() != EndpointType::Interrupt {
+            return Err(AdjustError::InvalidPollingInterval(interval));
+        }
+        let encoded = encode_polling_interval(interval, speed)
+            .ok_or(AdjustError::InvalidPollingInterval(interval))?;
+        self.interval(encoded)
+    }
+
+    ///`wMaxPacketSize`, bits 0..=10 holding the packet size and bits 11..=12 the additional
+    ///transactions per microframe
+    pub fn max_packet_size(mut self, max_packet_size: u16) -> Result<Self, AdjustError> {
+        let size = max_packet_size & 0x7ff;
+        let additional = (max_packet_size >> 11) & 0x3;
+        if size == 0 || size > 1024 || additional == 3 || max_packet_size >> 13 != 0 {
+            return Err(AdjustError::InvalidMaxPacketSize(max_packet_size));
+        }
+        let original = self.original.descriptor.wMaxPacketSize;
+        if self.transfer_type() == EndpointType::Bulk && max_packet_size != original {
+            return Err(AdjustError::InvalidMaxPacketSize(max_packet_size));
+        }
+        self.inner.descriptor.wMaxPacketSize = max_packet_size;
+        Ok(self)
+    }
+
+    ///`bMaxBurst` of the SuperSpeed companion descriptor
+    pub fn max_burst(mut self, max_burst: u8) -> Result<Self, AdjustError> {
+        if self.inner.bcdUSB < 0x0300 {
+            return Err(AdjustError::NotSuperSpeed);
+        }
+        if max_burst > 15 {
+            return Err(AdjustError::InvalidMaxBurst(max_burst));
+        }
+        self.inner.ssCompanionDescriptor.bMaxBurst = max_burst;
+        Ok(self)
+    }
+
+    ///`wBytesPerInterval` of the SuperSpeed companion descriptor
+    pub fn bytes_per_interval(mut self, bytes_per_interval: u16) -> Result<Self, AdjustError> {
+        if self.inner.bcdUSB < 0x0300 {
+            return Err(AdjustError::NotSuperSpeed);
+        }
+        self.inner.ssCompanionDescriptor.wBytesPerInterval = bytes_per_interval;
+        Ok(self)
+    }
+
+    ///the fields changed relative to the original descriptors
+    pub fn diff(&self) -> Vec<AdjustChange> {
+        let (original, adjusted) = (&self.original, &self.inner);
+        let mut changes = Vec::new();
+        if original.descriptor.bInterval != adjusted.descriptor.bInterval {
+            changes.push(AdjustChange::Interval {
+                from: original.descriptor.bInterval,
+                to: adjusted.descriptor.bInterval,
+            });
+        }
+        if original.descriptor.wMaxPacketSize != adjusted.descriptor.wMaxPacketSize {
+            changes.push(AdjustChange::MaxPacketSize {
+                from: original.descriptor.wMaxPacketSize,
+                to: adjusted.descriptor.wMaxPacketSize,
+            });
+        }
+        if original.ssCompanionDescriptor.bMaxBurst != adjusted.ssCompanionDescriptor.bMaxBurst {
+            changes.push(AdjustChange::MaxBurst {
+                from: original.ssCompanionDescriptor.bMaxBurst,
+                to: adjusted.ssCompanionDescriptor.bMaxBurst,
+            });
+        }
+        if original.ssCompanionDescriptor.wBytesPerInterval
+            != adjusted.ssCompanionDescriptor.wBytesPerInterval
+        {
+            changes.push(AdjustChange::BytesPerInterval {
+                from: original.ssCompanionDescriptor.wBytesPerInterval,
+                to: adjusted.ssCompanionDescriptor.wBytesPerInterval,
+            });
+        }
+        changes
+    }
+}
+
+const MICROFRAME: Duration = Duration::from_micros(125);
+
+//low and full speed devices (`USBSpeed` 0 and 1) count interrupt intervals in frames, faster ones
+//as the exponent of a number of microframes
+fn encode_polling_interval(interval: Duration, speed: u32) -> Option<u8> {
+    if speed <= 1 {
+        let millis = u8::try_from(interval.as_millis()).ok()?;
+        let exact = Duration::from_millis(millis.into()) == interval;
+        return (exact && millis >= 1).then_some(millis);
+    }
+    (0..16u8)
+        .find(|exponent| MICROFRAME * (1 << exponent) == interval)
+        .map(|exponent| exponent + 1)
+}
+
+fn decode_polling_interval(interval: u8, speed: u32) -> Option<Duration> {
+    if speed <= 1 {
+        return (interval >= 1).then(|| Duration::from_millis(interval.into()));
+    }
+    (1..=16)
+        .contains(&interval)
+        .then(|| MICROFRAME * (1 << (interval - 1)))
+}
+
+///an interrupt pipe, whose polling interval can be changed while the interface stays open, as
+///HID devices may want for power tuning
+pub struct InterruptPipe<'p, 'a> {
+    pipe: &'p HostPipe<'a>,
+}
+
+impl<'p, 'a> InterruptPipe<'p, 'a> {
+    ///`None` unless `pipe` is an interrupt pipe
+    pub fn new(pipe: &'p HostPipe<'a>) -> Option<Self> {
+        (pipe.transfer_type()? == EndpointType::Interrupt).then_some(Self { pipe })
+    }
+
+    pub fn pipe(&self) -> &'p HostPipe<'a> {
+        self.pipe
+    }
+
+    ///how often the host currently polls the endpoint
+    pub fn polling_interval(&self) -> Option<Duration> {
+        decode_polling_interval(self.pipe.interval()?, self.pipe.usb_device_speed()?)
+    }
+
+    ///adjusts the pipe to be polled every `interval`, see `AdjustBuilder::polling_interval` for
+    ///the intervals each speed can encode; the other fields go back to the original descriptors
+    pub fn set_polling_interval(&self, interval: Duration) -> Result<(), UsbError> {
+        let speed = self.pipe.usb_device_speed().ok_or(UsbError::NotSupported)?;
+        let adjusted = AdjustBuilder::new(self.pipe)
+            .ok_or(UsbError::InvalidObject)?
+            .polling_interval(interval, speed)?;
+        self.pipe.adjust(adjusted)
+    }
+}
+
+///a control pipe, whose max packet size can be fixed up once the device descriptor is known, as
+///enumeration tools need before sending requests longer than a packet
+pub struct ControlPipe<'p, 'a> {
+    pipe: &'p HostPipe<'a>,
+}
+
+impl<'p, 'a> ControlPipe<'p, 'a> {
+    ///`None` unless `pipe` is a control pipe
+    pub fn new(pipe: &'p HostPipe<'a>) -> Option<Self> {
+        (pipe.transfer_type()? == EndpointType::Control).then_some(Self { pipe })
+    }
+
+    pub fn pipe(&self) -> &'p HostPipe<'a> {
+        self.pipe
+    }
+
+    ///reads the device descriptor and adjusts the pipe to its `bMaxPacketSize0`, returning the max
+    ///packet size in bytes; from USB 3.0 on the field is an exponent, 9 meaning 512 bytes
+    pub fn update_max_packet_from_device_descriptor(&self) -> Result<u16, UsbError> {
+        let request = DeviceRequest::standard(
+            StandardRequest::GetDescriptor,
+            DeviceRequestRecipientValue::Device,
+            (u8::from(DescriptorType::Device) as u16) << 8,
+            0,
+            core::mem::size_of::<IOUSBDeviceDescriptor>() as u16,
+        );
+        let bytes = self.pipe.interface.device_request_in(request)?;
+        let descriptor =
+            DeviceDescriptor::from_bytes(&bytes).map_err(|_| UsbError::NoDeviceDescriptor)?;
+        let max_packet_size =
+            decode_max_packet_size0(descriptor.bcd_usb(), descriptor.max_packet_size())
+                .ok_or(UsbError::InvalidValue)?;
+        if self.pipe.max_packet_size() == Some(max_packet_size) {
+            return Ok(max_packet_size);
+        }
+        let adjusted = AdjustBuilder::new(self.pipe)
+            .ok_or(UsbError::InvalidObject)?
+            .max_packet_size(max_packet_size)?;
+        self.pipe.adjust(adjusted)?;
+        Ok(max_packet_size)
+    }
+}
+
+//`bMaxPacketSize0` is a size in bytes before USB 3.0 and the exponent 9 from then on
+fn decode_max_packet_size0(bcd_usb: u16, max_packet_size0: u8) -> Option<u16> {
+    if bcd_usb >= 0x0300 {
+        return (max_packet_size0 == 9).then_some(512);
+    }
+    matches!(max_packet_size0, 8 | 16 | 32 | 64).then_some(max_packet_size0.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdjustChange {
+    Interval { from: u8, to: u8 },
+    MaxPacketSize { from: u16, to: u16 },
+    MaxBurst { from: u8, to: u8 },
+    BytesPerInterval { from: u16, to: u16 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdjustError {
+    ///out of range for the endpoint's transfer type, or the endpoint has no interval
+    InvalidInterval(u8),
+    ///reserved bits set, a size above 1024, or a bulk endpoint's size changed
+    InvalidMaxPacketSize(u16),
+    InvalidMaxBurst(u8),
+    ///burst fields only exist on SuperSpeed endpoints
+    NotSuperSpeed,
+    ///not an interrupt endpoint, or the interval can not be encoded at the device's speed
+    InvalidPollingInterval(Duration),
+}
+
+impl From<AdjustError> for UsbError {
+    fn from(err: AdjustError) -> UsbError {
+        UsbError::InvalidAdjust(err)
+    }
+}
+
+impl core::fmt::Display for AdjustError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AdjustError::InvalidInterval(interval) => {
+                write!(f, "interval {interval} is not valid for this endpoint")
+            }
+            AdjustError::InvalidMaxPacketSize(size) => {
+                write!(f, "invalid max packet size {size:#06x}")
+            }
+            AdjustError::InvalidMaxBurst(burst) => write!(f, "max burst {burst} is above 15"),
+            AdjustError::NotSuperSpeed => write!(f, "burst fields require a SuperSpeed endpoint"),
+            AdjustError::InvalidPollingInterval(interval) => {
+                write!(
+                    f,
+                    "polling interval {interval:?} is not valid for this endpoint"
+                )
+            }
+        }
+    }
+}
+
+#[repr(transparent)]
+pub struct IsochronousFrame {
+    pub(crate) inner: IOUSBHostIsochronousFrame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    Err(UsbError),
+    ///kIOReturnIOError
+    IoError,
+    ///kIOReturnTimeout
+    Timeout,
+    ///kIOReturnUnderrun, fewer bytes than requested arrived
+    Underrun,
+    ///kIOReturnOverrun, more bytes than requested arrived
+    Overrun,
+    ///kIOReturnAborted, the request was aborted before the frame was transferred
+    Aborted,
+    ///kIOReturnNotResponding, no handshake from the device
+    NotResponding,
+    ///kIOReturnIsoTooOld, the frame number had already passed when the frame was scheduled
+    TooOld,
+    ///kIOReturnIsoTooNew, the frame number was too far in the future to be scheduled
+    TooNew,
+    ///kIOUSBPipeStalled
+    Stalled,
+    ///an IOReturn without a variant of its own
+    Other(i32),
+}
+
+impl Status {
+    const IO_ERROR: u32 = 0xe00002ca;
+    const TIMEOUT: u32 = 0xe00002d6;
+    const UNDERRUN: u32 = 0xe00002e7;
+    const OVERRUN: u32 = 0xe00002e8;
+    const ABORTED: u32 = 0xe00002eb;
+    const NOT_RESPONDING: u32 = 0xe00002ed;
+    const TOO_OLD: u32 = 0xe00002ee;
+    const TOO_NEW: u32 = 0xe00002ef;
+    const STALLED: u32 = 0xe000404f;
+
+    pub fn is_ok(&self) -> bool {
+        *self == Status::Ok
+    }
+}
+
+impl From<Status> for i32 {
+    fn from(status: Status) -> i32 {
+        let code = match status {
+            Status::Ok => return 0,
+            Status::Err(err) => return err.into(),
+            Status::Other(code) => return code,
+            Status::IoError => Status::IO_ERROR,
+            Status::Timeout => Status::TIMEOUT,
+            Status::Underrun => Status::UNDERRUN,
+            Status::Overrun => Status::OVERRUN,
+            Status::Aborted => Status::ABORTED,
+            Status::NotResponding => Status::NOT_RESPONDING,
+            Status::TooOld => Status::TOO_OLD,
+            Status::TooNew => Status::TOO_NEW,
+            Status::Stalled => Status::STALLED,
+        };
+        code as i32
+    }
+}
+
+impl From<i32> for Status {
+    fn from(code: i32) -> Status {
+        match code as u32 {
+            0 => Status::Ok,
+            Status::IO_ERROR => Status::IoError,
+            Status::TIMEOUT => Status::Timeout,
+            Status::UNDERRUN => Status::Underrun,
+            Status::OVERRUN => Status::Overrun,
+            Status::ABORTED => Status::Aborted,
+            Status::NOT_RESPONDING => Status::NotResponding,
+            Status::TOO_OLD => Status::TooOld,
+            Status::TOO_NEW => Status::TooNew,
+            Status::STALLED => Status::Stalled,
+            //the kern_return_t range `UsbError` covers
+            1..=49 => Status::Err(code.into()),
+            _ => Status::Other(code),
+        }
+    }
+}
+
+impl IsochronousFrame {
+    pub fn new(
+        status: Status,
+        request_count: u32,
+        complete_count: u32,
+        timestamp: HostTime,
+    ) -> Self {
+        let inner = IOUSBHostIsochronousFrame {
+            status: status.into(),
+            requestCount: request_count,
+            completeCount: complete_count,
+            reserved: 0,
+            timeStamp: timestamp.inner,
+        };
+        Self { inner }
+    }
+
+    pub fn request_count(&self) -> u32 {
+        self.inner.requestCount
+    }
+
+    ///the number of bytes actually transferred, filled in on completion
+    pub fn complete_count(&self) -> u32 {
+        self.inner.completeCount
+    }
+
+    ///the IOReturn the frame completed with, filled in on completion
+    pub fn status_code(&self) -> i32 {
+        self.inner.status
+    }
+
+    ///the decoded `status_code`
+    pub fn status(&self) -> Status {
+        self.inner.status.into()
+    }
+
+    pub fn timestamp(&self) -> HostTime {
+        HostTime {
+            inner: self.inner.timeStamp,
+        }
+    }
+
+    pub fn completion(&self) -> IsochronousCompletion {
+        IsochronousCompletion {
+            status: self.status(),
+            complete_count: self.complete_count(),
+            timestamp: self.timestamp(),
+        }
+    }
+}
+
+///how an isochronous frame or transaction completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IsochronousCompletion {
+    pub status: Status,
+    pub complete_count: u32,
+    ///when the (micro)frame was transferred, as recorded by the controller
+    pub timestamp: HostTime,
+}
+
+impl IsochronousCompletion {
+    ///`timestamp` on the `Instant` clock, to measure latency against `Instant::now`, see
+    ///`HostTime::to_instant`
+    pub fn instant(&self) -> Option<Instant> {
+        self.timestamp.to_instant()
+    }
+}
+
+///plans the request counts of an isochronous frame list, checking each frame against what the
+///endpoint can move per (micro)frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameListBuilder {
+    max_frame_size: Option<u32>,
+    request_counts: Vec<u32>,
+}
+
+impl FrameListBuilder {
+    ///a builder without a per frame limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///a builder limited by the pipe's max packet size times its mult and burst, `None` if the
+    ///pipe is not isochronous
+    pub fn for_pipe(pipe: &HostPipe<'_>) -> Option<Self> {
+        let descriptors = pipe.descriptors()?;
+        let endpoint = descriptors.endpoint_descriptor();
+        if endpoint.transfer_type() != EndpointType::Isochronous {
+            return None;
+        }
+
+        let max_frame_size = if let Some(ssp) = descriptors.super_speed_plus_companion_descriptor()
+        {
+            ssp.bytes_per_interval()
+        } else if let Some(ss) = descriptors.super_speed_companion_descriptor() {
+            ss.bytes_per_interval() as u32
+        } else {
+            let max_packet_size = endpoint.max_packet_size();
+            let mult = ((max_packet_size >> 11) & 3) as u32 + 1;
+            (max_packet_size & 0x7ff) as u32 * mult
+        };
+        Some(Self {
+            max_frame_size: Some(max_frame_size),
+            request_counts: Vec::new(),
+        })
+    }
+
+    pub fn max_frame_size(&self) -> Option<u32> {
+        self.max_frame_size
+    }
+
+    ///appends a frame requesting `request_count` bytes
+    pub fn frame(mut self, request_count: u32) -> Result<Self, FrameListError> {
+        if let Some(max) = self.max_frame_size.filter(|max| request_count > *max) {
+            return Err(FrameListError::FrameTooLarge {
+                frame: self.request_counts.len(),
+                size: request_count,
+                max,
+            });
+        }
+        let total: u64 = self.request_counts.iter().map(|count| *count as u64).sum();
+        if total + request_count as u64 > u32::MAX as u64 {
+            return Err(FrameListError::BufferTooLarge);
+        }
+        self.request_counts.push(request_count);
+        Ok(self)
+    }
+
+    ///appends a frame for every packet size of `plan`
+    pub fn plan(self, plan: impl IntoIterator<Item = u32>) -> Result<Self, FrameListError> {
+        plan.into_iter()
+            .try_fold(self, |builder, size| builder.frame(size))
+    }
+
+    ///appends `count` frames of `request_count` bytes each
+    pub fn repeat(self, count: usize, request_count: u32) -> Result<Self, FrameListError> {
+        self.plan(core::iter::repeat(request_count).take(count))
+    }
+
+    pub fn build(self) -> FrameList {
+        let frames = self
+            .request_counts
+            .into_iter()
+            .map(|count| IsochronousFrame::new(Status::Ok, count, 0, HostTime { inner: 0 }))
+            .collect();
+        FrameList { frames }
+    }
+
+    ///builds a transaction list for `send_io_request_isochronous_transaction` instead, each
+    ///(micro)frame's data placed right after the previous one's in the buffer
+    pub fn build_transactions(self) -> TransactionList {
+        let mut offset = 0;
+        let transactions = self
+            .request_counts
+            .into_iter()
+            .map(|count| {
+                let transaction = IsochronousTransaction::new(
+                    Status::Ok,
+                    count,
+                    offset,
+                    0,
+                    HostTime { inner: 0 },
+                    IsochronousTransactionOptions::None,
+                );
+                offset += count;
+                transaction
+            })
+            .collect();
+        TransactionList { transactions }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameListError {
+    ///the frame at index `frame` asks for more than the endpoint moves per frame
+    FrameTooLarge { frame: usize, size: u32, max: u32 },
+    ///the frames add up to more than the 32 bit transaction offsets can address
+    BufferTooLarge,
+}
+
+impl core::fmt::Display for FrameListError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrameListError::FrameTooLarge { frame, size, max } => {
+                write!(
+                    f,
+                    "frame {frame} requests {size} bytes but at most {max} fit"
+                )
+            }
+            FrameListError::BufferTooLarge => write!(f, "the frames need a buffer above 4GiB"),
+        }
+    }
+}
+
+///an isochronous frame list built by `FrameListBuilder`, handed to
+///`HostPipe::send_io_request_isochronous_frame` through `frames_mut`
+pub struct FrameList {
+    frames: Vec<IsochronousFrame>,
+}
+
+impl FrameList {
+    pub fn frames(&self) -> &[IsochronousFrame] {
+        &self.frames
+    }
+
+    pub fn frames_mut(&mut self) -> &mut [IsochronousFrame] {
+        &mut self.frames
+    }
+
+    ///the size of the data buffer the frame list describes
+    pub fn request_length(&self) -> u64 {
+        self.frames
+            .iter()
+            .map(|frame| frame.request_count() as u64)
+            .sum()
+    }
+
+    ///the bytes transferred across all frames once the request completed
+    pub fn complete_length(&self) -> u64 {
+        self.frames
+            .iter()
+            .map(|frame| frame.complete_count() as u64)
+            .sum()
+    }
+
+    pub fn completions(&self) -> impl Iterator<Item = IsochronousCompletion> + '_ {
+        self.frames.iter().map(IsochronousFrame::completion)
+    }
+}
+
+///an isochronous transaction list built by `FrameListBuilder::build_transactions`
+pub struct TransactionList {
+    transactions: Vec<IsochronousTransaction>,
+}
+
+impl TransactionList {
+    pub fn transactions(&self) -> &[IsochronousTransaction] {
+        &self.transactions
+    }
+
+    pub fn transactions_mut(&mut self) -> &mut [IsochronousTransaction] {
+        &mut self.transactions
+    }
+
+    ///the size of the data buffer the transactions are laid out in
+    pub fn request_length(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.request_count() as u64)
+            .sum()
+    }
+
+    ///the bytes transferred across all transactions once the request completed
+    pub fn complete_length(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.complete_count() as u64)
+            .sum()
+    }
+
+    pub fn completions(&self) -> impl Iterator<Item = IsochronousCompletion> + '_ {
+        self.transactions
+            .iter()
+            .map(IsochronousTransaction::completion)
+    }
+}
+
+#[repr(transparent)]
+pub struct IsochronousTransaction {
+    pub(crate) inner: IOUSBHostIsochronousTransaction,
+}
+
+impl IsochronousTransaction {
+    pub fn new(
+        status: Status,
+        request_count: u32,
+        offset: u32,
+        complete_count: u32,
+        timestamp: HostTime,
+        options: IsochronousTransactionOptions,
+    ) -> Self {
+        let inner = IOUSBHostIsochronousTransaction {
+            status: status.into(),
+            requestCount: request_count,
+            offset,
+            completeCount: complete_count,
+            timeStamp: timestamp.inner,
+            options: options.into(),
+        };
+        Self { inner }
+    }
+
+    pub fn request_count(&self) -> u32 {
+        self.inner.requestCount
+    }
+
+    ///where the transaction's data starts in the request's buffer
+    pub fn offset(&self) -> u32 {
+        self.inner.offset
+    }
+
+    ///the number of bytes actually transferred, filled in on completion
+    pub fn complete_count(&self) -> u32 {
+        self.inner.completeCount
+    }
+
+    ///the IOReturn the transaction completed with, filled in on completion
+    pub fn status(&self) -> Status {
+        self.inner.status.into()
+    }
+
+    pub fn timestamp(&self) -> HostTime {
+        HostTime {
+            inner: self.inner.timeStamp,
+        }
+    }
+
+    pub fn completion(&self) -> IsochronousCompletion {
+        IsochronousCompletion {
+            status: self.status(),
+            complete_count: self.complete_count(),
+            timestamp: self.timestamp(),
+        }
+    }
+}
+
+///the header only defines `Wrap` besides `None`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IsochronousTransactionOptions {
+    None = 0,
+    Wrap = 1,
+}
+
+impl From<IsochronousTransactionOptions> for IOUSBHostIsochronousTransferOptions {
+    fn from(options: IsochronousTransactionOptions) -> IOUSBHostIsochronousTransferOptions {
+        use IsochronousTransactionOptions as ITO;
+        match options {
+            ITO::None => 0,
+            ITO::Wrap => 1,
+        }
+    }
+}
+
+impl From<IOUSBHostIsochronousTransferOptions> for IsochronousTransactionOptions {
+    fn from(options: IOUSBHostIsochronousTransferOptions) -> IsochronousTransactionOptions {
+        use IsochronousTransactionOptions as ITO;
+        match options {
+            1 => ITO::Wrap,
+            _ => ITO::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HostTime {
+    pub(crate) inner: u64,
+}
+
+impl HostTime {
+    pub fn now() -> Self {
+        Self {
+            inner: unsafe { mach_absolute_time() },
+        }
+    }
+
+    ///a host time from absolute mach ticks, as used by `frameNumberWithTime_` and isochronous
+    ///frame timestamps
+    pub fn from_ticks(ticks: u64) -> Self {
+        Self { inner: ticks }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.inner
+    }
+
+    ///the time elapsed since `earlier`, zero if `earlier` is later than `self`
+    pub fn duration_since(&self, earlier: HostTime) -> Duration {
+        let ticks = self.inner.saturating_sub(earlier.inner);
+        Duration::from_nanos(ticks_to_nanos(ticks))
+    }
+
+    ///the same point in time on the `Instant` clock, `None` if it is too far off for `Instant`
+    pub fn to_instant(&self) -> Option<Instant> {
+        //both clocks are monotonic, so the offset from now carries over
+        let (host_now, now) = (HostTime::now(), Instant::now());
+        if *self <= host_now {
+            now.checked_sub(host_now - *self)
+        } else {
+            now.checked_add(*self - host_now)
+        }
+    }
+}
+
+///numerator and denominator converting mach ticks to nanoseconds
+fn timebase() -> (u64, u64) {
+    static TIMEBASE: std::sync::OnceLock<(u64, u64)> = std::sync::OnceLock::new();
+    *TIMEBASE.get_or_init(|| {
+        let mut info = mach_timebase_info_data_t { numer: 0, denom: 0 };
+        if unsafe { mach_timebase_info(&mut info) } != 0 || info.denom == 0 {
+            (1, 1)
+        } else {
+            (info.numer as u64, info.denom as u64)
+        }
+    })
+}
+
+fn ticks_to_nanos(ticks: u64) -> u64 {
+    let (numer, denom) = timebase();
+    (ticks as u128 * numer as u128 / denom as u128).min(u64::MAX as u128) as u64
+}
+
+fn nanos_to_ticks(nanos: u128) -> u64 {
+    let (numer, denom) = timebase();
+    (nanos * denom as u128 / numer as u128).min(u64::MAX as u128) as u64
+}
+
+impl Add<Duration> for HostTime {
+    type Output = HostTime;
+    fn add(self, duration: Duration) -> HostTime {
+        HostTime {
+            inner: self
+                .inner
+                .saturating_add(nanos_to_ticks(duration.as_nanos())),
+        }
+    }
+}
+
+impl AddAssign<Duration> for HostTime {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for HostTime {
+    type Output = HostTime;
+    fn sub(self, duration: Duration) -> HostTime {
+        HostTime {
+            inner: self
+                .inner
+                .saturating_sub(nanos_to_ticks(duration.as_nanos())),
+        }
+    }
+}
+
+impl SubAssign<Duration> for HostTime {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<HostTime> for HostTime {
+    type Output = Duration;
+    fn sub(self, earlier: HostTime) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+///saturates at either end of the host clock, which the `Instant` clock can not get past
+impl From<Instant> for HostTime {
+    fn from(instant: Instant) -> HostTime {
+        let (host_now, now) = (HostTime::now(), Instant::now());
+        host_now - now.saturating_duration_since(instant) + instant.saturating_duration_since(now)
+    }
+}
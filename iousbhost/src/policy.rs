@@ -0,0 +1,141 @@
+use crate::*;
+use core::ffi::CStr;
+use std::sync::RwLock;
+
+static OPEN_POLICY: RwLock<Option<Arc<dyn OpenPolicy>>> = RwLock::new(None);
+
+///decides whether a device or interface may be opened, consulted before every host object is
+///initialized so it is the one place allow and deny lists need to be enforced
+///
+///it runs on whichever thread opens the service, and must not open devices itself
+pub trait OpenPolicy: Send + Sync {
+    fn allow(&self, candidate: &OpenCandidate<'_>) -> bool;
+}
+
+impl<F> OpenPolicy for F
+where
+    F: Fn(&OpenCandidate<'_>) -> bool + Send + Sync,
+{
+    fn allow(&self, candidate: &OpenCandidate<'_>) -> bool {
+        self(candidate)
+    }
+}
+
+///installs the process wide open policy, `None` allows every open again; opens refused by it
+///fail with `UsbError::Vetoed`, which `UsbDevice::interfaces` reports per interface
+pub fn set_open_policy(policy: Option<Arc<dyn OpenPolicy>>) {
+    *OPEN_POLICY
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = policy;
+}
+
+pub fn open_policy() -> Option<Arc<dyn OpenPolicy>> {
+    OPEN_POLICY
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpenTarget {
+    Device,
+    Interface,
+}
+
+///a service about to be opened, as handed to the `OpenPolicy`
+pub struct OpenCandidate<'m> {
+    target: OpenTarget,
+    service: IoService,
+    matcher: Option<&'m DeviceMatcher>,
+    options: HostObjectInitOptions,
+}
+
+impl<'m> OpenCandidate<'m> {
+    pub(crate) fn new(
+        target: OpenTarget,
+        service: io_service_t,
+        matcher: Option<&'m DeviceMatcher>,
+        options: HostObjectInitOptions,
+    ) -> Self {
+        Self {
+            target,
+            service: IoService::from_raw(service),
+            matcher,
+            options,
+        }
+    }
+
+    ///asks the installed policy, if any, whether the candidate may be opened
+    pub(crate) fn check(&self) -> Result<(), UsbError> {
+        let Some(policy) = open_policy() else {
+            return Ok(());
+        };
+        if policy.allow(self) {
+            Ok(())
+        } else {
+            crate::diag!(
+                debug,
+                "open policy vetoed {:?} {:?}",
+                self.target,
+                self.registry_entry_id()
+            );
+            Err(UsbError::Vetoed)
+        }
+    }
+
+    pub fn target(&self) -> OpenTarget {
+        self.target
+    }
+
+    ///the matcher the service was found with, `None` for interfaces and for devices opened again
+    ///after a reset
+    pub fn matcher(&self) -> Option<&'m DeviceMatcher> {
+        self.matcher
+    }
+
+    pub fn options(&self) -> HostObjectInitOptions {
+        self.options
+    }
+
+    pub fn registry_entry_id(&self) -> Option<u64> {
+        let mut id = 0;
+        (unsafe { IORegistryEntryGetRegistryEntryID(self.service.inner, &mut id) } == 0)
+            .then_some(id)
+    }
+
+    ///a numeric property of the service's registry entry, such as `bInterfaceNumber`
+    pub fn number_property(&self, key: &CStr) -> Option<i64> {
+        self.service.number_property(key)
+    }
+
+    ///a string property of the service's registry entry, such as `kUSBProductString`
+    pub fn string_property(&self, key: &CStr) -> Option<String> {
+        self.service.string_property(key)
+    }
+
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.u16_property(c"idVendor")
+    }
+
+    pub fn product_id(&self) -> Option<u16> {
+        self.u16_property(c"idProduct")
+    }
+
+    pub fn location_id(&self) -> Option<u32> {
+        u32::try_from(self.number_property(c"locationID")?).ok()
+    }
+
+    pub fn serial_number(&self) -> Option<String> {
+        self.string_property(c"kUSBSerialNumberString")
+    }
+
+    ///checks the registry properties of the service against every field `matcher` sets, so
+    ///allow lists can be written as matchers
+    pub fn matches(&self, matcher: &DeviceMatcher) -> bool {
+        matcher.matches_service(&self.service)
+    }
+
+    fn u16_property(&self, key: &CStr) -> Option<u16> {
+        u16::try_from(self.number_property(key)?).ok()
+    }
+}
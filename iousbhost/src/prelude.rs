@@ -0,0 +1,13 @@
+#[cfg(feature = "async")]
+pub use crate::blocking::Cancel;
+pub use crate::descriptors::{
+    ConfigurationDescriptor, DescriptorType, DeviceDescriptor, EndpointAddress, InterfaceDescriptor,
+};
+pub use crate::device::{
+    DeviceRequest, DeviceRequestDirectionValue, DeviceRequestRecipientValue, DeviceRequestType,
+    DeviceRequestTypeValue, StandardRequest, UsbDevice,
+};
+pub use crate::interface::HostInterface;
+pub use crate::pipe::HostPipe;
+pub use crate::watch::{Context, DeviceMatcher, HotplugEvent, UsbManager};
+pub use crate::{AbortOption, HostObjectInitOptions, UsbError};
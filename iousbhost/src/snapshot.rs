@@ -0,0 +1,764 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+//the first bytes of every encoded snapshot
+const MAGIC: &[u8; 4] = b"USBD";
+const VERSION: u8 = 1;
+
+///an owned copy of a device's descriptors that outlives the device, so it can be stored and
+///compared with `diff` against the same device later or against other devices of a fleet
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceDescriptorSnapshot {
+    device: Vec<u8>,
+    configurations: Vec<Vec<u8>>,
+    serial_number: Option<String>,
+}
+
+impl DeviceDescriptorSnapshot {
+    ///copies the device descriptor, every configuration descriptor and the serial number string
+    ///of `device`
+    //
+    //what a device reports always fits the length prefixes of `to_bytes`: the descriptors carry
+    //their own u8 and u16 lengths and a string descriptor holds at most 126 UTF-16 units
+    pub fn capture(device: &UsbDevice<'_>) -> Result<Self, UsbError> {
+        let descriptor = device.device_descriptor().ok_or(UsbError::InvalidObject)?;
+        let configurations = (0..descriptor.configuration_count())
+            .map(|index| {
+                device
+                    .configuration_descriptor_at(index)
+                    .map(|configuration| configuration.bytes().to_vec())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let serial_number = match descriptor.serial_number() {
+            0 => None,
+            index => Some(device.string_descriptor(index as u64, None)?),
+        };
+        Ok(Self {
+            device: descriptor.bytes().to_vec(),
+            configurations,
+            serial_number,
+        })
+    }
+
+    ///a snapshot of descriptors obtained elsewhere, such as dumps taken by other tools
+    pub fn from_parts(
+        device: Vec<u8>,
+        configurations: Vec<Vec<u8>>,
+        serial_number: Option<String>,
+    ) -> Result<Self, SnapshotError> {
+        DeviceDescriptor::from_bytes(&device)?;
+        if device.len() > u8::MAX as usize {
+            return Err(SnapshotError::TooLong("device descriptor"));
+        }
+        if configurations.len() > u8::MAX as usize {
+            return Err(SnapshotError::TooLong("configuration list"));
+        }
+        for configuration in &configurations {
+            ConfigurationDescriptor::from_bytes(configuration)?;
+            if configuration.len() > u16::MAX as usize {
+                return Err(SnapshotError::TooLong("configuration descriptor"));
+            }
+        }
+        if serial_number
+            .as_ref()
+            .is_some_and(|serial_number| serial_number.len() > u16::MAX as usize)
+        {
+            return Err(SnapshotError::TooLong("serial number"));
+        }
+        Ok(Self {
+            device,
+            configurations,
+            serial_number,
+        })
+    }
+
+    pub fn device_descriptor(&self) -> Option<DeviceDescriptor<'_>> {
+        DeviceDescriptor::from_bytes(&self.device).ok()
+    }
+
+    pub fn device_descriptor_bytes(&self) -> &[u8] {
+        &self.device
+    }
+
+    ///the configuration descriptors in the order the device reported them
+    pub fn configurations(&self) -> impl Iterator<Item = ConfigurationDescriptor<'_>> {
+        self.configurations
+            .iter()
+            .filter_map(|configuration| ConfigurationDescriptor::from_bytes(configuration).ok())
+    }
+
+    pub fn configuration_bytes(&self) -> impl Iterator<Item = &[u8]> {
+        self.configurations.iter().map(Vec::as_slice)
+    }
+
+    pub fn serial_number(&self) -> Option<&str> {
+        self.serial_number.as_deref()
+    }
+
+    ///encodes the snapshot for storage, `from_bytes` reads it back
+    ///
+    ///the format is versioned: the magic `USBD`, a version byte, the device descriptor behind a
+    ///length byte, a count byte followed by every configuration blob behind its little endian
+    ///u16 length, then a flag byte and the serial number's UTF-8 behind its u16 length if set
+    ///
+    ///`from_parts` refuses anything longer than its length prefix, so the lengths are never cut
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            MAGIC.len()
+                + 2
+                + self.device.len()
+                + 1
+                + self
+                    .configurations
+                    .iter()
+                    .map(|c| 2 + c.len())
+                    .sum::<usize>()
+                + 3
+                + self.serial_number.as_ref().map_or(0, String::len),
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.device.len() as u8);
+        bytes.extend_from_slice(&self.device);
+        bytes.push(self.configurations.len() as u8);
+        for configuration in &self.configurations {
+            bytes.extend_from_slice(&(configuration.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(configuration);
+        }
+        match &self.serial_number {
+            Some(serial_number) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(serial_number.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(serial_number.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Magic);
+        }
+        match reader.u8()? {
+            VERSION => {}
+            version => return Err(SnapshotError::Version(version)),
+        }
+        let len = reader.u8()? as usize;
+        let device = reader.take(len)?.to_vec();
+        let count = reader.u8()?;
+        let configurations = (0..count)
+            .map(|_| {
+                let len = reader.u16()? as usize;
+                reader.take(len).map(<[u8]>::to_vec)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let serial_number = match reader.u8()? {
+            0 => None,
+            _ => {
+                let len = reader.u16()? as usize;
+                let serial_number = core::str::from_utf8(reader.take(len)?)
+                    .map_err(|_| SnapshotError::SerialNumber)?;
+                Some(serial_number.to_owned())
+            }
+        };
+        if !reader.0.is_empty() {
+            return Err(SnapshotError::TrailingBytes(reader.0.len()));
+        }
+        Self::from_parts(device, configurations, serial_number)
+    }
+
+    ///the SHA-256 of the descriptors and the serial number, unlike the `Hash` impl it stays the
+    ///same across processes, platforms and releases so it can be stored next to the snapshot
+    ///
+    ///the hashed encoding is fixed on its own instead of following `to_bytes`, so a new storage
+    ///format version does not change the digest: every field is length prefixed, the device
+    ///descriptor and each configuration blob with a little endian u32, then the configuration
+    ///count and a flag for the serial number the same way
+    pub fn digest(&self) -> [u8; 32] {
+        use sha2::Digest;
+        let mut hasher = sha2::Sha256::new();
+        let mut field = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u32).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(&self.device);
+        field(&(self.configurations.len() as u32).to_le_bytes());
+        for configuration in &self.configurations {
+            field(configuration);
+        }
+        match &self.serial_number {
+            Some(serial_number) => {
+                field(&[1]);
+                field(serial_number.as_bytes());
+            }
+            None => field(&[0]),
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint(self.digest())
+    }
+}
+
+///the `digest` of a device's descriptors, see `UsbDevice::fingerprint`; printed and parsed as 64
+///hex digits so it can be kept in allow and deny lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl core::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl core::str::FromStr for Fingerprint {
+    type Err = ParseFingerprintError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(ParseFingerprintError);
+        }
+        let mut fingerprint = [0; 32];
+        for (byte, digits) in fingerprint.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = core::str::from_utf8(digits).map_err(|_| ParseFingerprintError)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| ParseFingerprintError)?;
+        }
+        Ok(Fingerprint(fingerprint))
+    }
+}
+
+impl From<Fingerprint> for [u8; 32] {
+    fn from(fingerprint: Fingerprint) -> [u8; 32] {
+        fingerprint.0
+    }
+}
+
+///a string that is not 64 hex digits, optionally behind `0x`, given as a `Fingerprint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseFingerprintError;
+
+impl core::fmt::Display for ParseFingerprintError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "a fingerprint is 64 hex digits")
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        self.take(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapshotError {
+    ///the bytes do not start with the snapshot magic
+    Magic,
+    ///the snapshot was written in a format version this release does not read
+    Version(u8),
+    ///the bytes end before the snapshot does
+    Truncated,
+    ///the bytes go on after the snapshot
+    TrailingBytes(usize),
+    ///the serial number is not UTF-8
+    SerialNumber,
+    ///one of the descriptors is malformed
+    Descriptor(DescriptorError),
+    ///the named part is longer than the encoding's length prefix can hold
+    TooLong(&'static str),
+}
+
+impl From<DescriptorError> for SnapshotError {
+    fn from(err: DescriptorError) -> SnapshotError {
+        SnapshotError::Descriptor(err)
+    }
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::Magic => write!(f, "not a descriptor snapshot"),
+            SnapshotError::Version(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            SnapshotError::Truncated => write!(f, "the snapshot is truncated"),
+            SnapshotError::TrailingBytes(len) => {
+                write!(f, "{len} unexpected bytes after the snapshot")
+            }
+            SnapshotError::SerialNumber => write!(f, "the serial number is not valid UTF-8"),
+            SnapshotError::Descriptor(err) => write!(f, "invalid descriptor: {err}"),
+            SnapshotError::TooLong(part) => write!(f, "the {part} is too long for a snapshot"),
+        }
+    }
+}
+
+///identifies an alternate setting of an interface across snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InterfaceKey {
+    ///bConfigurationValue of the configuration holding the interface
+    pub configuration: u8,
+    pub interface: u8,
+    pub alternate_setting: u8,
+}
+
+impl core::fmt::Display for InterfaceKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "configuration {} interface {} alt {}",
+            self.configuration, self.interface, self.alternate_setting
+        )
+    }
+}
+
+///a difference found by `diff`, fields are named after the descriptor fields they come from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DescriptorChange {
+    Device {
+        field: &'static str,
+        old: u16,
+        new: u16,
+    },
+    SerialNumber {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    ///a configuration with this bConfigurationValue only exists in the new snapshot
+    ConfigurationAdded(u8),
+    ConfigurationRemoved(u8),
+    Configuration {
+        configuration: u8,
+        field: &'static str,
+        old: u16,
+        new: u16,
+    },
+    InterfaceAdded(InterfaceKey),
+    InterfaceRemoved(InterfaceKey),
+    Interface {
+        interface: InterfaceKey,
+        field: &'static str,
+        old: u16,
+        new: u16,
+    },
+    EndpointAdded {
+        interface: InterfaceKey,
+        address: EndpointAddress,
+    },
+    EndpointRemoved {
+        interface: InterfaceKey,
+        address: EndpointAddress,
+    },
+    Endpoint {
+        interface: InterfaceKey,
+        address: EndpointAddress,
+        field: &'static str,
+        old: u16,
+        new: u16,
+    },
+}
+
+impl core::fmt::Display for DescriptorChange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use DescriptorChange as DC;
+        match self {
+            DC::Device { field, old, new } => write!(f, "device {field}: {old:#x} -> {new:#x}"),
+            DC::SerialNumber { old, new } => write!(f, "serial number: {old:?} -> {new:?}"),
+            DC::ConfigurationAdded(value) => write!(f, "configuration {value} added"),
+            DC::ConfigurationRemoved(value) => write!(f, "configuration {value} removed"),
+            DC::Configuration {
+                configuration,
+                field,
+                old,
+                new,
+            } => write!(
+                f,
+                "configuration {configuration} {field}: {old:#x} -> {new:#x}"
+            ),
+            DC::InterfaceAdded(interface) => write!(f, "{interface} added"),
+            DC::InterfaceRemoved(interface) => write!(f, "{interface} removed"),
+            DC::Interface {
+                interface,
+                field,
+                old,
+                new,
+            } => write!(f, "{interface} {field}: {old:#x} -> {new:#x}"),
+            DC::EndpointAdded { interface, address } => {
+                write!(f, "{interface} endpoint {address} added")
+            }
+            DC::EndpointRemoved { interface, address } => {
+                write!(f, "{interface} endpoint {address} removed")
+            }
+            DC::Endpoint {
+                interface,
+                address,
+                field,
+                old,
+                new,
+            } => write!(
+                f,
+                "{interface} endpoint {address} {field}: {old:#x} -> {new:#x}"
+            ),
+        }
+    }
+}
+
+type Fields<const N: usize> = [(&'static str, u16); N];
+
+fn device_fields(descriptor: &DeviceDescriptor<'_>) -> Fields<12> {
+    [
+        ("bcdUSB", descriptor.bcd_usb()),
+        ("bDeviceClass", descriptor.device_class() as u16),
+        ("bDeviceSubClass", descriptor.device_subclass() as u16),
+        ("bDeviceProtocol", descriptor.device_protocol() as u16),
+        ("bMaxPacketSize0", descriptor.max_packet_size() as u16),
+        ("idVendor", descriptor.vendor_id()),
+        ("idProduct", descriptor.product_id()),
+        ("bcdDevice", descriptor.bcd_device()),
+        ("iManufacturer", descriptor.manufacturer() as u16),
+        ("iProduct", descriptor.product() as u16),
+        ("iSerialNumber", descriptor.serial_number() as u16),
+        (
+            "bNumConfigurations",
+            descriptor.configuration_count() as u16,
+        ),
+    ]
+}
+
+fn configuration_fields(descriptor: &ConfigurationDescriptor<'_>) -> Fields<4> {
+    [
+        ("bNumInterfaces", descriptor.interface_count() as u16),
+        ("iConfiguration", descriptor.configuration() as u16),
+        ("bmAttributes", descriptor.attributes() as u16),
+        ("bMaxPower", descriptor.max_power() as u16),
+    ]
+}
+
+fn interface_fields(descriptor: &InterfaceDescriptor<'_>) -> Fields<5> {
+    [
+        ("bNumEndpoints", descriptor.endpoint_count() as u16),
+        ("bInterfaceClass", descriptor.interface_class() as u16),
+        ("bInterfaceSubClass", descriptor.interface_subclass() as u16),
+        ("bInterfaceProtocol", descriptor.interface_protocol() as u16),
+        ("iInterface", descriptor.interface() as u16),
+    ]
+}
+
+fn endpoint_fields(descriptor: &EndpointDescriptor<'_>) -> Fields<3> {
+    [
+        ("bmAttributes", descriptor.attributes() as u16),
+        ("wMaxPacketSize", descriptor.max_packet_size()),
+        ("bInterval", descriptor.interval() as u16),
+    ]
+}
+
+//calls `changed` with every field whose value differs
+fn compare(
+    old: &[(&'static str, u16)],
+    new: &[(&'static str, u16)],
+    mut changed: impl FnMut(&'static str, u16, u16),
+) {
+    for ((field, old), (_, new)) in old.iter().zip(new) {
+        if old != new {
+            changed(field, *old, *new);
+        }
+    }
+}
+
+struct InterfaceSummary {
+    fields: Fields<5>,
+    endpoints: BTreeMap<EndpointAddress, Fields<3>>,
+}
+
+struct Summary {
+    device: Option<Fields<12>>,
+    configurations: BTreeMap<u8, Fields<4>>,
+    interfaces: BTreeMap<InterfaceKey, InterfaceSummary>,
+}
+
+impl Summary {
+    fn new(snapshot: &DeviceDescriptorSnapshot) -> Self {
+        let mut configurations = BTreeMap::new();
+        let mut interfaces = BTreeMap::new();
+        for configuration in snapshot.configurations() {
+            let value = configuration.configuration_value();
+            configurations.insert(value, configuration_fields(&configuration));
+            for interface in configuration.interface_descriptors() {
+                let key = InterfaceKey {
+                    configuration: value,
+                    interface: interface.interface_number(),
+                    alternate_setting: interface.alternate_setting(),
+                };
+                let endpoints = configuration
+                    .endpoint_descriptors(&interface)
+                    .map(|endpoint| (endpoint.endpoint_address(), endpoint_fields(&endpoint)))
+                    .collect();
+                interfaces.insert(
+                    key,
+                    InterfaceSummary {
+                        fields: interface_fields(&interface),
+                        endpoints,
+                    },
+                );
+            }
+        }
+        Self {
+            device: snapshot
+                .device_descriptor()
+                .map(|device| device_fields(&device)),
+            configurations,
+            interfaces,
+        }
+    }
+}
+
+///everything that changed from `old` to `new`, device fields first, then configurations,
+///interfaces and their endpoints in ascending order; empty if the descriptors are the same
+///
+///configurations are matched by bConfigurationValue, interfaces by their number and alternate
+///setting and endpoints by their address, so reordered descriptors are no change
+pub fn diff(
+    old: &DeviceDescriptorSnapshot,
+    new: &DeviceDescriptorSnapshot,
+) -> Vec<DescriptorChange> {
+    let mut changes = Vec::new();
+    let old_summary = Summary::new(old);
+    let new_summary = Summary::new(new);
+
+    if let (Some(old), Some(new)) = (&old_summary.device, &new_summary.device) {
+        compare(old, new, |field, old, new| {
+            changes.push(DescriptorChange::Device { field, old, new })
+        });
+    }
+    if old.serial_number != new.serial_number {
+        changes.push(DescriptorChange::SerialNumber {
+            old: old.serial_number.clone(),
+            new: new.serial_number.clone(),
+        });
+    }
+
+    for (value, old) in &old_summary.configurations {
+        match new_summary.configurations.get(value) {
+            Some(new) => compare(old, new, |field, old, new| {
+                changes.push(DescriptorChange::Configuration {
+                    configuration: *value,
+                    field,
+                    old,
+                    new,
+                })
+            }),
+            None => changes.push(DescriptorChange::ConfigurationRemoved(*value)),
+        }
+    }
+    for value in new_summary.configurations.keys() {
+        if !old_summary.configurations.contains_key(value) {
+            changes.push(DescriptorChange::ConfigurationAdded(*value));
+        }
+    }
+
+    for (key, old) in &old_summary.interfaces {
+        let Some(new) = new_summary.interfaces.get(key) else {
+            changes.push(DescriptorChange::InterfaceRemoved(*key));
+            continue;
+        };
+        compare(&old.fields, &new.fields, |field, old, new| {
+            changes.push(DescriptorChange::Interface {
+                interface: *key,
+                field,
+                old,
+                new,
+            })
+        });
+        for (address, old) in &old.endpoints {
+            match new.endpoints.get(address) {
+                Some(new) => compare(old, new, |field, old, new| {
+                    changes.push(DescriptorChange::Endpoint {
+                        interface: *key,
+                        address: *address,
+                        field,
+                        old,
+                        new,
+                    })
+                }),
+                None => changes.push(DescriptorChange::EndpointRemoved {
+                    interface: *key,
+                    address: *address,
+                }),
+            }
+        }
+        for address in new.endpoints.keys() {
+            if !old.endpoints.contains_key(address) {
+                changes.push(DescriptorChange::EndpointAdded {
+                    interface: *key,
+                    address: *address,
+                });
+            }
+        }
+    }
+    for key in new_summary.interfaces.keys() {
+        if !old_summary.interfaces.contains_key(key) {
+            changes.push(DescriptorChange::InterfaceAdded(*key));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: [u8; 18] = [
+        18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x09, 0x12, 0x01, 0x00, 0x00, 0x01, 0, 0, 3, 1,
+    ];
+
+    //one vendor specific interface with a 512 byte bulk endpoint each way
+    const CONFIGURATION: [u8; 32] = [
+        9, 2, 32, 0, 1, 1, 0, 0x80, 50, //
+        9, 4, 0, 0, 2, 0xff, 0, 0, 0, //
+        7, 5, 0x01, 2, 0x00, 0x02, 0, //
+        7, 5, 0x81, 2, 0x00, 0x02, 0,
+    ];
+
+    fn snapshot(configuration: &[u8], serial_number: Option<&str>) -> DeviceDescriptorSnapshot {
+        DeviceDescriptorSnapshot::from_parts(
+            DEVICE.to_vec(),
+            vec![configuration.to_vec()],
+            serial_number.map(str::to_owned),
+        )
+        .unwrap_or_else(|err| unreachable!("valid snapshot rejected: {err}"))
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        for serial_number in [None, Some(""), Some("0001-ab")] {
+            let snapshot = snapshot(&CONFIGURATION, serial_number);
+            let bytes = snapshot.to_bytes();
+            assert_eq!(DeviceDescriptorSnapshot::from_bytes(&bytes), Ok(snapshot));
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_bytes() {
+        let bytes = snapshot(&CONFIGURATION, Some("0001")).to_bytes();
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_bytes(&trailing),
+            Err(SnapshotError::TrailingBytes(1))
+        );
+        let mut version = bytes.clone();
+        version[MAGIC.len()] = VERSION + 1;
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_bytes(&version),
+            Err(SnapshotError::Version(VERSION + 1))
+        );
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_bytes(&bytes[1..]),
+            Err(SnapshotError::Magic)
+        );
+    }
+
+    #[test]
+    fn refuses_parts_longer_than_their_length_prefix() {
+        let serial_number = "0".repeat(u16::MAX as usize + 1);
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_parts(
+                DEVICE.to_vec(),
+                vec![CONFIGURATION.to_vec()],
+                Some(serial_number),
+            ),
+            Err(SnapshotError::TooLong("serial number"))
+        );
+        let mut device = DEVICE.to_vec();
+        device.resize(u8::MAX as usize + 1, 0);
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_parts(device, Vec::new(), None),
+            Err(SnapshotError::TooLong("device descriptor"))
+        );
+        assert_eq!(
+            DeviceDescriptorSnapshot::from_parts(
+                DEVICE.to_vec(),
+                vec![CONFIGURATION.to_vec(); u8::MAX as usize + 1],
+                None,
+            ),
+            Err(SnapshotError::TooLong("configuration list"))
+        );
+    }
+
+    #[test]
+    fn diff_of_the_same_descriptors_is_empty() {
+        let old = snapshot(&CONFIGURATION, Some("0001"));
+        assert_eq!(diff(&old, &old.clone()), Vec::new());
+    }
+
+    #[test]
+    fn diff_reports_changed_fields() {
+        let old = snapshot(&CONFIGURATION, None);
+        let mut configuration = CONFIGURATION;
+        //bMaxPower, then wMaxPacketSize of the bulk IN endpoint
+        configuration[8] = 100;
+        configuration[29] = 0x40;
+        configuration[30] = 0x00;
+        let new = snapshot(&configuration, Some("0001"));
+        let interface = InterfaceKey {
+            configuration: 1,
+            interface: 0,
+            alternate_setting: 0,
+        };
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                DescriptorChange::SerialNumber {
+                    old: None,
+                    new: Some("0001".to_owned()),
+                },
+                DescriptorChange::Configuration {
+                    configuration: 1,
+                    field: "bMaxPower",
+                    old: 50,
+                    new: 100,
+                },
+                DescriptorChange::Endpoint {
+                    interface,
+                    address: EndpointAddress(0x81),
+                    field: "wMaxPacketSize",
+                    old: 0x200,
+                    new: 0x40,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_matches_configurations_by_value() {
+        let old = snapshot(&CONFIGURATION, None);
+        let mut configuration = CONFIGURATION;
+        configuration[5] = 2;
+        let new = snapshot(&configuration, None);
+        let changes = diff(&old, &new);
+        assert!(changes.contains(&DescriptorChange::ConfigurationRemoved(1)));
+        assert!(changes.contains(&DescriptorChange::ConfigurationAdded(2)));
+    }
+}
@@ -0,0 +1,886 @@
+use crate::*;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr;
+use iousbhost_sys::*;
+
+///`kIOMainPortDefault`, which replaces the deprecated `kIOMasterPortDefault`; both are
+///`MACH_PORT_NULL` and let IOKit pick the main port itself
+const MAIN_PORT_DEFAULT: mach_port_t = 0;
+
+///the IOKit main port devices are looked up through, along with a notification port for matching
+///and interest notifications on it
+pub struct Context {
+    main_port: mach_port_t,
+    notification_port: IONotificationPortRef,
+    queue: Option<Queue>,
+    pool: Option<Arc<QueuePool>>,
+}
+
+thread_local! {
+    //created on first use, so lookups without a context of their own do not each create and
+    //destroy a notification port
+    static SHARED: Context = Context::new();
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::with_main_port(MAIN_PORT_DEFAULT)
+    }
+
+    ///runs `f` with the default context of this thread, which `UsbDevice::devices` and
+    ///`UsbDevice::device` look devices up through
+    pub fn with_shared<R>(f: impl FnOnce(&Context) -> R) -> R {
+        SHARED.with(f)
+    }
+
+    pub fn with_main_port(main_port: mach_port_t) -> Self {
+        let notification_port = unsafe { IONotificationPortCreate(main_port) };
+        Self {
+            main_port,
+            notification_port,
+            queue: None,
+            pool: None,
+        }
+    }
+
+    ///opens the devices of this context on `queue` instead of a queue of their own and delivers
+    ///its notifications there, for applications already running a dispatch main loop
+    pub fn with_queue(mut self, queue: Queue) -> Self {
+        self.set_dispatch_queue(&queue);
+        self.queue = Some(queue);
+        self
+    }
+
+    pub fn queue(&self) -> Option<&Queue> {
+        self.queue.as_ref()
+    }
+
+    ///spreads the devices opened through this context over the queues of `pool`, taking
+    ///precedence over `with_queue` for devices while notifications stay where they were
+    pub fn with_queue_pool(mut self, pool: Arc<QueuePool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn queue_pool(&self) -> Option<&Arc<QueuePool>> {
+        self.pool.as_ref()
+    }
+
+    ///the queue to open the device behind `service` on
+    pub(crate) fn queue_for(&self, service: io_service_t) -> Queue {
+        match &self.pool {
+            Some(pool) => pool.queue_for_service(service),
+            None => self.device_queue(),
+        }
+    }
+
+    ///the queue set with `with_queue`, otherwise a new serial queue
+    pub(crate) fn device_queue(&self) -> Queue {
+        match &self.queue {
+            Some(queue) => queue.clone(),
+            None => {
+                let label = &0;
+                let attr = NSObject(ptr::null_mut());
+                Queue::created(unsafe { dispatch_queue_create(label, attr) })
+            }
+        }
+    }
+
+    pub fn main_port(&self) -> mach_port_t {
+        self.main_port
+    }
+
+    pub fn notification_port(&self) -> IONotificationPortRef {
+        self.notification_port
+    }
+
+    ///delivers the notifications of this context on `queue`
+    pub fn set_dispatch_queue(&self, queue: &Queue) {
+        unsafe { IONotificationPortSetDispatchQueue(self.notification_port, queue.inner.clone()) }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        if !self.notification_port.is_null() {
+            unsafe { IONotificationPortDestroy(self.notification_port) }
+        }
+    }
+}
+
+pub(crate) struct Devices<'a> {
+    pub(crate) inner: io_service_t,
+    pub(crate) main_port: mach_port_t,
+    pub(crate) queue: Queue,
+    pub(crate) pool: Option<Arc<QueuePool>>,
+    pub(crate) matcher: DeviceMatcher,
+    pub(crate) options: HostObjectInitOptions,
+    pub(crate) lt: PhantomData<&'a ()>,
+}
+
+impl<'a> Iterator for Devices<'a> {
+    type Item = UsbDevice<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while unsafe { IOIteratorIsValid(self.inner) } == 0 {
+            let queue = match &self.pool {
+                Some(pool) => pool.queue_for_service(self.inner),
+                None => self.queue.clone(),
+            };
+            let res = UsbDevice::new(
+                self.inner,
+                self.options,
+                &queue,
+                self.main_port,
+                Some(&self.matcher),
+            );
+            let next = unsafe { IOIteratorNext(self.inner) };
+            match res {
+                Ok(dev) => {
+                    self.inner = next;
+                    return Some(dev);
+                }
+                //the policy only turned this device down, the ones after it are still offered
+                Err(UsbError::Vetoed) => self.inner = next,
+                Err(e) => {
+                    crate::diag!(warn, "unexpected err when enumerating devices: {:?}", e);
+                    return None;
+                }
+            }
+        }
+        None
+    }
+}
+
+///which devices a `UsbManager` handler is interested in, every `None` field matching anything
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceMatcher {
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub bcd_device: Option<u16>,
+    pub device_class: Option<u8>,
+    pub device_subclass: Option<u8>,
+    pub device_protocol: Option<u8>,
+    pub speed: Option<u16>,
+}
+
+impl DeviceMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vendor_id(mut self, vendor_id: u16) -> Self {
+        self.vendor_id = Some(vendor_id);
+        self
+    }
+
+    pub fn product_id(mut self, product_id: u16) -> Self {
+        self.product_id = Some(product_id);
+        self
+    }
+
+    pub fn device_class(mut self, device_class: u8) -> Self {
+        self.device_class = Some(device_class);
+        self
+    }
+
+    ///checks the registry properties of `service` against every field the matcher sets
+    pub(crate) fn matches_service(&self, service: &IoService) -> bool {
+        let fields = [
+            (c"idVendor", self.vendor_id),
+            (c"idProduct", self.product_id),
+            (c"bcdDevice", self.bcd_device),
+            (c"bDeviceClass", self.device_class.map(u16::from)),
+            (c"bDeviceSubClass", self.device_subclass.map(u16::from)),
+            (c"bDeviceProtocol", self.device_protocol.map(u16::from)),
+            (c"USBSpeed", self.speed),
+        ];
+        fields.into_iter().all(|(key, expected)| match expected {
+            Some(expected) => service.number_property(key) == Some(expected as i64),
+            None => true,
+        })
+    }
+
+    fn matching_dictionary(&self) -> Result<CFMutableDictionaryRef, UsbError> {
+        UsbDevice::create_matching_dictionary(
+            self.vendor_id,
+            self.product_id,
+            self.bcd_device,
+            self.device_class,
+            self.device_subclass,
+            self.device_protocol,
+            self.speed,
+        )
+    }
+}
+
+pub enum HotplugEvent<'m, 'a> {
+    ///a matching device appeared and was opened, `id` being its registry entry id
+    Arrived { id: u64, device: &'m UsbDevice<'a> },
+    ///a matching device appeared but could not be opened
+    OpenFailed { id: u64, error: UsbError },
+    ///a previously opened device is gone, its handle has already been dropped
+    Departed { id: u64 },
+}
+
+type HotplugHandler<'a> = Box<dyn FnMut(HotplugEvent<'_, 'a>)>;
+
+struct ManagedDevice<'a> {
+    device: UsbDevice<'a>,
+    //indices of the handlers whose matcher the device matched
+    handlers: Vec<usize>,
+}
+
+enum MatchEvent {
+    //the service is released once the arrival was dispatched
+    Matched {
+        handler: usize,
+        id: u64,
+        service: io_service_t,
+    },
+    Terminated {
+        id: u64,
+    },
+}
+
+type MatchEvents = Arc<std::sync::Mutex<std::collections::VecDeque<MatchEvent>>>;
+
+///what the callback of a matching notification is handed as its refcon
+struct MatchSource {
+    handler: usize,
+    terminated: bool,
+    events: MatchEvents,
+}
+
+impl MatchSource {
+    extern "C" fn notify(refcon: *mut c_void, iterator: io_iterator_t) {
+        if let Some(source) = unsafe { (refcon as *const MatchSource).as_ref() } {
+            source.drain(iterator);
+        }
+    }
+
+    ///queues every service of `iterator`, which arms the notification again
+    fn drain(&self, iterator: io_iterator_t) {
+        let mut events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        loop {
+            let service = unsafe { IOIteratorNext(iterator) };
+            if service == 0 {
+                break;
+            }
+            let mut id = 0;
+            if unsafe { IORegistryEntryGetRegistryEntryID(service, &mut id) } != 0 {
+                unsafe { IOObjectRelease(service) };
+                continue;
+            }
+            if self.terminated {
+                unsafe { IOObjectRelease(service) };
+                events.push_back(MatchEvent::Terminated { id });
+            } else {
+                events.push_back(MatchEvent::Matched {
+                    handler: self.handler,
+                    id,
+                    service,
+                });
+            }
+        }
+    }
+}
+
+struct MatchNotification {
+    iterator: io_iterator_t,
+    //handed to IOKit as the callback's refcon, so it has to stay put until the iterator is
+    //released
+    _source: Box<MatchSource>,
+}
+
+impl Drop for MatchNotification {
+    fn drop(&mut self) {
+        unsafe { IOObjectRelease(self.iterator) };
+    }
+}
+
+///single entry point for applications driving several devices: opens every device matching a
+///registered matcher, reports arrivals and departures to the handlers registered for it and shuts
+///all of them down together
+///
+///IOKit matching notifications collect arrivals and departures on the manager's queue as they
+///happen, `poll` hands them to the handlers and is expected to be called periodically from the
+///thread owning the manager
+pub struct UsbManager<'a> {
+    context: Context,
+    queue: Queue,
+    options: HostObjectInitOptions,
+    handlers: Vec<(DeviceMatcher, HotplugHandler<'a>)>,
+    devices: std::collections::HashMap<u64, ManagedDevice<'a>>,
+    //the first match and termination notifications of every handler registered so far
+    notifications: Vec<MatchNotification>,
+    //how many of the handlers have their notifications
+    armed: usize,
+    events: MatchEvents,
+}
+
+impl<'a> UsbManager<'a> {
+    pub fn new(options: HostObjectInitOptions) -> Self {
+        Self::with_context(Context::new(), options)
+    }
+
+    ///a context without a queue of its own gets its notifications delivered on the queue the
+    ///manager opens devices on
+    pub fn with_context(context: Context, options: HostObjectInitOptions) -> Self {
+        let queue = context.device_queue();
+        if context.queue().is_none() {
+            context.set_dispatch_queue(&queue);
+        }
+        Self {
+            context,
+            queue,
+            options,
+            handlers: Vec::new(),
+            devices: std::collections::HashMap::new(),
+            notifications: Vec::new(),
+            armed: 0,
+            events: MatchEvents::default(),
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    ///registers `handler` for devices matching `matcher`, devices already open are reported to it
+    ///on the next `poll`
+    pub fn on<F>(&mut self, matcher: DeviceMatcher, handler: F)
+    where
+        F: FnMut(HotplugEvent<'_, 'a>) + 'static,
+    {
+        self.handlers.push((matcher, Box::new(handler)));
+    }
+
+    ///dispatches the devices that arrived or departed since the last call to the matching
+    ///handlers, registering the notifications of handlers added in between first
+    pub fn poll(&mut self) -> Result<(), UsbError> {
+        while self.armed < self.handlers.len() {
+            self.arm(self.armed)?;
+            self.armed += 1;
+        }
+
+        let events: Vec<MatchEvent> = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .drain(..)
+            .collect();
+        for event in events {
+            match event {
+                MatchEvent::Matched {
+                    handler,
+                    id,
+                    service,
+                } => {
+                    self.dispatch_arrival(handler, id, service);
+                    unsafe { IOObjectRelease(service) };
+                }
+                MatchEvent::Terminated { id } => {
+                    if let Some(managed) = self.devices.remove(&id) {
+                        drop(managed.device);
+                        for idx in managed.handlers {
+                            (self.handlers[idx].1)(HotplugEvent::Departed { id });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///registers the first match and termination notifications of `handler`, the devices present
+    ///already are queued as arrivals right away
+    fn arm(&mut self, handler: usize) -> Result<(), UsbError> {
+        let matcher = self.handlers[handler].0;
+        let mut notifications = Vec::with_capacity(2);
+        for (kind, terminated) in [
+            (c"IOServiceFirstMatch", false),
+            (c"IOServiceTerminate", true),
+        ] {
+            let source = Box::new(MatchSource {
+                handler,
+                terminated,
+                events: self.events.clone(),
+            });
+            let mut iterator = 0;
+            let res = unsafe {
+                IOServiceAddMatchingNotification(
+                    self.context.notification_port(),
+                    kind.as_ptr(),
+                    matcher.matching_dictionary()?,
+                    Some(MatchSource::notify),
+                    &*source as *const MatchSource as *mut c_void,
+                    &mut iterator,
+                )
+            };
+            if res != 0 {
+                return Err(res.into());
+            }
+            //the notification only fires once its iterator was emptied
+            source.drain(iterator);
+            notifications.push(MatchNotification {
+                iterator,
+                _source: source,
+            });
+        }
+        self.notifications.extend(notifications);
+        Ok(())
+    }
+
+    fn dispatch_arrival(&mut self, handler: usize, id: u64, service: io_service_t) {
+        if let Some(managed) = self.devices.get_mut(&id) {
+            if managed.handlers.contains(&handler) {
+                return;
+            }
+            managed.handlers.push(handler);
+            let device = &managed.device;
+            (self.handlers[handler].1)(HotplugEvent::Arrived { id, device });
+            return;
+        }
+
+        let matcher = self.handlers[handler].0;
+        let queue = match self.context.queue_pool() {
+            Some(pool) => pool.queue_for_service(service),
+            None => self.queue.clone(),
+        };
+        match UsbDevice::new(
+            service,
+            self.options,
+            &queue,
+            self.context.main_port(),
+            Some(&matcher),
+        ) {
+            Ok(device) => {
+                let managed = self.devices.entry(id).or_insert(ManagedDevice {
+                    device,
+                    handlers: vec![handler],
+                });
+                let device = &managed.device;
+                (self.handlers[handler].1)(HotplugEvent::Arrived { id, device });
+            }
+            Err(error) => {
+                crate::diag!(debug, "failed to open device {:#x}: {:?}", id, error);
+                (self.handlers[handler].1)(HotplugEvent::OpenFailed { id, error })
+            }
+        }
+    }
+
+    pub fn device(&self, id: u64) -> Option<&UsbDevice<'a>> {
+        self.devices.get(&id).map(|managed| &managed.device)
+    }
+
+    ///every open device along with its registry entry id
+    pub fn devices(&self) -> impl Iterator<Item = (u64, &UsbDevice<'a>)> {
+        self.devices
+            .iter()
+            .map(|(id, managed)| (*id, &managed.device))
+    }
+
+    ///shuts every open device down with `UsbDevice::shutdown`, giving each up to `timeout`, and
+    ///reports them as departed, returning the first error encountered
+    pub fn shutdown(&mut self, timeout: std::time::Duration) -> Result<(), UsbError> {
+        let mut res = Ok(());
+        for (id, managed) in self.devices.drain() {
+            if let Err(err) = managed.device.shutdown(timeout) {
+                res = res.and(Err(err));
+            }
+            for idx in managed.handlers {
+                (self.handlers[idx].1)(HotplugEvent::Departed { id });
+            }
+        }
+        res
+    }
+}
+
+impl Drop for UsbManager<'_> {
+    fn drop(&mut self) {
+        //no callback queues anything once the notifications are gone
+        self.notifications.clear();
+        let events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .drain(..)
+            .collect::<Vec<_>>();
+        for event in events {
+            if let MatchEvent::Matched { service, .. } = event {
+                unsafe { IOObjectRelease(service) };
+            }
+        }
+    }
+}
+
+//`iokit_common_msg(0x010)`
+const MESSAGE_SERVICE_IS_TERMINATED: u32 = 0xe000_0010;
+//`iokit_usbhost_msg(0x00)`, the usb host messages being `sys_iokit | sub_iokit_usb | message`
+const MESSAGE_CONFIGURATION_SET: u32 = 0xe000_4000;
+//`iokit_usb_msg(0x0a)`
+const MESSAGE_PORT_HAS_BEEN_RESET: u32 = 0xe000_400a;
+//`iokit_usb_msg(0x0b)`
+const MESSAGE_PORT_HAS_BEEN_RESUMED: u32 = 0xe000_400b;
+
+///a change made to a device outside of this handle, after which its interfaces and pipes have to
+///be reopened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceEvent {
+    ///a configuration was set on the device, possibly by another process
+    ConfigurationChanged,
+    ///the device finished a reset
+    ResetComplete,
+    ///the device woke up from suspend
+    Resumed,
+    ///the device is gone
+    Terminated,
+    ///any other `kIOUSBHostMessage*` or IOKit message
+    Other(u32),
+}
+
+impl From<u32> for DeviceEvent {
+    fn from(message: u32) -> DeviceEvent {
+        match message {
+            MESSAGE_CONFIGURATION_SET => DeviceEvent::ConfigurationChanged,
+            MESSAGE_PORT_HAS_BEEN_RESET => DeviceEvent::ResetComplete,
+            MESSAGE_PORT_HAS_BEEN_RESUMED => DeviceEvent::Resumed,
+            MESSAGE_SERVICE_IS_TERMINATED => DeviceEvent::Terminated,
+            other => DeviceEvent::Other(other),
+        }
+    }
+}
+
+#[derive(Default)]
+struct EventState {
+    events: std::collections::VecDeque<DeviceEvent>,
+    #[cfg(feature = "async")]
+    waker: Option<core::task::Waker>,
+}
+
+///the IOKit interest notifications of a device, see `UsbDevice::events`
+pub struct DeviceEvents<'c> {
+    notification: io_object_t,
+    //handed to IOKit as the callback's refcon, so it has to stay put until the notification is
+    //released
+    state: Box<std::sync::Mutex<EventState>>,
+    lt: PhantomData<&'c Context>,
+}
+
+impl DeviceEvents<'_> {
+    extern "C" fn notify(
+        refcon: *mut c_void,
+        _service: io_service_t,
+        message_type: u32,
+        _argument: *mut c_void,
+    ) {
+        let Some(state) = (unsafe { (refcon as *const std::sync::Mutex<EventState>).as_ref() })
+        else {
+            return;
+        };
+        let mut state = state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        state.events.push_back(message_type.into());
+        #[cfg(feature = "async")]
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    ///the oldest event not taken yet
+    pub fn try_next(&self) -> Option<DeviceEvent> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .events
+            .pop_front()
+    }
+
+    ///every event not taken yet, oldest first
+    pub fn drain(&self) -> Vec<DeviceEvent> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .events
+            .drain(..)
+            .collect()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for DeviceEvents<'_> {
+    type Item = DeviceEvent;
+    fn poll_next(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Self::Item>> {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match state.events.pop_front() {
+            Some(event) => core::task::Poll::Ready(Some(event)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                core::task::Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for DeviceEvents<'_> {
+    fn drop(&mut self) {
+        unsafe { IOObjectRelease(self.notification) };
+    }
+}
+
+impl UsbDevice<'_> {
+    ///subscribes to the device's IOKit messages, such as a configuration being set or a reset
+    ///completing on behalf of another process
+    ///
+    ///events are only delivered while the notification port of `context` is serviced, e.g. after
+    ///`Context::set_dispatch_queue`
+    pub fn events<'c>(&self, context: &'c Context) -> Result<DeviceEvents<'c>, UsbError> {
+        let state = Box::new(std::sync::Mutex::new(EventState::default()));
+        let mut notification = 0;
+        let res = unsafe {
+            IOServiceAddInterestNotification(
+                context.notification_port(),
+                self.io_service().inner,
+                c"IOGeneralInterest".as_ptr(),
+                Some(DeviceEvents::notify),
+                &*state as *const std::sync::Mutex<EventState> as *mut c_void,
+                &mut notification,
+            )
+        };
+        if res != 0 {
+            return Err(res.into());
+        }
+        Ok(DeviceEvents {
+            notification,
+            state,
+            lt: PhantomData,
+        })
+    }
+}
+
+#[derive(Default)]
+struct ArrivalState {
+    services: std::sync::Mutex<std::collections::VecDeque<io_service_t>>,
+    arrived: std::sync::Condvar,
+}
+
+///the devices showing up at a port from now on, found by their locationID through a first match
+///notification, see `UsbDevice::reset_and_capture`
+pub(crate) struct PortArrivals {
+    port: IONotificationPortRef,
+    notification: io_iterator_t,
+    _queue: Queue,
+    //handed to IOKit as the callback's refcon, so it has to stay put until the notification is
+    //released
+    state: Box<ArrivalState>,
+}
+
+impl PortArrivals {
+    pub(crate) fn new(main_port: mach_port_t, location_id: u32) -> Result<Self, UsbError> {
+        let dict = unsafe { IOServiceMatching(c"IOUSBHostDevice".as_ptr()) };
+        if dict.is_null() {
+            return Err(UsbError::Failure);
+        }
+        unsafe {
+            let key = CFStringCreateWithCString(
+                kCFAllocatorDefault,
+                c"locationID".as_ptr(),
+                kCFStringEncodingUTF8,
+            );
+            let value = CFNumberCreate(
+                kCFAllocatorDefault,
+                kCFNumberSInt32Type as CFNumberType,
+                &location_id as *const u32 as *const c_void,
+            );
+            CFDictionarySetValue(dict, key as *const c_void, value as *const c_void);
+            CFRelease(key as CFTypeRef);
+            CFRelease(value as CFTypeRef);
+        }
+
+        let (port, queue) = private_port(main_port);
+        let state = Box::<ArrivalState>::default();
+        let mut notification = 0;
+        let res = unsafe {
+            IOServiceAddMatchingNotification(
+                port,
+                c"IOServiceFirstMatch".as_ptr(),
+                dict,
+                Some(Self::notify),
+                &*state as *const ArrivalState as *mut c_void,
+                &mut notification,
+            )
+        };
+        if res != 0 {
+            unsafe { IONotificationPortDestroy(port) };
+            return Err(res.into());
+        }
+        //emptying the iterator arms the notification, the device at the port now is not wanted
+        loop {
+            let service = unsafe { IOIteratorNext(notification) };
+            if service == 0 {
+                break;
+            }
+            unsafe { IOObjectRelease(service) };
+        }
+        Ok(Self {
+            port,
+            notification,
+            _queue: queue,
+            state,
+        })
+    }
+
+    extern "C" fn notify(refcon: *mut c_void, iterator: io_iterator_t) {
+        let Some(state) = (unsafe { (refcon as *const ArrivalState).as_ref() }) else {
+            return;
+        };
+        let mut services = state
+            .services
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        loop {
+            let service = unsafe { IOIteratorNext(iterator) };
+            if service == 0 {
+                break;
+            }
+            services.push_back(service);
+        }
+        state.arrived.notify_all();
+    }
+
+    ///waits until `deadline` for the next device at the port, which the caller has to release
+    pub(crate) fn next(&self, deadline: std::time::Instant) -> Option<io_service_t> {
+        let mut services = self
+            .state
+            .services
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        loop {
+            if let Some(service) = services.pop_front() {
+                return Some(service);
+            }
+            let remaining = deadline.checked_duration_since(std::time::Instant::now())?;
+            services = self
+                .state
+                .arrived
+                .wait_timeout(services, remaining)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+impl Drop for PortArrivals {
+    fn drop(&mut self) {
+        unsafe {
+            IOObjectRelease(self.notification);
+            IONotificationPortDestroy(self.port);
+        }
+        let services = self
+            .state
+            .services
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for service in services.drain(..) {
+            unsafe { IOObjectRelease(service) };
+        }
+    }
+}
+
+//a notification port of our own, serviced on a new serial queue
+fn private_port(main_port: mach_port_t) -> (IONotificationPortRef, Queue) {
+    let label = &0;
+    let attr = NSObject(ptr::null_mut());
+    let queue = Queue::created(unsafe { dispatch_queue_create(label, attr) });
+    let port = unsafe { IONotificationPortCreate(main_port) };
+    unsafe { IONotificationPortSetDispatchQueue(port, queue.inner.clone()) };
+    (port, queue)
+}
+
+///notes when a device finished a reset or woke up, so its profile is applied again without the
+///application servicing a `Context`, see `UsbDevice::apply_profile`
+pub(crate) struct ResumeWatch {
+    port: IONotificationPortRef,
+    notification: io_object_t,
+    _queue: Queue,
+    //handed to IOKit as the callback's refcon, so it has to stay put until the notification is
+    //released
+    stale: Box<core::sync::atomic::AtomicBool>,
+}
+
+impl ResumeWatch {
+    pub(crate) fn new(main_port: mach_port_t, service: io_service_t) -> Result<Self, UsbError> {
+        let (port, queue) = private_port(main_port);
+        let stale = Box::new(core::sync::atomic::AtomicBool::new(false));
+        let mut notification = 0;
+        let res = unsafe {
+            IOServiceAddInterestNotification(
+                port,
+                service,
+                c"IOGeneralInterest".as_ptr(),
+                Some(Self::notify),
+                &*stale as *const core::sync::atomic::AtomicBool as *mut c_void,
+                &mut notification,
+            )
+        };
+        if res != 0 {
+            unsafe { IONotificationPortDestroy(port) };
+            return Err(res.into());
+        }
+        Ok(Self {
+            port,
+            notification,
+            _queue: queue,
+            stale,
+        })
+    }
+
+    extern "C" fn notify(
+        refcon: *mut c_void,
+        _service: io_service_t,
+        message_type: u32,
+        _argument: *mut c_void,
+    ) {
+        let Some(stale) = (unsafe { (refcon as *const core::sync::atomic::AtomicBool).as_ref() })
+        else {
+            return;
+        };
+        if matches!(
+            DeviceEvent::from(message_type),
+            DeviceEvent::ResetComplete | DeviceEvent::Resumed
+        ) {
+            stale.store(true, core::sync::atomic::Ordering::Release);
+        }
+    }
+
+    ///whether the device was reset or woke up since the last call
+    pub(crate) fn take(&self) -> bool {
+        self.stale.swap(false, core::sync::atomic::Ordering::AcqRel)
+    }
+}
+
+impl Drop for ResumeWatch {
+    fn drop(&mut self) {
+        unsafe {
+            IOObjectRelease(self.notification);
+            IONotificationPortDestroy(self.port);
+        }
+    }
+}
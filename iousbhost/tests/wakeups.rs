@@ -0,0 +1,215 @@
+//! completions have to wake the task through the waker of its latest poll, whichever executor or
+//! thread that poll came from
+//!
+//! the detached completion tests hand the completion handler to another thread as the framework's
+//! queue would, the others need a device to send requests to and are skipped without one:
+//!
+//! IOUSBHOST_TEST_VID=0x1234 IOUSBHOST_TEST_PID=0x5678 cargo test --test wakeups
+use iousbhost::{
+    CompletionHandle, DetachedCompletion, DeviceRequest, DeviceRequestType, HostObjectInitOptions,
+    UsbDevice,
+};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn env_u16(name: &str) -> Option<u16> {
+    let value = std::env::var(name).ok()?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn open() -> Option<UsbDevice<'static>> {
+    let (Some(vendor_id), Some(product_id)) =
+        (env_u16("IOUSBHOST_TEST_VID"), env_u16("IOUSBHOST_TEST_PID"))
+    else {
+        println!("skipping: set IOUSBHOST_TEST_VID and IOUSBHOST_TEST_PID");
+        return None;
+    };
+    let device = UsbDevice::device::<0>(
+        Some(vendor_id),
+        Some(product_id),
+        None,
+        None,
+        None,
+        None,
+        None,
+        HostObjectInitOptions::None,
+    )
+    .expect("failed to open device");
+    Some(device)
+}
+
+//GET_DESCRIPTOR(DEVICE), which every device answers
+fn get_device_descriptor() -> DeviceRequest {
+    DeviceRequest::new(DeviceRequestType::DirectionIn, 6, 0x0100, 0, 18)
+}
+
+#[test]
+fn tokio_multi_thread() {
+    let Some(device) = open() else { return };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_time()
+        .build()
+        .expect("failed to build runtime");
+    runtime.block_on(async {
+        for _ in 0..64 {
+            let request =
+                device.enqueue_device_request_with_data(get_device_descriptor(), &[0; 18]);
+            tokio::time::timeout(TIMEOUT, request)
+                .await
+                .expect("completion never woke the task")
+                .expect("request failed");
+        }
+    });
+}
+
+//device futures can not leave their thread, so the spawned tasks await detached completions; the
+//handlers run on a thread of their own, waking tasks that workers may have stolen since
+#[test]
+fn tokio_spawned_tasks() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_time()
+        .build()
+        .expect("failed to build runtime");
+    let (handlers, received) = std::sync::mpsc::channel::<CompletionHandle>();
+    let queue = std::thread::spawn(move || {
+        for handle in received {
+            std::thread::sleep(Duration::from_micros(100));
+            handle.complete(0, 18);
+        }
+    });
+    let tasks: Vec<_> = (0..64)
+        .map(|_| {
+            let handlers = handlers.clone();
+            runtime.spawn(async move {
+                let mut transfer = DetachedCompletion::new();
+                let first = std::thread::current().id();
+                //the first poll registers the waker of the worker it ran on
+                let pending = std::future::poll_fn(|cx| {
+                    Poll::Ready(std::pin::Pin::new(&mut transfer).poll(cx).is_pending())
+                })
+                .await;
+                assert!(pending);
+                handlers
+                    .send(transfer.handle())
+                    .expect("completion thread exited");
+                tokio::task::yield_now().await;
+                let bytes = tokio::time::timeout(TIMEOUT, transfer)
+                    .await
+                    .expect("completion never woke the task")
+                    .expect("request failed");
+                assert_eq!(bytes, 18);
+                first != std::thread::current().id()
+            })
+        })
+        .collect();
+    drop(handlers);
+    let migrated = runtime.block_on(async {
+        let mut migrated = 0;
+        for task in tasks {
+            migrated += task.await.expect("task panicked") as usize;
+        }
+        migrated
+    });
+    println!("{migrated} of 64 tasks completed on another worker than they were first polled on");
+    queue.join().expect("completion thread panicked");
+}
+
+#[test]
+fn async_std() {
+    let Some(device) = open() else { return };
+    async_std::task::block_on(async {
+        for _ in 0..64 {
+            let request =
+                device.enqueue_device_request_with_data(get_device_descriptor(), &[0; 18]);
+            async_std::future::timeout(TIMEOUT, request)
+                .await
+                .expect("completion never woke the task")
+                .expect("request failed");
+        }
+    });
+}
+
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn detached_latest_waker_is_woken() {
+    let first = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let second = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let first_waker = Waker::from(first.clone());
+    let second_waker = Waker::from(second.clone());
+
+    let mut transfer = pin!(DetachedCompletion::new());
+    assert!(transfer
+        .as_mut()
+        .poll(&mut Context::from_waker(&first_waker))
+        .is_pending());
+    //as an executor moving the task to another worker would
+    let mut context = Context::from_waker(&second_waker);
+    assert!(transfer.as_mut().poll(&mut context).is_pending());
+
+    let handle = transfer.handle();
+    std::thread::spawn(move || handle.complete(0, 18))
+        .join()
+        .expect("completion thread panicked");
+    assert_eq!(second.0.load(Ordering::SeqCst), 1);
+    assert_eq!(first.0.load(Ordering::SeqCst), 0);
+    assert!(matches!(
+        transfer.as_mut().poll(&mut context),
+        Poll::Ready(Ok(18))
+    ));
+}
+
+#[test]
+fn latest_waker_is_woken() {
+    let Some(device) = open() else { return };
+    let first = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let second = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let first_waker = Waker::from(first.clone());
+    let second_waker = Waker::from(second.clone());
+
+    let mut request =
+        pin!(device.enqueue_device_request_with_data(get_device_descriptor(), &[0; 18]));
+    if request
+        .as_mut()
+        .poll(&mut Context::from_waker(&first_waker))
+        .is_ready()
+    {
+        return;
+    }
+    //as an executor moving the task to another worker would
+    let mut context = Context::from_waker(&second_waker);
+    if request.as_mut().poll(&mut context).is_ready() {
+        return;
+    }
+
+    let start = Instant::now();
+    while second.0.load(Ordering::SeqCst) == 0 {
+        assert!(
+            start.elapsed() < TIMEOUT,
+            "the latest waker was never woken"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(matches!(
+        request.as_mut().poll(&mut context),
+        Poll::Ready(Ok(_))
+    ));
+    assert_eq!(first.0.load(Ordering::SeqCst), 0);
+}